@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use lumen::layout::Viewport;
+use lumen::layout::{layout_document_cached, LayoutCache, LayoutOptions, Viewport};
 use lumen::{layout_document, parse_markdown, Theme};
 
 fn create_test_document(sections: usize) -> String {
@@ -92,12 +92,57 @@ fn layout_with_inline_images(c: &mut Criterion) {
     group.finish();
 }
 
+fn layout_cached(c: &mut Criterion) {
+    let markdown = create_test_document(2000);
+    let doc = parse_markdown(&markdown);
+    let theme = Theme::builtin("docs").unwrap();
+    let viewport = Viewport::new(80, 24);
+
+    let mut group = c.benchmark_group("layout_cached");
+
+    group.bench_function("warm_cache", |b| {
+        let options = LayoutOptions::default();
+        let mut cache = LayoutCache::new();
+        layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+        b.iter(|| {
+            layout_document_cached(
+                black_box(&doc),
+                black_box(&theme),
+                viewport,
+                &options,
+                &mut cache,
+            )
+        })
+    });
+
+    group.bench_function("lazy_cold", |b| {
+        let options = LayoutOptions {
+            inline_images: false,
+            lazy: true,
+            ..Default::default()
+        };
+        b.iter(|| {
+            let mut cache = LayoutCache::new();
+            layout_document_cached(
+                black_box(&doc),
+                black_box(&theme),
+                viewport,
+                &options,
+                &mut cache,
+            )
+        })
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     layout_small,
     layout_medium,
     layout_large,
     layout_different_widths,
-    layout_with_inline_images
+    layout_with_inline_images,
+    layout_cached
 );
 criterion_main!(benches);
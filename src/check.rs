@@ -0,0 +1,621 @@
+//! Link checker for `lumen check`
+//!
+//! Finds internal `#anchor` links that match no heading, relative links and
+//! images whose files don't exist, duplicate heading ids, headings whose
+//! repeated titles get numbered ids, and empty links.
+//! Positions are 1-based `line:column` in the Markdown source.
+
+use crate::ir::{Block, Document, Inline};
+use crate::parse_markdown;
+use crate::parser::{decode_fragment, github_slug};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How bad an issue is; only errors make `lumen check` fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// What is wrong
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// `#anchor` (in this file or another) matches no heading id
+    BrokenAnchor,
+    /// Relative link to a file that doesn't exist
+    MissingFile,
+    /// Relative image path that doesn't exist
+    MissingImage,
+    /// Two headings share an id, so one can't be linked to
+    DuplicateId,
+    /// A heading's title repeats an earlier one, so its id is numbered
+    /// (`#setup-1`) and links to the plain slug reach the first
+    RepeatedHeading,
+    /// Link without a target or without text
+    EmptyLink,
+}
+
+impl IssueKind {
+    /// Name shown in reports and JSON output (kebab-case)
+    pub fn name(self) -> &'static str {
+        match self {
+            IssueKind::BrokenAnchor => "broken-anchor",
+            IssueKind::MissingFile => "missing-file",
+            IssueKind::MissingImage => "missing-image",
+            IssueKind::DuplicateId => "duplicate-id",
+            IssueKind::RepeatedHeading => "repeated-heading",
+            IssueKind::EmptyLink => "empty-link",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            IssueKind::EmptyLink | IssueKind::RepeatedHeading => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl Serialize for IssueKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// A problem found in a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Issue {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for Issue {
+    /// `docs/guide.md:12:5: error: no heading with id #setup [broken-anchor]`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}:{}: {}: {} [{}]",
+            self.file.display(),
+            self.line,
+            self.column,
+            severity,
+            self.message,
+            self.kind.name()
+        )
+    }
+}
+
+/// Checks files, remembering the heading ids of Markdown files that links
+/// point into
+#[derive(Debug, Default)]
+pub struct Checker {
+    /// Markdown file → its heading ids (`None` if unreadable)
+    ids: HashMap<PathBuf, Option<HashSet<String>>>,
+    /// Directory that `/`-rooted links resolve against; without one they
+    /// aren't checked
+    root: Option<PathBuf>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve `/`-rooted links against `root` from now on
+    pub fn set_root(&mut self, root: Option<PathBuf>) {
+        self.root = root;
+    }
+
+    /// Check a Markdown file on disk
+    pub fn check_file(&mut self, path: &Path) -> io::Result<Vec<Issue>> {
+        let markdown = fs::read_to_string(path)?;
+        Ok(self.check_markdown(path, &markdown))
+    }
+
+    /// Check Markdown source; relative links resolve against `path`'s directory
+    pub fn check_markdown(&mut self, path: &Path, markdown: &str) -> Vec<Issue> {
+        let document = parse_markdown(markdown);
+        let mut targets = Vec::new();
+        collect_targets(&document, markdown, &mut targets);
+
+        let mut issues = Vec::new();
+        let mut issue = |offset: usize, kind: IssueKind, message: String| {
+            let (line, column) = line_column(markdown, offset);
+            issues.push(Issue {
+                file: path.to_path_buf(),
+                line,
+                column,
+                severity: kind.severity(),
+                kind,
+                message,
+            });
+        };
+
+        // Heading ids, and which ones repeat
+        let mut ids: HashMap<&str, usize> = HashMap::new();
+        for target in &targets {
+            if let Target::Heading { id, slug, offset } = target {
+                // A slug taken by an earlier heading gets `-1`, `-2`, ...
+                let numbered = id
+                    .strip_prefix(slug.as_str())
+                    .and_then(|rest| rest.strip_prefix('-'))
+                    .is_some_and(|n| n.parse::<usize>().is_ok());
+                if let Some(&first) = ids.get(slug.as_str()).filter(|_| numbered) {
+                    issue(
+                        *offset,
+                        IssueKind::RepeatedHeading,
+                        format!(
+                            "heading repeats #{} (line {}), so its id is #{}",
+                            slug,
+                            line_column(markdown, first).0,
+                            id
+                        ),
+                    );
+                }
+                match ids.get(id.as_str()) {
+                    Some(&first) => issue(
+                        *offset,
+                        IssueKind::DuplicateId,
+                        format!(
+                            "duplicate heading id #{} (first used on line {})",
+                            id,
+                            line_column(markdown, first).0
+                        ),
+                    ),
+                    None => {
+                        ids.insert(id, *offset);
+                    }
+                }
+            }
+        }
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for target in &targets {
+            let (url, offset, image) = match target {
+                Target::Link { url, text, offset } => {
+                    if url.is_empty() {
+                        issue(
+                            *offset,
+                            IssueKind::EmptyLink,
+                            "link has no target".to_string(),
+                        );
+                        continue;
+                    }
+                    if text.trim().is_empty() {
+                        issue(
+                            *offset,
+                            IssueKind::EmptyLink,
+                            format!("link to {} has no text", url),
+                        );
+                    }
+                    (url, *offset, false)
+                }
+                Target::Image { url, offset } => {
+                    if url.is_empty() {
+                        issue(
+                            *offset,
+                            IssueKind::EmptyLink,
+                            "image has no source".to_string(),
+                        );
+                        continue;
+                    }
+                    (url, *offset, true)
+                }
+                Target::Heading { .. } => continue,
+            };
+
+            if let Some(anchor) = url.strip_prefix('#') {
+                let anchor = decode_fragment(anchor);
+                if anchor.is_empty() {
+                    issue(
+                        offset,
+                        IssueKind::EmptyLink,
+                        "link to an empty anchor #".to_string(),
+                    );
+                } else if !ids.contains_key(anchor.as_str()) {
+                    issue(
+                        offset,
+                        IssueKind::BrokenAnchor,
+                        format!("no heading with id #{}", anchor),
+                    );
+                }
+                continue;
+            }
+            if !is_relative(url) {
+                continue;
+            }
+
+            let (file, anchor) = match url.split_once('#') {
+                Some((file, anchor)) => (file, Some(decode_fragment(anchor))),
+                None => (url.as_str(), None),
+            };
+            let file = decode_fragment(file.split('?').next().unwrap_or(file));
+            let resolved = match file.strip_prefix('/') {
+                Some(rooted) => match &self.root {
+                    Some(root) => root.join(rooted),
+                    None => continue,
+                },
+                None => dir.join(&file),
+            };
+            if !resolved.exists() {
+                let (kind, what) = if image {
+                    (IssueKind::MissingImage, "image")
+                } else {
+                    (IssueKind::MissingFile, "file")
+                };
+                issue(offset, kind, format!("{} not found: {}", what, file));
+                continue;
+            }
+
+            if let Some(anchor) = anchor.filter(|a| !a.is_empty()) {
+                if is_markdown(&resolved) {
+                    if let Some(ids) = self.ids_of(&resolved) {
+                        if !ids.contains(&anchor) {
+                            issue(
+                                offset,
+                                IssueKind::BrokenAnchor,
+                                format!("no heading with id #{} in {}", anchor, file),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// Heading ids of another Markdown file (cached)
+    fn ids_of(&mut self, path: &Path) -> Option<&HashSet<String>> {
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.ids
+            .entry(key)
+            .or_insert_with(|| {
+                let markdown = fs::read_to_string(path).ok()?;
+                let mut targets = Vec::new();
+                collect_targets(&parse_markdown(&markdown), &markdown, &mut targets);
+                Some(
+                    targets
+                        .into_iter()
+                        .filter_map(|target| match target {
+                            Target::Heading { id, .. } => Some(id),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+            })
+            .as_ref()
+    }
+}
+
+/// Markdown files to check for the given paths: files as given, and the
+/// `.md` / `.markdown` files under directories (sorted)
+pub fn markdown_files(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut found = Vec::new();
+            walk(path, &mut found)?;
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn walk(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, found)?;
+        } else if is_markdown(&path) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_markdown(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("md" | "markdown")
+    )
+}
+
+/// Whether a URL is a path on disk: no scheme (`https:`, `mailto:`, ...)
+fn is_relative(url: &str) -> bool {
+    let scheme = url
+        .find(':')
+        .is_some_and(|colon| !url[..colon].contains(['/', '?', '#']));
+    !scheme && !url.starts_with("//")
+}
+
+/// Something in the document to check, with its source offset
+#[derive(Debug)]
+enum Target {
+    Link {
+        url: String,
+        text: String,
+        offset: usize,
+    },
+    Image {
+        url: String,
+        offset: usize,
+    },
+    Heading {
+        id: String,
+        /// The slug of the heading's text, before any `-1`
+        slug: String,
+        offset: usize,
+    },
+}
+
+/// Walk the document in order. The IR has no inline positions, so each
+/// target is found by searching for it in its top-level block's source
+/// after the previous one; if that fails it is reported at the block start.
+fn collect_targets(document: &Document, markdown: &str, targets: &mut Vec<Target>) {
+    for (index, block) in document.blocks.iter().enumerate() {
+        let range = document
+            .source_ranges
+            .get(index)
+            .cloned()
+            .unwrap_or(0..markdown.len());
+        let mut locator = Locator {
+            source: markdown,
+            cursor: range.start,
+            start: range.start,
+            end: range.end.min(markdown.len()),
+        };
+        block_targets(block, &mut locator, targets);
+    }
+}
+
+struct Locator<'a> {
+    source: &'a str,
+    cursor: usize,
+    start: usize,
+    end: usize,
+}
+
+impl Locator<'_> {
+    /// Offset of the next occurrence of `needle` in the block
+    fn find(&mut self, needle: &str) -> Option<usize> {
+        let found = self
+            .source
+            .get(self.cursor..self.end)
+            .filter(|_| !needle.is_empty())
+            .and_then(|rest| rest.find(needle));
+        let offset = self.cursor + found?;
+        self.cursor = offset + needle.len();
+        Some(offset)
+    }
+
+    /// Offset of the last `marker` between `from` and `at`, e.g. the `[`
+    /// opening a link whose text was found at `at`
+    fn opening(&self, from: usize, at: usize, marker: &str) -> usize {
+        self.source
+            .get(from..at)
+            .and_then(|before| before.rfind(marker))
+            .map_or(at, |position| from + position)
+    }
+}
+
+fn block_targets(block: &Block, locator: &mut Locator, targets: &mut Vec<Target>) {
+    match block {
+        Block::Heading { content, id, .. } => {
+            let text: String = content.iter().map(Inline::to_plain_text).collect();
+            let offset = locator.find(&text).unwrap_or(locator.start);
+            // Links in the heading come after its start, not after its text
+            locator.cursor = offset;
+            targets.push(Target::Heading {
+                id: id.clone(),
+                slug: github_slug(&text),
+                offset,
+            });
+            inline_targets(content, locator, targets);
+        }
+        Block::Paragraph { content } => inline_targets(content, locator, targets),
+        Block::BlockQuote { blocks }
+        | Block::Callout {
+            content: blocks, ..
+        } => {
+            for block in blocks {
+                block_targets(block, locator, targets);
+            }
+        }
+        Block::Details {
+            summary, content, ..
+        } => {
+            inline_targets(summary, locator, targets);
+            for block in content {
+                block_targets(block, locator, targets);
+            }
+        }
+        Block::List { items, .. } => {
+            for item in items {
+                for block in &item.content {
+                    block_targets(block, locator, targets);
+                }
+            }
+        }
+        Block::Table { headers, rows, .. } => {
+            for cell in headers.iter().chain(rows.iter().flatten()) {
+                inline_targets(&cell.content, locator, targets);
+            }
+        }
+        Block::CodeBlock { .. } | Block::HorizontalRule => {}
+    }
+}
+
+fn inline_targets(inlines: &[Inline], locator: &mut Locator, targets: &mut Vec<Target>) {
+    for inline in inlines {
+        match inline {
+            Inline::Link { url, text, .. } => {
+                let label: String = text.iter().map(Inline::to_plain_text).collect();
+                let from = locator.cursor;
+                let text_at = if label.is_empty() {
+                    locator.find("[]").map(|at| at + 1)
+                } else {
+                    locator.find(&label)
+                };
+                let text_at = text_at.unwrap_or(locator.start);
+                let offset = locator.opening(from, text_at, "[");
+                // Skip past the target; empty targets are written `()`
+                locator.find(if url.is_empty() { "()" } else { url });
+                targets.push(Target::Link {
+                    url: url.clone(),
+                    text: label,
+                    offset,
+                });
+                inline_targets(text, locator, targets);
+            }
+            Inline::Image { url, .. } => {
+                let from = locator.cursor;
+                let url_at = locator.find(url).unwrap_or(locator.start);
+                targets.push(Target::Image {
+                    url: url.clone(),
+                    offset: locator.opening(from, url_at, "!["),
+                });
+            }
+            Inline::Strong(inner) | Inline::Emphasis(inner) | Inline::Strikethrough(inner) => {
+                inline_targets(inner, locator, targets)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 1-based line and column (in characters) of a byte offset
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(markdown: &str) -> Vec<(usize, usize, IssueKind)> {
+        Checker::new()
+            .check_markdown(Path::new("README.md"), markdown)
+            .into_iter()
+            .map(|issue| (issue.line, issue.column, issue.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_anchor_links() {
+        let markdown =
+            "# Intro\n\nSee [intro](#intro) and\n[setup](#setup).\n\n## Setup {#install}\n";
+        assert_eq!(check(markdown), vec![(4, 1, IssueKind::BrokenAnchor)]);
+    }
+
+    #[test]
+    fn test_relative_files_and_images() {
+        let markdown = "- [manifest](Cargo.toml)\n- [gone](docs/gone.md)\n- [web](https://example.com/x)\n\n\
+                        ![logo](missing.png) ![](src/lib.rs)\n\n[section](README.md#usage) [bad](README.md#nope)\n";
+        assert_eq!(
+            check(markdown),
+            vec![
+                (2, 3, IssueKind::MissingFile),
+                (5, 1, IssueKind::MissingImage),
+                (7, 28, IssueKind::BrokenAnchor),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_ids_and_empty_links() {
+        let markdown = "# Usage\n\n## Details {#usage}\n\n[]() and [todo]()\n";
+        let issues = check(markdown);
+        assert_eq!(
+            issues,
+            vec![
+                (3, 4, IssueKind::DuplicateId),
+                (5, 1, IssueKind::EmptyLink),
+                (5, 10, IssueKind::EmptyLink),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeated_headings_are_warned_about() {
+        let markdown = "# Setup\n\n## Setup\n\n### Setup\n\n[second](#setup-1)\n";
+        assert_eq!(
+            check(markdown),
+            vec![
+                (3, 4, IssueKind::RepeatedHeading),
+                (5, 5, IssueKind::RepeatedHeading),
+            ]
+        );
+        let issues = Checker::new().check_markdown(Path::new("README.md"), markdown);
+        assert_eq!(issues[1].severity, Severity::Warning);
+        assert_eq!(
+            issues[1].message,
+            "heading repeats #setup (line 1), so its id is #setup-2"
+        );
+    }
+
+    #[test]
+    fn test_issue_display() {
+        let issue = Issue {
+            file: PathBuf::from("docs/guide.md"),
+            line: 12,
+            column: 5,
+            severity: Severity::Error,
+            kind: IssueKind::BrokenAnchor,
+            message: "no heading with id #setup".to_string(),
+        };
+        assert_eq!(
+            issue.to_string(),
+            "docs/guide.md:12:5: error: no heading with id #setup [broken-anchor]"
+        );
+        assert_eq!(
+            serde_json::to_string(&issue.kind).unwrap(),
+            "\"broken-anchor\""
+        );
+    }
+
+    #[test]
+    fn test_empty_link_after_multibyte_text() {
+        assert_eq!(
+            check("é [<b></b>](https://x)\n"),
+            vec![(1, 1, IssueKind::EmptyLink)]
+        );
+    }
+
+    #[test]
+    fn test_rooted_links_resolve_against_the_root() {
+        let markdown = "[manifest](/Cargo.toml) [gone](/docs/gone.md)\n";
+        assert_eq!(check(markdown), vec![]);
+        let mut checker = Checker::new();
+        checker.set_root(Some(PathBuf::from(".")));
+        let issues = checker.check_markdown(Path::new("src/main.md"), markdown);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].message, "file not found: /docs/gone.md");
+    }
+}
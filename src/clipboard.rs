@@ -0,0 +1,124 @@
+//! Clipboard access for yanking text out of the viewer
+//!
+//! Text is handed to the terminal with an OSC 52 escape sequence, which works
+//! over SSH and inside tmux. When the terminal cannot take it (the console,
+//! `TERM=dumb`, or a payload too large for terminals to accept) `wl-copy` or
+//! `xclip` is used instead, if one is installed.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// Largest encoded OSC 52 payload we send; many terminals drop anything
+/// bigger (xterm's default limit is about 100 KB)
+const OSC52_MAX_BYTES: usize = 100_000;
+
+/// How text reached the clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardMethod {
+    /// OSC 52 escape sequence written to the terminal
+    Osc52,
+    /// An external helper program
+    Command(&'static str),
+}
+
+impl fmt::Display for ClipboardMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClipboardMethod::Osc52 => write!(f, "OSC 52"),
+            ClipboardMethod::Command(program) => write!(f, "{}", program),
+        }
+    }
+}
+
+/// Copy text to the system clipboard
+pub fn copy(text: &str) -> io::Result<ClipboardMethod> {
+    let encoded = STANDARD.encode(text);
+    if osc52_supported() && encoded.len() <= OSC52_MAX_BYTES {
+        let in_tmux = std::env::var_os("TMUX").is_some();
+        let mut stdout = io::stdout();
+        if stdout
+            .write_all(osc52_sequence(&encoded, in_tmux).as_bytes())
+            .and_then(|_| stdout.flush())
+            .is_ok()
+        {
+            return Ok(ClipboardMethod::Osc52);
+        }
+    }
+
+    copy_with_command(text).map(ClipboardMethod::Command)
+}
+
+/// Build the OSC 52 "set clipboard" sequence for base64 `payload`, wrapped
+/// in tmux's passthrough escape when running inside tmux
+pub fn osc52_sequence(payload: &str, in_tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", payload);
+    if in_tmux {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+/// Whether the terminal is likely to understand OSC 52
+fn osc52_supported() -> bool {
+    let term = std::env::var("TERM").unwrap_or_default();
+    io::stdout().is_terminal() && term != "dumb" && term != "linux"
+}
+
+/// Pipe text into the first available clipboard program
+fn copy_with_command(text: &str) -> io::Result<&'static str> {
+    let mut candidates: Vec<(&'static str, &[&str])> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        candidates.push(("wl-copy", &[]));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        candidates.push(("xclip", &["-selection", "clipboard"]));
+    }
+
+    for (program, args) in candidates {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            continue; // Not installed
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(program);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "no clipboard available (terminal lacks OSC 52; install wl-copy or xclip)",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_sequence() {
+        let payload = STANDARD.encode("cargo test");
+        assert_eq!(payload, "Y2FyZ28gdGVzdA==");
+        assert_eq!(
+            osc52_sequence(&payload, false),
+            "\x1b]52;c;Y2FyZ28gdGVzdA==\x07"
+        );
+    }
+
+    #[test]
+    fn test_osc52_sequence_in_tmux() {
+        let sequence = osc52_sequence("eA==", true);
+        assert_eq!(sequence, "\x1bPtmux;\x1b\x1b]52;c;eA==\x07\x1b\\");
+    }
+}
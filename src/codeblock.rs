@@ -0,0 +1,409 @@
+//! Pluggable renderers for fenced code blocks
+//!
+//! A [`CodeBlockRenderer`] turns the source of a code block into something
+//! else to show in its place, e.g. a mermaid diagram drawn as box art or a
+//! diff coloured by what it adds and removes. The
+//! layout engine looks up the fence language (the first word of the info
+//! string) in a [`CodeBlockRenderers`] registry; blocks whose language has
+//! no renderer, or whose renderer gives up, are shown as code. Each call
+//! gets a [`BlockContext`] with the block's width and what the reader chose
+//! for it, e.g. which nodes of a tree they folded.
+//!
+//! The document itself is never changed: copying or yanking a rendered
+//! block still gives its source, and [`DiagramSources`] flips rendered
+//! blocks back to showing it (or, for renderers that aren't
+//! [rendered by default](CodeBlockRenderer::rendered_by_default), code
+//! blocks to their rendering).
+//!
+//! ```
+//! use lumen::codeblock::{BlockContext, CodeBlockRenderers, Rendered};
+//!
+//! let mut renderers = CodeBlockRenderers::default();
+//! renderers.register("shout", |code: &str, _block: &BlockContext| {
+//!     Some(Rendered::Text(code.to_uppercase()))
+//! });
+//! assert!(renderers.get("mermaid").is_some());
+//! assert!(renderers.get("shout").is_some());
+//! ```
+
+use crate::ir::{Alignment, TableCell};
+use crate::json_tree::{Syntax, TreeRenderer};
+use crate::parser::CsvRenderer;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
+
+/// What a renderer shows instead of the code. Either way the block becomes
+/// a diagram: framed like a code block, but never wrapped, numbered or
+/// searched as code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rendered {
+    /// Preformatted text (box art) in the code block colours
+    Text(String),
+    /// Rows of text whose parts are coloured by what they draw
+    Diagram(Vec<DiagramLine>),
+    /// Diagram rows grouped into nodes the reader can fold
+    Tree(Tree),
+    /// A table, laid out like a Markdown table in the theme's table style
+    Table {
+        headers: Vec<TableCell>,
+        rows: Vec<Vec<TableCell>>,
+        alignment: Vec<Alignment>,
+    },
+}
+
+impl Rendered {
+    /// The rows to show, as diagram lines; `None` for tables, which are
+    /// laid out as tables instead
+    pub fn into_lines(self) -> Option<Vec<DiagramLine>> {
+        match self {
+            Rendered::Text(text) => Some(text.lines().map(DiagramLine::plain).collect()),
+            Rendered::Diagram(lines) => Some(lines),
+            Rendered::Tree(tree) => Some(tree.lines),
+            Rendered::Table { .. } => None,
+        }
+    }
+}
+
+/// One node of a [`Tree`], e.g. an object member of a JSON block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeNode {
+    /// Path from the root, e.g. `$.items[2].name`
+    pub path: String,
+    /// Rows the node covers, closing bracket included
+    pub rows: Range<usize>,
+    /// Nodes with children can be folded
+    pub foldable: bool,
+    pub folded: bool,
+}
+
+/// Rows drawn by a renderer, with the nodes those rows belong to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tree {
+    pub lines: Vec<DiagramLine>,
+    pub nodes: Vec<TreeNode>,
+}
+
+/// Why a block's source is invalid, and where (1-based)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// The block being rendered, as the reader left it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockContext {
+    /// Columns available inside the block's frame
+    pub width: u16,
+    /// Whether the reader asked for the block side by side (`S`)
+    pub split: bool,
+    /// Paths of [`TreeNode`]s the reader flipped from how they start
+    pub toggled: BTreeSet<String>,
+}
+
+impl BlockContext {
+    /// A block `width` columns wide that the reader hasn't touched
+    pub fn new(width: u16) -> Self {
+        Self {
+            width,
+            ..Self::default()
+        }
+    }
+}
+
+/// What part of a diagram a piece of text draws; the theme's `diagram`
+/// slots colour each role
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagramRole {
+    /// Anything else (chart text), in the code block foreground
+    #[default]
+    Text,
+    /// Outlines of nodes, boxes, groups and frames
+    NodeBorder,
+    /// Text inside nodes, boxes, group titles and notes
+    NodeLabel,
+    /// Lines between nodes
+    Edge,
+    /// Arrowheads and other line ends
+    Arrowhead,
+    /// Text on or beside lines
+    EdgeLabel,
+    /// Lines a diff adds
+    Added,
+    /// Lines a diff removes
+    Removed,
+    /// The words that changed within an added line
+    AddedWord,
+    /// The words that changed within a removed line
+    RemovedWord,
+    /// `@@ -1,4 +1,5 @@` lines starting each hunk of a diff
+    HunkHeader,
+    /// `diff --git`, `---` and `+++` lines naming the files a diff changes
+    FileHeader,
+}
+
+/// A run of diagram text drawing one role
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagramSpan {
+    pub text: String,
+    pub role: DiagramRole,
+}
+
+/// One row of a rendered diagram
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagramLine {
+    pub spans: Vec<DiagramSpan>,
+}
+
+impl DiagramLine {
+    /// A row that is all [`DiagramRole::Text`]
+    pub fn plain(text: &str) -> Self {
+        let mut line = Self::default();
+        line.push(text, DiagramRole::Text);
+        line
+    }
+
+    /// Append text, merging it into the last span when the role is the same
+    pub fn push(&mut self, text: &str, role: DiagramRole) {
+        if text.is_empty() {
+            return;
+        }
+        match self.spans.last_mut() {
+            Some(last) if last.role == role => last.text.push_str(text),
+            _ => self.spans.push(DiagramSpan {
+                text: text.to_string(),
+                role,
+            }),
+        }
+    }
+
+    /// The row's text without roles
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+}
+
+/// Code blocks the reader flipped between their rendering and their source,
+/// by the source they have (so the choice survives reloads that leave it
+/// alone)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DiagramSources {
+    shown: BTreeSet<u64>,
+}
+
+impl DiagramSources {
+    fn key(code: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether blocks with this source were flipped from how they start
+    pub fn contains(&self, code: &str) -> bool {
+        self.shown.contains(&Self::key(code))
+    }
+
+    /// Flip blocks with this code between rendering and source; returns
+    /// whether they are now flipped
+    pub fn toggle(&mut self, code: &str) -> bool {
+        let key = Self::key(code);
+        if self.shown.remove(&key) {
+            false
+        } else {
+            self.shown.insert(key);
+            true
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shown.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.shown.clear();
+    }
+}
+
+/// Renders code blocks of one or more fence languages
+pub trait CodeBlockRenderer: Send + Sync {
+    /// Render a block's source. Returns `None` to fall back to showing the
+    /// source, e.g. when it can't be parsed.
+    fn render(&self, code: &str, block: &BlockContext) -> Option<Rendered>;
+
+    /// Where the source is invalid, marked under the line when the block
+    /// is shown as code
+    fn source_error(&self, _code: &str) -> Option<SourceError> {
+        None
+    }
+
+    /// Whether blocks start rendered. Renderers that offer another view of
+    /// readable source (a JSON tree) return `false`, so their blocks start
+    /// as code and [`DiagramSources`] flips them to the rendering.
+    fn rendered_by_default(&self) -> bool {
+        true
+    }
+}
+
+impl<F> CodeBlockRenderer for F
+where
+    F: Fn(&str, &BlockContext) -> Option<Rendered> + Send + Sync,
+{
+    fn render(&self, code: &str, block: &BlockContext) -> Option<Rendered> {
+        self(code, block)
+    }
+}
+
+/// Code block renderers by fence language.
+///
+/// The default registry has Lumen's built-in renderers (`mermaid`,
+/// `diff`/`patch`, `dot`/`graphviz`, `csv`/`tsv` and `json`/`yaml`);
+/// [`CodeBlockRenderers::empty`] has none, so every block shows as code.
+#[derive(Clone)]
+pub struct CodeBlockRenderers {
+    renderers: BTreeMap<String, Arc<dyn CodeBlockRenderer>>,
+}
+
+impl CodeBlockRenderers {
+    /// A registry without any renderers
+    pub fn empty() -> Self {
+        Self {
+            renderers: BTreeMap::new(),
+        }
+    }
+
+    /// The built-in renderers, shared
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<CodeBlockRenderers> = OnceLock::new();
+        BUILTIN.get_or_init(|| {
+            let mut renderers = Self::empty();
+            renderers.register("mermaid", crate::mermaid::MermaidRenderer);
+            renderers.register("diff", crate::diff::DiffRenderer);
+            renderers.register("patch", crate::diff::DiffRenderer);
+            renderers.register("dot", crate::dot::DotRenderer);
+            renderers.register("graphviz", crate::dot::DotRenderer);
+            renderers.register("csv", CsvRenderer(','));
+            renderers.register("tsv", CsvRenderer('\t'));
+            renderers.register("json", TreeRenderer(Syntax::Json));
+            renderers.register("yaml", TreeRenderer(Syntax::Yaml));
+            renderers.register("yml", TreeRenderer(Syntax::Yaml));
+            renderers
+        })
+    }
+
+    /// Render blocks fenced as `lang` (case-insensitive) with `renderer`,
+    /// replacing any renderer registered for it before
+    pub fn register(&mut self, lang: &str, renderer: impl CodeBlockRenderer + 'static) {
+        self.renderers
+            .insert(lang.to_lowercase(), Arc::new(renderer));
+    }
+
+    /// Stop rendering `lang` blocks; returns whether it had a renderer
+    pub fn unregister(&mut self, lang: &str) -> bool {
+        self.renderers.remove(&lang.to_lowercase()).is_some()
+    }
+
+    /// The renderer for a fence language
+    pub fn get(&self, lang: &str) -> Option<&dyn CodeBlockRenderer> {
+        self.renderers
+            .get(&lang.to_lowercase())
+            .map(|renderer| renderer.as_ref())
+    }
+
+    /// Render a block of fence language `lang`, if it has a renderer that
+    /// can handle `code`
+    pub fn render(&self, lang: &str, code: &str, block: &BlockContext) -> Option<Rendered> {
+        self.get(lang)?.render(code, block)
+    }
+
+    /// Languages with a renderer, sorted
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.renderers.keys().map(String::as_str)
+    }
+
+    /// Hash of the languages and which renderer each has, so layouts
+    /// cached with one registry aren't reused with another
+    pub(crate) fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for (lang, renderer) in &self.renderers {
+            lang.hash(&mut hasher);
+            (Arc::as_ptr(renderer) as *const () as usize).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+impl Default for CodeBlockRenderers {
+    fn default() -> Self {
+        Self::builtin().clone()
+    }
+}
+
+impl fmt::Debug for CodeBlockRenderers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.languages()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_lookup_and_override() {
+        let mut renderers = CodeBlockRenderers::default();
+        assert_eq!(
+            renderers.languages().collect::<Vec<_>>(),
+            ["csv", "diff", "dot", "graphviz", "json", "mermaid", "patch", "tsv", "yaml", "yml"]
+        );
+        let block = BlockContext::new(40);
+        assert!(renderers
+            .render("Mermaid", "graph LR\nA --> B", &block)
+            .is_some());
+        // Unparseable source falls back to code
+        assert!(renderers
+            .render("mermaid", "not a diagram", &block)
+            .is_none());
+
+        renderers.register("mermaid", |_: &str, block: &BlockContext| {
+            Some(Rendered::Text(format!("{} columns", block.width)))
+        });
+        assert_eq!(
+            renderers.render("mermaid", "graph LR\nA --> B", &block),
+            Some(Rendered::Text("40 columns".to_string()))
+        );
+
+        assert!(renderers.unregister("mermaid"));
+        assert!(renderers
+            .render("mermaid", "graph LR\nA --> B", &block)
+            .is_none());
+        assert!(CodeBlockRenderers::empty().get("mermaid").is_none());
+    }
+
+    #[test]
+    fn test_diagram_lines_merge_roles_and_toggle_sources() {
+        let mut line = DiagramLine::default();
+        line.push("┌─", DiagramRole::NodeBorder);
+        line.push("─┐", DiagramRole::NodeBorder);
+        line.push("──▶", DiagramRole::Edge);
+        assert_eq!(line.spans.len(), 2);
+        assert_eq!(line.text(), "┌──┐──▶");
+        assert_eq!(
+            Rendered::Text("a\nb".into()).into_lines(),
+            Some(vec![DiagramLine::plain("a"), DiagramLine::plain("b")])
+        );
+
+        let mut sources = DiagramSources::default();
+        assert!(sources.toggle("graph LR\nA --> B"));
+        assert!(sources.contains("graph LR\nA --> B"));
+        assert!(!sources.contains("graph LR\nA --> C"));
+        assert!(!sources.toggle("graph LR\nA --> B"));
+        assert!(sources.is_empty());
+    }
+}
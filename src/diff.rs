@@ -0,0 +1,778 @@
+//! Unified diff renderer — colours ` ```diff ` and ` ```patch ` code blocks.
+//!
+//! Added and removed lines take the theme's success and error colours,
+//! hunk (`@@ -1,4 +1,5 @@`) and file (`diff --git`, `---`, `+++`) headers
+//! are set apart, and when a removed line is replaced by a similar added
+//! line the words that changed are picked out. Blocks flipped with
+//! [`SplitDiffs`] show the old and new text side by side instead, when the
+//! block is at least [`MIN_SPLIT_WIDTH`] columns wide.
+//!
+//! Hand-written diffs are common in review comments, so hunk headers are
+//! optional and their line counts are only trusted while they add up.
+
+use crate::codeblock::{BlockContext, CodeBlockRenderer, DiagramLine, DiagramRole, Rendered};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// ── Public API ──────────────────────────────────────────────────────────────
+
+/// Narrowest content area (in columns) that side-by-side diffs are drawn
+/// in; narrower blocks stay inline
+pub const MIN_SPLIT_WIDTH: u16 = 60;
+
+/// Fence languages drawn as diffs
+pub fn is_diff(lang: &str) -> bool {
+    lang.eq_ignore_ascii_case("diff") || lang.eq_ignore_ascii_case("patch")
+}
+
+/// A diff as it is written (one row per line), coloured
+pub fn render_diff_lines(code: &str) -> Vec<DiagramLine> {
+    let mut lines = Vec::new();
+    for row in parse(code) {
+        match row {
+            Row::FileHeader(text) => lines.push(single(&text, DiagramRole::FileHeader)),
+            Row::HunkHeader(text) => lines.push(single(&text, DiagramRole::HunkHeader)),
+            Row::Other(text) => lines.push(single(&text, DiagramRole::Text)),
+            Row::Context { text, .. } => {
+                lines.push(single(&format!(" {}", text), DiagramRole::Text))
+            }
+            Row::Change { removed, added } => {
+                let (removed, added, _) = highlight_words(&removed, &added);
+                for words in removed {
+                    lines.push(signed('-', &words, DiagramRole::Removed));
+                }
+                for words in added {
+                    lines.push(signed('+', &words, DiagramRole::Added));
+                }
+            }
+        }
+    }
+    lines
+}
+
+/// A diff with the old text on the left and the new on the right, for a
+/// content area `width` columns wide. Returns `None` if that is narrower
+/// than [`MIN_SPLIT_WIDTH`].
+pub fn render_split_diff_lines(code: &str, width: u16) -> Option<Vec<DiagramLine>> {
+    if width < MIN_SPLIT_WIDTH {
+        return None;
+    }
+    let rows = parse(code);
+    let number_width = rows
+        .iter()
+        .flat_map(|row| match row {
+            Row::Context { old, new, .. } => vec![*old, *new],
+            Row::Change { removed, added } => removed
+                .iter()
+                .chain(added)
+                .map(|line| line.number)
+                .collect(),
+            _ => Vec::new(),
+        })
+        .flatten()
+        .max()
+        .map_or(0, |n| n.to_string().len());
+    let left = (width as usize - SEPARATOR.width()) / 2;
+    let right = width as usize - SEPARATOR.width() - left;
+    let split = Split {
+        number_width,
+        left,
+        right,
+    };
+
+    let mut lines = Vec::new();
+    for row in rows {
+        match row {
+            Row::FileHeader(text) => lines.push(single(&text, DiagramRole::FileHeader)),
+            Row::HunkHeader(text) => lines.push(single(&text, DiagramRole::HunkHeader)),
+            Row::Other(text) => lines.push(single(&text, DiagramRole::Text)),
+            Row::Context { old, new, text } => {
+                let words = [(text, false)];
+                split.push(
+                    &mut lines,
+                    Some((old, ' ', &words[..], DiagramRole::Text)),
+                    Some((new, ' ', &words[..], DiagramRole::Text)),
+                );
+            }
+            Row::Change { removed, added } => {
+                let (removed_words, added_words, pairs) = highlight_words(&removed, &added);
+                for (i, j) in side_by_side(removed.len(), added.len(), &pairs) {
+                    split.push(
+                        &mut lines,
+                        i.map(|i| {
+                            let words = &removed_words[i][..];
+                            (removed[i].number, '-', words, DiagramRole::Removed)
+                        }),
+                        j.map(|j| {
+                            (
+                                added[j].number,
+                                '+',
+                                &added_words[j][..],
+                                DiagramRole::Added,
+                            )
+                        }),
+                    );
+                }
+            }
+        }
+    }
+    Some(lines)
+}
+
+/// Renders ` ```diff ` and ` ```patch ` code blocks, inline or side by side
+/// when split; registered by default in
+/// [`CodeBlockRenderers`](crate::codeblock::CodeBlockRenderers)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiffRenderer;
+
+impl CodeBlockRenderer for DiffRenderer {
+    fn render(&self, code: &str, block: &BlockContext) -> Option<Rendered> {
+        // Split diffs stay inline when the block is too narrow
+        let split = block
+            .split
+            .then(|| render_split_diff_lines(code, block.width))
+            .flatten();
+        Some(Rendered::Diagram(
+            split.unwrap_or_else(|| render_diff_lines(code)),
+        ))
+    }
+}
+
+/// Diff blocks the reader switched to side by side, by their source (so
+/// the choice survives reloads that leave it alone)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SplitDiffs {
+    split: BTreeSet<u64>,
+}
+
+impl SplitDiffs {
+    fn key(code: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether a diff with this source is shown side by side
+    pub fn contains(&self, code: &str) -> bool {
+        self.split.contains(&Self::key(code))
+    }
+
+    /// Show diffs with this source side by side, or inline again; returns
+    /// whether they are now side by side
+    pub fn toggle(&mut self, code: &str) -> bool {
+        let key = Self::key(code);
+        if self.split.remove(&key) {
+            false
+        } else {
+            self.split.insert(key);
+            true
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.split.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.split.clear();
+    }
+}
+
+// ── Parsing ─────────────────────────────────────────────────────────────────
+
+/// A removed or added line: its number in the old or new file (when a
+/// hunk header says where it is) and its text without the `-`/`+`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ChangedLine {
+    number: Option<usize>,
+    text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Row {
+    FileHeader(String),
+    HunkHeader(String),
+    Context {
+        old: Option<usize>,
+        new: Option<usize>,
+        text: String,
+    },
+    /// A run of removed lines and the added lines that replace them
+    Change {
+        removed: Vec<ChangedLine>,
+        added: Vec<ChangedLine>,
+    },
+    /// `\ No newline at end of file`, commit messages and the like
+    Other(String),
+}
+
+fn parse(code: &str) -> Vec<Row> {
+    let lines: Vec<String> = code
+        .lines()
+        .map(|line| line.replace('\t', "    "))
+        .collect();
+    let mut rows = Vec::new();
+    let mut old = None;
+    let mut new = None;
+    // Lines left in the hunk by its header's counts (old, new)
+    let mut remaining: Option<(usize, usize)> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(hunk) = hunk_header(line) {
+            rows.push(Row::HunkHeader(line.clone()));
+            old = Some(hunk.old);
+            new = Some(hunk.new);
+            remaining = hunk.counts;
+            continue;
+        }
+        let in_counted_hunk = remaining.is_some_and(|(o, n)| o + n > 0);
+        let next = lines.get(i + 1).map(String::as_str);
+        let previous = i.checked_sub(1).map(|p| lines[p].as_str());
+        if !in_counted_hunk && is_file_header(line, previous, next) {
+            rows.push(Row::FileHeader(line.clone()));
+            continue;
+        }
+
+        let take = |number: &mut Option<usize>| {
+            let current = *number;
+            *number = number.map(|n| n + 1);
+            current
+        };
+        match line.chars().next() {
+            Some('-') => {
+                let line = ChangedLine {
+                    number: take(&mut old),
+                    text: line[1..].to_string(),
+                };
+                match rows.last_mut() {
+                    Some(Row::Change { removed, added }) if added.is_empty() => removed.push(line),
+                    _ => rows.push(Row::Change {
+                        removed: vec![line],
+                        added: Vec::new(),
+                    }),
+                }
+                count_down(&mut remaining, true, false);
+            }
+            Some('+') => {
+                let line = ChangedLine {
+                    number: take(&mut new),
+                    text: line[1..].to_string(),
+                };
+                match rows.last_mut() {
+                    Some(Row::Change { added, .. }) => added.push(line),
+                    _ => rows.push(Row::Change {
+                        removed: Vec::new(),
+                        added: vec![line],
+                    }),
+                }
+                count_down(&mut remaining, false, true);
+            }
+            // Some tools strip the space from blank context lines
+            Some(' ') | None => {
+                rows.push(Row::Context {
+                    old: take(&mut old),
+                    new: take(&mut new),
+                    text: line.get(1..).unwrap_or("").to_string(),
+                });
+                count_down(&mut remaining, true, true);
+            }
+            _ => rows.push(Row::Other(line.clone())),
+        }
+    }
+    rows
+}
+
+fn count_down(remaining: &mut Option<(usize, usize)>, old: bool, new: bool) {
+    if let Some((o, n)) = remaining {
+        *o = o.saturating_sub(old as usize);
+        *n = n.saturating_sub(new as usize);
+    }
+}
+
+/// Where a hunk starts, from its header
+struct Hunk {
+    /// First line number in the old and new file
+    old: usize,
+    new: usize,
+    /// How many old and new lines the hunk holds, if the header says
+    counts: Option<(usize, usize)>,
+}
+
+/// `@@ -12,5 +12,6 @@ fn main` → lines 12 and 12, counts 5 and 6 (a
+/// count left out is 1)
+fn hunk_header(line: &str) -> Option<Hunk> {
+    let rest = line.strip_prefix("@@")?;
+    let mut parts = rest.split_whitespace();
+    let range = |part: Option<&str>, sign: char| -> Option<(usize, usize)> {
+        let part = part?.strip_prefix(sign)?;
+        match part.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((part.parse().ok()?, 1)),
+        }
+    };
+    match (range(parts.next(), '-'), range(parts.next(), '+')) {
+        (Some((old, old_count)), Some((new, new_count))) => {
+            Some((old, new, Some((old_count, new_count))))
+        }
+        // `@@ @@` or `@@ ... @@` as people write by hand
+        _ => Some((1, 1, None)).filter(|_| rest.trim_end().ends_with("@@")),
+    }
+    .map(|(old, new, counts)| Hunk {
+        old: old.max(1),
+        new: new.max(1),
+        counts,
+    })
+}
+
+/// Lines git writes before a file's hunks. `---`/`+++` only count as a
+/// pair, since a lone `---` may be a removed `--` line.
+fn is_file_header(line: &str, previous: Option<&str>, next: Option<&str>) -> bool {
+    const PREFIXES: &[&str] = &[
+        "diff ",
+        "index ",
+        "new file mode",
+        "deleted file mode",
+        "old mode",
+        "new mode",
+        "similarity index",
+        "rename from",
+        "rename to",
+        "Binary files",
+    ];
+    PREFIXES.iter().any(|prefix| line.starts_with(prefix))
+        || (line.starts_with("--- ") && next.is_some_and(|next| next.starts_with("+++ ")))
+        || (line.starts_with("+++ ") && previous.is_some_and(|prev| prev.starts_with("--- ")))
+}
+
+// ── Word-level changes ──────────────────────────────────────────────────────
+
+/// Pieces of a line, each marked if it changed
+type Words = Vec<(String, bool)>;
+
+/// Pair each removed line with the next added line similar to it, in
+/// order, and mark the words that differ between them. Only the next
+/// [`PAIR_WINDOW`] added lines are tried, so large hunks stay linear.
+/// Lines with no similar partner are left unmarked: the whole line
+/// changed. Also returns the pairs, as (removed, added) indices.
+fn highlight_words(
+    removed: &[ChangedLine],
+    added: &[ChangedLine],
+) -> (Vec<Words>, Vec<Words>, Vec<(usize, usize)>) {
+    let whole = |line: &ChangedLine| vec![(line.text.clone(), false)];
+    let mut old: Vec<Words> = removed.iter().map(whole).collect();
+    let mut new: Vec<Words> = added.iter().map(whole).collect();
+    let mut pairs = Vec::new();
+    let mut next = 0;
+    for (i, before) in removed.iter().enumerate() {
+        for (j, after) in added.iter().enumerate().skip(next).take(PAIR_WINDOW) {
+            if let Some((before, after)) = word_diff(&before.text, &after.text) {
+                old[i] = before;
+                new[j] = after;
+                pairs.push((i, j));
+                next = j + 1;
+                break;
+            }
+        }
+    }
+    (old, new, pairs)
+}
+
+/// How many added lines a removed line is compared with for a partner
+const PAIR_WINDOW: usize = 8;
+
+/// Rows of a side-by-side change: paired lines share a row, and the lines
+/// between pairs fill rows from the top down on each side
+fn side_by_side(
+    removed: usize,
+    added: usize,
+    pairs: &[(usize, usize)],
+) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut rows = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for &(pair_i, pair_j) in pairs.iter().chain([(removed, added)].iter()) {
+        let gap = (pair_i - i).max(pair_j - j);
+        for k in 0..gap {
+            rows.push((
+                Some(i + k).filter(|&r| r < pair_i),
+                Some(j + k).filter(|&a| a < pair_j),
+            ));
+        }
+        if pair_i < removed {
+            rows.push((Some(pair_i), Some(pair_j)));
+        }
+        i = pair_i + 1;
+        j = pair_j + 1;
+    }
+    rows
+}
+
+/// Lines with more tokens than this aren't compared word by word
+const MAX_WORD_DIFF_TOKENS: usize = 400;
+
+/// Mark the tokens of `old` and `new` outside their longest common
+/// subsequence. `None` if the lines have too little in common for the
+/// marks to help.
+fn word_diff(old: &str, new: &str) -> Option<(Words, Words)> {
+    let a = tokens(old);
+    let b = tokens(new);
+    if a.len() > MAX_WORD_DIFF_TOKENS || b.len() > MAX_WORD_DIFF_TOKENS {
+        return None;
+    }
+    // The words both lines have bound how much they can share, so lines
+    // that are too different skip the table
+    let visible = |s: &str| s.chars().filter(|c| !c.is_whitespace()).count();
+    let mut unmatched: HashMap<&str, usize> = HashMap::new();
+    for token in &b {
+        *unmatched.entry(token).or_default() += 1;
+    }
+    let shared: usize = a
+        .iter()
+        .filter(|token| !token.trim().is_empty())
+        .filter(|token| match unmatched.get_mut(*token) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        })
+        .map(|token| token.len())
+        .sum();
+    if shared * 2 < visible(old).max(visible(new)) {
+        return None;
+    }
+
+    // lcs[i][j]: common subsequence length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut keep_a = vec![false; a.len()];
+    let mut keep_b = vec![false; b.len()];
+    let (mut i, mut j) = (0, 0);
+    let mut common = 0;
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            keep_a[i] = true;
+            keep_b[j] = true;
+            if !a[i].trim().is_empty() {
+                common += a[i].len();
+            }
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    if common * 2 < visible(old).max(visible(new))
+        || (keep_a.iter().all(|k| *k) && keep_b.iter().all(|k| *k))
+    {
+        return None;
+    }
+    Some((marked(&a, &keep_a), marked(&b, &keep_b)))
+}
+
+/// Words, runs of spaces, and single punctuation characters
+fn tokens(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let same_kind = |next: char| {
+            (is_word(c) && is_word(next)) || (c.is_whitespace() && next.is_whitespace())
+        };
+        match chars.peek() {
+            Some(&(_, next)) if same_kind(next) => {}
+            _ => {
+                let end = i + c.len_utf8();
+                tokens.push(&line[start..end]);
+                start = end;
+            }
+        }
+    }
+    tokens
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Join tokens into runs, marking the ones not kept. Spaces between two
+/// changed tokens count as changed, so `a b` → `x y` is one run.
+fn marked(tokens: &[&str], keep: &[bool]) -> Words {
+    let mut changed: Vec<bool> = keep.iter().map(|k| !k).collect();
+    for i in 1..tokens.len().saturating_sub(1) {
+        if tokens[i].trim().is_empty() && changed[i - 1] && changed[i + 1] {
+            changed[i] = true;
+        }
+    }
+    let mut words: Words = Vec::new();
+    for (token, changed) in tokens.iter().zip(changed) {
+        match words.last_mut() {
+            Some((text, last)) if *last == changed => text.push_str(token),
+            _ => words.push((token.to_string(), changed)),
+        }
+    }
+    words
+}
+
+// ── Drawing ─────────────────────────────────────────────────────────────────
+
+const SEPARATOR: &str = " │ ";
+
+fn single(text: &str, role: DiagramRole) -> DiagramLine {
+    let mut line = DiagramLine::default();
+    line.push(text, role);
+    line
+}
+
+/// The role for the changed words of a line drawn in `role`
+fn word_role(role: DiagramRole) -> DiagramRole {
+    match role {
+        DiagramRole::Added => DiagramRole::AddedWord,
+        DiagramRole::Removed => DiagramRole::RemovedWord,
+        other => other,
+    }
+}
+
+/// An inline `-`/`+` line
+fn signed(sign: char, words: &Words, role: DiagramRole) -> DiagramLine {
+    let mut line = DiagramLine::default();
+    line.push(&sign.to_string(), role);
+    for (text, changed) in words {
+        line.push(text, if *changed { word_role(role) } else { role });
+    }
+    line
+}
+
+/// Column widths of a side-by-side diff
+struct Split {
+    number_width: usize,
+    left: usize,
+    right: usize,
+}
+
+/// One side of a row: line number, sign, words and the line's role
+type Side<'a> = (Option<usize>, char, &'a [(String, bool)], DiagramRole);
+
+impl Split {
+    /// Draw a row, wrapping each side to its column; a missing side is
+    /// left blank
+    fn push(&self, lines: &mut Vec<DiagramLine>, left: Option<Side>, right: Option<Side>) {
+        let left = self.side(left, self.left);
+        let right = self.side(right, self.right);
+        for i in 0..left.len().max(right.len()) {
+            let mut line = DiagramLine::default();
+            match left.get(i) {
+                Some(row) => append(&mut line, row),
+                None => line.push(&" ".repeat(self.left), DiagramRole::Text),
+            }
+            line.push(SEPARATOR, DiagramRole::Edge);
+            if let Some(row) = right.get(i) {
+                append(&mut line, row);
+            }
+            lines.push(line);
+        }
+    }
+
+    /// The rows of one side, each padded to `width`
+    fn side(&self, side: Option<Side>, width: usize) -> Vec<DiagramLine> {
+        let Some((number, sign, words, role)) = side else {
+            return Vec::new();
+        };
+        // `12 + ` before the text, blank on wrapped rows
+        let gutter = if self.number_width > 0 {
+            self.number_width + 3
+        } else {
+            2
+        };
+        let text_width = width.saturating_sub(gutter).max(1);
+        let pieces = wrap(words, text_width);
+
+        let mut rows = Vec::new();
+        for (i, piece) in pieces.iter().enumerate() {
+            let mut line = DiagramLine::default();
+            if i == 0 {
+                if self.number_width > 0 {
+                    let number = number.map_or(String::new(), |n| n.to_string());
+                    line.push(
+                        &format!("{:>width$} ", number, width = self.number_width),
+                        DiagramRole::Edge,
+                    );
+                }
+                line.push(&format!("{} ", sign), role);
+            } else {
+                line.push(&" ".repeat(gutter), DiagramRole::Text);
+            }
+            let mut used = gutter;
+            for (text, changed) in piece {
+                line.push(text, if *changed { word_role(role) } else { role });
+                used += text.width();
+            }
+            line.push(&" ".repeat(width.saturating_sub(used)), DiagramRole::Text);
+            rows.push(line);
+        }
+        rows
+    }
+}
+
+fn append(line: &mut DiagramLine, other: &DiagramLine) {
+    for span in &other.spans {
+        line.push(&span.text, span.role);
+    }
+}
+
+/// Split marked words into rows at most `width` columns wide
+fn wrap(words: &[(String, bool)], width: usize) -> Vec<Words> {
+    let mut rows: Vec<Words> = vec![Vec::new()];
+    let mut used = 0;
+    for (text, changed) in words {
+        for c in text.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > width && used > 0 {
+                rows.push(Vec::new());
+                used = 0;
+            }
+            let row = rows.last_mut().expect("rows is never empty");
+            match row.last_mut() {
+                Some((run, last)) if last == changed => run.push(c),
+                _ => row.push((c.to_string(), *changed)),
+            }
+            used += w;
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+diff --git a/src/main.rs b/src/main.rs
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    let x = 1;
++    let x = 2;
+ }
+";
+
+    fn roles(line: &DiagramLine) -> Vec<(&str, DiagramRole)> {
+        line.spans
+            .iter()
+            .map(|span| (span.text.as_str(), span.role))
+            .collect()
+    }
+
+    #[test]
+    fn test_inline_roles_and_changed_words() {
+        let lines = render_diff_lines(PATCH);
+        let text: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(text.join("\n"), PATCH.trim_end());
+        assert_eq!(lines[0].spans[0].role, DiagramRole::FileHeader);
+        assert_eq!(lines[2].spans[0].role, DiagramRole::FileHeader);
+        assert_eq!(lines[3].spans[0].role, DiagramRole::HunkHeader);
+        assert_eq!(lines[4].spans[0].role, DiagramRole::Text);
+        assert_eq!(
+            roles(&lines[5]),
+            [
+                ("-    let x = ", DiagramRole::Removed),
+                ("1", DiagramRole::RemovedWord),
+                (";", DiagramRole::Removed),
+            ]
+        );
+        assert_eq!(
+            roles(&lines[6]),
+            [
+                ("+    let x = ", DiagramRole::Added),
+                ("2", DiagramRole::AddedWord),
+                (";", DiagramRole::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_headers_need_context() {
+        // Inside a counted hunk, `--- ` is a removed line
+        let lines = render_diff_lines("@@ -1,2 +1 @@\n--- old\n--- older\n+new");
+        assert_eq!(lines[1].spans[0].role, DiagramRole::Removed);
+        // Hand-written diffs without hunk headers still get colours, and
+        // unrelated lines aren't word-diffed
+        let lines = render_diff_lines("-alpha beta\n+something else entirely");
+        assert_eq!(roles(&lines[0]), [("-alpha beta", DiagramRole::Removed)]);
+        assert_eq!(lines[1].spans.len(), 1);
+    }
+
+    #[test]
+    fn test_large_hunks_stay_fast() {
+        // 1000 lines replaced by the same words in reverse: every pair of
+        // lines has to be compared, and none pairs up
+        let words: Vec<String> = (0..5).map(|i| format!("word{i}")).collect();
+        let reversed: Vec<String> = words.iter().rev().cloned().collect();
+        let mut patch = String::from("@@ -1,1000 +1,1000 @@\n");
+        patch.extend((0..1000).map(|_| format!("- {}\n", words.join(" "))));
+        patch.extend((0..1000).map(|_| format!("+ {}\n", reversed.join(" "))));
+        let start = std::time::Instant::now();
+        let lines = render_diff_lines(&patch);
+        assert_eq!(lines.len(), 2001);
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_side_by_side() {
+        assert!(render_split_diff_lines(PATCH, MIN_SPLIT_WIDTH - 1).is_none());
+        let lines = render_split_diff_lines(PATCH, 60).unwrap();
+        // The renderer splits blocks the reader flipped, when they fit
+        let mut block = BlockContext::new(60);
+        block.split = true;
+        assert_eq!(
+            DiffRenderer.render(PATCH, &block),
+            Some(Rendered::Diagram(lines.clone()))
+        );
+        block.width = MIN_SPLIT_WIDTH - 1;
+        assert_eq!(
+            DiffRenderer.render(PATCH, &block),
+            Some(Rendered::Diagram(render_diff_lines(PATCH)))
+        );
+        let text: Vec<String> = lines.iter().map(|line| line.text()).collect();
+        assert_eq!(text[3], "@@ -1,3 +1,3 @@");
+        // 28 columns a side around the separator
+        assert_eq!(
+            text[4].trim_end(),
+            format!("{:<28} │ {}", "1   fn main() {", "1   fn main() {")
+        );
+        assert_eq!(
+            text[5].trim_end(),
+            format!("{:<28} │ {}", "2 -     let x = 1;", "2 +     let x = 2;")
+        );
+        assert!(text[4..].iter().all(|row| row.width() == 60));
+
+        // A replaced line sits opposite its replacement
+        assert_eq!(
+            side_by_side(1, 2, &[(0, 1)]),
+            [(None, Some(0)), (Some(0), Some(1))]
+        );
+        assert_eq!(
+            side_by_side(2, 1, &[]),
+            [(Some(0), Some(0)), (Some(1), None)]
+        );
+
+        // Long lines wrap within their side
+        let long = format!("@@ -1 +1 @@\n-{}\n+short", "x".repeat(40));
+        let lines = render_split_diff_lines(&long, 60).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].text().starts_with("    xxxx"));
+    }
+}
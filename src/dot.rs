@@ -0,0 +1,647 @@
+//! Graphviz DOT renderer — draws ` ```dot ` code blocks as ASCII/Unicode art.
+//!
+//! Parses the common DOT subset: `graph` and `digraph`, node and edge
+//! statements (chained `a -> b -> c`, `a -> {b c}`), `node`/`edge`/`graph`
+//! defaults, the `label`, `shape`, `style`, `dir` and `rankdir` attributes,
+//! and `cluster` subgraphs. The graph is handed to the mermaid flowchart
+//! layout, so DOT and mermaid graphs look the same; no `dot` binary is run.
+//! Anything else (ports, HTML table layouts, `rank=same`, colours) is read
+//! and ignored.
+
+use crate::codeblock::{BlockContext, CodeBlockRenderer, DiagramLine, Rendered};
+use crate::mermaid::{
+    parse_direction, render_graph, Direction, Edge, EdgeStyle, FlowGraph, Node, NodeShape, Subgraph,
+};
+use std::collections::HashMap;
+
+// ── Public API ──────────────────────────────────────────────────────────────
+
+/// Render a DOT graph to ASCII art. Returns `None` if it can't be parsed or
+/// has no nodes.
+pub fn render_dot(input: &str) -> Option<String> {
+    let lines = render_dot_lines(input)?;
+    Some(
+        lines
+            .iter()
+            .map(DiagramLine::text)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+/// Like [`render_dot`], with every part of the drawing tagged with its
+/// [`DiagramRole`](crate::codeblock::DiagramRole)
+pub fn render_dot_lines(input: &str) -> Option<Vec<DiagramLine>> {
+    let graph = parse_dot(input)?;
+    (!graph.nodes.is_empty()).then(|| render_graph(&graph))
+}
+
+/// Renders ` ```dot ` and ` ```graphviz ` code blocks; registered by
+/// default in [`CodeBlockRenderers`](crate::codeblock::CodeBlockRenderers)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DotRenderer;
+
+impl CodeBlockRenderer for DotRenderer {
+    fn render(&self, code: &str, _block: &BlockContext) -> Option<Rendered> {
+        render_dot_lines(code).map(Rendered::Diagram)
+    }
+}
+
+// ── Lexer ───────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A name, number, quoted string or HTML string (tags stripped)
+    Id(String),
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Equals,
+    /// `;` or `,`
+    Separator,
+    Colon,
+    /// `->` or `--`
+    EdgeOp,
+}
+
+fn tokenize(input: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line_start = true;
+    // After a `+` between quoted strings
+    let mut concat = false;
+    while i < chars.len() {
+        let c = chars[i];
+        // `#` lines are C preprocessor output
+        if line_start && c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        line_start = c == '\n' || (line_start && c.is_whitespace());
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            '{' | '}' | '[' | ']' | '=' | ';' | ',' | ':' => {
+                tokens.push(match c {
+                    '{' => Token::OpenBrace,
+                    '}' => Token::CloseBrace,
+                    '[' => Token::OpenBracket,
+                    ']' => Token::CloseBracket,
+                    '=' => Token::Equals,
+                    ':' => Token::Colon,
+                    _ => Token::Separator,
+                });
+                i += 1;
+            }
+            '-' if matches!(chars.get(i + 1), Some('>' | '-')) => {
+                tokens.push(Token::EdgeOp);
+                i += 2;
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && chars.get(i + 1) == Some(&'"') {
+                        i += 1;
+                    }
+                    text.push(chars[i]);
+                    i += 1;
+                }
+                i += 1;
+                // "a" + "b" concatenates
+                match tokens.last_mut() {
+                    Some(Token::Id(last)) if concat => last.push_str(&text),
+                    _ => tokens.push(Token::Id(text)),
+                }
+                concat = false;
+            }
+            '+' => {
+                concat = true;
+                i += 1;
+            }
+            '<' => {
+                let start = i;
+                let mut depth = 0;
+                while i < chars.len() {
+                    match chars[i] {
+                        '<' => depth += 1,
+                        '>' => depth -= 1,
+                        _ => {}
+                    }
+                    i += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                let html: String = chars[start + 1..i - 1].iter().collect();
+                tokens.push(Token::Id(html_text(&html)));
+            }
+            _ if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let start = i;
+                i += 1;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Id(chars[start..i].iter().collect()));
+            }
+            _ => return None,
+        }
+    }
+    Some(tokens)
+}
+
+/// The text of an HTML-like label: tags dropped, `<br/>` as a space
+fn html_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(open) = rest.find('<') {
+        text.push_str(&rest[..open]);
+        let close = rest[open..].find('>').map_or(rest.len(), |c| open + c + 1);
+        if rest[open..close].to_lowercase().starts_with("<br") {
+            text.push(' ');
+        }
+        rest = &rest[close..];
+    }
+    text.push_str(rest);
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// ── Parser ──────────────────────────────────────────────────────────────────
+
+type Attrs = HashMap<String, String>;
+
+/// Defaults set with `node [...]` and `edge [...]`, and the cluster new
+/// nodes join; subgraphs start from a copy of their parent's
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    node: Attrs,
+    edge: Attrs,
+    cluster: Option<usize>,
+}
+
+struct DotParser {
+    tokens: Vec<Token>,
+    pos: usize,
+    directed: bool,
+    graph: FlowGraph,
+    index: HashMap<String, usize>,
+    scopes: Vec<Scope>,
+}
+
+fn parse_dot(input: &str) -> Option<FlowGraph> {
+    let tokens = tokenize(input)?;
+    let mut parser = DotParser {
+        tokens,
+        pos: 0,
+        directed: false,
+        graph: FlowGraph {
+            // Graphviz draws top to bottom unless told otherwise
+            direction: Direction::TD,
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            subgraphs: Vec::new(),
+        },
+        index: HashMap::new(),
+        scopes: vec![Scope::default()],
+    };
+
+    // [strict] (graph | digraph) [ID] { ... }
+    parser.keyword("strict");
+    parser.directed = if parser.keyword("digraph") {
+        true
+    } else if parser.keyword("graph") {
+        false
+    } else {
+        return None;
+    };
+    if matches!(parser.peek(), Some(Token::Id(_))) {
+        parser.pos += 1;
+    }
+    if parser.next() != Some(Token::OpenBrace) {
+        return None;
+    }
+    parser.statements()?;
+    Some(parser.graph)
+}
+
+impl DotParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consume a (case-insensitive) keyword if it's next
+    fn keyword(&mut self, word: &str) -> bool {
+        match self.peek() {
+            Some(Token::Id(id)) if id.eq_ignore_ascii_case(word) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn scope(&self) -> &Scope {
+        self.scopes.last().expect("the graph's own scope")
+    }
+
+    /// Statements up to and including the closing `}`; the ids of the
+    /// nodes they mention
+    fn statements(&mut self) -> Option<Vec<String>> {
+        let mut members = Vec::new();
+        loop {
+            match self.peek()? {
+                Token::CloseBrace => {
+                    self.pos += 1;
+                    return Some(members);
+                }
+                Token::Separator => self.pos += 1,
+                _ => self.statement(&mut members)?,
+            }
+        }
+    }
+
+    fn statement(&mut self, members: &mut Vec<String>) -> Option<()> {
+        let Token::Id(word) = self.peek()?.clone() else {
+            // `{ a b } -> c`
+            let operand = self.operand()?;
+            return self.edges_from(operand, members);
+        };
+        let lower = word.to_lowercase();
+
+        if matches!(lower.as_str(), "graph" | "node" | "edge")
+            && self.tokens.get(self.pos + 1) == Some(&Token::OpenBracket)
+        {
+            self.pos += 1;
+            let attrs = self.attributes()?;
+            let scope = self.scopes.last_mut().expect("the graph's own scope");
+            match lower.as_str() {
+                "node" => scope.node.extend(attrs),
+                "edge" => scope.edge.extend(attrs),
+                _ => {
+                    for (key, value) in attrs {
+                        self.graph_attribute(&key, &value);
+                    }
+                }
+            }
+            return Some(());
+        }
+        if lower == "subgraph" {
+            let operand = self.operand()?;
+            return self.edges_from(operand, members);
+        }
+        if self.tokens.get(self.pos + 1) == Some(&Token::Equals) {
+            self.pos += 2;
+            let Some(Token::Id(value)) = self.next() else {
+                return None;
+            };
+            self.graph_attribute(&word, &value);
+            return Some(());
+        }
+
+        let operand = self.operand()?;
+        if self.peek() == Some(&Token::EdgeOp) {
+            return self.edges_from(operand, members);
+        }
+        // A node statement
+        let attrs = self.attributes()?;
+        for id in &operand {
+            self.apply_node_attributes(id, &attrs);
+        }
+        members.extend(operand);
+        Some(())
+    }
+
+    /// One side of an edge: a node (`a`, `a:port:n`) or a subgraph, as the
+    /// nodes it stands for
+    fn operand(&mut self) -> Option<Vec<String>> {
+        match self.peek()? {
+            Token::OpenBrace => {
+                self.pos += 1;
+                self.subgraph(None)
+            }
+            Token::Id(word) if word.eq_ignore_ascii_case("subgraph") => {
+                self.pos += 1;
+                let name = match self.peek()? {
+                    Token::Id(name) => {
+                        let name = name.clone();
+                        self.pos += 1;
+                        Some(name)
+                    }
+                    _ => None,
+                };
+                if self.next()? != Token::OpenBrace {
+                    return None;
+                }
+                self.subgraph(name)
+            }
+            Token::Id(id) => {
+                let id = id.clone();
+                self.pos += 1;
+                // Ports and compass points don't change the layout
+                while self.peek() == Some(&Token::Colon) {
+                    self.pos += 2;
+                }
+                self.node(&id);
+                Some(vec![id])
+            }
+            _ => None,
+        }
+    }
+
+    /// The body of a subgraph, after its `{`. Subgraphs named `cluster…`
+    /// are drawn as boxes; others only scope defaults.
+    fn subgraph(&mut self, name: Option<String>) -> Option<Vec<String>> {
+        let mut scope = self.scope().clone();
+        if let Some(name) = name.filter(|name| name.starts_with("cluster")) {
+            self.graph.subgraphs.push(Subgraph {
+                id: name,
+                title: String::new(),
+                parent: scope.cluster,
+            });
+            scope.cluster = Some(self.graph.subgraphs.len() - 1);
+        }
+        self.scopes.push(scope);
+        let members = self.statements();
+        self.scopes.pop();
+        members
+    }
+
+    /// `-> b -> { c d } [attrs]` after the first operand
+    fn edges_from(&mut self, first: Vec<String>, members: &mut Vec<String>) -> Option<()> {
+        let mut operands = vec![first];
+        while self.peek() == Some(&Token::EdgeOp) {
+            self.pos += 1;
+            operands.push(self.operand()?);
+        }
+        let mut attrs = self.scope().edge.clone();
+        attrs.extend(self.attributes()?);
+
+        let label = attrs
+            .get("label")
+            .or_else(|| attrs.get("xlabel"))
+            .map(|label| label_text(label, ""))
+            .filter(|label| !label.is_empty());
+        let style = attrs.get("style").map_or(EdgeStyle::Solid, |style| {
+            let style = style.to_lowercase();
+            if style.contains("invis") {
+                EdgeStyle::Invisible
+            } else if style.contains("dashed") || style.contains("dotted") {
+                EdgeStyle::Dotted
+            } else if style.contains("bold") {
+                EdgeStyle::Thick
+            } else {
+                EdgeStyle::Solid
+            }
+        });
+        let dir = attrs.get("dir").map(|dir| dir.to_lowercase());
+        let head = match dir.as_deref() {
+            Some("forward" | "both" | "back") => true,
+            Some("none") => false,
+            _ => self.directed,
+        };
+        let back = dir.as_deref() == Some("back");
+
+        for pair in operands.windows(2) {
+            for from in &pair[0] {
+                for to in &pair[1] {
+                    let (from, to) = if back { (to, from) } else { (from, to) };
+                    self.graph.edges.push(Edge {
+                        from: from.clone(),
+                        to: to.clone(),
+                        label: label.clone(),
+                        style,
+                        head,
+                    });
+                }
+            }
+        }
+        members.extend(operands.into_iter().flatten());
+        Some(())
+    }
+
+    /// `[a=b, c=d][e=f]`, if any
+    fn attributes(&mut self) -> Option<Attrs> {
+        let mut attrs = Attrs::new();
+        while self.peek() == Some(&Token::OpenBracket) {
+            self.pos += 1;
+            loop {
+                match self.next()? {
+                    Token::CloseBracket => break,
+                    Token::Separator => {}
+                    Token::Id(key) => {
+                        let value = if self.peek() == Some(&Token::Equals) {
+                            self.pos += 1;
+                            match self.next()? {
+                                Token::Id(value) => value,
+                                _ => return None,
+                            }
+                        } else {
+                            "true".to_string()
+                        };
+                        attrs.insert(key.to_lowercase(), value);
+                    }
+                    _ => return None,
+                }
+            }
+        }
+        Some(attrs)
+    }
+
+    fn graph_attribute(&mut self, key: &str, value: &str) {
+        match key.to_lowercase().as_str() {
+            "rankdir" if self.scopes.len() == 1 => {
+                if let Some(direction) = parse_direction(value) {
+                    self.graph.direction = direction;
+                }
+            }
+            "label" => {
+                if let Some(cluster) = self.scope().cluster {
+                    let title = label_text(value, &self.graph.subgraphs[cluster].id);
+                    self.graph.subgraphs[cluster].title = title;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Declare a node the first time it's mentioned, with the current
+    /// defaults; a node mentioned again inside a cluster moves into it
+    fn node(&mut self, id: &str) {
+        let cluster = self.scope().cluster;
+        if let Some(&index) = self.index.get(id) {
+            if cluster.is_some() && self.encloses(self.graph.nodes[index].subgraph, cluster) {
+                self.graph.nodes[index].subgraph = cluster;
+            }
+            return;
+        }
+        self.index.insert(id.to_string(), self.graph.nodes.len());
+        self.graph.nodes.push(Node {
+            id: id.to_string(),
+            label: id.to_string(),
+            shape: NodeShape::Rect,
+            subgraph: cluster,
+        });
+        let defaults = self.scope().node.clone();
+        self.apply_node_attributes(id, &defaults);
+    }
+
+    /// Whether cluster `outer` (`None`: the whole graph) contains `inner`
+    fn encloses(&self, outer: Option<usize>, inner: Option<usize>) -> bool {
+        let mut current = inner;
+        while let Some(cluster) = current {
+            if Some(cluster) == outer {
+                return true;
+            }
+            current = self.graph.subgraphs[cluster].parent;
+        }
+        outer.is_none()
+    }
+
+    fn apply_node_attributes(&mut self, id: &str, attrs: &Attrs) {
+        let Some(&index) = self.index.get(id) else {
+            return;
+        };
+        let node = &mut self.graph.nodes[index];
+        if let Some(label) = attrs.get("label") {
+            node.label = label_text(label, id);
+        }
+        if let Some(shape) = attrs.get("shape") {
+            node.shape = node_shape(shape);
+        }
+    }
+}
+
+/// A label on one line: `\n`, `\l` and `\r` breaks become spaces and `\N`
+/// (or `\G`) the node's (or cluster's) name
+fn label_text(label: &str, name: &str) -> String {
+    let label = label
+        .replace("\\N", name)
+        .replace("\\G", name)
+        .replace("\\n", " ")
+        .replace("\\l", " ")
+        .replace("\\r", " ")
+        .replace("\\\\", "\\");
+    label.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The flowchart shape closest to a DOT `shape`; boxes for anything else
+fn node_shape(shape: &str) -> NodeShape {
+    match shape.to_lowercase().as_str() {
+        "ellipse" | "oval" | "egg" => NodeShape::Round,
+        "circle" | "doublecircle" | "point" => NodeShape::Circle,
+        "diamond" | "mdiamond" | "hexagon" | "octagon" => NodeShape::Diamond,
+        "parallelogram" => NodeShape::Parallelogram,
+        "box3d" | "component" => NodeShape::Subroutine,
+        "cds" | "rarrow" => NodeShape::Asymmetric,
+        _ => NodeShape::Rect,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mermaid::render_mermaid;
+
+    #[test]
+    fn test_parse_statements_attributes_and_defaults() {
+        let graph = parse_dot(
+            r#"
+            // build graph
+            digraph deps {
+                rankdir=LR;
+                node [shape=box];
+                app [label="App\nserver"];
+                app -> {db cache} [label="uses", style=dashed];
+                db -> "disk:0" -> backup:n;
+                edge [dir=none];
+                cache -> db
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(graph.direction, Direction::LR);
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["app", "db", "cache", "disk:0", "backup"]);
+        assert_eq!(graph.nodes[0].label, "App server");
+        assert!(graph.nodes.iter().all(|n| n.shape == NodeShape::Rect));
+
+        let edges: Vec<(&str, &str)> = graph
+            .edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str()))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("app", "db"),
+                ("app", "cache"),
+                ("db", "disk:0"),
+                ("disk:0", "backup"),
+                ("cache", "db"),
+            ]
+        );
+        assert_eq!(graph.edges[0].label.as_deref(), Some("uses"));
+        assert_eq!(graph.edges[1].style, EdgeStyle::Dotted);
+        assert!(graph.edges[2].head);
+        assert!(!graph.edges[4].head, "dir=none");
+    }
+
+    #[test]
+    fn test_clusters_become_subgraphs() {
+        let graph = parse_dot(
+            "graph {\n  a -- b\n  subgraph cluster_api {\n    label=\"API\"\n    b; c\n    subgraph cluster_db { d }\n  }\n  subgraph ranks { rank=same; a; e }\n}",
+        )
+        .unwrap();
+        let titles: Vec<&str> = graph.subgraphs.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["API", ""]);
+        assert_eq!(graph.subgraphs[1].parent, Some(0));
+        let group = |id: &str| graph.nodes.iter().find(|n| n.id == id).unwrap().subgraph;
+        assert_eq!(group("a"), None);
+        assert_eq!(group("b"), Some(0), "moves into the cluster listing it");
+        assert_eq!(group("d"), Some(1));
+        assert_eq!(group("e"), None, "plain subgraphs aren't boxes");
+        assert!(!graph.edges[0].head, "undirected");
+    }
+
+    #[test]
+    fn test_renders_like_the_same_mermaid_flowchart() {
+        let dot = render_dot(
+            "digraph G { rankdir=LR; A [label=\"Input\"]; B [label=\"Process\", shape=ellipse]; A -> B [label=\"go\"] }",
+        )
+        .unwrap();
+        let mermaid = render_mermaid("graph LR\n    A[Input] -->|go| B(Process)").unwrap();
+        assert_eq!(dot, mermaid);
+
+        assert!(render_dot("digraph { }").is_none());
+        assert!(render_dot("not a graph").is_none());
+        assert!(render_dot("digraph { a -> }").is_none());
+    }
+}
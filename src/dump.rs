@@ -0,0 +1,87 @@
+//! JSON dumps of the IR and layout tree for tooling (`lumen --dump`)
+//!
+//! Every dump is an object with a `schema_version` next to the payload.
+//! The version is bumped whenever the shape of the IR or layout types
+//! changes in a way consumers could notice (renamed or removed fields,
+//! new variants).
+
+use crate::ir::Document;
+use crate::layout::LayoutTree;
+use serde::{Deserialize, Serialize};
+
+/// Version of the JSON produced by [`ir_json`] and [`layout_json`]
+pub const SCHEMA_VERSION: u32 = 4;
+
+/// `lumen --dump ir`: the parsed document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IrDump {
+    pub schema_version: u32,
+    pub document: Document,
+}
+
+/// `lumen --dump layout`: the document laid out at a given width
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutDump {
+    pub schema_version: u32,
+    pub width: u16,
+    pub layout: LayoutTree,
+}
+
+/// Pretty-printed JSON of a document's IR
+pub fn ir_json(document: &Document) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&IrDump {
+        schema_version: SCHEMA_VERSION,
+        document: document.clone(),
+    })
+}
+
+/// Pretty-printed JSON of a layout tree
+pub fn layout_json(tree: &LayoutTree) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&LayoutDump {
+        schema_version: SCHEMA_VERSION,
+        width: tree.viewport.width,
+        layout: tree.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{layout_document, HitElement, Viewport};
+    use crate::{parse_markdown, Theme};
+
+    const MARKDOWN: &str = "# Intro\n\nSee [setup](#setup).\n\n## Setup\n\n```rust\nfn main() {}\n```\n\n> [!tip] Hint\n> - [x] done\n";
+
+    #[test]
+    fn test_ir_round_trips() {
+        let document = parse_markdown(MARKDOWN);
+        let json = ir_json(&document).unwrap();
+        let dump: IrDump = serde_json::from_str(&json).unwrap();
+        assert_eq!(dump.schema_version, SCHEMA_VERSION);
+        assert_eq!(dump.document, document);
+        assert!(json.contains("\"id\": \"setup\""));
+    }
+
+    #[test]
+    fn test_layout_dump_has_rects_and_hit_regions() {
+        let document = parse_markdown(MARKDOWN);
+        let theme = Theme::builtin("docs").unwrap();
+        let tree = layout_document(&document, &theme, Viewport::new(60, 24), false);
+        let json = layout_json(&tree).unwrap();
+
+        let dump: LayoutDump = serde_json::from_str(&json).unwrap();
+        assert_eq!(dump.width, 60);
+        assert_eq!(dump.layout.root.children.len(), document.blocks.len());
+        assert_eq!(dump.layout.root.rect, tree.root.rect);
+        let headings: Vec<&str> = dump
+            .layout
+            .hit_regions
+            .iter()
+            .filter_map(|region| match &region.element {
+                HitElement::Heading { id, .. } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(headings, ["intro", "setup"]);
+    }
+}
@@ -3,7 +3,15 @@
 //! This module provides state management for working with multiple markdown files
 //! simultaneously, including tracking scroll positions and current selections.
 
-use crate::{Document, LayoutTree};
+use crate::codeblock::DiagramSources;
+use crate::diff::SplitDiffs;
+use crate::json_tree::TreeFolds;
+use crate::layout::{Folds, LayoutCache};
+use crate::Document;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
 use std::path::PathBuf;
 
 /// Represents a single open file with its state.
@@ -18,10 +26,22 @@ pub struct OpenFile {
     pub name: String,
     /// Parsed document
     pub document: Document,
-    /// Current layout tree (cached)
-    pub layout: Option<LayoutTree>,
+    /// Markdown source the document was parsed from (empty if unknown)
+    pub source: String,
+    /// Block-level layout cache, reused across relayouts of this file
+    pub layout: LayoutCache,
     /// Current scroll position
     pub scroll_position: u16,
+    /// Named marks (`Ma` / `'a`): mark letter → scroll position
+    pub marks: HashMap<char, u16>,
+    /// Folded sections, callouts and code blocks
+    pub folds: Folds,
+    /// Diagrams flipped back to their source
+    pub sources: DiagramSources,
+    /// JSON/YAML tree nodes folded or unfolded by hand
+    pub tree_folds: TreeFolds,
+    /// Diffs shown side by side
+    pub split_diffs: SplitDiffs,
 }
 
 impl OpenFile {
@@ -35,11 +55,23 @@ impl OpenFile {
         Self {
             path,
             name,
+            folds: Folds::for_document(&document),
             document,
-            layout: None,
+            source: String::new(),
+            layout: LayoutCache::new(),
             scroll_position: 0,
+            marks: HashMap::new(),
+            sources: DiagramSources::default(),
+            tree_folds: TreeFolds::default(),
+            split_diffs: SplitDiffs::default(),
         }
     }
+
+    /// Markdown source of top-level block `index`, if the source is known
+    pub fn block_source(&self, index: usize) -> Option<&str> {
+        let range = self.document.source_ranges.get(index)?;
+        self.source.get(range.clone())
+    }
 }
 
 /// Manages multiple open files
@@ -73,6 +105,14 @@ impl FileManager {
         self.files.push(OpenFile::new(path, document));
     }
 
+    /// Add a file together with the Markdown it was parsed from, so that
+    /// blocks can be yanked as source text.
+    pub fn add_file_with_source(&mut self, path: PathBuf, source: String, document: Document) {
+        let mut file = OpenFile::new(path, document);
+        file.source = source;
+        self.files.push(file);
+    }
+
     /// Get a reference to the currently active file.
     ///
     /// Returns `None` if no files are open.
@@ -126,17 +166,12 @@ impl FileManager {
     pub fn reload_current(&mut self) -> std::io::Result<()> {
         if let Some(file) = self.current_file_mut() {
             let markdown = std::fs::read_to_string(&file.path)?;
-            file.document = crate::parse_markdown(&markdown);
-            file.layout = None; // Force relayout
-        }
-        Ok(())
-    }
+            file.document = crate::parse_file(&file.path, &markdown);
+            file.source = markdown;
+            file.layout.clear(); // Force relayout
 
-    /// Reload current file from disk and apply mermaid rendering
-    pub fn reload_current_with_mermaid(&mut self) -> std::io::Result<()> {
-        self.reload_current()?;
-        if let Some(file) = self.current_file_mut() {
-            crate::mermaid::transform_mermaid_blocks(&mut file.document);
+            // Block indices may have moved; start from the document's own folds
+            file.folds = Folds::for_document(&file.document);
         }
         Ok(())
     }
@@ -152,4 +187,106 @@ impl FileManager {
     pub fn get_scroll_position(&self) -> u16 {
         self.current_file().map(|f| f.scroll_position).unwrap_or(0)
     }
+
+    /// Set a named mark in the current file
+    pub fn set_mark(&mut self, mark: char, scroll_y: u16) {
+        if let Some(file) = self.current_file_mut() {
+            file.marks.insert(mark, scroll_y);
+        }
+    }
+
+    /// Get a named mark in the current file
+    pub fn get_mark(&self, mark: char) -> Option<u16> {
+        self.current_file()
+            .and_then(|f| f.marks.get(&mark).copied())
+    }
+
+    /// Restore marks saved in a previous session for every open file
+    pub fn restore_marks(&mut self, saved: &SavedMarks) {
+        for file in &mut self.files {
+            if let Some(marks) = saved.files.get(&SavedMarks::key(&file.path)) {
+                file.marks.extend(marks.iter().map(|(k, v)| (*k, *v)));
+            }
+        }
+    }
+
+    /// Record the marks of every open file so they can be saved
+    pub fn store_marks(&self, saved: &mut SavedMarks) {
+        for file in &self.files {
+            let key = SavedMarks::key(&file.path);
+            if file.marks.is_empty() {
+                saved.files.remove(&key);
+            } else {
+                saved
+                    .files
+                    .insert(key, file.marks.iter().map(|(k, v)| (*k, *v)).collect());
+            }
+        }
+    }
+}
+
+/// Marks persisted across sessions in ~/.lumen/marks.yaml
+///
+/// Only used when `persist_marks` is enabled in the preferences.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedMarks {
+    /// Canonical file path → mark letter → scroll position
+    #[serde(default)]
+    pub files: BTreeMap<String, BTreeMap<char, u16>>,
+}
+
+impl SavedMarks {
+    /// Get the path to the marks file (~/.lumen/marks.yaml)
+    pub fn file_path() -> Option<PathBuf> {
+        crate::Preferences::config_dir().map(|dir| dir.join("marks.yaml"))
+    }
+
+    /// Key used for a file: its canonical path when it can be resolved
+    fn key(path: &std::path::Path) -> String {
+        fs::canonicalize(path)
+            .unwrap_or_else(|_| path.to_path_buf())
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Load saved marks, or return none if the file is missing or invalid
+    pub fn load() -> Self {
+        let Some(path) = Self::file_path() else {
+            return Self::default();
+        };
+        if !path.exists() {
+            return Self::default();
+        }
+        match fs::read_to_string(&path).map(|s| serde_yaml::from_str(&s)) {
+            Ok(Ok(marks)) => marks,
+            Ok(Err(e)) => {
+                eprintln!("Warning: Failed to parse marks: {}", e);
+                Self::default()
+            }
+            Err(e) => {
+                eprintln!("Warning: Failed to read marks: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save marks to disk
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::file_path().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "Could not determine home directory",
+            )
+        })?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let yaml = serde_yaml::to_string(self).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to serialize marks: {}", e),
+            )
+        })?;
+        fs::write(path, yaml)
+    }
 }
@@ -9,31 +9,43 @@
 //! - Flat-ish: Easy to traverse for layout engine
 //! - Recursive: Blocks can contain blocks, inlines can contain inlines
 
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
 
 /// Top-level document structure
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Document {
     /// Document metadata (title, frontmatter, etc.)
     pub metadata: Metadata,
     /// Block-level content
     pub blocks: Vec<Block>,
+    /// Byte range in the Markdown source of each top-level block, parallel
+    /// to `blocks` (empty for documents not built by the parser)
+    pub source_ranges: Vec<Range<usize>>,
 }
 
 /// Document metadata
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct Metadata {
     /// Document title (if specified)
     pub title: Option<String>,
     /// Frontmatter key-value pairs
-    pub frontmatter: HashMap<String, String>,
+    pub frontmatter: BTreeMap<String, String>,
 }
 
 /// Block-level elements (vertical stacking)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Block {
     /// Heading with level (1-6) and inline content
-    Heading { level: u8, content: Vec<Inline> },
+    Heading {
+        level: u8,
+        content: Vec<Inline>,
+        /// Anchor id: the explicit `{#id}`, or a GitHub-style slug of the
+        /// text, unique within the document (`setup`, `setup-1`)
+        id: String,
+    },
 
     /// Paragraph with inline content
     Paragraph { content: Vec<Inline> },
@@ -61,16 +73,28 @@ pub enum Block {
     /// Horizontal rule / separator
     HorizontalRule,
 
-    /// Callout / admonition (GitHub-style)
+    /// Callout / admonition (GitHub alerts and Obsidian callouts)
     Callout {
         kind: CalloutKind,
+        /// Text after the marker on the first line (`> [!tip] Title`)
         title: Option<String>,
+        /// `None` if the callout can't be folded; otherwise whether it
+        /// starts folded (`[!tip]-`) or open (`[!tip]+`)
+        folded: Option<bool>,
+        content: Vec<Block>,
+    },
+
+    /// Collapsible section (raw HTML `<details>` with its `<summary>`);
+    /// `open` is whether it starts expanded
+    Details {
+        summary: Vec<Inline>,
+        open: bool,
         content: Vec<Block>,
     },
 }
 
 /// List item (can contain multiple blocks for nested content)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ListItem {
     /// Block-level content of this item
     pub content: Vec<Block>,
@@ -79,13 +103,14 @@ pub struct ListItem {
 }
 
 /// Table cell containing inline content
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TableCell {
     pub content: Vec<Inline>,
 }
 
 /// Column alignment for tables
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Alignment {
     Left,
     Center,
@@ -93,18 +118,89 @@ pub enum Alignment {
     None,
 }
 
-/// Callout / admonition type
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Callout / admonition type: GitHub's five alerts plus Obsidian's family
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CalloutKind {
     Note,
     Warning,
     Important,
     Tip,
     Caution,
+    Abstract,
+    Info,
+    Todo,
+    Success,
+    Question,
+    Failure,
+    Danger,
+    Bug,
+    Example,
+    Quote,
+    /// Any other type name (lowercased), styled from the theme's `custom` map
+    Custom(String),
+}
+
+impl CalloutKind {
+    /// Look up a type name case-insensitively, accepting Obsidian's aliases
+    /// (`summary`, `hint`, `faq`, `error`, ...)
+    pub fn from_name(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "note" => CalloutKind::Note,
+            "warning" | "attention" => CalloutKind::Warning,
+            "important" => CalloutKind::Important,
+            "tip" | "hint" => CalloutKind::Tip,
+            "caution" => CalloutKind::Caution,
+            "abstract" | "summary" | "tldr" => CalloutKind::Abstract,
+            "info" => CalloutKind::Info,
+            "todo" => CalloutKind::Todo,
+            "success" | "check" | "done" => CalloutKind::Success,
+            "question" | "help" | "faq" => CalloutKind::Question,
+            "failure" | "fail" | "missing" => CalloutKind::Failure,
+            "danger" | "error" => CalloutKind::Danger,
+            "bug" => CalloutKind::Bug,
+            "example" => CalloutKind::Example,
+            "quote" | "cite" => CalloutKind::Quote,
+            other => CalloutKind::Custom(other.to_string()),
+        }
+    }
+
+    /// Canonical lowercase name, as used for theme keys
+    pub fn name(&self) -> &str {
+        match self {
+            CalloutKind::Note => "note",
+            CalloutKind::Warning => "warning",
+            CalloutKind::Important => "important",
+            CalloutKind::Tip => "tip",
+            CalloutKind::Caution => "caution",
+            CalloutKind::Abstract => "abstract",
+            CalloutKind::Info => "info",
+            CalloutKind::Todo => "todo",
+            CalloutKind::Success => "success",
+            CalloutKind::Question => "question",
+            CalloutKind::Failure => "failure",
+            CalloutKind::Danger => "danger",
+            CalloutKind::Bug => "bug",
+            CalloutKind::Example => "example",
+            CalloutKind::Quote => "quote",
+            CalloutKind::Custom(name) => name,
+        }
+    }
+
+    /// Title shown when the callout doesn't give one ("Note", "Bug")
+    pub fn default_title(&self) -> String {
+        let name = self.name();
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    }
 }
 
 /// Inline elements (horizontal flow within blocks)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Inline {
     /// Plain text
     Text(String),
@@ -140,6 +236,12 @@ pub enum Inline {
 
     /// Soft line break (rendered as space)
     SoftBreak,
+
+    /// Keyboard key (`<kbd>Ctrl</kbd>`)
+    Kbd(String),
+
+    /// Raw HTML outside the supported subset, shown as dimmed source
+    Html(String),
 }
 
 impl Document {
@@ -148,6 +250,7 @@ impl Document {
         Self {
             metadata: Metadata::default(),
             blocks: Vec::new(),
+            source_ranges: Vec::new(),
         }
     }
 
@@ -156,6 +259,7 @@ impl Document {
         Self {
             metadata: Metadata::default(),
             blocks,
+            source_ranges: Vec::new(),
         }
     }
 }
@@ -174,7 +278,7 @@ impl Inline {
             Inline::Strong(inlines)
             | Inline::Emphasis(inlines)
             | Inline::Strikethrough(inlines) => inlines.iter().map(|i| i.to_plain_text()).collect(),
-            Inline::Code(s) => s.clone(),
+            Inline::Code(s) | Inline::Kbd(s) | Inline::Html(s) => s.clone(),
             Inline::Link { text, .. } => text.iter().map(|i| i.to_plain_text()).collect(),
             Inline::Image { alt, .. } => alt.clone(),
             Inline::LineBreak => "\n".to_string(),
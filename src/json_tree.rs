@@ -0,0 +1,777 @@
+//! JSON and YAML code blocks drawn as a foldable tree
+//!
+//! ```` ```json ```` and ```` ```yaml ```` blocks are shown as code until
+//! the reader flips them with `s`; then they are pretty-printed one member
+//! per row, with a `▾` marker on every object and array that can be
+//! folded. A folded node collapses to a single row that says how big it is
+//! (`"deps": {… 12 keys}`). Every node has a JSONPath (`$.items[2].name`)
+//! that can be copied.
+//!
+//! The tree shows keys and scalars as the source writes them, so numbers
+//! keep their digits, quoted strings keep their quotes and repeated keys
+//! are all kept. YAML comments are left out. YAML is only read through
+//! serde, so a YAML block the tree couldn't show as written (one with
+//! anchors, several documents, or scalars serde would rewrite) stays code.
+//!
+//! Rows are [`DiagramLine`]s so they are coloured like diagrams: keys are
+//! labels, brackets and punctuation are edges, fold markers are arrowheads
+//! and sizes are edge labels.
+//!
+//! A block that doesn't parse is shown as code, with a marker row under the
+//! error (see [`SourceError`]).
+
+use crate::codeblock::{
+    BlockContext, CodeBlockRenderer, DiagramLine, DiagramRole, Rendered, SourceError, Tree,
+    TreeNode,
+};
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+
+/// Marker on an unfolded object or array
+pub const OPEN_MARKER: &str = "▾ ";
+/// Marker on a folded object or array
+pub const FOLDED_MARKER: &str = "▸ ";
+
+/// Trees with more rows than this start with only their top two levels
+/// unfolded
+pub const LARGE_TREE_ROWS: usize = 100;
+
+/// How a tree is printed: JSON with brackets, or YAML's indented `key:`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Json,
+    Yaml,
+}
+
+/// A parsed value, with its scalars and keys as the source writes them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    /// A string, number, boolean or null
+    Scalar(String),
+    /// Members in source order, repeated keys included
+    Object(Vec<Member>),
+    Array(Vec<Node>),
+}
+
+/// One member of an object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    /// The key as written (quoted, in JSON)
+    pub key: String,
+    /// What the key says, for paths
+    pub name: String,
+    pub value: Node,
+}
+
+/// Renders ` ```json ` and ` ```yaml ` code blocks as trees, once the reader
+/// asks for them; registered by default in
+/// [`CodeBlockRenderers`](crate::codeblock::CodeBlockRenderers)
+#[derive(Debug, Clone, Copy)]
+pub struct TreeRenderer(pub Syntax);
+
+impl CodeBlockRenderer for TreeRenderer {
+    fn render(&self, code: &str, block: &BlockContext) -> Option<Rendered> {
+        let node = parse(self.0, code).ok()??;
+        let tree = layout_tree(&node, self.0, &|path| block.toggled.contains(path));
+        Some(Rendered::Tree(tree))
+    }
+
+    fn source_error(&self, code: &str) -> Option<SourceError> {
+        parse(self.0, code).err()
+    }
+
+    fn rendered_by_default(&self) -> bool {
+        false
+    }
+}
+
+/// Parse a block's source, keeping object keys in order. `Ok(None)` means
+/// the source is valid but the tree couldn't show it as written.
+pub fn parse(syntax: Syntax, code: &str) -> Result<Option<Node>, SourceError> {
+    match syntax {
+        Syntax::Json => {
+            serde_json::from_str::<IgnoredAny>(code).map_err(|e| SourceError {
+                line: e.line(),
+                column: e.column(),
+                message: strip_location(&e.to_string()),
+            })?;
+            let mut reader = JsonReader { code, at: 0 };
+            Ok(reader.value())
+        }
+        Syntax::Yaml => {
+            let yaml_error = |e: serde_yaml::Error| {
+                let location = e.location();
+                SourceError {
+                    line: location.as_ref().map_or(1, |l| l.line()),
+                    column: location.as_ref().map_or(1, |l| l.column()),
+                    message: strip_location(&e.to_string()),
+                }
+            };
+            if serde_yaml::from_str::<IgnoredAny>(code).is_err() {
+                // Either invalid, or several documents, which stay code
+                for document in serde_yaml::Deserializer::from_str(code) {
+                    IgnoredAny::deserialize(document).map_err(yaml_error)?;
+                }
+                return Ok(None);
+            }
+            // Valid YAML serde can't hold (repeated keys, huge integers)
+            // stays code, like YAML serde would rewrite
+            let value = serde_yaml::from_str(code).ok();
+            let lines: Vec<&str> = code.lines().map(yaml_uncommented).collect();
+            Ok(value
+                .filter(|_| !yaml_has_extras(&lines))
+                .and_then(|value| yaml_node(&value, &lines)))
+        }
+    }
+}
+
+/// `"expected value at line 1 column 5"` → `"expected value"`
+fn strip_location(message: &str) -> String {
+    match message.find(" at line ") {
+        Some(at) => message[..at].to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// Reads JSON that serde has already checked, slicing out the text of
+/// every key and scalar
+struct JsonReader<'a> {
+    code: &'a str,
+    at: usize,
+}
+
+impl JsonReader<'_> {
+    fn value(&mut self) -> Option<Node> {
+        match self.peek()? {
+            b'{' => {
+                self.at += 1;
+                let mut members = Vec::new();
+                while self.peek()? != b'}' {
+                    let key = self.string()?;
+                    let name = serde_json::from_str(&key).ok()?;
+                    self.expect(b':')?;
+                    let value = self.value()?;
+                    members.push(Member { key, name, value });
+                    self.comma(b'}')?;
+                }
+                self.at += 1;
+                Some(Node::Object(members))
+            }
+            b'[' => {
+                self.at += 1;
+                let mut items = Vec::new();
+                while self.peek()? != b']' {
+                    items.push(self.value()?);
+                    self.comma(b']')?;
+                }
+                self.at += 1;
+                Some(Node::Array(items))
+            }
+            b'"' => self.string().map(Node::Scalar),
+            _ => {
+                let start = self.at;
+                let bytes = self.code.as_bytes();
+                while bytes
+                    .get(self.at)
+                    .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+                {
+                    self.at += 1;
+                }
+                Some(Node::Scalar(self.code[start..self.at].to_string()))
+            }
+        }
+    }
+
+    /// A string with its quotes and escapes as written
+    fn string(&mut self) -> Option<String> {
+        let start = self.at;
+        self.expect(b'"')?;
+        let bytes = self.code.as_bytes();
+        loop {
+            match bytes.get(self.at)? {
+                b'\\' => self.at += 2,
+                b'"' => break,
+                _ => self.at += 1,
+            }
+        }
+        self.at += 1;
+        Some(self.code[start..self.at].to_string())
+    }
+
+    /// The next byte after any whitespace
+    fn peek(&mut self) -> Option<u8> {
+        let bytes = self.code.as_bytes();
+        while bytes.get(self.at)?.is_ascii_whitespace() {
+            self.at += 1;
+        }
+        bytes.get(self.at).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        (self.peek()? == byte).then(|| self.at += 1)
+    }
+
+    /// Step over the comma between members, if this wasn't the last
+    fn comma(&mut self, close: u8) -> Option<()> {
+        match self.peek()? {
+            b',' => {
+                self.at += 1;
+                Some(())
+            }
+            byte => (byte == close).then_some(()),
+        }
+    }
+}
+
+/// A YAML value with its keys and scalars as `lines` (the source without
+/// comments) write them, or `None` if one isn't written the way the tree
+/// would write it, or is a key that isn't a scalar or a tagged value
+fn yaml_node(value: &serde_yaml::Value, lines: &[&str]) -> Option<Node> {
+    use serde_yaml::Value as Yaml;
+    Some(match value {
+        Yaml::Sequence(items) => Node::Array(
+            items
+                .iter()
+                .map(|item| yaml_node(item, lines))
+                .collect::<Option<_>>()?,
+        ),
+        Yaml::Mapping(map) => Node::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let name = match key {
+                        Yaml::String(s) => s.clone(),
+                        Yaml::Mapping(_) | Yaml::Sequence(_) | Yaml::Tagged(_) => return None,
+                        other => yaml_scalar(other)?,
+                    };
+                    let key = yaml_forms(key).into_iter().find(|form| {
+                        lines.iter().any(|line| {
+                            let mut rest = line.trim_start();
+                            while let Some(item) = rest.strip_prefix("- ") {
+                                rest = item.trim_start();
+                            }
+                            rest.strip_prefix(form.as_str())
+                                .and_then(|rest| rest.strip_prefix(':'))
+                                .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+                        })
+                    })?;
+                    Some(Member {
+                        key,
+                        name,
+                        value: yaml_node(value, lines)?,
+                    })
+                })
+                .collect::<Option<_>>()?,
+        ),
+        Yaml::Tagged(_) => return None,
+        scalar => Node::Scalar(yaml_forms(scalar).into_iter().find(|form| {
+            lines.iter().any(|line| {
+                line.trim_end()
+                    .strip_suffix(form.as_str())
+                    .is_some_and(|before| {
+                        // The whole line, or what follows `key: ` or `- `
+                        let lead = before.trim_end();
+                        lead.is_empty() || (lead.len() < before.len() && lead.ends_with([':', '-']))
+                    })
+            })
+        })?),
+    })
+}
+
+/// Whether YAML source (without comments) has anything the tree leaves
+/// out: anchors, aliases or tags
+fn yaml_has_extras(lines: &[&str]) -> bool {
+    lines.iter().any(|line| {
+        let line = line.trim_start();
+        line.starts_with(['&', '*', '!'])
+            || [": ", "- "].iter().any(|separator| {
+                line.split(separator)
+                    .skip(1)
+                    .any(|rest| rest.trim_start().starts_with(['&', '*', '!']))
+            })
+    })
+}
+
+/// A YAML line without its comment: a `#` at the start or after a space,
+/// outside quotes
+fn yaml_uncommented(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            // Quotes only open a scalar at its start, not in `it's`
+            None if matches!(c, '"' | '\'')
+                && (previous.is_whitespace() || "-:[{,".contains(previous)) =>
+            {
+                quote = Some(c)
+            }
+            None if c == '#' && previous.is_whitespace() => return line[..i].trim_end(),
+            None => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+/// Tree nodes the reader folded or unfolded, by the source of their block
+/// and their path (so the choice survives reloads that leave it alone)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TreeFolds {
+    toggled: BTreeSet<(u64, String)>,
+}
+
+impl TreeFolds {
+    fn key(code: &str, path: &str) -> (u64, String) {
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        (hasher.finish(), path.to_string())
+    }
+
+    /// Whether the node at `path` was flipped from how it starts
+    pub fn contains(&self, code: &str, path: &str) -> bool {
+        self.toggled.contains(&Self::key(code, path))
+    }
+
+    /// Flip the node at `path` between folded and unfolded
+    pub fn toggle(&mut self, code: &str, path: &str) {
+        let key = Self::key(code, path);
+        if !self.toggled.remove(&key) {
+            self.toggled.insert(key);
+        }
+    }
+
+    /// Paths of the nodes flipped in blocks with this source
+    pub fn paths(&self, code: &str) -> BTreeSet<String> {
+        let (hash, _) = Self::key(code, "");
+        self.toggled
+            .range((hash, String::new())..)
+            .take_while(|(key, _)| *key == hash)
+            .map(|(_, path)| path.clone())
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.toggled.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.toggled.clear();
+    }
+}
+
+/// Draw `value` as a tree. `toggled` says which node paths the reader
+/// flipped from their starting state.
+pub fn layout_tree(value: &Node, syntax: Syntax, toggled: &dyn Fn(&str) -> bool) -> Tree {
+    let mut writer = Writer {
+        syntax,
+        toggled,
+        large: expanded_rows(value, syntax, 0) > LARGE_TREE_ROWS,
+        tree: Tree::default(),
+    };
+    writer.node(value, Label::Root, "$".to_string(), 0, 0, true);
+    writer.tree
+}
+
+/// Rows `value` takes with everything unfolded
+fn expanded_rows(value: &Node, syntax: Syntax, level: usize) -> usize {
+    let children = match value {
+        Node::Object(members) if !members.is_empty() => members
+            .iter()
+            .map(|member| &member.value)
+            .collect::<Vec<_>>(),
+        Node::Array(items) if !items.is_empty() => items.iter().collect(),
+        _ => return 1,
+    };
+    let inner: usize = children
+        .into_iter()
+        .map(|child| expanded_rows(child, syntax, level + 1))
+        .sum();
+    match syntax {
+        Syntax::Json => inner + 2,
+        // A YAML document's top level has no row of its own
+        Syntax::Yaml if level == 0 => inner,
+        Syntax::Yaml => inner + 1,
+    }
+}
+
+/// What a node's row starts with
+#[derive(Debug, Clone, Copy)]
+enum Label<'a> {
+    Root,
+    Key(&'a str),
+    /// An array item: nothing in JSON, `-` in YAML
+    Item,
+}
+
+struct Writer<'a> {
+    syntax: Syntax,
+    toggled: &'a dyn Fn(&str) -> bool,
+    large: bool,
+    tree: Tree,
+}
+
+impl Writer<'_> {
+    /// Draw one node at `indent` levels of indentation, `level` deep in
+    /// the value; `last` is whether a JSON comma is left off
+    fn node(
+        &mut self,
+        value: &Node,
+        label: Label,
+        path: String,
+        indent: usize,
+        level: usize,
+        last: bool,
+    ) {
+        let first = self.tree.lines.len();
+        let size = match value {
+            Node::Object(members) => Some((
+                members.len(),
+                if members.len() == 1 { "key" } else { "keys" },
+            )),
+            Node::Array(items) => {
+                Some((items.len(), if items.len() == 1 { "item" } else { "items" }))
+            }
+            Node::Scalar(_) => None,
+        };
+        let foldable = size.is_some_and(|(len, _)| len > 0);
+        let folded = foldable && ((self.large && level >= 2) != (self.toggled)(&path));
+        let (open, close) = match value {
+            Node::Object(_) => ("{", "}"),
+            _ => ("[", "]"),
+        };
+        let comma = if last || self.syntax == Syntax::Yaml {
+            ""
+        } else {
+            ","
+        };
+
+        // A YAML document's top-level members sit at the left edge
+        if self.syntax == Syntax::Yaml && matches!(label, Label::Root) && foldable {
+            self.children(value, &path, indent, level);
+            self.push_node(path, first, false, false);
+            return;
+        }
+
+        let mut line = DiagramLine::default();
+        line.push(&"  ".repeat(indent), DiagramRole::Text);
+        let marker = match (foldable, folded) {
+            (false, _) => "  ",
+            (true, false) => OPEN_MARKER,
+            (true, true) => FOLDED_MARKER,
+        };
+        line.push(marker, DiagramRole::Arrowhead);
+        self.label(&mut line, label, size.is_some() && foldable && !folded);
+
+        match size {
+            None => {
+                if let Node::Scalar(text) = value {
+                    line.push(text, DiagramRole::Text);
+                }
+                line.push(comma, DiagramRole::Edge);
+                self.tree.lines.push(line);
+            }
+            Some((len, noun)) if folded || len == 0 => {
+                line.push(open, DiagramRole::Edge);
+                if len > 0 {
+                    line.push(&format!("… {} {}", len, noun), DiagramRole::EdgeLabel);
+                }
+                line.push(close, DiagramRole::Edge);
+                line.push(comma, DiagramRole::Edge);
+                self.tree.lines.push(line);
+            }
+            Some(_) => {
+                if self.syntax == Syntax::Json {
+                    line.push(open, DiagramRole::Edge);
+                }
+                self.tree.lines.push(line);
+                self.children(value, &path, indent + 1, level + 1);
+                if self.syntax == Syntax::Json {
+                    let mut line = DiagramLine::default();
+                    line.push(&"  ".repeat(indent + 1), DiagramRole::Text);
+                    line.push(close, DiagramRole::Edge);
+                    line.push(comma, DiagramRole::Edge);
+                    self.tree.lines.push(line);
+                }
+            }
+        }
+        self.push_node(path, first, foldable, folded);
+    }
+
+    fn children(&mut self, value: &Node, path: &str, indent: usize, level: usize) {
+        match value {
+            Node::Object(members) => {
+                for (i, member) in members.iter().enumerate() {
+                    let child_path = key_path(path, &member.name);
+                    let last = i + 1 == members.len();
+                    let label = Label::Key(&member.key);
+                    self.node(&member.value, label, child_path, indent, level, last);
+                }
+            }
+            Node::Array(items) => {
+                for (i, child) in items.iter().enumerate() {
+                    let child_path = format!("{}[{}]", path, i);
+                    let last = i + 1 == items.len();
+                    self.node(child, Label::Item, child_path, indent, level, last);
+                }
+            }
+            Node::Scalar(_) => {}
+        }
+    }
+
+    /// `"key": ` in JSON; `key: ` or `- ` in YAML (without the trailing
+    /// space when the row ends there, i.e. an unfolded YAML container)
+    fn label(&self, line: &mut DiagramLine, label: Label, opens_block: bool) {
+        let yaml_gap = if opens_block { "" } else { " " };
+        match (label, self.syntax) {
+            (Label::Root, _) | (Label::Item, Syntax::Json) => {}
+            (Label::Key(key), Syntax::Json) => {
+                line.push(key, DiagramRole::NodeLabel);
+                line.push(": ", DiagramRole::Edge);
+            }
+            (Label::Key(key), Syntax::Yaml) => {
+                line.push(key, DiagramRole::NodeLabel);
+                line.push(&format!(":{}", yaml_gap), DiagramRole::Edge);
+            }
+            (Label::Item, Syntax::Yaml) => {
+                line.push(&format!("-{}", yaml_gap), DiagramRole::Edge);
+            }
+        }
+    }
+
+    fn push_node(&mut self, path: String, first: usize, foldable: bool, folded: bool) {
+        self.tree.nodes.push(TreeNode {
+            path,
+            rows: first..self.tree.lines.len(),
+            foldable,
+            folded,
+        });
+    }
+}
+
+/// The JSONPath of member `key` of the object at `path`
+fn key_path(path: &str, key: &str) -> String {
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{}]", path, Value::from(key))
+    }
+}
+
+/// The ways the source may write a scalar: plain, double-quoted or
+/// single-quoted for strings
+fn yaml_forms(value: &serde_yaml::Value) -> Vec<String> {
+    match value {
+        serde_yaml::Value::String(s) => {
+            let mut forms = vec![yaml_string(s), Value::from(s.as_str()).to_string()];
+            if !s.contains('\n') {
+                forms.push(format!("'{}'", s.replace('\'', "''")));
+            }
+            forms.dedup();
+            forms
+        }
+        other => yaml_scalar(other).into_iter().collect(),
+    }
+}
+
+/// A scalar as YAML would write it
+fn yaml_scalar(value: &serde_yaml::Value) -> Option<String> {
+    use serde_yaml::Value as Yaml;
+    match value {
+        Yaml::Null => Some("null".to_string()),
+        Yaml::Bool(b) => Some(b.to_string()),
+        Yaml::Number(n) => Some(n.to_string()),
+        Yaml::String(s) => Some(yaml_string(s)),
+        Yaml::Sequence(_) | Yaml::Mapping(_) | Yaml::Tagged(_) => None,
+    }
+}
+
+/// A string plain when YAML would read it back unchanged, else quoted
+fn yaml_string(s: &str) -> String {
+    let ambiguous = s.is_empty()
+        || s != s.trim()
+        || s.contains(['\n', '\t'])
+        || s.starts_with('#')
+        || s.contains(" #")
+        || s.contains(": ")
+        || s.ends_with(':')
+        || s.starts_with(|c: char| "-?:,[]{}&*!|>'\"%@`".contains(c))
+        || matches!(
+            s.to_ascii_lowercase().as_str(),
+            "true" | "false" | "null" | "~" | "yes" | "no" | "on" | "off"
+        )
+        || s.parse::<f64>().is_ok();
+    if ambiguous {
+        Value::from(s).to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(tree: &Tree) -> String {
+        tree.lines
+            .iter()
+            .map(|line| line.text())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_json_tree_folds_and_paths() {
+        let value = parse(
+            Syntax::Json,
+            r#"{"name": "lumen", "tags": ["a", "b"], "odd key": {"x": 1}, "none": {}}"#,
+        )
+        .unwrap()
+        .unwrap();
+        let tree = layout_tree(&value, Syntax::Json, &|_| false);
+        assert_eq!(
+            text(&tree),
+            [
+                "▾ {",
+                "    \"name\": \"lumen\",",
+                "  ▾ \"tags\": [",
+                "      \"a\",",
+                "      \"b\"",
+                "    ],",
+                "  ▾ \"odd key\": {",
+                "      \"x\": 1",
+                "    },",
+                "    \"none\": {}",
+                "  }",
+            ]
+            .join("\n")
+        );
+        let tags = tree.nodes.iter().find(|n| n.path == "$.tags").unwrap();
+        assert_eq!(tags.rows, 2..6);
+        assert!(tags.foldable && !tags.folded);
+        assert!(tree.nodes.iter().any(|n| n.path == "$.tags[1]"));
+        assert!(tree.nodes.iter().any(|n| n.path == r#"$["odd key"].x"#));
+        let root = tree.nodes.iter().find(|n| n.path == "$").unwrap();
+        assert_eq!(root.rows, 0..11);
+
+        let tree = layout_tree(&value, Syntax::Json, &|path| path == "$.tags");
+        assert_eq!(tree.lines[2].text(), "  ▸ \"tags\": [… 2 items],");
+        // Folded names and sizes are labels, so search finds them
+        assert!(tree.lines[2]
+            .spans
+            .iter()
+            .any(|s| s.role == DiagramRole::EdgeLabel && s.text == "… 2 items"));
+    }
+
+    #[test]
+    fn test_yaml_tree() {
+        let value = parse(
+            Syntax::Yaml,
+            "name: lumen\nversion: \"1.0\"\ndeps:\n  - serde\n  - ratatui\n",
+        )
+        .unwrap()
+        .unwrap();
+        let tree = layout_tree(&value, Syntax::Yaml, &|_| false);
+        assert_eq!(
+            text(&tree),
+            [
+                "  name: lumen",
+                "  version: \"1.0\"",
+                "▾ deps:",
+                "    - serde",
+                "    - ratatui",
+            ]
+            .join("\n")
+        );
+        let tree = layout_tree(&value, Syntax::Yaml, &|path| path == "$.deps");
+        assert_eq!(tree.lines[2].text(), "▸ deps: [… 2 items]");
+    }
+
+    #[test]
+    fn test_large_trees_start_folded_below_two_levels() {
+        let items: Vec<String> = (0..LARGE_TREE_ROWS)
+            .map(|i| format!(r#"{{"id": {}, "tags": ["x"]}}"#, i))
+            .collect();
+        let code = format!(r#"{{"items": [{}]}}"#, items.join(","));
+        let value = parse(Syntax::Json, &code).unwrap().unwrap();
+        let tree = layout_tree(&value, Syntax::Json, &|_| false);
+        assert_eq!(tree.lines[2].text(), "    ▸ {… 2 keys},");
+        let tree = layout_tree(&value, Syntax::Json, &|path| path == "$.items[0]");
+        assert_eq!(tree.lines[2].text(), "    ▾ {");
+    }
+
+    #[test]
+    fn test_parse_errors_have_a_location() {
+        let error = parse(Syntax::Json, "{\n  \"a\": 1\n  \"b\": 2\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 3));
+        assert_eq!(error.message, "expected `,` or `}`");
+        assert!(parse(Syntax::Yaml, "a: [1, 2").is_err());
+    }
+
+    #[test]
+    fn test_trees_show_the_source_as_written() {
+        let code = r#"{"big": 12345678901234567890123, "v": 1.10, "a": 1, "a": "\u00e9"}"#;
+        let value = parse(Syntax::Json, code).unwrap().unwrap();
+        let tree = layout_tree(&value, Syntax::Json, &|_| false);
+        assert_eq!(
+            text(&tree),
+            [
+                "▾ {",
+                "    \"big\": 12345678901234567890123,",
+                "    \"v\": 1.10,",
+                "    \"a\": 1,",
+                "    \"a\": \"\\u00e9\"",
+                "  }",
+            ]
+            .join("\n")
+        );
+
+        // YAML the tree would rewrite stays code
+        for code in [
+            "zip: 05432\n",
+            "v: 1.10\n",
+            "a: &x 1\nb: *x\n",
+            "a: 1\na: 2\n",
+            "a: 1\n---\nb: 2\n",
+        ] {
+            assert_eq!(parse(Syntax::Yaml, code), Ok(None), "{}", code);
+        }
+        assert!(parse(Syntax::Yaml, "zip: \"05432\"\nv: 1.1\n")
+            .unwrap()
+            .is_some());
+
+        // Quotes are kept and comments left out
+        let code = "# Site\nname: \"foo\" # quoted\n'a': 'it''s'\nurl: http://x/#frag\n";
+        let value = parse(Syntax::Yaml, code).unwrap().unwrap();
+        let tree = layout_tree(&value, Syntax::Yaml, &|_| false);
+        assert_eq!(
+            text(&tree),
+            "  name: \"foo\"\n  'a': 'it''s'\n  url: http://x/#frag"
+        );
+    }
+
+    #[test]
+    fn test_folds_are_kept_per_block() {
+        let mut folds = TreeFolds::default();
+        folds.toggle("[1]", "$");
+        folds.toggle("[2]", "$[0]");
+        folds.toggle("[2]", "$[1]");
+        assert_eq!(
+            folds.paths("[2]"),
+            BTreeSet::from(["$[0]".into(), "$[1]".into()])
+        );
+        assert!(folds.paths("[3]").is_empty());
+    }
+}
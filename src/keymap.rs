@@ -0,0 +1,870 @@
+//! Key bindings: named actions, the default keymap and user overrides
+//!
+//! Keys are resolved in two steps: key presses accumulate into a pending
+//! sequence until it matches a binding (or can no longer match one), and the
+//! matched binding yields an [`Action`] that the viewer dispatches. Users can
+//! remap or add bindings in the `keys:` section of `~/.lumen/config.yaml`:
+//!
+//! ```yaml
+//! keys:
+//!   gg: top
+//!   Ctrl-e: scroll_down
+//!   d: none        # unbind
+//! ```
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Something the viewer can do in response to a key sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    ScrollDown,
+    ScrollUp,
+    HalfPageDown,
+    HalfPageUp,
+    PageDown,
+    PageUp,
+    Top,
+    Bottom,
+    /// Go to a percentage of the document given by the count (`50%`)
+    GotoPercent,
+    NextHeading,
+    PrevHeading,
+    NextLink,
+    FollowLink,
+    NextFile,
+    PrevFile,
+    JumpToFile,
+    Search,
+    NextMatch,
+    PrevMatch,
+    /// Set a mark; the next key names it
+    SetMark,
+    /// Jump to a mark; the next key names it
+    JumpToMark,
+    /// Fold or unfold the JSON/YAML tree node, section, callout or code
+    /// block in view
+    ToggleFold,
+    FoldAll,
+    UnfoldAll,
+    /// Copy the visual selection, or the first visible code block (code block N with a count)
+    Yank,
+    /// Copy the JSONPath of the JSON/YAML tree node in view
+    CopyTreePath,
+    /// Start or leave visual line selection
+    VisualLine,
+    ToggleTheme,
+    /// Switch code blocks between wrapping and clipping long lines
+    ToggleCodeWrap,
+    ToggleLineNumbers,
+    /// Flip the diagram in view between its drawing and its source
+    ToggleDiagramSource,
+    /// Show the diff in view side by side or inline
+    ToggleDiffSplit,
+    ToggleFileSidebar,
+    Reload,
+    ToggleMouse,
+    ToggleHelp,
+    /// Close help, clear search results, or quit - whichever applies first
+    Cancel,
+    Quit,
+}
+
+impl Action {
+    /// All actions, in the order they appear in the help menu
+    pub const ALL: &'static [Action] = &[
+        Action::ScrollDown,
+        Action::ScrollUp,
+        Action::HalfPageDown,
+        Action::HalfPageUp,
+        Action::PageDown,
+        Action::PageUp,
+        Action::Top,
+        Action::Bottom,
+        Action::GotoPercent,
+        Action::NextHeading,
+        Action::PrevHeading,
+        Action::NextLink,
+        Action::FollowLink,
+        Action::NextFile,
+        Action::PrevFile,
+        Action::JumpToFile,
+        Action::Search,
+        Action::NextMatch,
+        Action::PrevMatch,
+        Action::SetMark,
+        Action::JumpToMark,
+        Action::ToggleFold,
+        Action::FoldAll,
+        Action::UnfoldAll,
+        Action::Yank,
+        Action::CopyTreePath,
+        Action::VisualLine,
+        Action::ToggleTheme,
+        Action::ToggleCodeWrap,
+        Action::ToggleLineNumbers,
+        Action::ToggleDiagramSource,
+        Action::ToggleDiffSplit,
+        Action::ToggleFileSidebar,
+        Action::Reload,
+        Action::ToggleMouse,
+        Action::ToggleHelp,
+        Action::Cancel,
+        Action::Quit,
+    ];
+
+    /// Name used in the config file (snake_case)
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::ScrollDown => "scroll_down",
+            Action::ScrollUp => "scroll_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::PageDown => "page_down",
+            Action::PageUp => "page_up",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::GotoPercent => "goto_percent",
+            Action::NextHeading => "next_heading",
+            Action::PrevHeading => "prev_heading",
+            Action::NextLink => "next_link",
+            Action::FollowLink => "follow_link",
+            Action::NextFile => "next_file",
+            Action::PrevFile => "prev_file",
+            Action::JumpToFile => "jump_to_file",
+            Action::Search => "search",
+            Action::NextMatch => "next_match",
+            Action::PrevMatch => "prev_match",
+            Action::SetMark => "set_mark",
+            Action::JumpToMark => "jump_to_mark",
+            Action::ToggleFold => "toggle_fold",
+            Action::FoldAll => "fold_all",
+            Action::UnfoldAll => "unfold_all",
+            Action::Yank => "yank",
+            Action::CopyTreePath => "copy_tree_path",
+            Action::VisualLine => "visual_line",
+            Action::ToggleTheme => "toggle_theme",
+            Action::ToggleCodeWrap => "toggle_code_wrap",
+            Action::ToggleLineNumbers => "toggle_line_numbers",
+            Action::ToggleDiagramSource => "toggle_diagram_source",
+            Action::ToggleDiffSplit => "toggle_diff_split",
+            Action::ToggleFileSidebar => "toggle_file_sidebar",
+            Action::Reload => "reload",
+            Action::ToggleMouse => "toggle_mouse",
+            Action::ToggleHelp => "toggle_help",
+            Action::Cancel => "cancel",
+            Action::Quit => "quit",
+        }
+    }
+
+    /// Look up an action by its config name
+    pub fn from_name(name: &str) -> Option<Action> {
+        let name = name.trim().to_lowercase().replace('-', "_");
+        Self::ALL.iter().copied().find(|a| a.name() == name)
+    }
+
+    /// One-line description shown in the help menu
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::ScrollDown => "Scroll down one line",
+            Action::ScrollUp => "Scroll up one line",
+            Action::HalfPageDown => "Scroll down half page",
+            Action::HalfPageUp => "Scroll up half page",
+            Action::PageDown => "Scroll down one page",
+            Action::PageUp => "Scroll up one page",
+            Action::Top => "Go to top (or line N)",
+            Action::Bottom => "Go to bottom (or line N)",
+            Action::GotoPercent => "Go to N% of document",
+            Action::NextHeading => "Jump to next heading",
+            Action::PrevHeading => "Jump to previous heading",
+            Action::NextLink => "Cycle through links",
+            Action::FollowLink => "Follow selected link",
+            Action::NextFile => "Switch to next file (or file N)",
+            Action::PrevFile => "Switch to previous file",
+            Action::JumpToFile => "Jump to file N (type number)",
+            Action::Search => "Start search",
+            Action::NextMatch => "Next match (or next heading)",
+            Action::PrevMatch => "Previous match",
+            Action::SetMark => "Set mark (then a-z)",
+            Action::JumpToMark => "Jump to mark (then a-z)",
+            Action::ToggleFold => "Fold / unfold tree node, section, callout or code",
+            Action::FoldAll => "Fold everything",
+            Action::UnfoldAll => "Unfold everything",
+            Action::Yank => "Copy selection or code block (N)",
+            Action::CopyTreePath => "Copy JSON path of tree node",
+            Action::VisualLine => "Select whole lines (visual mode)",
+            Action::ToggleTheme => "Cycle through themes",
+            Action::ToggleCodeWrap => "Wrap / clip long code lines",
+            Action::ToggleLineNumbers => "Toggle code line numbers",
+            Action::ToggleDiagramSource => "Show diagram source / drawing",
+            Action::ToggleDiffSplit => "Side-by-side / inline diff",
+            Action::ToggleFileSidebar => "Toggle file sidebar",
+            Action::Reload => "Reload current file",
+            Action::ToggleMouse => "Toggle mouse mode",
+            Action::ToggleHelp => "Toggle this help menu",
+            Action::Cancel => "Close help / clear search / quit",
+            Action::Quit => "Quit",
+        }
+    }
+
+    /// Help menu section this action is listed under
+    pub fn category(self) -> &'static str {
+        match self {
+            Action::ScrollDown
+            | Action::ScrollUp
+            | Action::HalfPageDown
+            | Action::HalfPageUp
+            | Action::PageDown
+            | Action::PageUp
+            | Action::Top
+            | Action::Bottom
+            | Action::GotoPercent => "Navigation",
+            Action::NextHeading | Action::PrevHeading => "Header Navigation",
+            Action::NextLink | Action::FollowLink => "Link Navigation",
+            Action::NextFile | Action::PrevFile | Action::JumpToFile => "File Navigation",
+            Action::Search | Action::NextMatch | Action::PrevMatch => "Search",
+            Action::SetMark | Action::JumpToMark => "Marks",
+            Action::ToggleFold | Action::FoldAll | Action::UnfoldAll => "Folding",
+            Action::Yank | Action::CopyTreePath | Action::VisualLine => "Clipboard",
+            Action::ToggleTheme
+            | Action::ToggleCodeWrap
+            | Action::ToggleLineNumbers
+            | Action::ToggleDiagramSource
+            | Action::ToggleDiffSplit
+            | Action::ToggleFileSidebar
+            | Action::Reload
+            | Action::ToggleMouse
+            | Action::ToggleHelp
+            | Action::Cancel
+            | Action::Quit => "Other",
+        }
+    }
+}
+
+/// A single key press (code + modifiers), normalised for lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Shift is already encoded in the character ('G') and in BackTab
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Self {
+            code,
+            modifiers: modifiers
+                & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT),
+        }
+    }
+
+    pub fn plain(code: KeyCode) -> Self {
+        Self::new(code, KeyModifiers::NONE)
+    }
+
+    /// Parse a single key such as `j`, `Ctrl-d`, `Alt-x`, `PageDown`, `Space`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+
+        loop {
+            let lower = rest.to_lowercase();
+            let prefix = ["ctrl-", "c-", "alt-", "a-", "m-", "shift-", "s-"]
+                .iter()
+                .find(|p| lower.starts_with(**p) && rest.len() > p.len());
+            match prefix {
+                Some(p) => {
+                    modifiers |= match *p {
+                        "ctrl-" | "c-" => KeyModifiers::CONTROL,
+                        "alt-" | "a-" | "m-" => KeyModifiers::ALT,
+                        _ => KeyModifiers::SHIFT,
+                    };
+                    rest = &rest[p.len()..];
+                }
+                None => break,
+            }
+        }
+
+        let code = match rest.to_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" | "return" | "cr" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" | "bs" => KeyCode::Backspace,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => {
+                        // Ctrl-D is the same key press as Ctrl-d
+                        if modifiers.contains(KeyModifiers::CONTROL) {
+                            KeyCode::Char(c.to_ascii_lowercase())
+                        } else {
+                            KeyCode::Char(c)
+                        }
+                    }
+                    _ => match rest
+                        .strip_prefix(['F', 'f'])
+                        .and_then(|n| n.parse::<u8>().ok())
+                    {
+                        Some(n) => KeyCode::F(n),
+                        None => return Err(format!("unknown key '{}'", s)),
+                    },
+                }
+            }
+        };
+
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            KeyCode::Delete => write!(f, "Delete"),
+            KeyCode::Insert => write!(f, "Insert"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Parse a key sequence: `gg`, `zz`, `Ctrl-d`, `g Tab`, `Ctrl-w j`.
+///
+/// Whitespace separates keys. A token that is not a named key is read as
+/// one key per character, so `gg` is two presses of `g`.
+pub fn parse_sequence(s: &str) -> Result<Vec<Key>, String> {
+    let mut keys = Vec::new();
+    for token in s.split_whitespace() {
+        match Key::parse(token) {
+            Ok(key) => keys.push(key),
+            Err(err) => {
+                if token.contains('-') {
+                    return Err(err);
+                }
+                keys.extend(token.chars().map(|c| Key::plain(KeyCode::Char(c))));
+            }
+        }
+    }
+    if keys.is_empty() {
+        // A lone space is a valid binding
+        if s == " " {
+            keys.push(Key::plain(KeyCode::Char(' ')));
+        } else {
+            return Err("empty key sequence".to_string());
+        }
+    }
+    Ok(keys)
+}
+
+/// Format a key sequence for display (`gg`, `Ctrl-d`, `g Tab`)
+pub fn format_sequence(keys: &[Key]) -> String {
+    let all_plain_chars = keys
+        .iter()
+        .all(|k| k.modifiers.is_empty() && matches!(k.code, KeyCode::Char(c) if c != ' '));
+    let parts: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+    if all_plain_chars {
+        parts.concat()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// Largest count prefix accepted; more digits are ignored
+const MAX_COUNT: usize = 99_999;
+
+/// Input typed so far that has not produced an action yet: a count prefix
+/// (`10` in `10j`), a partial key sequence, or an action waiting for its
+/// argument key (the mark name after `M`)
+#[derive(Debug, Clone, Default)]
+pub struct PendingInput {
+    pub count: Option<usize>,
+    pub keys: Vec<Key>,
+    pub awaiting: Option<Action>,
+}
+
+impl PendingInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accumulate a count digit. Returns false if the key is not part of a
+    /// count (a leading `0`, a digit inside a key sequence, a modified key).
+    pub fn push_digit(&mut self, key: Key) -> bool {
+        if !self.keys.is_empty() || self.awaiting.is_some() || !key.modifiers.is_empty() {
+            return false;
+        }
+        let digit = match key.code {
+            KeyCode::Char(c) => match c.to_digit(10) {
+                Some(d) => d as usize,
+                None => return false,
+            },
+            _ => return false,
+        };
+        if digit == 0 && self.count.is_none() {
+            return false;
+        }
+        let count = self.count.unwrap_or(0) * 10 + digit;
+        self.count = Some(count.min(MAX_COUNT));
+        true
+    }
+
+    /// Take the count prefix, leaving none behind
+    pub fn take_count(&mut self) -> Option<usize> {
+        self.count.take()
+    }
+
+    pub fn clear(&mut self) {
+        self.count = None;
+        self.keys.clear();
+        self.awaiting = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count.is_none() && self.keys.is_empty() && self.awaiting.is_none()
+    }
+
+    /// Text shown in the status bar while input is pending (`10`, `3g`, `M`)
+    pub fn display(&self, keymap: &Keymap) -> String {
+        let mut text = self.count.map(|c| c.to_string()).unwrap_or_default();
+        text.push_str(&format_sequence(&self.keys));
+        if let Some(action) = self.awaiting {
+            if let Some(keys) = keymap.keys_for(action).first() {
+                text.push_str(keys);
+            }
+        }
+        text
+    }
+}
+
+/// Result of feeding the pending key sequence to the keymap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// The sequence is bound to this action
+    Action(Action),
+    /// The sequence is a prefix of a longer binding; wait for more keys
+    Pending,
+    /// Nothing is bound to this sequence
+    Unbound,
+}
+
+/// Mapping from key sequences to actions
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Vec<Key>, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use KeyCode::*;
+
+        let ctrl = |c| Key::new(Char(c), KeyModifiers::CONTROL);
+        let plain = Key::plain;
+        let ch = |c| Key::plain(Char(c));
+
+        let defaults: &[(&[Key], Action)] = &[
+            (&[ch('j')], Action::ScrollDown),
+            (&[plain(Down)], Action::ScrollDown),
+            (&[ch('k')], Action::ScrollUp),
+            (&[plain(Up)], Action::ScrollUp),
+            (&[ch('d')], Action::HalfPageDown),
+            (&[ctrl('d')], Action::HalfPageDown),
+            (&[ch('u')], Action::HalfPageUp),
+            (&[ctrl('u')], Action::HalfPageUp),
+            (&[ch(' ')], Action::PageDown),
+            (&[plain(PageDown)], Action::PageDown),
+            (&[ctrl('f')], Action::PageDown),
+            (&[plain(PageUp)], Action::PageUp),
+            (&[ctrl('b')], Action::PageUp),
+            (&[ch('g')], Action::Top),
+            (&[plain(Home)], Action::Top),
+            (&[ch('G')], Action::Bottom),
+            (&[plain(End)], Action::Bottom),
+            (&[ch('%')], Action::GotoPercent),
+            (&[ch('p')], Action::PrevHeading),
+            (&[ch('a')], Action::NextLink),
+            (&[plain(Enter)], Action::FollowLink),
+            (&[plain(Tab)], Action::NextFile),
+            (&[plain(BackTab)], Action::PrevFile),
+            (&[ch(':')], Action::JumpToFile),
+            (&[ch('/')], Action::Search),
+            (&[ch('n')], Action::NextMatch),
+            (&[ch('N')], Action::PrevMatch),
+            (&[ch('M')], Action::SetMark),
+            (&[ch('\'')], Action::JumpToMark),
+            (&[ch('`')], Action::JumpToMark),
+            (&[ch('t')], Action::ToggleTheme),
+            (&[ch('z'), ch('a')], Action::ToggleFold),
+            (&[ch('z'), ch('M')], Action::FoldAll),
+            (&[ch('z'), ch('R')], Action::UnfoldAll),
+            (&[ch('y')], Action::Yank),
+            (&[ch('Y')], Action::CopyTreePath),
+            (&[ch('V')], Action::VisualLine),
+            (&[ch('w')], Action::ToggleCodeWrap),
+            (&[ch('#')], Action::ToggleLineNumbers),
+            (&[ch('s')], Action::ToggleDiagramSource),
+            (&[ch('S')], Action::ToggleDiffSplit),
+            (&[ch('f')], Action::ToggleFileSidebar),
+            (&[ch('r')], Action::Reload),
+            (&[ch('m')], Action::ToggleMouse),
+            (&[ch('h')], Action::ToggleHelp),
+            (&[plain(Esc)], Action::Cancel),
+            (&[ch('q')], Action::Quit),
+            (&[ctrl('c')], Action::Quit),
+        ];
+
+        Self {
+            bindings: defaults
+                .iter()
+                .map(|(keys, action)| (keys.to_vec(), *action))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Default keymap with the user's `keys:` overrides applied.
+    ///
+    /// Returns the keymap and a warning for every entry that could not be
+    /// understood; those entries are skipped.
+    pub fn with_overrides(overrides: &BTreeMap<String, String>) -> (Self, Vec<String>) {
+        let mut keymap = Self::default();
+        let warnings = keymap.apply_overrides(overrides);
+        (keymap, warnings)
+    }
+
+    /// Apply `sequence: action` overrides; `none` removes a binding
+    pub fn apply_overrides(&mut self, overrides: &BTreeMap<String, String>) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        for (sequence, action_name) in overrides {
+            let keys = match parse_sequence(sequence) {
+                Ok(keys) => keys,
+                Err(e) => {
+                    warnings.push(format!("key binding '{}': {}", sequence, e));
+                    continue;
+                }
+            };
+
+            if matches!(action_name.trim(), "none" | "unbind" | "") {
+                self.bindings.remove(&keys);
+                continue;
+            }
+
+            match Action::from_name(action_name) {
+                Some(action) => self.bind(keys, action),
+                None => warnings.push(format!(
+                    "key binding '{}': unknown action '{}'",
+                    sequence, action_name
+                )),
+            }
+        }
+
+        warnings
+    }
+
+    /// Bind a key sequence to an action, replacing any existing binding
+    pub fn bind(&mut self, keys: Vec<Key>, action: Action) {
+        self.bindings.insert(keys, action);
+    }
+
+    /// Look up the action bound to exactly this sequence
+    pub fn get(&self, keys: &[Key]) -> Option<Action> {
+        self.bindings.get(keys).copied()
+    }
+
+    /// Resolve a pending key sequence.
+    ///
+    /// A sequence that is both bound and a prefix of a longer binding waits
+    /// for the next key; see [`Keymap::resolve_pending`] for how such a
+    /// sequence is finished when the next key does not continue it.
+    pub fn resolve(&self, keys: &[Key]) -> Resolution {
+        let is_prefix = self
+            .bindings
+            .keys()
+            .any(|seq| seq.len() > keys.len() && seq.starts_with(keys));
+        if is_prefix {
+            return Resolution::Pending;
+        }
+        match self.get(keys) {
+            Some(action) => Resolution::Action(action),
+            None => Resolution::Unbound,
+        }
+    }
+
+    /// Feed a key into a pending sequence and collect the actions it produces.
+    ///
+    /// When the new key breaks a pending sequence, the longest bound prefix
+    /// of it (if any) runs and the remaining keys are resolved again, so
+    /// binding both `g` and `gg` behaves like vim.
+    pub fn resolve_pending(&self, pending: &mut Vec<Key>, key: Key) -> Vec<Action> {
+        pending.push(key);
+        let mut actions = Vec::new();
+
+        while !pending.is_empty() {
+            match self.resolve(pending) {
+                Resolution::Pending => break,
+                Resolution::Action(action) => {
+                    actions.push(action);
+                    pending.clear();
+                }
+                Resolution::Unbound => {
+                    // Run the longest bound prefix, then retry the rest
+                    let prefix_len = (1..pending.len())
+                        .rev()
+                        .find(|&len| self.get(&pending[..len]).is_some());
+                    match prefix_len {
+                        Some(len) => {
+                            actions.extend(self.get(&pending[..len]));
+                            pending.drain(..len);
+                        }
+                        None => {
+                            pending.remove(0);
+                        }
+                    }
+                }
+            }
+        }
+
+        actions
+    }
+
+    /// Key sequences bound to an action, shortest first
+    pub fn keys_for(&self, action: Action) -> Vec<String> {
+        let mut sequences: Vec<&Vec<Key>> = self
+            .bindings
+            .iter()
+            .filter(|(_, a)| **a == action)
+            .map(|(keys, _)| keys)
+            .collect();
+        sequences.sort_by_key(|keys| {
+            let named = keys.iter().any(|k| !matches!(k.code, KeyCode::Char(_)));
+            let modified = keys.iter().any(|k| !k.modifiers.is_empty());
+            (named, modified, keys.len(), format_sequence(keys))
+        });
+        sequences
+            .into_iter()
+            .map(|keys| format_sequence(keys))
+            .collect()
+    }
+
+    /// Help menu contents: (section title, [(keys, description)])
+    pub fn help_sections(&self) -> Vec<(&'static str, Vec<(String, &'static str)>)> {
+        let mut sections: Vec<(&'static str, Vec<(String, &'static str)>)> = Vec::new();
+
+        for &action in Action::ALL {
+            let keys = self.keys_for(action);
+            if keys.is_empty() {
+                continue;
+            }
+            let entry = (keys.join(" / "), action.description());
+            match sections
+                .iter_mut()
+                .find(|(title, _)| *title == action.category())
+            {
+                Some((_, entries)) => entries.push(entry),
+                None => sections.push((action.category(), vec![entry])),
+            }
+        }
+
+        sections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ch(c: char) -> Key {
+        Key::plain(KeyCode::Char(c))
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(Key::parse("j").unwrap(), ch('j'));
+        assert_eq!(
+            Key::parse("Ctrl-d").unwrap(),
+            Key::new(KeyCode::Char('d'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(Key::parse("C-D").unwrap(), Key::parse("ctrl-d").unwrap());
+        assert_eq!(
+            Key::parse("PageDown").unwrap(),
+            Key::plain(KeyCode::PageDown)
+        );
+        assert_eq!(
+            Key::parse("Shift-Tab").unwrap(),
+            Key::plain(KeyCode::BackTab)
+        );
+        assert_eq!(Key::parse("space").unwrap(), ch(' '));
+        assert!(Key::parse("Ctrl-nope").is_err());
+    }
+
+    #[test]
+    fn test_parse_sequences() {
+        assert_eq!(parse_sequence("gg").unwrap(), vec![ch('g'), ch('g')]);
+        assert_eq!(parse_sequence("zz").unwrap(), vec![ch('z'), ch('z')]);
+        assert_eq!(
+            parse_sequence("g Tab").unwrap(),
+            vec![ch('g'), Key::plain(KeyCode::Tab)]
+        );
+        assert_eq!(parse_sequence("Up").unwrap(), vec![Key::plain(KeyCode::Up)]);
+        assert_eq!(format_sequence(&parse_sequence("gg").unwrap()), "gg");
+    }
+
+    #[test]
+    fn test_shift_is_normalised() {
+        let event = KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT);
+        assert_eq!(Key::from(event), ch('G'));
+        let event = KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert_eq!(Key::from(event), Key::plain(KeyCode::BackTab));
+    }
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.get(&[ch('j')]), Some(Action::ScrollDown));
+        assert_eq!(keymap.get(&[ch('/')]), Some(Action::Search));
+        // `m` has toggled mouse capture since before marks existed
+        assert_eq!(keymap.get(&[ch('m')]), Some(Action::ToggleMouse));
+        assert_eq!(keymap.get(&[ch('M')]), Some(Action::SetMark));
+        assert_eq!(
+            keymap.get(&[Key::parse("Ctrl-d").unwrap()]),
+            Some(Action::HalfPageDown)
+        );
+    }
+
+    #[test]
+    fn test_overrides_remap_add_and_unbind() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("gg".to_string(), "top".to_string());
+        overrides.insert("zz".to_string(), "half_page_down".to_string());
+        overrides.insert("d".to_string(), "none".to_string());
+        overrides.insert("x".to_string(), "explode".to_string());
+
+        let (keymap, warnings) = Keymap::with_overrides(&overrides);
+
+        assert_eq!(keymap.get(&[ch('g'), ch('g')]), Some(Action::Top));
+        assert_eq!(keymap.get(&[ch('z'), ch('z')]), Some(Action::HalfPageDown));
+        assert_eq!(keymap.get(&[ch('d')]), None);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("explode"));
+    }
+
+    #[test]
+    fn test_resolve_multi_key_sequences() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("gg".to_string(), "top".to_string());
+        overrides.insert("zz".to_string(), "half_page_down".to_string());
+        let (keymap, _) = Keymap::with_overrides(&overrides);
+        let mut pending = Vec::new();
+
+        // `z` alone waits for the second key
+        assert!(keymap.resolve_pending(&mut pending, ch('z')).is_empty());
+        assert_eq!(
+            keymap.resolve_pending(&mut pending, ch('z')),
+            vec![Action::HalfPageDown]
+        );
+        assert!(pending.is_empty());
+
+        // `g` is bound on its own and as `gg`; `gj` runs both
+        assert!(keymap.resolve_pending(&mut pending, ch('g')).is_empty());
+        assert_eq!(
+            keymap.resolve_pending(&mut pending, ch('j')),
+            vec![Action::Top, Action::ScrollDown]
+        );
+
+        // Unbound keys are dropped
+        assert!(keymap.resolve_pending(&mut pending, ch('Z')).is_empty());
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_count_prefix() {
+        let mut pending = PendingInput::new();
+
+        // A leading zero is not a count
+        assert!(!pending.push_digit(ch('0')));
+        assert!(pending.push_digit(ch('1')));
+        assert!(pending.push_digit(ch('0')));
+        assert!(!pending.push_digit(ch('j')));
+        assert_eq!(pending.display(&Keymap::default()), "10");
+        assert_eq!(pending.take_count(), Some(10));
+        assert!(pending.is_empty());
+
+        // Digits inside a key sequence are not counts
+        pending.keys.push(ch('g'));
+        assert!(!pending.push_digit(ch('5')));
+    }
+
+    #[test]
+    fn test_help_sections_follow_keymap() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("J".to_string(), "scroll_down".to_string());
+        overrides.insert("r".to_string(), "none".to_string());
+        let (keymap, _) = Keymap::with_overrides(&overrides);
+
+        let sections = keymap.help_sections();
+        let navigation = &sections
+            .iter()
+            .find(|(title, _)| *title == "Navigation")
+            .unwrap()
+            .1;
+        assert_eq!(
+            navigation[0],
+            ("J / j / ↓".to_string(), "Scroll down one line")
+        );
+
+        let other = &sections
+            .iter()
+            .find(|(title, _)| *title == "Other")
+            .unwrap()
+            .1;
+        assert!(other.iter().all(|(_, desc)| *desc != "Reload current file"));
+    }
+}
@@ -0,0 +1,253 @@
+//! Block-level layout cache
+//!
+//! Laying out a top-level block depends only on the block itself, the
+//! available width, the theme, the image mode, the code block renderers
+//! and what the reader chose for the code blocks in it (source or
+//! rendering, side-by-side diffs, folded tree nodes). The cache stores each
+//! block's layout at the origin so that a relayout (scrolling in lazy mode,
+//! switching back to a file, toggling a sidebar back) only has to lay out
+//! blocks it has not seen before and translate the rest into place.
+
+use super::engine::LayoutOptions;
+use super::types::*;
+use crate::ir::Block;
+use crate::theme::Theme;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Upper bound on cached blocks before the cache is flushed.
+///
+/// Every (width, theme) combination gets its own entries, so resizing a
+/// terminal repeatedly would otherwise grow the cache without bound.
+const MAX_ENTRIES: usize = 50_000;
+
+/// Cache key: a block laid out at a given width with a given theme
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    block: u64,
+    width: u16,
+    theme: u64,
+    inline_images: bool,
+    renderers: u64,
+    code_blocks: u64,
+}
+
+impl CacheKey {
+    pub(crate) fn new(block: &Block, width: u16, theme: u64, options: &LayoutOptions) -> Self {
+        let mut hasher = DefaultHasher::new();
+        hash_code_blocks(std::slice::from_ref(block), options, &mut hasher);
+        let code_blocks = hasher.finish();
+        let mut hasher = DefaultHasher::new();
+        block.hash(&mut hasher);
+        Self {
+            block: hasher.finish(),
+            width,
+            theme,
+            inline_images: options.inline_images,
+            renderers: options.renderers.fingerprint(),
+            code_blocks,
+        }
+    }
+}
+
+/// Hash the reader's choices for the code blocks in `blocks` only, so
+/// flipping one block doesn't invalidate every other
+fn hash_code_blocks(blocks: &[Block], options: &LayoutOptions, hasher: &mut DefaultHasher) {
+    for block in blocks {
+        match block {
+            Block::CodeBlock { code, .. } => {
+                options.sources.contains(code).hash(hasher);
+                options.split_diffs.contains(code).hash(hasher);
+                options.tree_folds.paths(code).hash(hasher);
+            }
+            Block::BlockQuote { blocks }
+            | Block::Callout {
+                content: blocks, ..
+            }
+            | Block::Details {
+                content: blocks, ..
+            } => hash_code_blocks(blocks, options, hasher),
+            Block::List { items, .. } => {
+                for item in items {
+                    hash_code_blocks(&item.content, options, hasher);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A block laid out at y = 0, together with everything it registered
+#[derive(Debug, Clone)]
+pub(crate) struct CachedBlock {
+    pub(crate) node: LayoutNode,
+    pub(crate) hit_regions: Vec<HitRegion>,
+    pub(crate) images: Vec<ImageReference>,
+}
+
+impl CachedBlock {
+    /// Clone this block into a document at row `y`, appending its hit
+    /// regions and images to the document's lists
+    pub(crate) fn place(
+        &self,
+        y: u16,
+        node_counter: &mut NodeId,
+        hit_regions: &mut Vec<HitRegion>,
+        images: &mut Vec<ImageReference>,
+    ) -> LayoutNode {
+        let mut node = self.node.clone();
+        translate_node(&mut node, y, node_counter);
+
+        hit_regions.extend(self.hit_regions.iter().map(|region| {
+            let mut region = region.clone();
+            region.rect.y = region.rect.y.saturating_add(y);
+            region
+        }));
+        images.extend(self.images.iter().map(|image| {
+            let mut image = image.clone();
+            image.y_position = image.y_position.saturating_add(y);
+            image
+        }));
+
+        node
+    }
+}
+
+/// Shift a node tree down by `dy` rows and give it fresh node ids
+fn translate_node(node: &mut LayoutNode, dy: u16, node_counter: &mut NodeId) {
+    *node_counter += 1;
+    node.id = *node_counter;
+    node.rect.y = node.rect.y.saturating_add(dy);
+    for child in &mut node.children {
+        translate_node(child, dy, node_counter);
+    }
+}
+
+/// Cache of laid-out top-level blocks, owned per open file
+#[derive(Debug, Clone, Default)]
+pub struct LayoutCache {
+    entries: HashMap<CacheKey, CachedBlock>,
+}
+
+impl LayoutCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of cached blocks
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop all cached blocks (e.g. after the document was reloaded)
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub(crate) fn get(&self, key: &CacheKey) -> Option<&CachedBlock> {
+        self.entries.get(key)
+    }
+
+    pub(crate) fn insert(&mut self, key: CacheKey, block: CachedBlock) {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.clear();
+        }
+        self.entries.insert(key, block);
+    }
+}
+
+/// Fingerprint of everything in a theme that can affect layout
+pub(crate) fn theme_fingerprint(theme: &Theme) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", theme).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::Inline;
+    use crate::theme;
+
+    fn paragraph(text: &str) -> Block {
+        Block::Paragraph {
+            content: vec![Inline::Text(text.to_string())],
+        }
+    }
+
+    #[test]
+    fn test_cache_key_depends_on_block_width_and_theme() {
+        let docs = theme_fingerprint(&theme::docs_theme());
+        let neon = theme_fingerprint(&theme::neon_theme());
+        assert_ne!(docs, neon);
+
+        let options = LayoutOptions::default();
+        let a = CacheKey::new(&paragraph("a"), 80, docs, &options);
+        assert_eq!(a, CacheKey::new(&paragraph("a"), 80, docs, &options));
+        assert_ne!(a, CacheKey::new(&paragraph("b"), 80, docs, &options));
+        assert_ne!(a, CacheKey::new(&paragraph("a"), 40, docs, &options));
+        assert_ne!(a, CacheKey::new(&paragraph("a"), 80, neon, &options));
+
+        // Flipping a code block only changes keys of blocks holding it
+        let code = Block::CodeBlock {
+            lang: Some("mermaid".to_string()),
+            code: "graph LR\nA --> B".to_string(),
+        };
+        let quote = Block::BlockQuote {
+            blocks: vec![code.clone()],
+        };
+        let mut sources = options.clone();
+        sources.sources.toggle("graph LR\nA --> B");
+        assert_eq!(a, CacheKey::new(&paragraph("a"), 80, docs, &sources));
+        assert_ne!(
+            CacheKey::new(&code, 80, docs, &options),
+            CacheKey::new(&code, 80, docs, &sources)
+        );
+        assert_ne!(
+            CacheKey::new(&quote, 80, docs, &options),
+            CacheKey::new(&quote, 80, docs, &sources)
+        );
+        let mut folds = options.clone();
+        folds.tree_folds.toggle("{}", "$");
+        assert_eq!(
+            CacheKey::new(&code, 80, docs, &options),
+            CacheKey::new(&code, 80, docs, &folds)
+        );
+    }
+
+    #[test]
+    fn test_place_translates_regions_and_renumbers() {
+        let node = LayoutNode {
+            id: 1,
+            rect: Rectangle::new(0, 0, 10, 2),
+            element: LayoutElement::HorizontalRule,
+            children: Vec::new(),
+            style: ComputedStyle::default(),
+        };
+        let cached = CachedBlock {
+            node,
+            hit_regions: vec![HitRegion {
+                rect: Rectangle::new(0, 1, 10, 1),
+                element: HitElement::CodeBlock {
+                    lang: None,
+                    code: String::new(),
+                },
+            }],
+            images: Vec::new(),
+        };
+
+        let mut counter = 41;
+        let mut regions = Vec::new();
+        let mut images = Vec::new();
+        let placed = cached.place(7, &mut counter, &mut regions, &mut images);
+
+        assert_eq!(placed.id, 42);
+        assert_eq!(placed.rect.y, 7);
+        assert_eq!(regions[0].rect.y, 8);
+    }
+}
@@ -0,0 +1,253 @@
+//! Code block layout: fence info, line numbers and soft wrapping
+
+use super::types::{CodeLine, Rectangle};
+use std::ops::RangeInclusive;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Marker drawn in the gutter of a wrapped row
+pub const CONTINUATION_MARKER: &str = "↪";
+
+/// Clickable badge on a code block's top border that copies the code
+pub const COPY_BADGE: &str = " copy ";
+
+/// A fence info string split into its parts, e.g. ```` ```rust {3,7-9} ````
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FenceInfo {
+    /// Language name (first word of the info string)
+    pub lang: Option<String>,
+    /// Source lines to highlight (1-based, inclusive)
+    pub highlight: Vec<RangeInclusive<usize>>,
+}
+
+impl FenceInfo {
+    /// Parse an info string such as `rust {3,7-9}` or `python title="x" {2}`.
+    ///
+    /// Malformed highlight entries are ignored rather than rejected, so a
+    /// typo in the braces never hides the code block.
+    pub fn parse(info: &str) -> Self {
+        let info = info.trim();
+        let lang = info
+            .split_whitespace()
+            .next()
+            .filter(|word| !word.starts_with('{'))
+            .map(|word| word.to_string());
+
+        let mut highlight = Vec::new();
+        if let (Some(open), Some(close)) = (info.find('{'), info.rfind('}')) {
+            if open < close {
+                for part in info[open + 1..close].split(',') {
+                    let part = part.trim();
+                    let range = match part.split_once('-') {
+                        Some((start, end)) => {
+                            start.trim().parse().ok().zip(end.trim().parse().ok())
+                        }
+                        None => part.parse().ok().map(|line| (line, line)),
+                    };
+                    if let Some((start, end)) = range {
+                        if start >= 1 && start <= end {
+                            highlight.push(start..=end);
+                        }
+                    }
+                }
+            }
+        }
+
+        Self { lang, highlight }
+    }
+
+    pub fn is_highlighted(&self, line: usize) -> bool {
+        self.highlight.iter().any(|range| range.contains(&line))
+    }
+}
+
+/// Width of the gutter for a code block, given the widest content area.
+///
+/// With line numbers the gutter holds the largest number plus a space;
+/// without them it only exists (as two columns for `↪ `) when some line is
+/// actually too wide and gets wrapped.
+pub fn gutter_width(code: &str, width: u16, wrap: bool, line_numbers: bool) -> u16 {
+    if line_numbers {
+        let count = code.lines().count().max(1);
+        return count.to_string().len() as u16 + 1;
+    }
+    if wrap
+        && code
+            .lines()
+            .any(|line| display_width(line) > width as usize)
+    {
+        return 2;
+    }
+    0
+}
+
+/// Split a code block into rows, soft-wrapping lines wider than `width`
+/// when `wrap` is set (otherwise long lines are left for the renderer to clip)
+pub fn layout_code_lines(code: &str, width: u16, wrap: bool, info: &FenceInfo) -> Vec<CodeLine> {
+    let mut rows = Vec::new();
+    for (index, line) in code.lines().enumerate() {
+        let number = index + 1;
+        let highlighted = info.is_highlighted(number);
+        let pieces = if wrap {
+            wrap_code_line(line, width as usize)
+        } else {
+            vec![line.to_string()]
+        };
+        for (piece_index, text) in pieces.into_iter().enumerate() {
+            rows.push(CodeLine {
+                text,
+                number,
+                continuation: piece_index > 0,
+                highlighted,
+                error: false,
+            });
+        }
+    }
+    rows
+}
+
+/// Insert a marker row pointing at 1-based `line` and `column`, under
+/// the row (or wrapped piece) holding that column
+pub fn insert_error_marker(
+    rows: &mut Vec<CodeLine>,
+    line: usize,
+    column: usize,
+    message: &str,
+    width: u16,
+) {
+    let Some(first) = rows.iter().position(|row| row.number == line) else {
+        return;
+    };
+    let pieces = rows[first..]
+        .iter()
+        .take_while(|row| row.number == line)
+        .count();
+    let mut offset = column.saturating_sub(1);
+    let mut piece = 0;
+    while piece + 1 < pieces && offset >= display_width(&rows[first + piece].text) {
+        offset -= display_width(&rows[first + piece].text);
+        piece += 1;
+    }
+    // Keep the caret on screen for errors past the right edge
+    let offset = offset.min((width as usize).saturating_sub(1));
+    rows.insert(
+        first + piece + 1,
+        CodeLine {
+            text: format!("{}^ {}", " ".repeat(offset), message),
+            number: line,
+            continuation: true,
+            highlighted: false,
+            error: true,
+        },
+    );
+}
+
+/// Break one source line into pieces no wider than `width` display columns
+fn wrap_code_line(line: &str, width: usize) -> Vec<String> {
+    if width == 0 || display_width(line) <= width {
+        return vec![line.to_string()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0;
+    for ch in line.chars() {
+        let ch_width = ch.width().unwrap_or(0);
+        if current_width + ch_width > width && !current.is_empty() {
+            pieces.push(std::mem::take(&mut current));
+            current_width = 0;
+        }
+        current.push(ch);
+        current_width += ch_width;
+    }
+    pieces.push(current);
+    pieces
+}
+
+/// Where the copy badge sits on the top border of a code block at `block`:
+/// just left of the language badge, or near the right corner without one
+pub fn copy_badge_rect(block: Rectangle, lang_badge: Option<&str>) -> Rectangle {
+    let badge_width = COPY_BADGE.len() as u16;
+    // The language badge is ` lang ` ending one column before the corner
+    let right = match lang_badge {
+        Some(lang) => block
+            .width
+            .saturating_sub(display_width(lang) as u16 + 3)
+            .saturating_sub(1),
+        None => block.width.saturating_sub(2),
+    };
+    Rectangle::new(
+        block.x + right.saturating_sub(badge_width),
+        block.y,
+        badge_width.min(right),
+        1,
+    )
+}
+
+fn display_width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fence_info() {
+        let info = FenceInfo::parse("rust {3,7-9}");
+        assert_eq!(info.lang.as_deref(), Some("rust"));
+        assert_eq!(info.highlight, vec![3..=3, 7..=9]);
+        assert!(info.is_highlighted(8));
+        assert!(!info.is_highlighted(4));
+
+        let bare = FenceInfo::parse("{2}");
+        assert_eq!(bare.lang, None);
+        assert_eq!(bare.highlight, vec![2..=2]);
+
+        // Bad entries are skipped, good ones kept
+        let typo = FenceInfo::parse("js {x, 4, 9-2}");
+        assert_eq!(typo.highlight, vec![4..=4]);
+    }
+
+    #[test]
+    fn test_wrap_keeps_line_numbers() {
+        let code = "short\nabcdefghij\nend";
+        let rows = layout_code_lines(code, 4, true, &FenceInfo::parse("txt {2}"));
+
+        let texts: Vec<&str> = rows.iter().map(|row| row.text.as_str()).collect();
+        assert_eq!(texts, vec!["shor", "t", "abcd", "efgh", "ij", "end"]);
+        let numbers: Vec<usize> = rows.iter().map(|row| row.number).collect();
+        assert_eq!(numbers, vec![1, 1, 2, 2, 2, 3]);
+        assert!(rows[3].continuation && !rows[2].continuation);
+        assert!(rows[2].highlighted && rows[4].highlighted && !rows[5].highlighted);
+    }
+
+    #[test]
+    fn test_clip_mode_keeps_one_row_per_line() {
+        let rows = layout_code_lines("abcdefghij", 4, false, &FenceInfo::default());
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].text, "abcdefghij");
+    }
+
+    #[test]
+    fn test_copy_badge_sits_left_of_language_badge() {
+        let block = Rectangle::new(4, 10, 40, 5);
+        let badge = copy_badge_rect(block, Some("rust"));
+        // Language badge " rust " starts at column 4 + 40 - 7 = 37
+        assert_eq!(badge, Rectangle::new(30, 10, 6, 1));
+
+        let plain = copy_badge_rect(block, None);
+        assert_eq!(plain.x + plain.width, 4 + 38);
+    }
+
+    #[test]
+    fn test_gutter_width() {
+        let code = (1..=12)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(gutter_width(&code, 40, true, true), 3);
+        assert_eq!(gutter_width("fits", 40, true, false), 0);
+        assert_eq!(gutter_width("does not fit", 4, true, false), 2);
+        assert_eq!(gutter_width("does not fit", 4, false, false), 0);
+    }
+}
@@ -1,9 +1,16 @@
 //! Main layout engine
 
+use super::cache::{theme_fingerprint, CacheKey, CachedBlock, LayoutCache};
+use super::code::{self, FenceInfo};
+use super::fold::{fold_summary, section_range, Folds};
 use super::text::layout_text;
 use super::types::*;
-use crate::ir::{Block, CalloutKind, Document, Inline, ListItem};
-use crate::theme::Theme;
+use crate::codeblock::{BlockContext, CodeBlockRenderers, DiagramSources, Rendered};
+use crate::diff::SplitDiffs;
+use crate::ir::{Alignment, Block, CalloutKind, Document, Inline, ListItem, TableCell};
+use crate::json_tree::TreeFolds;
+use crate::theme::{CodeWrap, Theme};
+use std::borrow::Cow;
 use unicode_width::UnicodeWidthStr;
 
 /// Context for layout operations to reduce parameter passing
@@ -13,6 +20,10 @@ struct LayoutContext<'a> {
     hit_regions: &'a mut Vec<HitRegion>,
     images: &'a mut Vec<ImageReference>,
     inline_images: bool,
+    renderers: &'a CodeBlockRenderers,
+    sources: &'a DiagramSources,
+    tree_folds: &'a TreeFolds,
+    split_diffs: &'a SplitDiffs,
 }
 
 /// Layout a document into a positioned tree with computed positions and sizes.
@@ -28,6 +39,9 @@ struct LayoutContext<'a> {
 /// * `viewport` - Viewport dimensions (width, height, scroll position)
 /// * `inline_images` - Whether to render images inline (true) or in sidebar (false)
 ///
+/// Code blocks are drawn with the built-in [`CodeBlockRenderers`]; use
+/// [`layout_document_cached`] to choose others.
+///
 /// # Returns
 ///
 /// A `LayoutTree` containing:
@@ -60,12 +74,19 @@ pub fn layout_document(
     let mut hit_regions = Vec::new();
     let mut images = Vec::new();
 
+    let sources = DiagramSources::default();
+    let tree_folds = TreeFolds::default();
+    let split_diffs = SplitDiffs::default();
     let mut ctx = LayoutContext {
         theme,
         node_counter: &mut node_counter,
         hit_regions: &mut hit_regions,
         images: &mut images,
         inline_images,
+        renderers: CodeBlockRenderers::builtin(),
+        sources: &sources,
+        tree_folds: &tree_folds,
+        split_diffs: &split_diffs,
     };
 
     let root = layout_blocks(&document.blocks, 0, 0, viewport.width, &mut ctx);
@@ -93,6 +114,396 @@ pub fn layout_document(
     }
 }
 
+/// Options that affect how a document is laid out
+#[derive(Debug, Clone, Default)]
+pub struct LayoutOptions {
+    /// Render images inline (true) or in the sidebar (false)
+    pub inline_images: bool,
+    /// Only lay out blocks near the viewport; the rest get estimated heights
+    pub lazy: bool,
+    /// Folded blocks, replaced by one-line placeholders
+    pub folds: Folds,
+    /// Renderers for fenced code blocks, by language
+    pub renderers: CodeBlockRenderers,
+    /// Code blocks flipped between their rendering and their source
+    pub sources: DiagramSources,
+    /// JSON/YAML tree nodes folded or unfolded by the reader
+    pub tree_folds: TreeFolds,
+    /// Diff blocks shown side by side instead of inline
+    pub split_diffs: SplitDiffs,
+}
+
+/// Documents with at least this many top-level blocks are laid out lazily
+pub const LAZY_LAYOUT_MIN_BLOCKS: usize = 1000;
+
+/// Layout a document, reusing and filling a block-level cache.
+///
+/// Produces the same tree as [`layout_document`], but every top-level block
+/// that was laid out before at the same width and theme is taken from
+/// `cache` instead of being laid out again.
+///
+/// With `options.lazy` set, blocks that are neither cached nor near the
+/// viewport are not laid out at all: they become
+/// [`LayoutElement::Pending`] nodes with an estimated height. Headings are
+/// always laid out so heading navigation and anchors keep working. Use
+/// [`LayoutTree::needs_refinement`] and [`refine_layout`] to lay out
+/// pending blocks as they scroll into view.
+///
+/// Blocks in `options.folds` collapse into [`LayoutElement::Folded`]
+/// placeholders: a folded heading keeps its own line and replaces its section.
+pub fn layout_document_cached(
+    document: &Document,
+    theme: &Theme,
+    viewport: Viewport,
+    options: &LayoutOptions,
+    cache: &mut LayoutCache,
+) -> LayoutTree {
+    let theme_key = theme_fingerprint(theme);
+    let width = viewport.width;
+
+    // Rows around the viewport that must be laid out for real
+    let window_start = viewport.scroll_y.saturating_sub(viewport.height);
+    let window_end = viewport
+        .scroll_y
+        .saturating_add(viewport.height.saturating_mul(2));
+
+    let mut node_counter = 0;
+    let mut hit_regions = Vec::new();
+    let mut images = Vec::new();
+    let mut children = Vec::with_capacity(document.blocks.len());
+    let mut y = 0u16;
+    // Section of the last folded heading
+    let mut hidden = 0..0;
+
+    for (index, block) in document.blocks.iter().enumerate() {
+        if hidden.contains(&index) {
+            // The first block of the section shows the placeholder
+            let (summary, height) = if index == hidden.start {
+                let rows = document.blocks[hidden.clone()]
+                    .iter()
+                    .map(|block| {
+                        block_margin_top(block, theme)
+                            + folded_height(block, width, theme, theme_key, options, cache)
+                            + block_margin_bottom(block, theme)
+                    })
+                    .fold(0u16, u16::saturating_add);
+                (fold_summary(None, rows), 1)
+            } else {
+                (String::new(), 0)
+            };
+            node_counter += 1;
+            let heading = hidden.start - 1;
+            children.push(folded_node(
+                node_counter,
+                y,
+                width,
+                heading,
+                summary,
+                height,
+            ));
+            if height > 0 {
+                y = y.saturating_add(height + theme.spacing.paragraph_spacing);
+            }
+            continue;
+        }
+
+        y = y.saturating_add(block_margin_top(block, theme));
+
+        if options.folds.is_folded(index) {
+            match block {
+                Block::Heading { .. } => {
+                    // The heading stays; its section becomes the placeholder
+                    hidden = section_range(&document.blocks, index);
+                }
+                _ => {
+                    let hidden = folded_height(block, width, theme, theme_key, options, cache);
+                    let summary = fold_summary(Some(&fold_label(block)), hidden);
+                    node_counter += 1;
+                    children.push(folded_node(node_counter, y, width, index, summary, 1));
+                    y = y.saturating_add(1 + theme.spacing.paragraph_spacing);
+                    continue;
+                }
+            }
+        }
+
+        let block = &*opened(block);
+        let key = CacheKey::new(block, width, theme_key, options);
+        let node = if let Some(cached) = cache.get(&key) {
+            cached.place(y, &mut node_counter, &mut hit_regions, &mut images)
+        } else {
+            let estimate = estimate_block_height(block, width, theme);
+            let near_viewport = y < window_end && y.saturating_add(estimate) > window_start;
+
+            if options.lazy && !near_viewport && !matches!(block, Block::Heading { .. }) {
+                node_counter += 1;
+                LayoutNode {
+                    id: node_counter,
+                    rect: Rectangle::new(0, y, width, estimate),
+                    element: LayoutElement::Pending { block: index },
+                    children: Vec::new(),
+                    style: ComputedStyle::default(),
+                }
+            } else {
+                let cached = layout_detached(block, width, theme, options);
+                let node = cached.place(y, &mut node_counter, &mut hit_regions, &mut images);
+                cache.insert(key, cached);
+                node
+            }
+        };
+
+        y = y.saturating_add(node.rect.height);
+        y = y.saturating_add(block_margin_bottom(block, theme));
+        children.push(node);
+    }
+
+    let doc_height = children
+        .iter()
+        .map(|n| n.rect.y + n.rect.height)
+        .max()
+        .unwrap_or(0);
+
+    node_counter += 1;
+    let document_node = LayoutNode {
+        id: node_counter,
+        rect: Rectangle::new(0, 0, width, doc_height),
+        element: LayoutElement::Document,
+        children,
+        style: ComputedStyle::default(),
+    };
+
+    LayoutTree {
+        root: document_node,
+        viewport,
+        hit_regions,
+        images,
+    }
+}
+
+/// Placeholder node for folded content; `block` is the folded block
+fn folded_node(
+    id: NodeId,
+    y: u16,
+    width: u16,
+    block: usize,
+    summary: String,
+    height: u16,
+) -> LayoutNode {
+    LayoutNode {
+        id,
+        rect: Rectangle::new(0, y, width, height),
+        element: LayoutElement::Folded { block, summary },
+        children: Vec::new(),
+        style: ComputedStyle::default(),
+    }
+}
+
+/// A block as it is drawn when not folded. Open callouts ignore their
+/// `[!tip]-` marker: the fold state decides.
+fn opened(block: &Block) -> Cow<'_, Block> {
+    match block {
+        Block::Callout {
+            kind,
+            title,
+            folded: Some(true),
+            content,
+        } => Cow::Owned(Block::Callout {
+            kind: kind.clone(),
+            title: title.clone(),
+            folded: Some(false),
+            content: content.clone(),
+        }),
+        _ => Cow::Borrowed(block),
+    }
+}
+
+/// Rows a block would take if it were not folded: exact when it has been
+/// laid out before (or layout is eager), estimated otherwise
+fn folded_height(
+    block: &Block,
+    width: u16,
+    theme: &Theme,
+    theme_key: u64,
+    options: &LayoutOptions,
+    cache: &mut LayoutCache,
+) -> u16 {
+    let block = &*opened(block);
+    let key = CacheKey::new(block, width, theme_key, options);
+    if let Some(cached) = cache.get(&key) {
+        return cached.node.rect.height;
+    }
+    if options.lazy {
+        return estimate_block_height(block, width, theme);
+    }
+    let cached = layout_detached(block, width, theme, options);
+    let height = cached.node.rect.height;
+    cache.insert(key, cached);
+    height
+}
+
+/// What a folded callout or code block is called in its placeholder
+fn fold_label(block: &Block) -> String {
+    match block {
+        Block::Callout { kind, title, .. } => match title {
+            Some(title) => format!("{}: {}", kind.default_title(), title),
+            None => kind.default_title(),
+        },
+        Block::Details { summary, .. } => summary.iter().map(Inline::to_plain_text).collect(),
+        Block::CodeBlock { lang, .. } => {
+            match FenceInfo::parse(lang.as_deref().unwrap_or("")).lang {
+                Some(lang) => format!("{} code", lang),
+                None => "code".to_string(),
+            }
+        }
+        _ => "block".to_string(),
+    }
+}
+
+/// Lay out pending blocks that have scrolled into view.
+///
+/// Keeps the block at the top of the viewport in place, so refining
+/// estimated heights above it does not make the content jump.
+pub fn refine_layout(
+    tree: &mut LayoutTree,
+    document: &Document,
+    theme: &Theme,
+    options: &LayoutOptions,
+    cache: &mut LayoutCache,
+) {
+    // A handful of passes is enough: each one lays out the whole window
+    for _ in 0..4 {
+        if !tree.needs_refinement() {
+            break;
+        }
+
+        let scroll_y = tree.viewport.scroll_y;
+        let anchor = tree
+            .root
+            .children
+            .iter()
+            .position(|n| n.rect.y + n.rect.height > scroll_y);
+        let offset = anchor
+            .map(|i| scroll_y.saturating_sub(tree.root.children[i].rect.y))
+            .unwrap_or(0);
+
+        let mut refined = layout_document_cached(document, theme, tree.viewport, options, cache);
+        let new_scroll = match anchor.and_then(|i| refined.root.children.get(i)) {
+            Some(node) => node.rect.y.saturating_add(offset),
+            None => scroll_y,
+        };
+        let doc_height = refined.document_height();
+        refined.viewport.scroll_to_clamped(new_scroll, doc_height);
+        *tree = refined;
+    }
+}
+
+/// Lay out a single top-level block at the origin, capturing what it registers
+fn layout_detached(
+    block: &Block,
+    width: u16,
+    theme: &Theme,
+    options: &LayoutOptions,
+) -> CachedBlock {
+    let mut node_counter = 0;
+    let mut hit_regions = Vec::new();
+    let mut images = Vec::new();
+
+    let mut ctx = LayoutContext {
+        theme,
+        node_counter: &mut node_counter,
+        hit_regions: &mut hit_regions,
+        images: &mut images,
+        inline_images: options.inline_images,
+        renderers: &options.renderers,
+        sources: &options.sources,
+        tree_folds: &options.tree_folds,
+        split_diffs: &options.split_diffs,
+    };
+    let node = layout_block(block, 0, 0, width, &mut ctx);
+
+    CachedBlock {
+        node,
+        hit_regions,
+        images,
+    }
+}
+
+/// Cheap height estimate for a block that has not been laid out yet
+fn estimate_block_height(block: &Block, width: u16, theme: &Theme) -> u16 {
+    fn inline_width(inlines: &[Inline]) -> (usize, usize) {
+        // (text width, explicit line breaks)
+        let breaks = inlines
+            .iter()
+            .filter(|i| matches!(i, Inline::SoftBreak | Inline::LineBreak))
+            .count();
+        (inlines.iter().map(inline_text_length).sum(), breaks)
+    }
+
+    fn text_height(inlines: &[Inline], width: u16) -> u16 {
+        let (chars, breaks) = inline_width(inlines);
+        let wrapped = chars.div_ceil(width.max(1) as usize);
+        (wrapped + breaks).max(1).min(u16::MAX as usize) as u16
+    }
+
+    fn blocks_height(blocks: &[Block], width: u16, theme: &Theme) -> u16 {
+        blocks
+            .iter()
+            .map(|b| {
+                block_margin_top(b, theme)
+                    .saturating_add(estimate_block_height(b, width, theme))
+                    .saturating_add(block_margin_bottom(b, theme))
+            })
+            .fold(0u16, u16::saturating_add)
+    }
+
+    match block {
+        Block::Heading { content, .. } | Block::Paragraph { content } => {
+            text_height(content, width)
+        }
+        Block::CodeBlock { code, .. } => {
+            (code.lines().count() as u16).saturating_add(theme.spacing.code_block_padding * 2)
+        }
+        Block::BlockQuote { blocks } => blocks_height(
+            blocks,
+            width.saturating_sub(theme.spacing.blockquote_indent),
+            theme,
+        ),
+        Block::Callout {
+            kind,
+            title,
+            folded,
+            content,
+        } => {
+            let title_height = callout_heading(kind, title.as_deref(), *folded).is_some() as u16;
+            if *folded == Some(true) {
+                title_height
+            } else {
+                title_height + blocks_height(content, width.saturating_sub(2), theme).max(1)
+            }
+        }
+        Block::List { items, .. } => items
+            .iter()
+            .map(|item| {
+                item.content
+                    .iter()
+                    .map(|b| estimate_block_height(b, width.saturating_sub(3), theme))
+                    .fold(0u16, u16::saturating_add)
+                    .max(1)
+            })
+            .fold(0u16, u16::saturating_add),
+        Block::Table { headers, rows, .. } => {
+            let padding = theme.blocks.table.padding * 2;
+            let header = if headers.is_empty() { 0 } else { 1 + padding };
+            (rows.len() as u16)
+                .saturating_mul(1 + padding)
+                .saturating_add(header)
+        }
+        Block::HorizontalRule => 1,
+        Block::Details { content, .. } => {
+            1 + blocks_height(content, width.saturating_sub(2), theme)
+        }
+    }
+}
+
 fn layout_blocks(
     blocks: &[Block],
     x: u16,
@@ -147,6 +558,7 @@ fn layout_list_item_blocks(
             Block::CodeBlock { .. } => 1,
             Block::BlockQuote { .. } => 1,
             Block::Table { .. } => 1,
+            Block::Details { .. } => 1,
             _ => 0, // No spacing for paragraphs and lists in tight list items
         };
         y += margin_bottom;
@@ -162,7 +574,22 @@ fn layout_block(block: &Block, x: u16, y: u16, width: u16, ctx: &mut LayoutConte
     let id = *ctx.node_counter;
 
     match block {
-        Block::Heading { level, content } => layout_heading(*level, content, x, y, width, id, ctx),
+        Block::Heading {
+            level,
+            content,
+            id: anchor,
+        } => {
+            let node = layout_heading(*level, content, x, y, width, id, ctx);
+            // A hit region, so `#anchor` links can find the heading
+            ctx.hit_regions.push(HitRegion {
+                rect: node.rect,
+                element: HitElement::Heading {
+                    level: *level,
+                    id: anchor.clone(),
+                },
+            });
+            node
+        }
         Block::Paragraph { content } => layout_paragraph(content, x, y, width, id, ctx),
         Block::CodeBlock { lang, code } => {
             layout_code_block(lang.as_deref(), code, x, y, width, id, ctx)
@@ -176,12 +603,33 @@ fn layout_block(block: &Block, x: u16, y: u16, width: u16, ctx: &mut LayoutConte
         Block::Table {
             headers,
             rows,
-            alignment: _,
-        } => layout_table(headers, rows, x, y, width, id, ctx),
+            alignment,
+        } => {
+            let table = TableCells {
+                headers,
+                rows,
+                alignment,
+            };
+            layout_table(&table, x, y, width, id, ctx)
+        }
         Block::HorizontalRule => layout_horizontal_rule(x, y, width, ctx.theme, id),
-        Block::Callout { kind, content, .. } => {
-            layout_callout(*kind, content, x, y, width, id, ctx)
+        Block::Callout {
+            kind,
+            title,
+            folded,
+            content,
+        } => {
+            let callout = CalloutParts {
+                kind,
+                title: title.as_deref(),
+                folded: *folded,
+                blocks: content,
+            };
+            layout_callout(&callout, x, y, width, id, ctx)
         }
+        Block::Details {
+            summary, content, ..
+        } => layout_details(summary, content, x, y, width, id, ctx),
     }
 }
 
@@ -202,25 +650,6 @@ fn layout_heading(
         .collect::<String>();
     let height = lines.len() as u16;
 
-    // Add hit region for heading
-    // Generate standard markdown anchor ID (lowercase, alphanumeric + hyphens only)
-    let heading_id = text
-        .to_lowercase()
-        .chars()
-        .map(|c| if c.is_alphanumeric() { c } else { '-' })
-        .collect::<String>()
-        .split('-')
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<_>>()
-        .join("-");
-    ctx.hit_regions.push(HitRegion {
-        rect: Rectangle::new(x, y, width, height),
-        element: HitElement::Heading {
-            level,
-            id: heading_id,
-        },
-    });
-
     LayoutNode {
         id,
         rect: Rectangle::new(x, y, width, height),
@@ -310,26 +739,125 @@ fn layout_code_block(
     ctx: &mut LayoutContext,
 ) -> LayoutNode {
     let padding = ctx.theme.spacing.code_block_padding;
-    let _content_width = width.saturating_sub(padding * 2);
-
-    let lines: Vec<String> = code.lines().map(|line| line.to_string()).collect();
-    let height = lines.len() as u16 + padding * 2;
+    let code_style = &ctx.theme.blocks.code_block;
+    let info = FenceInfo::parse(lang.unwrap_or(""));
+
+    // Code sits between the side borders, after the gutter
+    let text_width = width.saturating_sub(2);
+    let lang = info.lang.as_deref();
+    let renderer = lang.and_then(|lang| ctx.renderers.get(lang));
+    let flipped = ctx.sources.contains(code);
+    let mut tree_nodes = Vec::new();
+    let drawn = renderer.filter(|renderer| renderer.rendered_by_default() != flipped);
+    let rendered = drawn.and_then(|renderer| {
+        let block = BlockContext {
+            width: text_width,
+            split: ctx.split_diffs.contains(code),
+            toggled: ctx.tree_folds.paths(code),
+        };
+        renderer.render(code, &block)
+    });
+    let lines = match rendered {
+        // Data is laid out as a table, in the theme's table style
+        Some(Rendered::Table {
+            headers,
+            rows,
+            alignment,
+        }) => {
+            let table = TableCells {
+                headers: &headers,
+                rows: &rows,
+                alignment: &alignment,
+            };
+            let node = layout_table(&table, x, y, width, id, ctx);
+            ctx.hit_regions.push(HitRegion {
+                rect: node.rect,
+                element: HitElement::CodeBlock {
+                    lang: info.lang.clone(),
+                    code: code.to_string(),
+                },
+            });
+            return node;
+        }
+        Some(Rendered::Tree(tree)) => {
+            tree_nodes = tree.nodes;
+            Some(tree.lines)
+        }
+        rendered => rendered.and_then(Rendered::into_lines),
+    };
+    let (element, rows) = match lines {
+        // Rendered diagrams are art, not code: no wrapping, numbers or
+        // highlights (those refer to source lines)
+        Some(lines) => {
+            let rows = lines.len();
+            (
+                LayoutElement::Diagram {
+                    lang: lang.map(|s| s.to_string()),
+                    lines,
+                },
+                rows,
+            )
+        }
+        None => {
+            let wrap = code_style.wrap == CodeWrap::Wrap;
+            let line_numbers = code_style.line_numbers;
+            let gutter_width = code::gutter_width(code, text_width, wrap, line_numbers);
+            let code_width = text_width.saturating_sub(gutter_width);
+            let mut lines = code::layout_code_lines(code, code_width, wrap, &info);
+            // A block its renderer can't read is marked where it goes wrong
+            if let Some(error) = renderer.and_then(|renderer| renderer.source_error(code)) {
+                code::insert_error_marker(
+                    &mut lines,
+                    error.line,
+                    error.column,
+                    &error.message,
+                    code_width,
+                );
+            }
+            let rows = lines.len();
+            (
+                LayoutElement::CodeBlock {
+                    lang: lang.map(|s| s.to_string()),
+                    lines,
+                    gutter_width,
+                    line_numbers,
+                },
+                rows,
+            )
+        }
+    };
+    let height = rows as u16 + padding * 2;
 
     // Add hit region for code block
     ctx.hit_regions.push(HitRegion {
         rect: Rectangle::new(x, y, width, height),
         element: HitElement::CodeBlock {
             lang: lang.map(|s| s.to_string()),
+            code: code.to_string(),
         },
     });
+    // Tree rows start under the top border
+    for node in tree_nodes {
+        ctx.hit_regions.push(HitRegion {
+            rect: Rectangle::new(
+                x,
+                y + 1 + node.rows.start as u16,
+                width,
+                node.rows.len() as u16,
+            ),
+            element: HitElement::TreeNode {
+                code: code.to_string(),
+                path: node.path,
+                foldable: node.foldable,
+                folded: node.folded,
+            },
+        });
+    }
 
     LayoutNode {
         id,
         rect: Rectangle::new(x, y, width, height),
-        element: LayoutElement::CodeBlock {
-            lang: lang.map(|s| s.to_string()),
-            lines,
-        },
+        element,
         children: Vec::new(),
         style: ComputedStyle::default(),
     }
@@ -359,27 +887,127 @@ fn layout_blockquote(
     }
 }
 
+/// Title row of a callout: its own title, or the kind's name for foldable
+/// callouts (which need something to show when folded). Plain GitHub alerts
+/// have none and put the icon beside their first line.
+fn callout_heading(
+    kind: &CalloutKind,
+    title: Option<&str>,
+    folded: Option<bool>,
+) -> Option<String> {
+    let title = match (title, folded) {
+        (Some(title), _) => title.to_string(),
+        (None, Some(_)) => kind.default_title(),
+        (None, None) => return None,
+    };
+    Some(match folded {
+        Some(true) => format!("▸ {}", title),
+        Some(false) => format!("▾ {}", title),
+        None => title,
+    })
+}
+
+/// A callout's fields, borrowed from its block
+struct CalloutParts<'a> {
+    kind: &'a CalloutKind,
+    title: Option<&'a str>,
+    folded: Option<bool>,
+    blocks: &'a [Block],
+}
+
 fn layout_callout(
-    kind: CalloutKind,
-    blocks: &[Block],
+    callout: &CalloutParts,
     x: u16,
     y: u16,
     width: u16,
     id: NodeId,
     ctx: &mut LayoutContext,
 ) -> LayoutNode {
+    let CalloutParts {
+        kind,
+        title,
+        folded,
+        blocks,
+    } = *callout;
     // Callouts have a 2-character indent for the icon/border
     let indent = 2u16;
     let content_width = width.saturating_sub(indent);
 
-    let children = layout_blocks(blocks, x + indent, y, content_width, ctx);
+    let mut children = Vec::new();
+    if let Some(heading) = callout_heading(kind, title, folded) {
+        // The title is a bold paragraph in the callout's colour, so it wraps
+        // and is searchable like any other text
+        let color = ctx.theme.blocks.callout.style_for(kind).color;
+        *ctx.node_counter += 1;
+        let title_id = *ctx.node_counter;
+        let title = [Inline::Strong(vec![Inline::Text(heading)])];
+        let mut node = layout_paragraph(&title, x + indent, y, content_width, title_id, ctx);
+        if let LayoutElement::Paragraph { lines } = &mut node.element {
+            for segment in lines.iter_mut().flat_map(|line| line.segments.iter_mut()) {
+                segment.style.foreground = Some(color);
+            }
+        }
+        children.push(node);
+    }
+    let body_y = y + children.iter().map(|n| n.rect.height).sum::<u16>();
+    if folded != Some(true) {
+        children.extend(layout_blocks(
+            blocks,
+            x + indent,
+            body_y,
+            content_width,
+            ctx,
+        ));
+    }
 
     let height = children.iter().map(|n| n.rect.height).sum::<u16>().max(1);
 
     LayoutNode {
         id,
         rect: Rectangle::new(x, y, width, height),
-        element: LayoutElement::Callout { kind },
+        element: LayoutElement::Callout { kind: kind.clone() },
+        children,
+        style: ComputedStyle::default(),
+    }
+}
+
+/// `<details>`: a `▾ summary` row above the indented content. Collapsing
+/// it is a fold, handled like any other.
+fn layout_details(
+    summary: &[Inline],
+    blocks: &[Block],
+    x: u16,
+    y: u16,
+    width: u16,
+    id: NodeId,
+    ctx: &mut LayoutContext,
+) -> LayoutNode {
+    let indent = 2u16;
+
+    *ctx.node_counter += 1;
+    let summary_id = *ctx.node_counter;
+    let mut row = vec![Inline::Text("▾ ".to_string())];
+    row.extend(summary.iter().cloned());
+    let summary = [Inline::Strong(row)];
+    let mut children = vec![layout_paragraph(&summary, x, y, width, summary_id, ctx)];
+
+    let body_y = y + children[0].rect.height;
+    children.extend(layout_blocks(
+        blocks,
+        x + indent,
+        body_y,
+        width.saturating_sub(indent),
+        ctx,
+    ));
+
+    let height = children
+        .last()
+        .map_or(0, |last| last.rect.y + last.rect.height - y);
+
+    LayoutNode {
+        id,
+        rect: Rectangle::new(x, y, width, height),
+        element: LayoutElement::Details,
         children,
         style: ComputedStyle::default(),
     }
@@ -465,30 +1093,37 @@ fn layout_list(
     }
 }
 
+/// A table's cells and column alignment, from a Markdown table or a CSV
+/// block
+struct TableCells<'a> {
+    headers: &'a [TableCell],
+    rows: &'a [Vec<TableCell>],
+    alignment: &'a [Alignment],
+}
+
 fn layout_table(
-    headers: &[crate::ir::TableCell],
-    rows: &[Vec<crate::ir::TableCell>],
+    table: &TableCells,
     x: u16,
     y: u16,
     width: u16,
     id: NodeId,
     ctx: &mut LayoutContext,
 ) -> LayoutNode {
-    let column_widths = compute_column_widths(headers, rows, ctx.theme, width);
+    let column_widths = compute_column_widths(table.headers, table.rows, ctx.theme, width);
 
     let mut children = Vec::new();
     let mut current_y = y;
 
     // Layout header row
-    if !headers.is_empty() {
+    if !table.headers.is_empty() {
         *ctx.node_counter += 1;
         let row_node = layout_table_row(
-            headers,
+            table,
+            None,
             &column_widths,
             x,
             current_y,
             *ctx.node_counter,
-            true,
             ctx,
         );
         current_y += row_node.rect.height;
@@ -496,15 +1131,15 @@ fn layout_table(
     }
 
     // Layout data rows
-    for row in rows {
+    for row in 0..table.rows.len() {
         *ctx.node_counter += 1;
         let row_node = layout_table_row(
-            row,
+            table,
+            Some(row),
             &column_widths,
             x,
             current_y,
             *ctx.node_counter,
-            false,
             ctx,
         );
         current_y += row_node.rect.height;
@@ -527,15 +1162,18 @@ fn layout_table(
     }
 }
 
+/// Data row `row` of a table, or its header row for `None`
 fn layout_table_row(
-    cells: &[crate::ir::TableCell],
+    table: &TableCells,
+    row: Option<usize>,
     column_widths: &[u16],
     x: u16,
     y: u16,
     id: NodeId,
-    is_header: bool,
     ctx: &mut LayoutContext,
 ) -> LayoutNode {
+    let cells = row.map_or(table.headers, |row| &table.rows[row]);
+    let is_header = row.is_none();
     let mut children = Vec::new();
     let mut current_x = x;
     let mut max_height = 1u16;
@@ -548,7 +1186,7 @@ fn layout_table_row(
         *ctx.node_counter += 1;
         let cell_id = *ctx.node_counter;
 
-        let (lines, _inline_imgs) = layout_text(
+        let (mut lines, _inline_imgs) = layout_text(
             &cell.content,
             content_width,
             ctx.theme,
@@ -556,6 +1194,7 @@ fn layout_table_row(
             ctx.images,
             ctx.inline_images,
         );
+        align_cell_lines(&mut lines, content_width, table.alignment.get(i));
         let cell_height = lines.len() as u16 + padding * 2;
         max_height = max_height.max(cell_height);
 
@@ -598,9 +1237,36 @@ fn layout_table_row(
     }
 }
 
+/// Shift right- and centre-aligned cell lines over with leading spaces
+fn align_cell_lines(lines: &mut [Line], width: u16, alignment: Option<&Alignment>) {
+    for line in lines {
+        let slack = width.saturating_sub(line.width());
+        let indent = match alignment {
+            Some(Alignment::Right) => slack,
+            Some(Alignment::Center) => slack / 2,
+            _ => 0,
+        };
+        if indent > 0 && !line.is_empty() {
+            line.segments.insert(
+                0,
+                TextSegment {
+                    text: " ".repeat(indent as usize),
+                    style: TextStyle::default(),
+                    link_url: None,
+                    image_url: None,
+                    image_alt: None,
+                },
+            );
+        }
+    }
+}
+
 fn inline_text_length(inline: &crate::ir::Inline) -> usize {
     match inline {
-        crate::ir::Inline::Text(s) | crate::ir::Inline::Code(s) => s.len(),
+        crate::ir::Inline::Text(s) | crate::ir::Inline::Code(s) | crate::ir::Inline::Html(s) => {
+            s.len()
+        }
+        crate::ir::Inline::Kbd(s) => s.len() + 2, // Keycap padding
         crate::ir::Inline::Strong(inlines)
         | crate::ir::Inline::Emphasis(inlines)
         | crate::ir::Inline::Strikethrough(inlines) => inlines.iter().map(inline_text_length).sum(),
@@ -690,6 +1356,7 @@ fn block_margin_bottom(block: &Block, theme: &Theme) -> u16 {
         Block::List { .. } => 0,       // Lists handle their own spacing
         Block::BlockQuote { .. } => 1, // Add spacing after blockquotes
         Block::Table { .. } => 1,      // Add spacing after tables
+        Block::Details { .. } => 1,
         _ => 0,
     }
 }
@@ -697,7 +1364,9 @@ fn block_margin_bottom(block: &Block, theme: &Theme) -> u16 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::codeblock::Rendered;
     use crate::ir::{Block, Document, Inline};
+    use crate::layout::LayoutCache;
     use crate::theme;
 
     #[test]
@@ -709,6 +1378,7 @@ mod tests {
             Block::Heading {
                 level: 1,
                 content: vec![Inline::Text("Title".to_string())],
+                id: "title".to_string(),
             },
             Block::Paragraph {
                 content: vec![Inline::Text("Content".to_string())],
@@ -736,6 +1406,7 @@ mod tests {
         let doc = Document::with_blocks(vec![Block::Heading {
             level: 1,
             content: vec![Inline::Text("Test Heading".to_string())],
+            id: "test-heading".to_string(),
         }]);
 
         let tree = layout_document(&doc, &theme, viewport, false);
@@ -765,4 +1436,421 @@ mod tests {
             HitElement::CodeBlock { .. }
         ));
     }
+
+    fn collect_rects(node: &LayoutNode, rects: &mut Vec<Rectangle>) {
+        rects.push(node.rect);
+        for child in &node.children {
+            collect_rects(child, rects);
+        }
+    }
+
+    fn sample_document(paragraphs: usize) -> Document {
+        let mut blocks = Vec::new();
+        for i in 0..paragraphs {
+            if i % 10 == 0 {
+                blocks.push(Block::Heading {
+                    level: 2,
+                    content: vec![Inline::Text(format!("Section {}", i))],
+                    id: format!("section-{}", i),
+                });
+            }
+            blocks.push(Block::Paragraph {
+                content: vec![Inline::Text(format!(
+                    "Paragraph {} with enough text to wrap onto a second line at narrow widths",
+                    i
+                ))],
+            });
+        }
+        Document::with_blocks(blocks)
+    }
+
+    #[test]
+    fn test_cached_layout_matches_full_layout() {
+        let theme = theme::docs_theme();
+        let viewport = Viewport::new(40, 24);
+        let doc = sample_document(30);
+        let options = LayoutOptions::default();
+        let mut cache = LayoutCache::new();
+
+        let full = layout_document(&doc, &theme, viewport, false);
+        let cold = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+        let cached_blocks = cache.len();
+        let warm = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+
+        let (mut expected, mut cold_rects, mut warm_rects) = (Vec::new(), Vec::new(), Vec::new());
+        collect_rects(&full.root, &mut expected);
+        collect_rects(&cold.root, &mut cold_rects);
+        collect_rects(&warm.root, &mut warm_rects);
+
+        assert_eq!(cold_rects, expected);
+        assert_eq!(warm_rects, expected);
+        assert_eq!(warm.hit_regions.len(), full.hit_regions.len());
+        // Second pass is served entirely from the cache
+        assert_eq!(cache.len(), cached_blocks);
+    }
+
+    #[test]
+    fn test_lazy_layout_defers_offscreen_blocks() {
+        let theme = theme::docs_theme();
+        let viewport = Viewport::new(40, 10);
+        let doc = sample_document(200);
+        let options = LayoutOptions {
+            inline_images: false,
+            lazy: true,
+            ..Default::default()
+        };
+        let mut cache = LayoutCache::new();
+
+        let tree = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+
+        assert!(tree.has_pending());
+        assert!(!tree.needs_refinement());
+        // Headings are always laid out so they can be navigated to
+        let headings = tree
+            .root
+            .children
+            .iter()
+            .filter(|n| matches!(n.element, LayoutElement::Heading { .. }))
+            .count();
+        assert_eq!(headings, 20);
+    }
+
+    #[test]
+    fn test_refine_layout_fills_viewport() {
+        let theme = theme::docs_theme();
+        let viewport = Viewport::new(40, 10);
+        let doc = sample_document(200);
+        let options = LayoutOptions {
+            inline_images: false,
+            lazy: true,
+            ..Default::default()
+        };
+        let mut cache = LayoutCache::new();
+
+        let mut tree = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+        let middle = tree.document_height() / 2;
+        tree.viewport.scroll_to(middle);
+        assert!(tree.needs_refinement());
+
+        refine_layout(&mut tree, &doc, &theme, &options, &mut cache);
+
+        assert!(!tree.needs_refinement());
+        assert!(tree.has_pending());
+    }
+
+    #[test]
+    fn test_callout_title_row_and_folding() {
+        let theme = theme::docs_theme();
+        let viewport = Viewport::new(40, 24);
+        let doc = crate::parse_markdown(
+            "> [!tip] Shortcut\n> Press y.\n\n> [!bug]- Known issue\n> Hidden body.",
+        );
+
+        let tree = layout_document(&doc, &theme, viewport, false);
+        let first_line = |node: &LayoutNode| match &node.element {
+            LayoutElement::Paragraph { lines } => lines[0]
+                .segments
+                .iter()
+                .map(|segment| segment.text.as_str())
+                .collect::<String>(),
+            other => panic!("Expected a title paragraph, got {:?}", other),
+        };
+
+        let open = &tree.root.children[0];
+        assert_eq!(open.children.len(), 2);
+        assert_eq!(first_line(&open.children[0]), "Shortcut");
+        assert_eq!(open.children[1].rect.y, open.rect.y + 1);
+
+        // A folded callout is just its title row
+        let folded = &tree.root.children[1];
+        assert_eq!(folded.rect.height, 1);
+        assert_eq!(folded.children.len(), 1);
+        assert_eq!(first_line(&folded.children[0]), "▸ Known issue");
+
+        // Its placeholder counts the body too
+        let mut options = LayoutOptions::default();
+        options.folds.fold(1);
+        let tree =
+            layout_document_cached(&doc, &theme, viewport, &options, &mut LayoutCache::new());
+        match &tree.root.children[1].element {
+            LayoutElement::Folded { summary, .. } => {
+                assert_eq!(summary, "▸ Bug: Known issue · 2 lines hidden")
+            }
+            other => panic!("Expected a placeholder, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_folds_replace_content_with_placeholders() {
+        let theme = theme::docs_theme();
+        let viewport = Viewport::new(60, 24);
+        let code = "x\n".repeat(12);
+        let doc = crate::parse_markdown(&format!(
+            "# Report\n\nIntro.\n\nMore.\n\n# Code\n\n```rust\n{}```\n\nAfter.",
+            code
+        ));
+        let mut cache = LayoutCache::new();
+        let mut options = LayoutOptions::default();
+        let open = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+
+        options.folds.fold(0);
+        options.folds.fold(4);
+        let tree = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+
+        // One node per block is kept, so indices still line up
+        assert_eq!(tree.root.children.len(), doc.blocks.len());
+        let summaries: Vec<(usize, &str, u16)> = tree
+            .root
+            .children
+            .iter()
+            .filter_map(|node| match &node.element {
+                LayoutElement::Folded { block, summary } => {
+                    Some((*block, summary.as_str(), node.rect.height))
+                }
+                _ => None,
+            })
+            .collect();
+        // Two one-line paragraphs, each followed by paragraph spacing
+        assert_eq!(
+            summaries,
+            vec![
+                (0, "▸ 4 lines hidden", 1),
+                (0, "", 0),
+                (4, "▸ rust code · 14 lines hidden", 1),
+            ]
+        );
+        assert!(tree.document_height() < open.document_height());
+
+        // Hidden blocks register no hit regions (the code block's is gone)
+        assert!(!tree
+            .hit_regions
+            .iter()
+            .any(|region| matches!(region.element, HitElement::CodeBlock { .. })));
+    }
+
+    #[test]
+    fn test_code_block_renderers_replace_source() {
+        let theme = theme::docs_theme();
+        let viewport = Viewport::new(60, 24);
+        let source = "graph LR\n    A --> B";
+        let doc = crate::parse_markdown(&format!(
+            "```mermaid\n{}\n```\n\n```text\nplain\n```",
+            source
+        ));
+        let code_text = |tree: &LayoutTree, index: usize| match &tree.root.children[index].element {
+            LayoutElement::CodeBlock { lines, .. } => lines
+                .iter()
+                .map(|line| line.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            other => panic!("expected a code block, got {:?}", other),
+        };
+        let diagram_text =
+            |tree: &LayoutTree, index: usize| match &tree.root.children[index].element {
+                LayoutElement::Diagram { lines, .. } => lines
+                    .iter()
+                    .map(|line| line.text())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                other => panic!("expected a diagram, got {:?}", other),
+            };
+
+        let mut options = LayoutOptions::default();
+        let mut cache = LayoutCache::new();
+        let tree = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+        assert!(diagram_text(&tree, 0).contains('┌'), "mermaid is drawn");
+        assert_eq!(code_text(&tree, 1), "plain");
+        // Copying still gives the source
+        let code = tree
+            .hit_regions
+            .iter()
+            .find_map(|region| match &region.element {
+                HitElement::CodeBlock { code, .. } if code.trim_end() == source => Some(code),
+                _ => None,
+            })
+            .unwrap();
+
+        // Flipped back to its source, past the cached drawing
+        options.sources.toggle(code);
+        let tree = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+        assert_eq!(code_text(&tree, 0), source);
+        options.sources.clear();
+
+        options.renderers.unregister("mermaid");
+        options
+            .renderers
+            .register("text", |code: &str, _: &BlockContext| {
+                Some(Rendered::Text(code.to_uppercase()))
+            });
+        // The cache doesn't hand back layouts from the old renderers
+        let tree = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+        assert_eq!(code_text(&tree, 0), source);
+        assert_eq!(diagram_text(&tree, 1), "PLAIN");
+    }
+
+    #[test]
+    fn test_csv_code_block_is_a_table() {
+        let theme = theme::docs_theme();
+        let viewport = Viewport::new(60, 24);
+        let source = "name,qty\npen,3\nstapler,12\n";
+        let doc = crate::parse_markdown(&format!("```csv\n{}```", source));
+
+        let mut options = LayoutOptions::default();
+        let tree =
+            layout_document_cached(&doc, &theme, viewport, &options, &mut LayoutCache::new());
+        let table = &tree.root.children[0];
+        assert!(matches!(table.element, LayoutElement::Table { .. }));
+        let cell_text = |row: usize, column: usize| match &table.children[row].children[column]
+            .children[0]
+            .element
+        {
+            LayoutElement::Paragraph { lines } => lines[0]
+                .segments
+                .iter()
+                .map(|s| s.text.as_str())
+                .collect::<String>(),
+            other => panic!("expected cell text, got {:?}", other),
+        };
+        assert_eq!(cell_text(0, 0), "name");
+        assert_eq!(cell_text(1, 0), "pen");
+        // The numeric column is right-aligned under its header
+        assert_eq!(cell_text(1, 1), "  3");
+        assert_eq!(cell_text(2, 1), " 12");
+        // Copying and `s` still find the source
+        let region = tree
+            .hit_regions
+            .iter()
+            .find(|region| matches!(region.element, HitElement::CodeBlock { .. }))
+            .unwrap();
+        assert_eq!(region.rect, table.rect);
+
+        options.sources.toggle(source);
+        let tree =
+            layout_document_cached(&doc, &theme, viewport, &options, &mut LayoutCache::new());
+        assert!(matches!(
+            tree.root.children[0].element,
+            LayoutElement::CodeBlock { .. }
+        ));
+    }
+
+    #[test]
+    fn test_json_code_block_flips_to_a_foldable_tree() {
+        let theme = theme::docs_theme();
+        let viewport = Viewport::new(60, 24);
+        let source = "{\"a\": [1, 2], \"b\": true}\n";
+        let doc = crate::parse_markdown(&format!(
+            "```json\n{}```\n\n```json\n{{\"a\" 1}}\n```",
+            source
+        ));
+        let rows = |tree: &LayoutTree| match &tree.root.children[0].element {
+            LayoutElement::Diagram { lines, .. } => {
+                lines.iter().map(|line| line.text()).collect::<Vec<_>>()
+            }
+            other => panic!("expected a tree, got {:?}", other),
+        };
+
+        // Shown as code until the reader asks for the tree
+        let mut options = LayoutOptions::default();
+        let tree =
+            layout_document_cached(&doc, &theme, viewport, &options, &mut LayoutCache::new());
+        assert!(matches!(
+            tree.root.children[0].element,
+            LayoutElement::CodeBlock { .. }
+        ));
+        options.sources.toggle(source);
+        let tree =
+            layout_document_cached(&doc, &theme, viewport, &options, &mut LayoutCache::new());
+        assert_eq!(rows(&tree).len(), 7);
+        // Each node's hit region covers its rows, under the top border
+        let block_y = tree.root.children[0].rect.y;
+        let (rect, foldable) = tree
+            .hit_regions
+            .iter()
+            .find_map(|region| match &region.element {
+                HitElement::TreeNode { path, foldable, .. } if path == "$.a" => {
+                    Some((region.rect, *foldable))
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert!(foldable);
+        assert_eq!((rect.y, rect.height), (block_y + 2, 4));
+
+        options.tree_folds.toggle(source, "$.a");
+        let tree =
+            layout_document_cached(&doc, &theme, viewport, &options, &mut LayoutCache::new());
+        assert_eq!(rows(&tree)[1], "  ▸ \"a\": [… 2 items],");
+
+        // Invalid JSON stays code, with a marker under the error
+        let LayoutElement::CodeBlock { lines, .. } = &tree.root.children[1].element else {
+            panic!("expected code");
+        };
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].error);
+        assert_eq!(lines[1].text, "     ^ expected `:`");
+    }
+
+    #[test]
+    fn test_diff_code_block_side_by_side() {
+        let theme = theme::docs_theme();
+        let source = "@@ -1 +1 @@\n-old line\n+new line\n";
+        let doc = crate::parse_markdown(&format!("```diff\n{}```", source));
+        let rows = |width: u16, options: &LayoutOptions| {
+            let viewport = Viewport::new(width, 24);
+            let tree =
+                layout_document_cached(&doc, &theme, viewport, options, &mut LayoutCache::new());
+            match &tree.root.children[0].element {
+                LayoutElement::Diagram { lines, .. } => {
+                    lines.iter().map(|line| line.text()).collect::<Vec<_>>()
+                }
+                other => panic!("expected a diff, got {:?}", other),
+            }
+        };
+
+        let mut options = LayoutOptions::default();
+        assert_eq!(
+            rows(80, &options),
+            ["@@ -1 +1 @@", "-old line", "+new line"]
+        );
+
+        options.split_diffs.toggle(source);
+        let split = rows(80, &options);
+        assert_eq!(split.len(), 2);
+        assert!(split[1].starts_with("1 - old line"));
+        assert!(split[1].contains(" │ 1 + new line"));
+        // Too narrow to split: stays inline
+        assert_eq!(rows(40, &options).len(), 3);
+    }
+
+    #[test]
+    fn test_details_summary_row_and_initial_fold() {
+        let theme = theme::docs_theme();
+        let viewport = Viewport::new(60, 24);
+        let doc = crate::parse_markdown(
+            "<details>\n<summary>Setup</summary>\n\nInstall it.\n\n</details>\n\nAfter.",
+        );
+
+        let mut cache = LayoutCache::new();
+        let options = LayoutOptions::default();
+        let tree = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+        let details = &tree.root.children[0];
+        assert!(matches!(details.element, LayoutElement::Details));
+        let LayoutElement::Paragraph { lines } = &details.children[0].element else {
+            panic!("expected the summary row");
+        };
+        let summary: String = lines[0].segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(summary, "▾ Setup");
+        assert_eq!(details.children[1].rect.x, 2, "content is indented");
+
+        // Closed `<details>` start folded
+        let options = LayoutOptions {
+            folds: Folds::for_document(&doc),
+            ..Default::default()
+        };
+        let tree = layout_document_cached(&doc, &theme, viewport, &options, &mut cache);
+        assert!(matches!(
+            &tree.root.children[0].element,
+            LayoutElement::Folded { summary, .. } if summary.starts_with("▸ Setup ·")
+        ));
+    }
 }
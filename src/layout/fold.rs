@@ -0,0 +1,165 @@
+//! Folding: heading sections, callouts, `<details>` and long code blocks can
+//! collapse into a one-line `▸ 42 lines hidden` placeholder
+
+use crate::ir::{Block, Document};
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// Code blocks need more lines than this to be foldable, by default
+pub const DEFAULT_FOLD_CODE_LINES: usize = 10;
+
+/// Folded top-level blocks of a document, by index into `Document::blocks`.
+///
+/// A folded heading hides its section; a folded callout, `<details>` or
+/// code block hides itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Folds {
+    folded: BTreeSet<usize>,
+}
+
+impl Folds {
+    /// Initial folds for a document: callouts written as `[!tip]-` and
+    /// `<details>` without `open`
+    pub fn for_document(document: &Document) -> Self {
+        let folded = document
+            .blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| {
+                matches!(
+                    block,
+                    Block::Callout {
+                        folded: Some(true),
+                        ..
+                    } | Block::Details { open: false, .. }
+                )
+            })
+            .map(|(index, _)| index)
+            .collect();
+        Self { folded }
+    }
+
+    pub fn is_folded(&self, index: usize) -> bool {
+        self.folded.contains(&index)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folded.is_empty()
+    }
+
+    pub fn fold(&mut self, index: usize) {
+        self.folded.insert(index);
+    }
+
+    /// Returns whether the block was folded
+    pub fn unfold(&mut self, index: usize) -> bool {
+        self.folded.remove(&index)
+    }
+
+    /// Fold or unfold a block; returns whether it is now folded
+    pub fn toggle(&mut self, index: usize) -> bool {
+        if self.unfold(index) {
+            false
+        } else {
+            self.fold(index);
+            true
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.folded.clear();
+    }
+
+    /// The outermost fold hiding block `index`: a folded heading whose
+    /// section contains it, or the block itself if it's a folded callout or
+    /// code block
+    pub fn hidden_by(&self, blocks: &[Block], index: usize) -> Option<usize> {
+        self.folded
+            .iter()
+            .copied()
+            .find(|&fold| match blocks.get(fold) {
+                Some(Block::Heading { .. }) => section_range(blocks, fold).contains(&index),
+                Some(_) => fold == index,
+                None => false,
+            })
+    }
+}
+
+/// Blocks in the section under heading `index`: everything up to the next
+/// heading of the same or a higher level
+pub fn section_range(blocks: &[Block], index: usize) -> Range<usize> {
+    let Some(Block::Heading { level, .. }) = blocks.get(index) else {
+        return index..index;
+    };
+    let end = blocks[index + 1..]
+        .iter()
+        .position(|block| matches!(block, Block::Heading { level: next, .. } if next <= level))
+        .map_or(blocks.len(), |offset| index + 1 + offset);
+    index + 1..end
+}
+
+/// Whether top-level block `index` can be folded: a heading with a
+/// non-empty section, a callout or `<details>`, or a code block longer than
+/// `min_code_lines`
+pub fn is_foldable(blocks: &[Block], index: usize, min_code_lines: usize) -> bool {
+    match blocks.get(index) {
+        Some(Block::Heading { .. }) => !section_range(blocks, index).is_empty(),
+        Some(Block::Callout { .. } | Block::Details { .. }) => true,
+        Some(Block::CodeBlock { code, .. }) => code.lines().count() > min_code_lines,
+        _ => false,
+    }
+}
+
+/// Text of a fold placeholder, e.g. `▸ rust code · 42 lines hidden`
+pub fn fold_summary(label: Option<&str>, hidden_lines: u16) -> String {
+    let plural = if hidden_lines == 1 { "" } else { "s" };
+    match label {
+        Some(label) => format!("▸ {} · {} line{} hidden", label, hidden_lines, plural),
+        None => format!("▸ {} line{} hidden", hidden_lines, plural),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_markdown;
+
+    const REPORT: &str = "# Report\n\nIntro.\n\n## Details\n\nA.\n\nB.\n\n## Summary\n\nDone.\n\n# Appendix\n\n> [!note]- Raw data\n> 1 2 3\n";
+
+    #[test]
+    fn test_section_range_stops_at_same_or_higher_level() {
+        let doc = parse_markdown(REPORT);
+        // 0 # Report, 1 Intro, 2 ## Details, 3 A, 4 B, 5 ## Summary, 6 Done,
+        // 7 # Appendix, 8 callout
+        assert_eq!(section_range(&doc.blocks, 0), 1..7);
+        assert_eq!(section_range(&doc.blocks, 2), 3..5);
+        assert_eq!(section_range(&doc.blocks, 7), 8..9);
+        assert_eq!(section_range(&doc.blocks, 1), 1..1);
+    }
+
+    #[test]
+    fn test_hidden_by_finds_outermost_fold() {
+        let doc = parse_markdown(REPORT);
+        let mut folds = Folds::for_document(&doc);
+        assert!(folds.is_folded(8), "[!note]- starts folded");
+        assert_eq!(folds.hidden_by(&doc.blocks, 8), Some(8));
+
+        folds.fold(2);
+        assert_eq!(folds.hidden_by(&doc.blocks, 3), Some(2));
+        assert_eq!(folds.hidden_by(&doc.blocks, 2), None);
+        folds.fold(0);
+        assert_eq!(folds.hidden_by(&doc.blocks, 3), Some(0));
+
+        assert!(!folds.toggle(0));
+        assert_eq!(folds.hidden_by(&doc.blocks, 3), Some(2));
+    }
+
+    #[test]
+    fn test_is_foldable() {
+        let code = format!("```\n{}```\n\nText", "line\n".repeat(12));
+        let doc = parse_markdown(&code);
+        assert!(is_foldable(&doc.blocks, 0, DEFAULT_FOLD_CODE_LINES));
+        assert!(!is_foldable(&doc.blocks, 0, 20));
+        assert!(!is_foldable(&doc.blocks, 1, DEFAULT_FOLD_CODE_LINES));
+    }
+}
@@ -1,10 +1,18 @@
 //! Layout engine for positioning document elements
 
+pub mod cache;
+pub mod code;
 pub mod engine;
+pub mod fold;
 pub mod text;
 pub mod types;
 
-pub use engine::layout_document;
+pub use cache::LayoutCache;
+pub use code::FenceInfo;
+pub use engine::{
+    layout_document, layout_document_cached, refine_layout, LayoutOptions, LAZY_LAYOUT_MIN_BLOCKS,
+};
+pub use fold::Folds;
 pub use types::*;
 
 impl LayoutTree {
@@ -20,6 +28,14 @@ impl LayoutTree {
         self.root.rect.height
     }
 
+    /// Index of the top-level block covering document row `y`
+    pub fn block_at(&self, y: u16) -> Option<usize> {
+        self.root
+            .children
+            .iter()
+            .position(|n| n.rect.y <= y && y < n.rect.y + n.rect.height)
+    }
+
     /// Check if viewport can scroll down
     pub fn can_scroll_down(&self) -> bool {
         self.viewport.scroll_y + self.viewport.height < self.document_height()
@@ -29,6 +45,24 @@ impl LayoutTree {
     pub fn can_scroll_up(&self) -> bool {
         self.viewport.scroll_y > 0
     }
+
+    /// Check if any block is still waiting to be laid out (lazy layout)
+    pub fn has_pending(&self) -> bool {
+        self.root
+            .children
+            .iter()
+            .any(|n| matches!(n.element, LayoutElement::Pending { .. }))
+    }
+
+    /// Check if a pending block intersects the viewport
+    pub fn needs_refinement(&self) -> bool {
+        let visible = self.viewport.visible_rect();
+        self.root.children.iter().any(|n| {
+            matches!(n.element, LayoutElement::Pending { .. })
+                && n.rect.y < visible.y + visible.height
+                && n.rect.y + n.rect.height > visible.y
+        })
+    }
 }
 
 #[cfg(test)]
@@ -45,6 +79,7 @@ mod tests {
         let doc = Document::with_blocks(vec![Block::Heading {
             level: 1,
             content: vec![Inline::Text("Title".to_string())],
+            id: "title".to_string(),
         }]);
 
         let tree = layout_document(&doc, &theme, viewport, false);
@@ -123,6 +123,54 @@ fn layout_inline(inline: &Inline, ctx: &mut InlineLayoutContext) {
                 ctx.link_url.clone(),
             );
         }
+        Inline::Kbd(key) => {
+            // A keycap: padded, on the code background, and never split
+            let style = match &ctx.theme.inlines.kbd {
+                Some(kbd) => TextStyle {
+                    foreground: kbd.foreground,
+                    background: kbd.background,
+                    weight: kbd.weight,
+                    style: kbd.style,
+                },
+                None => TextStyle {
+                    foreground: ctx.theme.inlines.code.foreground,
+                    background: ctx.theme.inlines.code.background,
+                    weight: FontWeight::Bold,
+                    ..ctx.base_style
+                },
+            };
+            let keycap = format!(" {} ", key);
+            let keycap_width = UnicodeWidthStr::width(keycap.as_str()) as u16;
+            if *ctx.current_width > 0 && *ctx.current_width + 1 + keycap_width > ctx.max_width {
+                ctx.lines.push(std::mem::take(ctx.current_line));
+                *ctx.current_width = 0;
+            }
+            if *ctx.current_width > 0 {
+                ctx.current_line.add_segment_with_link(
+                    " ".to_string(),
+                    ctx.base_style,
+                    ctx.link_url.clone(),
+                );
+                *ctx.current_width += 1;
+            }
+            ctx.current_line
+                .add_segment_with_link(keycap, style, ctx.link_url.clone());
+            *ctx.current_width += keycap_width;
+        }
+        Inline::Html(html) => {
+            layout_text_content(
+                html,
+                ctx.current_line,
+                ctx.current_width,
+                ctx.max_width,
+                ctx.lines,
+                TextStyle {
+                    foreground: Some(ctx.theme.colors.muted),
+                    ..ctx.base_style
+                },
+                ctx.link_url.clone(),
+            );
+        }
         Inline::Link { text, url, .. } => {
             let old_style = ctx.base_style;
             let old_link = ctx.link_url.clone();
@@ -219,6 +267,8 @@ fn layout_text_content(
     style: TextStyle,
     link_url: Option<String>,
 ) {
+    // At least one column, or long words would never fit
+    let max_width = max_width.max(1);
     // Split by whitespace for word wrapping
     let words: Vec<&str> = text.split_whitespace().collect();
 
@@ -332,6 +382,16 @@ mod tests {
         assert!(lines.len() > 1, "Long word should break across lines");
     }
 
+    #[test]
+    fn test_zero_width_breaks_into_single_columns() {
+        let theme = theme::docs_theme();
+        let inlines = vec![Inline::Text("abc".to_string())];
+        let mut images = Vec::new();
+
+        let (lines, _) = layout_text(&inlines, 0, &theme, 0, &mut images, false);
+        assert_eq!(lines.len(), 3);
+    }
+
     #[test]
     fn test_utf8_multibyte_word_breaking() {
         let theme = theme::docs_theme();
@@ -407,4 +467,28 @@ mod tests {
         let (lines, _) = layout_text(&inlines, 80, &theme, 0, &mut images, false);
         assert_eq!(lines.len(), 1); // At least one empty line
     }
+
+    #[test]
+    fn test_kbd_keycap_and_dimmed_html() {
+        let theme = theme::docs_theme();
+        let inlines = vec![
+            Inline::Text("Press".to_string()),
+            Inline::Kbd("Ctrl".to_string()),
+            Inline::Html("<blink>".to_string()),
+        ];
+        let mut images = Vec::new();
+
+        let (lines, _) = layout_text(&inlines, 80, &theme, 0, &mut images, false);
+        let segments = &lines[0].segments;
+        let keycap = segments.iter().find(|s| s.text == " Ctrl ").unwrap();
+        assert_eq!(keycap.style.background, theme.inlines.code.background);
+        assert_eq!(keycap.style.weight, FontWeight::Bold);
+        let html = segments.iter().find(|s| s.text == "<blink>").unwrap();
+        assert_eq!(html.style.foreground, Some(theme.colors.muted));
+
+        // A keycap is never split across lines
+        let (lines, _) = layout_text(&inlines[..2], 10, &theme, 0, &mut images, false);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].segments[0].text, " Ctrl ");
+    }
 }
@@ -1,12 +1,13 @@
 //! Core layout types and structures
 
 use crate::theme::{Color, FontStyle, FontWeight};
+use serde::{Deserialize, Serialize};
 
 /// Unique identifier for layout nodes
 pub type NodeId = usize;
 
 /// Reference to an image that should be displayed in the sidebar
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageReference {
     pub path: String,
     pub alt_text: String,
@@ -14,7 +15,7 @@ pub struct ImageReference {
 }
 
 /// Complete layout tree with viewport
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LayoutTree {
     pub root: LayoutNode,
     pub viewport: Viewport,
@@ -23,7 +24,7 @@ pub struct LayoutTree {
 }
 
 /// A positioned and sized layout node
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LayoutNode {
     pub id: NodeId,
     pub rect: Rectangle,
@@ -33,7 +34,7 @@ pub struct LayoutNode {
 }
 
 /// Rectangle in terminal coordinate space (character cells)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Rectangle {
     pub x: u16,      // Column (0-based)
     pub y: u16,      // Row (0-based)
@@ -63,8 +64,23 @@ impl Rectangle {
     }
 }
 
+/// One row of a code block; a long source line may span several rows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeLine {
+    pub text: String,
+    /// 1-based source line number
+    pub number: usize,
+    /// True for the second and later rows of a wrapped source line
+    pub continuation: bool,
+    /// Referenced by the fence's highlight list (`{3,7-9}`)
+    pub highlighted: bool,
+    /// Not code: a marker under line `number` pointing at a parse error
+    pub error: bool,
+}
+
 /// Layout element types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LayoutElement {
     Document,
     Heading {
@@ -76,12 +92,23 @@ pub enum LayoutElement {
     },
     CodeBlock {
         lang: Option<String>,
-        lines: Vec<String>,
+        lines: Vec<CodeLine>,
+        /// Columns before the code on every row (line numbers, `↪`)
+        gutter_width: u16,
+        line_numbers: bool,
+    },
+    /// A code block drawn by its renderer (a mermaid diagram): framed like
+    /// code, with each part of a row coloured by the role it plays
+    Diagram {
+        lang: Option<String>,
+        lines: Vec<crate::codeblock::DiagramLine>,
     },
     BlockQuote,
     Callout {
         kind: crate::ir::CalloutKind,
     },
+    /// `<details>` section: its summary row, then its content
+    Details,
     List {
         ordered: bool,
         start: usize,
@@ -102,10 +129,21 @@ pub enum LayoutElement {
         path: String,
         alt_text: String,
     },
+    /// Top-level block not laid out yet (lazy layout); its height is an estimate
+    Pending {
+        block: usize,
+    },
+    /// Stand-in for folded content (`▸ 42 lines hidden`). `block` is the
+    /// folded block; the rest of a folded section gets zero-height
+    /// placeholders with an empty summary, so every block keeps one node.
+    Folded {
+        block: usize,
+        summary: String,
+    },
 }
 
 /// A line of text (result of inline layout)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Line {
     pub segments: Vec<TextSegment>,
 }
@@ -167,7 +205,7 @@ impl Line {
 }
 
 /// A styled text segment within a line
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextSegment {
     pub text: String,
     pub style: TextStyle,
@@ -177,7 +215,7 @@ pub struct TextSegment {
 }
 
 /// Computed style for a layout node
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComputedStyle {
     pub foreground: Option<Color>,
     pub background: Option<Color>,
@@ -201,7 +239,7 @@ impl Default for ComputedStyle {
 }
 
 /// Edge sizes (top, right, bottom, left)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct EdgeSizes {
     pub top: u16,
     pub right: u16,
@@ -248,7 +286,7 @@ impl EdgeSizes {
 }
 
 /// Text style (subset of ComputedStyle for inline elements)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TextStyle {
     pub foreground: Option<Color>,
     pub background: Option<Color>,
@@ -268,7 +306,7 @@ impl Default for TextStyle {
 }
 
 /// Viewport (terminal window)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Viewport {
     pub width: u16,    // Terminal width (columns)
     pub height: u16,   // Terminal height (rows)
@@ -322,18 +360,37 @@ impl Viewport {
 }
 
 /// Hit testing region
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HitRegion {
     pub rect: Rectangle,
     pub element: HitElement,
 }
 
 /// Interactive elements
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum HitElement {
-    Link { url: String, text: String },
-    CodeBlock { lang: Option<String> },
-    Heading { level: u8, id: String },
+    Link {
+        url: String,
+        text: String,
+    },
+    /// A code block; `code` is its source, for copying
+    CodeBlock {
+        lang: Option<String>,
+        code: String,
+    },
+    Heading {
+        level: u8,
+        id: String,
+    },
+    /// A node of a JSON/YAML tree (see [`crate::json_tree`]), covering its
+    /// rows; `code` is the block's source
+    TreeNode {
+        code: String,
+        path: String,
+        foldable: bool,
+        folded: bool,
+    },
 }
 
 #[cfg(test)]
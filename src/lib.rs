@@ -18,10 +18,25 @@
 //! - `layout`: Layout engine (positions + sizes)
 //! - `render`: Terminal renderer
 //! - `search`: Search functionality
+//! - `check`: Link checker for `lumen check`
+//! - `codeblock`: Pluggable renderers for fenced code blocks (mermaid, ...)
+//! - `json_tree`: JSON/YAML code blocks as foldable trees
+//! - `diff`: Coloured unified diffs, inline or side by side
+//! - `dump`: JSON dumps of the IR and layout for tooling
+//! - `clipboard`: Copying text via OSC 52 or wl-copy/xclip
+//! - `keymap`: Named actions and configurable key bindings
 //! - `preferences`: User preferences management
 
+pub mod check;
+pub mod clipboard;
+pub mod codeblock;
+pub mod diff;
+pub mod dot;
+pub mod dump;
 pub mod file_manager;
 pub mod ir;
+pub mod json_tree;
+pub mod keymap;
 pub mod layout;
 pub mod mermaid;
 pub mod parser;
@@ -30,10 +45,11 @@ pub mod render;
 pub mod search;
 pub mod theme;
 
-pub use file_manager::{FileManager, OpenFile};
+pub use file_manager::{FileManager, OpenFile, SavedMarks};
 pub use ir::Document;
+pub use keymap::{Action, Keymap};
 pub use layout::{layout_document, LayoutTree};
-pub use parser::parse_markdown;
+pub use parser::{parse_file, parse_markdown};
 pub use preferences::Preferences;
 pub use search::SearchState;
 pub use theme::Theme;
@@ -1,15 +1,25 @@
 //! Lumen: Interactive Markdown viewer
 
-use crossterm::event::{self, Event, KeyCode, KeyEvent, MouseEvent, MouseEventKind};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers, MouseEvent, MouseEventKind};
+use lumen::codeblock::BlockContext;
+use lumen::diff;
 use lumen::ir::{Block, Inline};
-use lumen::layout::{LayoutElement, Viewport};
+use lumen::keymap::{Action, Keymap, PendingInput};
+use lumen::layout::code::copy_badge_rect;
+use lumen::layout::fold::{is_foldable, section_range};
+use lumen::layout::{
+    layout_document, layout_document_cached, refine_layout, FenceInfo, Folds, HitElement,
+    LayoutElement, LayoutNode, LayoutOptions, Viewport, LAZY_LAYOUT_MIN_BLOCKS,
+};
+use lumen::parser::decode_fragment;
+use lumen::theme::CodeWrap;
 use lumen::{
-    layout_document, parse_markdown, render, FileManager, LayoutTree, Preferences, SearchState,
+    parse_file, render, FileManager, LayoutTree, OpenFile, Preferences, SavedMarks, SearchState,
     Theme,
 };
 use std::fs;
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 /// Check if a document contains any images
@@ -22,6 +32,9 @@ fn document_has_images(document: &lumen::Document) -> bool {
             Block::BlockQuote { blocks }
             | Block::Callout {
                 content: blocks, ..
+            }
+            | Block::Details {
+                content: blocks, ..
             } => blocks.iter().any(block_has_images),
             Block::List { items, .. } => items
                 .iter()
@@ -92,6 +105,185 @@ fn handle_import_theme(source: &str, name_override: Option<&str>) -> io::Result<
     Ok(())
 }
 
+/// Handle `lumen check [--format text|json] <file-or-dir>...`
+///
+/// Exits with 1 if any errors were found, 2 on usage errors or unreadable
+/// files.
+fn handle_check(args: &[String]) -> io::Result<()> {
+    use lumen::check::{markdown_files, Checker, Issue, Severity};
+
+    let mut json = false;
+    let mut paths = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => match iter.next().map(|s| s.as_str()) {
+                Some("json") => json = true,
+                Some("text") => json = false,
+                other => {
+                    eprintln!(
+                        "Error: --format expects 'text' or 'json', got {}",
+                        other.unwrap_or("nothing")
+                    );
+                    std::process::exit(2);
+                }
+            },
+            "--json" => json = true,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("Usage: lumen check [--format text|json] <file.md|dir> ...");
+        std::process::exit(2);
+    }
+
+    let mut checker = Checker::new();
+    let mut issues: Vec<Issue> = Vec::new();
+    let mut unreadable = false;
+    let mut checked = 0;
+    for path in &paths {
+        let files = markdown_files(std::slice::from_ref(path)).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        });
+        // `/`-rooted links resolve against the directory given, or the
+        // directory of a file given
+        let root = if path.is_dir() {
+            path.clone()
+        } else {
+            path.parent().unwrap_or(Path::new("")).to_path_buf()
+        };
+        checker.set_root(Some(root));
+        for file in &files {
+            match checker.check_file(file) {
+                Ok(found) => issues.extend(found),
+                Err(e) => {
+                    eprintln!("{}: {}", file.display(), e);
+                    unreadable = true;
+                }
+            }
+        }
+        checked += files.len();
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    let mut output = String::new();
+    if json {
+        output = serde_json::to_string_pretty(&issues).map_err(io::Error::other)?;
+        output.push('\n');
+    } else {
+        for issue in &issues {
+            output.push_str(&format!("{}\n", issue));
+        }
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        let warnings = issues.len() - errors;
+        output.push_str(&format!(
+            "Checked {} file{}: {} error{}, {} warning{}\n",
+            checked,
+            plural(checked),
+            errors,
+            plural(errors),
+            warnings,
+            plural(warnings)
+        ));
+    }
+    print_output(&output)?;
+
+    if unreadable {
+        std::process::exit(2);
+    }
+    if errors > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Handle `lumen --dump ir|layout [--width N] <file.md> [theme]`: print the
+/// document's IR or its layout as JSON
+fn handle_dump(args: &[String], inline_images: bool) -> io::Result<()> {
+    let value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+    };
+    let usage = || -> ! {
+        eprintln!("Usage: lumen --dump ir|layout [--width N] <file.md> [theme]");
+        std::process::exit(2);
+    };
+
+    let kind = value("--dump").map(|s| s.as_str());
+    if !matches!(kind, Some("ir" | "layout")) {
+        usage();
+    }
+    let width = match value("--width") {
+        Some(width) => width
+            .parse::<u16>()
+            .ok()
+            .filter(|&w| w > 0)
+            .unwrap_or_else(|| {
+                eprintln!("Error: --width expects a number of columns, got {}", width);
+                std::process::exit(2);
+            }),
+        None => 80,
+    };
+
+    // Positional arguments: the file, then optionally a theme
+    let mut positional = Vec::new();
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        if arg == "--dump" || arg == "--width" {
+            iter.next();
+        } else if !arg.starts_with('-') {
+            positional.push(arg.as_str());
+        }
+    }
+    let Some(&file_path) = positional.first() else {
+        usage();
+    };
+
+    let markdown = fs::read_to_string(file_path).unwrap_or_else(|e| {
+        eprintln!("Error reading file '{}': {}", file_path, e);
+        std::process::exit(1);
+    });
+    let document = parse_file(Path::new(file_path), &markdown);
+
+    let json = match kind {
+        Some("ir") => lumen::dump::ir_json(&document),
+        Some("layout") => {
+            let preferences = Preferences::load();
+            let theme_name = positional.get(1).copied().unwrap_or(&preferences.theme);
+            let theme = Theme::load(theme_name).unwrap_or_else(|| {
+                eprintln!("Unknown theme '{}', using 'docs'", theme_name);
+                Theme::builtin("docs").expect("Built-in 'docs' theme should always exist")
+            });
+            // Lay out the whole document, all of it visible
+            let mut tree =
+                layout_document(&document, &theme, Viewport::new(width, 0), inline_images);
+            tree.viewport.height = tree.root.rect.height;
+            lumen::dump::layout_json(&tree)
+        }
+        _ => usage(),
+    };
+    let json = json.map_err(io::Error::other)?;
+    print_output(&format!("{}\n", json))
+}
+
+/// Write a subcommand's output to stdout. It is often piped into `head` or
+/// `jq`, and a pipe closed early isn't an error: the rest is dropped.
+fn print_output(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    match stdout
+        .write_all(text.as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
 fn main() -> io::Result<()> {
     // Set up panic handler to ensure terminal is always restored
     let original_hook = std::panic::take_hook();
@@ -116,6 +308,16 @@ fn main() -> io::Result<()> {
         .any(|arg| arg == "--inline-images" || arg == "-i");
     let list_themes = args.iter().any(|arg| arg == "--list-themes");
 
+    // Handle --dump ir|layout
+    if args.iter().any(|arg| arg == "--dump") {
+        return handle_dump(&args, inline_images);
+    }
+
+    // Handle `lumen check <files...>`
+    if args.get(1).map(|s| s.as_str()) == Some("check") {
+        return handle_check(&args[2..]);
+    }
+
     // Handle --import-theme <url-or-path> [--name <name>]
     let import_theme_idx = args.iter().position(|arg| arg == "--import-theme");
     if let Some(idx) = import_theme_idx {
@@ -167,6 +369,11 @@ fn main() -> io::Result<()> {
         eprintln!("  --list-themes         List all available themes");
         eprintln!("  --import-theme <src>  Import a vim colorscheme from URL or file");
         eprintln!("  --name <name>         Override theme name (with --import-theme)");
+        eprintln!("  --dump ir|layout      Print the document's IR or layout as JSON");
+        eprintln!("  --width <N>           Layout width for --dump layout (default: 80)");
+        eprintln!("\nCommands:");
+        eprintln!("  check <file|dir> ...  Report broken links, anchors and images");
+        eprintln!("                        (--format json for machine-readable output)");
         eprintln!(
             "\nAvailable themes: {}",
             Theme::all_theme_names().join(", ")
@@ -177,12 +384,14 @@ fn main() -> io::Result<()> {
         eprintln!("  lumen README.md neon");
         eprintln!("  lumen README.md --inline-images");
         eprintln!("  lumen README.md --no-images");
+        eprintln!("  lumen data.csv");
+        eprintln!("  lumen check docs/");
         eprintln!("  lumen --import-theme https://github.com/folke/tokyonight.nvim");
         eprintln!("  lumen --import-theme https://vimcolorschemes.com/catppuccin/nvim");
         eprintln!("  lumen --import-theme ./colors/mytheme.vim --name mytheme");
         eprintln!("\nKeyboard shortcuts:");
         eprintln!("  Tab / Shift+Tab       Switch between open files");
-        eprintln!("  1-9                   Jump to file 1-9");
+        eprintln!("  N Tab                 Jump to file N (e.g., 3 Tab)");
         eprintln!("  :N                    Jump to file N (e.g., :44)");
         std::process::exit(1);
     }
@@ -196,7 +405,10 @@ fn main() -> io::Result<()> {
     let mut theme_name_override: Option<&str> = None;
 
     for arg in &non_flag_args {
-        if arg.ends_with(".md") || arg.ends_with(".markdown") {
+        if [".md", ".markdown", ".csv", ".tsv"]
+            .iter()
+            .any(|ext| arg.ends_with(ext))
+        {
             file_paths.push(arg.as_str());
         } else {
             // Assume it's a theme name
@@ -204,7 +416,7 @@ fn main() -> io::Result<()> {
         }
     }
 
-    // If no .md files found, treat all as file paths (last one might be theme)
+    // If no .md/.csv files found, treat all as file paths (last one might be theme)
     if file_paths.is_empty() {
         if non_flag_args.len() == 1 {
             file_paths.push(non_flag_args[0]);
@@ -230,9 +442,8 @@ fn main() -> io::Result<()> {
             std::process::exit(1);
         });
 
-        let mut document = parse_markdown(&markdown);
-        lumen::mermaid::transform_mermaid_blocks(&mut document);
-        file_manager.add_file(PathBuf::from(file_path), document);
+        let document = parse_file(Path::new(file_path), &markdown);
+        file_manager.add_file_with_source(PathBuf::from(file_path), markdown, document);
     }
 
     // Load theme — check user themes first, then built-in
@@ -248,6 +459,16 @@ fn main() -> io::Result<()> {
     run_interactive(file_manager, theme, preferences, no_images, inline_images)
 }
 
+/// Keep the session's code block toggles when the theme changes
+fn apply_code_overrides(theme: &mut Theme, wrap: Option<CodeWrap>, line_numbers: Option<bool>) {
+    if let Some(wrap) = wrap {
+        theme.blocks.code_block.wrap = wrap;
+    }
+    if let Some(line_numbers) = line_numbers {
+        theme.blocks.code_block.line_numbers = line_numbers;
+    }
+}
+
 /// Run the interactive viewer with proper terminal cleanup
 fn run_interactive(
     mut file_manager: FileManager,
@@ -266,54 +487,18 @@ fn run_interactive(
 
     // Ensure terminal is ALWAYS restored, even on error
     let cleanup_result = (|| -> io::Result<()> {
-        let size = terminal.size()?;
-        let full_width = size.width;
-        let height = size.height.saturating_sub(1); // -1 for status bar
-
         // File sidebar visibility (from preferences, can be toggled by user)
         let mut file_sidebar_visible = preferences.file_sidebar_visible;
 
-        // Calculate layout based on sidebars
-        // File sidebar: shown when multiple files open AND user hasn't hidden it (20% width on left)
-        // Image sidebar: shown when document has images (30% width on right)
-        let show_file_sidebar = file_manager.has_multiple_files() && file_sidebar_visible;
-
-        let file_sidebar_width = if show_file_sidebar {
-            (full_width * 20) / 100
-        } else {
-            0
-        };
-
-        // Get current document for image check
-        let current_file = file_manager
-            .current_file()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "No files open"))?;
-
-        let has_images =
-            !inline_images && !no_images && document_has_images(&current_file.document);
-        let image_sidebar_width = if has_images {
-            (full_width * 30) / 100
-        } else {
-            0
-        };
-
-        // Content area is remainder
-        let layout_width = full_width.saturating_sub(file_sidebar_width + image_sidebar_width);
-
-        let mut viewport = Viewport::new(layout_width, height);
-
-        // Layout current document
-        let mut tree = {
-            let current_file = file_manager
-                .current_file()
-                .expect("Bug: file_manager should always have at least one file");
-            layout_document(&current_file.document, &theme, viewport, inline_images)
-        };
-
-        // Disable image sidebar if requested
-        if no_images {
-            tree.images.clear();
-        }
+        // Layout current document (sidebar widths are derived from the terminal size)
+        let mut tree = recalculate_layout(
+            &mut file_manager,
+            &terminal,
+            &theme,
+            file_sidebar_visible,
+            no_images,
+            inline_images,
+        )?;
 
         // Frame rate limiting - target 60 FPS
         let frame_duration = Duration::from_millis(16);
@@ -327,6 +512,28 @@ fn run_interactive(
         let mut selected_link_index: Option<usize> = None; // Currently selected link for navigation
         let mut status_message: Option<String> = None; // Transient status bar message
         let mut status_message_expiry: Option<Instant> = None; // When to clear the message
+        let mut pending = PendingInput::new(); // Count prefix / partial key sequence
+        let mut code_wrap: Option<CodeWrap> = None; // Session override of the theme's code wrap
+        let mut code_line_numbers: Option<bool> = None; // Session override of line numbers
+        let mut visual: Option<(u16, u16)> = None; // Visual line mode: (anchor, cursor) rows
+
+        // Marks from previous sessions, if the user opted in
+        let mut saved_marks = if preferences.persist_marks {
+            let saved = SavedMarks::load();
+            file_manager.restore_marks(&saved);
+            Some(saved)
+        } else {
+            None
+        };
+
+        // Key bindings: defaults plus overrides from the config file
+        let (overrides, mut keymap_warnings) = preferences.key_overrides();
+        let (keymap, warnings) = Keymap::with_overrides(&overrides);
+        keymap_warnings.extend(warnings);
+        if let Some(warning) = keymap_warnings.first() {
+            status_message = Some(format!("Config: {}", warning));
+            status_message_expiry = Some(Instant::now() + Duration::from_secs(5));
+        }
 
         // Enable mouse capture if preference is set
         if mouse_enabled {
@@ -334,7 +541,7 @@ fn run_interactive(
         }
 
         // Main event loop
-        loop {
+        'main: loop {
             // Clear expired status messages
             let now = Instant::now();
             if let Some(expiry) = status_message_expiry {
@@ -345,6 +552,18 @@ fn run_interactive(
                 }
             }
 
+            // Lay out blocks that lazy layout skipped once they scroll into view
+            if tree.needs_refinement() {
+                refine_current_layout(
+                    &mut tree,
+                    &mut file_manager,
+                    &theme,
+                    no_images,
+                    inline_images,
+                );
+                needs_render = true;
+            }
+
             // Render only if needed and enough time has passed
             if needs_render && now.duration_since(last_render) >= frame_duration {
                 let show_file_sidebar = file_manager.has_multiple_files() && file_sidebar_visible;
@@ -353,6 +572,7 @@ fn run_interactive(
                     &tree,
                     &theme,
                     show_help,
+                    &keymap,
                     &search_state,
                     &file_manager,
                     show_file_sidebar,
@@ -360,6 +580,8 @@ fn run_interactive(
                     &file_jump_buffer,
                     selected_link_index,
                     status_message.as_deref(),
+                    &pending.display(&keymap),
+                    visual.map(|(anchor, cursor)| (anchor.min(cursor), anchor.max(cursor))),
                 )?;
                 last_render = now;
                 needs_render = false;
@@ -386,10 +608,11 @@ fn run_interactive(
 
                                             // Switch to new file
                                             file_manager.switch_to(file_num - 1);
+                                            visual = None;
 
                                             // Recalculate layout
-                                            (viewport, tree) = recalculate_layout(
-                                                &file_manager,
+                                            tree = recalculate_layout(
+                                                &mut file_manager,
                                                 &terminal,
                                                 &theme,
                                                 file_sidebar_visible,
@@ -430,7 +653,27 @@ fn run_interactive(
                                 }
                                 KeyCode::Enter => {
                                     search_state.execute_search(&tree.root);
-                                    search_state.accept(); // Exit input mode but keep results
+                                    // Stay in the prompt so an invalid regex can be fixed
+                                    if search_state.error.is_none() {
+                                        search_state.accept(); // Exit input mode but keep results
+                                        if file_manager
+                                            .current_file()
+                                            .is_some_and(|file| !file.folds.is_empty())
+                                            && reveal_search_match(
+                                                &mut tree,
+                                                &mut file_manager,
+                                                &theme,
+                                                &mut search_state,
+                                                MatchTarget::First,
+                                                no_images,
+                                                inline_images,
+                                            )
+                                        {
+                                            status_message = Some("Unfolded to show match".into());
+                                            status_message_expiry =
+                                                Some(Instant::now() + Duration::from_secs(2));
+                                        }
+                                    }
                                     if let Some(m) = search_state.current_match() {
                                         tree.viewport.scroll_to_clamped(
                                             m.y.saturating_sub(5),
@@ -450,6 +693,24 @@ fn run_interactive(
                                     }
                                     needs_render = true;
                                 }
+                                KeyCode::Char('r' | 'w')
+                                    if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                                {
+                                    // Ctrl-R toggles regex mode, Ctrl-W whole-word mode
+                                    if key.code == KeyCode::Char('r') {
+                                        search_state.toggle_regex();
+                                    } else {
+                                        search_state.toggle_whole_word();
+                                    }
+                                    search_state.execute_search(&tree.root);
+                                    if let Some(m) = search_state.current_match() {
+                                        tree.viewport.scroll_to_clamped(
+                                            m.y.saturating_sub(5),
+                                            tree.document_height(),
+                                        );
+                                    }
+                                    needs_render = true;
+                                }
                                 KeyCode::Char(c) => {
                                     search_state.add_char(c);
                                     search_state.execute_search(&tree.root);
@@ -463,306 +724,814 @@ fn run_interactive(
                                 }
                                 _ => {}
                             }
-                        } else if key.code == KeyCode::Esc && !search_state.matches.is_empty() {
-                            // Clear search results when Esc is pressed and we have results
-                            search_state.deactivate();
-                            needs_render = true;
-                        } else if key.code == KeyCode::Char('/') {
-                            // Activate search mode
-                            search_state.activate();
+                        } else if let Some(action) = pending.awaiting.take() {
+                            // The key after `M` / `'` names the mark
+                            pending.clear();
+                            if let KeyCode::Char(mark) = key.code {
+                                let scroll_y = tree.viewport.scroll_y;
+                                let message = match action {
+                                    Action::SetMark if mark.is_ascii_alphabetic() => {
+                                        file_manager.set_mark(mark, scroll_y);
+                                        if let Some(saved) = saved_marks.as_mut() {
+                                            file_manager.store_marks(saved);
+                                            if let Err(e) = saved.save() {
+                                                eprintln!("Warning: Failed to save marks: {}", e);
+                                            }
+                                        }
+                                        format!("Mark '{}' set", mark)
+                                    }
+                                    Action::JumpToMark => {
+                                        // '' and `` jump back to where the last jump started
+                                        let name = if mark == '`' { '\'' } else { mark };
+                                        match file_manager.get_mark(name) {
+                                            Some(y) => {
+                                                file_manager.set_mark('\'', scroll_y);
+                                                tree.viewport
+                                                    .scroll_to_clamped(y, tree.document_height());
+                                                format!("Mark '{}'", name)
+                                            }
+                                            None => format!("Mark '{}' not set", name),
+                                        }
+                                    }
+                                    _ => format!("Invalid mark '{}'", mark),
+                                };
+                                status_message = Some(message);
+                                status_message_expiry =
+                                    Some(Instant::now() + Duration::from_secs(2));
+                            }
                             needs_render = true;
-                        } else if key.code == KeyCode::Char(':') && file_manager.file_count() > 1 {
-                            // Activate file jump mode
-                            file_jump_mode = true;
-                            file_jump_buffer.clear();
+                        } else if pending.push_digit(key.into()) {
+                            // Count prefix (10j, 3n, 5G)
                             needs_render = true;
-                        } else if key.code == KeyCode::Char('h') {
-                            show_help = !show_help;
+                        } else {
+                            let actions = keymap.resolve_pending(&mut pending.keys, key.into());
+                            // A count applies to the action it prefixes; drop it
+                            // when the sequence turned out to be unbound
+                            let count = if actions.is_empty() && !pending.keys.is_empty() {
+                                None
+                            } else {
+                                pending.take_count()
+                            };
                             needs_render = true;
-                        } else if key.code == KeyCode::Char('a') {
-                            // Cycle through anchor links
-                            let link_count = tree
-                                .hit_regions
-                                .iter()
-                                .filter(|r| {
-                                    matches!(r.element, lumen::layout::HitElement::Link { .. })
-                                })
-                                .count();
-
-                            if link_count > 0 {
-                                selected_link_index = Some(match selected_link_index {
-                                    None => 0,
-                                    Some(idx) => (idx + 1) % link_count,
-                                });
-
-                                // Scroll to make selected link visible
-                                if let Some(link_idx) = selected_link_index {
-                                    let link_regions: Vec<_> = tree
-                                        .hit_regions
-                                        .iter()
-                                        .filter(|r| {
-                                            matches!(
-                                                r.element,
-                                                lumen::layout::HitElement::Link { .. }
-                                            )
-                                        })
-                                        .collect();
+                            for action in actions {
+                                match action {
+                                    Action::Cancel if count.is_some() => {
+                                        // Esc discards a typed count
+                                    }
+                                    Action::SetMark | Action::JumpToMark => {
+                                        pending.awaiting = Some(action);
+                                    }
+                                    Action::Cancel | Action::VisualLine if visual.is_some() => {
+                                        visual = None;
+                                    }
+                                    Action::VisualLine => {
+                                        let row = tree.viewport.scroll_y;
+                                        visual = Some((row, row));
+                                    }
+                                    Action::ScrollDown | Action::ScrollUp
+                                        if visual.is_some() && !show_help =>
+                                    {
+                                        // j/k move the selection cursor, scrolling to follow it
+                                        if let Some((_, cursor)) = visual.as_mut() {
+                                            let delta = count.unwrap_or(1) as i64;
+                                            let delta = if action == Action::ScrollUp {
+                                                -delta
+                                            } else {
+                                                delta
+                                            };
+                                            let last = tree.document_height().saturating_sub(1);
+                                            *cursor = (*cursor as i64 + delta).clamp(0, last as i64)
+                                                as u16;
+                                            let doc_height = tree.document_height();
+                                            let viewport = &mut tree.viewport;
+                                            if *cursor < viewport.scroll_y {
+                                                viewport.scroll_to_clamped(*cursor, doc_height);
+                                            } else if *cursor >= viewport.scroll_y + viewport.height
+                                            {
+                                                viewport.scroll_to_clamped(
+                                                    *cursor + 1 - viewport.height,
+                                                    doc_height,
+                                                );
+                                            }
+                                        }
+                                    }
+                                    Action::NextMatch | Action::PrevMatch
+                                        if !show_help
+                                            && !search_state.matches.is_empty()
+                                            && file_manager
+                                                .current_file()
+                                                .is_some_and(|file| !file.folds.is_empty()) =>
+                                    {
+                                        // Step through matches inside folds too
+                                        let target = if action == Action::NextMatch {
+                                            MatchTarget::Next
+                                        } else {
+                                            MatchTarget::Prev
+                                        };
+                                        let mut unfolded = false;
+                                        for _ in 0..count.unwrap_or(1) {
+                                            unfolded |= reveal_search_match(
+                                                &mut tree,
+                                                &mut file_manager,
+                                                &theme,
+                                                &mut search_state,
+                                                target,
+                                                no_images,
+                                                inline_images,
+                                            );
+                                        }
+                                        if let Some(m) = search_state.current_match() {
+                                            tree.viewport.scroll_to_clamped(
+                                                m.y.saturating_sub(5),
+                                                tree.document_height(),
+                                            );
+                                        }
+                                        if unfolded {
+                                            status_message = Some("Unfolded to show match".into());
+                                            status_message_expiry =
+                                                Some(Instant::now() + Duration::from_secs(2));
+                                        }
+                                    }
+                                    Action::ToggleFold | Action::FoldAll | Action::UnfoldAll => {
+                                        let min_code_lines = preferences.fold_code_lines;
+                                        let mut target = None;
+                                        let row = visual
+                                            .map_or(tree.viewport.scroll_y, |(_, cursor)| cursor);
+                                        let tree_node = (action == Action::ToggleFold)
+                                            .then(|| tree_node_at(&tree, row, true))
+                                            .flatten();
+                                        let message = file_manager.current_file_mut().map(|file| {
+                                            let blocks = &file.document.blocks;
+                                            match action {
+                                                Action::ToggleFold => {
+                                                    if let Some(node) = &tree_node {
+                                                        return toggle_tree_node(file, node);
+                                                    }
+                                                    target =
+                                                        fold_target(&tree, blocks, min_code_lines);
+                                                    match target {
+                                                        Some(index) => toggle_fold(file, index),
+                                                        None => "Nothing to fold here".to_string(),
+                                                    }
+                                                }
+                                                Action::FoldAll => {
+                                                    for index in 0..blocks.len() {
+                                                        if is_foldable(
+                                                            &file.document.blocks,
+                                                            index,
+                                                            min_code_lines,
+                                                        ) {
+                                                            file.folds.fold(index);
+                                                        }
+                                                    }
+                                                    "Folded everything".to_string()
+                                                }
+                                                _ => {
+                                                    file.folds.clear();
+                                                    "Unfolded everything".to_string()
+                                                }
+                                            }
+                                        });
+
+                                        let old_scroll = tree.viewport.scroll_y;
+                                        tree = recalculate_layout(
+                                            &mut file_manager,
+                                            &terminal,
+                                            &theme,
+                                            file_sidebar_visible,
+                                            no_images,
+                                            inline_images,
+                                        )?;
+                                        // Folding a section or tree node from inside it
+                                        // jumps to its first row
+                                        let scroll = match (target, &tree_node) {
+                                            (Some(index), _) => {
+                                                old_scroll.min(tree.root.children[index].rect.y)
+                                            }
+                                            (None, Some(node)) => old_scroll.min(node.y),
+                                            (None, None) => old_scroll,
+                                        };
+                                        tree.viewport
+                                            .scroll_to_clamped(scroll, tree.document_height());
+                                        if search_state.match_count() > 0 {
+                                            rerun_search(
+                                                &mut search_state,
+                                                &mut tree,
+                                                &mut file_manager,
+                                                &theme,
+                                                no_images,
+                                                inline_images,
+                                            );
+                                        }
 
-                                    if let Some(region) = link_regions.get(link_idx) {
-                                        tree.viewport.scroll_to_clamped(
-                                            region.rect.y.saturating_sub(3),
-                                            tree.document_height(),
+                                        status_message = message;
+                                        status_message_expiry =
+                                            Some(Instant::now() + Duration::from_secs(2));
+                                    }
+                                    Action::Yank => {
+                                        if count.is_some() && tree.has_pending() {
+                                            complete_layout(
+                                                &mut tree,
+                                                &mut file_manager,
+                                                &theme,
+                                                no_images,
+                                                inline_images,
+                                            );
+                                        }
+                                        let selection = visual.take().map(|(anchor, cursor)| {
+                                            (anchor.min(cursor), anchor.max(cursor))
+                                        });
+                                        let message =
+                                            match yank_text(&tree, &file_manager, selection, count)
+                                            {
+                                                Ok((text, what)) => copy_to_clipboard(&text, &what),
+                                                Err(message) => message,
+                                            };
+                                        status_message = Some(message);
+                                        status_message_expiry =
+                                            Some(Instant::now() + Duration::from_secs(2));
+                                    }
+                                    Action::CopyTreePath => {
+                                        let row = visual
+                                            .map_or(tree.viewport.scroll_y, |(_, cursor)| cursor);
+                                        status_message =
+                                            Some(match tree_node_at(&tree, row, false) {
+                                                Some(node) => {
+                                                    copy_to_clipboard(&node.path, &node.path)
+                                                }
+                                                None => "No JSON/YAML tree node here".to_string(),
+                                            });
+                                        status_message_expiry =
+                                            Some(Instant::now() + Duration::from_secs(2));
+                                    }
+                                    Action::Cancel if !search_state.matches.is_empty() => {
+                                        // Clear search results first
+                                        search_state.deactivate();
+                                        needs_render = true;
+                                    }
+                                    Action::Cancel if show_help => {
+                                        show_help = false;
+                                        needs_render = true;
+                                    }
+                                    Action::Cancel | Action::Quit => {
+                                        if !show_help {
+                                            break 'main;
+                                        }
+                                    }
+                                    Action::Search => {
+                                        // Search needs every block laid out
+                                        if tree.has_pending() {
+                                            complete_layout(
+                                                &mut tree,
+                                                &mut file_manager,
+                                                &theme,
+                                                no_images,
+                                                inline_images,
+                                            );
+                                        }
+
+                                        // Activate search mode
+                                        search_state.activate();
+                                        needs_render = true;
+                                    }
+                                    Action::JumpToFile => {
+                                        if file_manager.file_count() > 1 {
+                                            // Activate file jump mode
+                                            file_jump_mode = true;
+                                            file_jump_buffer.clear();
+                                            needs_render = true;
+                                        }
+                                    }
+                                    Action::ToggleHelp => {
+                                        show_help = !show_help;
+                                        needs_render = true;
+                                    }
+                                    Action::NextLink => {
+                                        // Cycle through anchor links
+                                        let link_count = tree
+                                            .hit_regions
+                                            .iter()
+                                            .filter(|r| {
+                                                matches!(
+                                                    r.element,
+                                                    lumen::layout::HitElement::Link { .. }
+                                                )
+                                            })
+                                            .count();
+
+                                        if link_count > 0 {
+                                            selected_link_index = Some(match selected_link_index {
+                                                None => 0,
+                                                Some(idx) => (idx + 1) % link_count,
+                                            });
+
+                                            // Scroll to make selected link visible
+                                            if let Some(link_idx) = selected_link_index {
+                                                let link_regions: Vec<_> = tree
+                                                    .hit_regions
+                                                    .iter()
+                                                    .filter(|r| {
+                                                        matches!(
+                                                            r.element,
+                                                            lumen::layout::HitElement::Link { .. }
+                                                        )
+                                                    })
+                                                    .collect();
+
+                                                if let Some(region) = link_regions.get(link_idx) {
+                                                    tree.viewport.scroll_to_clamped(
+                                                        region.rect.y.saturating_sub(3),
+                                                        tree.document_height(),
+                                                    );
+                                                }
+                                            }
+                                            needs_render = true;
+                                        }
+                                    }
+                                    Action::FollowLink => {
+                                        // Follow the selected link
+                                        if let Some(link_idx) = selected_link_index {
+                                            let link_regions: Vec<_> = tree
+                                                .hit_regions
+                                                .iter()
+                                                .filter(|r| {
+                                                    matches!(
+                                                        r.element,
+                                                        lumen::layout::HitElement::Link { .. }
+                                                    )
+                                                })
+                                                .collect();
+
+                                            let anchor =
+                                                link_regions.get(link_idx).and_then(|region| {
+                                                    match &region.element {
+                                                        lumen::layout::HitElement::Link {
+                                                            url,
+                                                            ..
+                                                        } => url
+                                                            .strip_prefix('#')
+                                                            .map(str::to_string),
+                                                        _ => None,
+                                                    }
+                                                });
+                                            // Handle internal anchor links
+                                            if let Some(anchor) = anchor {
+                                                match follow_anchor(
+                                                    &mut tree,
+                                                    &mut file_manager,
+                                                    &theme,
+                                                    &anchor,
+                                                    no_images,
+                                                    inline_images,
+                                                ) {
+                                                    Ok(unfolded) => {
+                                                        if unfolded
+                                                            && search_state.match_count() > 0
+                                                        {
+                                                            rerun_search(
+                                                                &mut search_state,
+                                                                &mut tree,
+                                                                &mut file_manager,
+                                                                &theme,
+                                                                no_images,
+                                                                inline_images,
+                                                            );
+                                                        }
+                                                        selected_link_index = None;
+                                                        // Deselect after jump
+                                                    }
+                                                    Err(message) => {
+                                                        status_message = Some(message);
+                                                        status_message_expiry = Some(
+                                                            Instant::now() + Duration::from_secs(2),
+                                                        );
+                                                    }
+                                                }
+                                                needs_render = true;
+                                            }
+                                        }
+                                    }
+                                    Action::ToggleMouse => {
+                                        mouse_enabled = !mouse_enabled;
+                                        preferences.mouse_enabled = mouse_enabled;
+                                        if let Err(e) = preferences.save() {
+                                            eprintln!("Warning: Failed to save preferences: {}", e);
+                                        }
+
+                                        if mouse_enabled {
+                                            crossterm::execute!(
+                                                io::stdout(),
+                                                crossterm::event::EnableMouseCapture
+                                            )?;
+                                        } else {
+                                            crossterm::execute!(
+                                                io::stdout(),
+                                                crossterm::event::DisableMouseCapture
+                                            )?;
+                                        }
+                                        needs_render = true;
+                                    }
+                                    Action::ToggleFileSidebar => {
+                                        if file_manager.file_count() > 1 {
+                                            file_sidebar_visible = !file_sidebar_visible;
+                                            preferences.file_sidebar_visible = file_sidebar_visible;
+                                            if let Err(e) = preferences.save() {
+                                                eprintln!(
+                                                    "Warning: Failed to save preferences: {}",
+                                                    e
+                                                );
+                                            }
+
+                                            // Save current scroll before recalculating
+                                            let old_scroll = tree.viewport.scroll_y;
+
+                                            // Recalculate layout with new sidebar state
+                                            tree = recalculate_layout(
+                                                &mut file_manager,
+                                                &terminal,
+                                                &theme,
+                                                file_sidebar_visible,
+                                                no_images,
+                                                inline_images,
+                                            )?;
+
+                                            // Restore scroll position
+                                            tree.viewport.scroll_to_clamped(
+                                                old_scroll,
+                                                tree.document_height(),
+                                            );
+                                            needs_render = true;
+                                        }
+                                    }
+                                    Action::ToggleTheme => {
+                                        // Cycle to next theme (built-in + user themes)
+                                        let theme_names = Theme::all_theme_names();
+                                        let current_index = theme_names
+                                            .iter()
+                                            .position(|n| *n == preferences.theme)
+                                            .unwrap_or(0);
+                                        let next_index = (current_index + 1) % theme_names.len();
+                                        preferences.theme = theme_names[next_index].clone();
+
+                                        // Load new theme (user or built-in)
+                                        theme = Theme::load(&preferences.theme).expect(
+                                            "Theme from all_theme_names should always load",
                                         );
+                                        apply_code_overrides(
+                                            &mut theme,
+                                            code_wrap,
+                                            code_line_numbers,
+                                        );
+
+                                        // Save preferences
+                                        if let Err(e) = preferences.save() {
+                                            eprintln!("Warning: Failed to save preferences: {}", e);
+                                        }
+
+                                        // Save current scroll
+                                        let old_scroll = tree.viewport.scroll_y;
+
+                                        // Recalculate layout with new theme
+                                        tree = recalculate_layout(
+                                            &mut file_manager,
+                                            &terminal,
+                                            &theme,
+                                            file_sidebar_visible,
+                                            no_images,
+                                            inline_images,
+                                        )?;
+
+                                        // Restore scroll position
+                                        tree.viewport
+                                            .scroll_to_clamped(old_scroll, tree.document_height());
+
+                                        // Show theme name briefly
+                                        status_message =
+                                            Some(format!("Theme: {}", preferences.theme));
+                                        status_message_expiry =
+                                            Some(Instant::now() + Duration::from_secs(2));
+                                        needs_render = true;
                                     }
-                                }
-                                needs_render = true;
-                            }
-                        } else if key.code == KeyCode::Enter && selected_link_index.is_some() {
-                            // Follow the selected link
-                            if let Some(link_idx) = selected_link_index {
-                                let link_regions: Vec<_> = tree
-                                    .hit_regions
-                                    .iter()
-                                    .filter(|r| {
-                                        matches!(r.element, lumen::layout::HitElement::Link { .. })
-                                    })
-                                    .collect();
+                                    Action::ToggleCodeWrap | Action::ToggleLineNumbers => {
+                                        let code_style = &mut theme.blocks.code_block;
+                                        let message = if action == Action::ToggleCodeWrap {
+                                            code_style.wrap = code_style.wrap.toggled();
+                                            code_wrap = Some(code_style.wrap);
+                                            match code_style.wrap {
+                                                CodeWrap::Wrap => "Code: wrap long lines",
+                                                CodeWrap::Clip => "Code: clip long lines",
+                                            }
+                                        } else {
+                                            code_style.line_numbers = !code_style.line_numbers;
+                                            code_line_numbers = Some(code_style.line_numbers);
+                                            if code_style.line_numbers {
+                                                "Code: line numbers on"
+                                            } else {
+                                                "Code: line numbers off"
+                                            }
+                                        };
+
+                                        let old_scroll = tree.viewport.scroll_y;
+                                        tree = recalculate_layout(
+                                            &mut file_manager,
+                                            &terminal,
+                                            &theme,
+                                            file_sidebar_visible,
+                                            no_images,
+                                            inline_images,
+                                        )?;
+                                        tree.viewport
+                                            .scroll_to_clamped(old_scroll, tree.document_height());
+                                        // Matches moved with the rows; keep the same one selected
+                                        if search_state.match_count() > 0 {
+                                            let current = search_state.current_index;
+                                            rerun_search(
+                                                &mut search_state,
+                                                &mut tree,
+                                                &mut file_manager,
+                                                &theme,
+                                                no_images,
+                                                inline_images,
+                                            );
+                                            if current < Some(search_state.match_count()) {
+                                                search_state.current_index = current;
+                                            }
+                                        }
 
-                                if let Some(region) = link_regions.get(link_idx) {
-                                    if let lumen::layout::HitElement::Link { url, .. } =
-                                        &region.element
-                                    {
-                                        // Handle internal anchor links
-                                        if url.starts_with('#') {
-                                            let anchor = &url[1..]; // Remove the '#'
-
-                                            // Find the heading with this anchor
-                                            for heading_region in &tree.hit_regions {
-                                                if let lumen::layout::HitElement::Heading {
-                                                    id,
-                                                    ..
-                                                } = &heading_region.element
-                                                {
-                                                    if id == anchor {
-                                                        // Jump to this heading
-                                                        tree.viewport.scroll_y =
-                                                            heading_region.rect.y;
-                                                        selected_link_index = None; // Deselect after jump
-                                                        needs_render = true;
-                                                        break;
+                                        status_message = Some(message.to_string());
+                                        status_message_expiry =
+                                            Some(Instant::now() + Duration::from_secs(2));
+                                        needs_render = true;
+                                    }
+                                    Action::ToggleDiagramSource => {
+                                        let message = match file_manager.current_file_mut() {
+                                            Some(file) => {
+                                                let options = layout_options(file, inline_images);
+                                                match diagram_target(&tree, &options) {
+                                                    // Drawn now, or can be
+                                                    Some((code, drawn_by_default, drawable))
+                                                        if drawable
+                                                            || file.sources.contains(&code)
+                                                                != drawn_by_default =>
+                                                    {
+                                                        if file.sources.toggle(&code)
+                                                            == drawn_by_default
+                                                        {
+                                                            "Diagram: source"
+                                                        } else {
+                                                            "Diagram: drawing"
+                                                        }
                                                     }
+                                                    Some(_) => "Diagram: can't draw this block",
+                                                    None => "No diagram on screen",
                                                 }
                                             }
+                                            None => "No file open",
+                                        };
+
+                                        let old_scroll = tree.viewport.scroll_y;
+                                        tree = recalculate_layout(
+                                            &mut file_manager,
+                                            &terminal,
+                                            &theme,
+                                            file_sidebar_visible,
+                                            no_images,
+                                            inline_images,
+                                        )?;
+                                        tree.viewport
+                                            .scroll_to_clamped(old_scroll, tree.document_height());
+                                        if search_state.match_count() > 0 {
+                                            rerun_search(
+                                                &mut search_state,
+                                                &mut tree,
+                                                &mut file_manager,
+                                                &theme,
+                                                no_images,
+                                                inline_images,
+                                            );
                                         }
+
+                                        status_message = Some(message.to_string());
+                                        status_message_expiry =
+                                            Some(Instant::now() + Duration::from_secs(2));
+                                        needs_render = true;
                                     }
-                                }
-                            }
-                        } else if key.code == KeyCode::Char('m') {
-                            // Toggle mouse mode
-                            mouse_enabled = !mouse_enabled;
-                            preferences.mouse_enabled = mouse_enabled;
-                            if let Err(e) = preferences.save() {
-                                eprintln!("Warning: Failed to save preferences: {}", e);
-                            }
+                                    Action::ToggleDiffSplit => {
+                                        let message = match file_manager.current_file_mut() {
+                                            Some(file) => match diff_target(&tree) {
+                                                Some((_, width))
+                                                    if width < diff::MIN_SPLIT_WIDTH =>
+                                                {
+                                                    format!(
+                                                        "Side-by-side diffs need {} columns",
+                                                        diff::MIN_SPLIT_WIDTH + 2
+                                                    )
+                                                }
+                                                Some((code, _))
+                                                    if file.split_diffs.toggle(&code) =>
+                                                {
+                                                    "Diff: side by side".to_string()
+                                                }
+                                                Some(_) => "Diff: inline".to_string(),
+                                                None => "No diff on screen".to_string(),
+                                            },
+                                            None => "No file open".to_string(),
+                                        };
+
+                                        let old_scroll = tree.viewport.scroll_y;
+                                        tree = recalculate_layout(
+                                            &mut file_manager,
+                                            &terminal,
+                                            &theme,
+                                            file_sidebar_visible,
+                                            no_images,
+                                            inline_images,
+                                        )?;
+                                        tree.viewport
+                                            .scroll_to_clamped(old_scroll, tree.document_height());
+                                        if search_state.match_count() > 0 {
+                                            rerun_search(
+                                                &mut search_state,
+                                                &mut tree,
+                                                &mut file_manager,
+                                                &theme,
+                                                no_images,
+                                                inline_images,
+                                            );
+                                        }
 
-                            if mouse_enabled {
-                                crossterm::execute!(
-                                    io::stdout(),
-                                    crossterm::event::EnableMouseCapture
-                                )?;
-                            } else {
-                                crossterm::execute!(
-                                    io::stdout(),
-                                    crossterm::event::DisableMouseCapture
-                                )?;
-                            }
-                            needs_render = true;
-                        } else if key.code == KeyCode::Char('f') && file_manager.file_count() > 1 {
-                            // Toggle file sidebar visibility
-                            file_sidebar_visible = !file_sidebar_visible;
-                            preferences.file_sidebar_visible = file_sidebar_visible;
-                            if let Err(e) = preferences.save() {
-                                eprintln!("Warning: Failed to save preferences: {}", e);
-                            }
+                                        status_message = Some(message);
+                                        status_message_expiry =
+                                            Some(Instant::now() + Duration::from_secs(2));
+                                        needs_render = true;
+                                    }
+                                    Action::NextFile | Action::PrevFile => {
+                                        visual = None;
 
-                            // Save current scroll before recalculating
-                            let old_scroll = tree.viewport.scroll_y;
-
-                            // Recalculate layout with new sidebar state
-                            (viewport, tree) = recalculate_layout(
-                                &file_manager,
-                                &terminal,
-                                &theme,
-                                file_sidebar_visible,
-                                no_images,
-                                inline_images,
-                            )?;
-
-                            // Restore scroll position
-                            tree.viewport
-                                .scroll_to_clamped(old_scroll, tree.document_height());
-                            needs_render = true;
-                        } else if key.code == KeyCode::Char('t') {
-                            // Cycle to next theme (built-in + user themes)
-                            let theme_names = Theme::all_theme_names();
-                            let current_index = theme_names
-                                .iter()
-                                .position(|n| *n == preferences.theme)
-                                .unwrap_or(0);
-                            let next_index = (current_index + 1) % theme_names.len();
-                            preferences.theme = theme_names[next_index].clone();
-
-                            // Load new theme (user or built-in)
-                            theme = Theme::load(&preferences.theme)
-                                .expect("Theme from all_theme_names should always load");
-
-                            // Save preferences
-                            if let Err(e) = preferences.save() {
-                                eprintln!("Warning: Failed to save preferences: {}", e);
-                            }
+                                        // Save current scroll before switching
+                                        file_manager.save_scroll_position(tree.viewport.scroll_y);
 
-                            // Save current scroll
-                            let old_scroll = tree.viewport.scroll_y;
-
-                            // Recalculate layout with new theme
-                            (viewport, tree) = recalculate_layout(
-                                &file_manager,
-                                &terminal,
-                                &theme,
-                                file_sidebar_visible,
-                                no_images,
-                                inline_images,
-                            )?;
-
-                            // Restore scroll position
-                            tree.viewport
-                                .scroll_to_clamped(old_scroll, tree.document_height());
-
-                            // Show theme name briefly
-                            status_message = Some(format!("Theme: {}", preferences.theme));
-                            status_message_expiry = Some(Instant::now() + Duration::from_secs(2));
-                            needs_render = true;
-                        } else if key.code == KeyCode::Tab {
-                            // Save current scroll before switching
-                            file_manager.save_scroll_position(tree.viewport.scroll_y);
-
-                            // Switch to next file
-                            file_manager.next_file();
-
-                            // Recalculate layout
-                            (viewport, tree) = recalculate_layout(
-                                &file_manager,
-                                &terminal,
-                                &theme,
-                                file_sidebar_visible,
-                                no_images,
-                                inline_images,
-                            )?;
-
-                            // Restore saved scroll for the new file
-                            let saved_scroll = file_manager.get_scroll_position();
-                            tree.viewport
-                                .scroll_to_clamped(saved_scroll, tree.document_height());
-
-                            // Clear search state when switching files
-                            search_state.deactivate();
-                            needs_render = true;
-                        } else if key.code == KeyCode::BackTab {
-                            // Save current scroll before switching
-                            file_manager.save_scroll_position(tree.viewport.scroll_y);
-
-                            // Switch to previous file (Shift+Tab)
-                            file_manager.prev_file();
-
-                            // Recalculate layout
-                            (viewport, tree) = recalculate_layout(
-                                &file_manager,
-                                &terminal,
-                                &theme,
-                                file_sidebar_visible,
-                                no_images,
-                                inline_images,
-                            )?;
-
-                            // Restore saved scroll for the new file
-                            let saved_scroll = file_manager.get_scroll_position();
-                            tree.viewport
-                                .scroll_to_clamped(saved_scroll, tree.document_height());
-
-                            // Clear search state when switching files
-                            search_state.deactivate();
-                            needs_render = true;
-                        } else if key.code >= KeyCode::Char('1')
-                            && key.code <= KeyCode::Char('9')
-                            && file_manager.file_count() > 1
-                        {
-                            // Jump to file by number (1-9)
-                            if let KeyCode::Char(c) = key.code {
-                                let index = (c as u8 - b'1') as usize;
-                                if index < file_manager.file_count() {
-                                    // Save current scroll before switching
-                                    file_manager.save_scroll_position(tree.viewport.scroll_y);
-
-                                    // Switch to file
-                                    file_manager.switch_to(index);
-
-                                    // Recalculate layout
-                                    (viewport, tree) = recalculate_layout(
-                                        &file_manager,
-                                        &terminal,
-                                        &theme,
-                                        file_sidebar_visible,
-                                        no_images,
-                                        inline_images,
-                                    )?;
+                                        // Switch to next/previous file; `3<Tab>` goes to file 3
+                                        match (action, count) {
+                                            (Action::NextFile, Some(n)) => {
+                                                file_manager.switch_to(n.saturating_sub(1))
+                                            }
+                                            (Action::NextFile, None) => file_manager.next_file(),
+                                            _ => {
+                                                for _ in 0..count.unwrap_or(1) {
+                                                    file_manager.prev_file();
+                                                }
+                                            }
+                                        }
 
-                                    // Restore saved scroll for the new file
-                                    let saved_scroll = file_manager.get_scroll_position();
-                                    tree.viewport
-                                        .scroll_to_clamped(saved_scroll, tree.document_height());
+                                        // Recalculate layout
+                                        tree = recalculate_layout(
+                                            &mut file_manager,
+                                            &terminal,
+                                            &theme,
+                                            file_sidebar_visible,
+                                            no_images,
+                                            inline_images,
+                                        )?;
+
+                                        // Restore saved scroll for the new file
+                                        let saved_scroll = file_manager.get_scroll_position();
+                                        tree.viewport.scroll_to_clamped(
+                                            saved_scroll,
+                                            tree.document_height(),
+                                        );
 
-                                    // Clear search state when switching files
-                                    search_state.deactivate();
-                                    needs_render = true;
+                                        // Clear search state when switching files
+                                        search_state.deactivate();
+                                        needs_render = true;
+                                    }
+                                    Action::Reload => {
+                                        visual = None;
+
+                                        // Save scroll before reload
+                                        let old_scroll = tree.viewport.scroll_y;
+
+                                        if let Err(e) = file_manager.reload_current() {
+                                            eprintln!("Failed to reload file: {}", e);
+                                        } else {
+                                            // Recalculate layout
+                                            tree = recalculate_layout(
+                                                &mut file_manager,
+                                                &terminal,
+                                                &theme,
+                                                file_sidebar_visible,
+                                                no_images,
+                                                inline_images,
+                                            )?;
+
+                                            // Restore scroll position
+                                            tree.viewport.scroll_to_clamped(
+                                                old_scroll,
+                                                tree.document_height(),
+                                            );
+                                            needs_render = true;
+                                        }
+                                    }
+                                    _ => {
+                                        if !show_help
+                                            && handle_action(
+                                                action,
+                                                count,
+                                                &mut tree,
+                                                &mut search_state,
+                                            )
+                                        {
+                                            // Keep the selection cursor on screen
+                                            if let Some((_, cursor)) = visual.as_mut() {
+                                                let top = tree.viewport.scroll_y;
+                                                let bottom = (top + tree.viewport.height)
+                                                    .min(tree.document_height())
+                                                    .saturating_sub(1);
+                                                *cursor = (*cursor).clamp(top, bottom.max(top));
+                                            }
+                                            needs_render = true;
+                                        }
+                                    }
                                 }
                             }
-                        } else if key.code == KeyCode::Char('r') {
-                            // Save scroll before reload
-                            let old_scroll = tree.viewport.scroll_y;
-
-                            if let Err(e) = file_manager.reload_current_with_mermaid() {
-                                eprintln!("Failed to reload file: {}", e);
-                            } else {
-                                // Recalculate layout
-                                (viewport, tree) = recalculate_layout(
-                                    &file_manager,
+                        }
+                    }
+                    Event::Mouse(mouse) => {
+                        if !show_help && mouse_enabled {
+                            let fold_click = file_manager.current_file().and_then(|file| {
+                                tree_click_target(&tree, mouse)
+                                    .map(FoldClick::TreeNode)
+                                    .or_else(|| {
+                                        fold_click_target(&tree, &file.document.blocks, mouse)
+                                            .map(FoldClick::Block)
+                                    })
+                            });
+                            if let Some((lang, code)) = copy_badge_at(&tree, &theme, mouse) {
+                                let what = code_block_description(lang.as_deref(), &code);
+                                status_message = Some(copy_to_clipboard(&code, &what));
+                                status_message_expiry =
+                                    Some(Instant::now() + Duration::from_secs(2));
+                                needs_render = true;
+                            } else if let Some(click) = fold_click {
+                                status_message =
+                                    file_manager.current_file_mut().map(|file| match &click {
+                                        FoldClick::Block(index) => toggle_fold(file, *index),
+                                        FoldClick::TreeNode(node) => toggle_tree_node(file, node),
+                                    });
+                                status_message_expiry =
+                                    Some(Instant::now() + Duration::from_secs(2));
+
+                                let old_scroll = tree.viewport.scroll_y;
+                                tree = recalculate_layout(
+                                    &mut file_manager,
                                     &terminal,
                                     &theme,
                                     file_sidebar_visible,
                                     no_images,
                                     inline_images,
                                 )?;
-
-                                // Restore scroll position
                                 tree.viewport
                                     .scroll_to_clamped(old_scroll, tree.document_height());
+                                if search_state.match_count() > 0 {
+                                    rerun_search(
+                                        &mut search_state,
+                                        &mut tree,
+                                        &mut file_manager,
+                                        &theme,
+                                        no_images,
+                                        inline_images,
+                                    );
+                                }
                                 needs_render = true;
-                            }
-                        } else if show_help && key.code == KeyCode::Esc {
-                            show_help = false;
-                            needs_render = true;
-                        } else if !show_help {
-                            match handle_key(key, &mut tree, &mut search_state) {
-                                Action::Quit => break,
-                                Action::Continue => {
-                                    needs_render = true; // Mark that we need to render
+                            } else if let Some(anchor) = anchor_at(&tree, mouse) {
+                                match follow_anchor(
+                                    &mut tree,
+                                    &mut file_manager,
+                                    &theme,
+                                    &anchor,
+                                    no_images,
+                                    inline_images,
+                                ) {
+                                    Ok(unfolded) => {
+                                        if unfolded && search_state.match_count() > 0 {
+                                            rerun_search(
+                                                &mut search_state,
+                                                &mut tree,
+                                                &mut file_manager,
+                                                &theme,
+                                                no_images,
+                                                inline_images,
+                                            );
+                                        }
+                                    }
+                                    Err(message) => {
+                                        status_message = Some(message);
+                                        status_message_expiry =
+                                            Some(Instant::now() + Duration::from_secs(2));
+                                    }
                                 }
-                            }
-                        }
-                    }
-                    Event::Mouse(mouse) => {
-                        if !show_help && mouse_enabled {
-                            if handle_mouse(mouse, &mut tree) {
+                                needs_render = true;
+                            } else if handle_mouse(mouse, &mut tree) {
                                 needs_render = true;
                             }
                         }
@@ -772,8 +1541,8 @@ fn run_interactive(
                         let old_scroll = tree.viewport.scroll_y;
 
                         // Recalculate layout with new terminal size
-                        (viewport, tree) = recalculate_layout(
-                            &file_manager,
+                        tree = recalculate_layout(
+                            &mut file_manager,
                             &terminal,
                             &theme,
                             file_sidebar_visible,
@@ -801,22 +1570,96 @@ fn run_interactive(
     cleanup_result.and(restore_result)
 }
 
+/// Layout options for a file: large documents are laid out lazily
+fn layout_options(file: &OpenFile, inline_images: bool) -> LayoutOptions {
+    LayoutOptions {
+        inline_images,
+        lazy: file.document.blocks.len() >= LAZY_LAYOUT_MIN_BLOCKS,
+        folds: file.folds.clone(),
+        sources: file.sources.clone(),
+        tree_folds: file.tree_folds.clone(),
+        split_diffs: file.split_diffs.clone(),
+        ..LayoutOptions::default()
+    }
+}
+
+/// Lay out pending blocks of the current file that are now in view
+fn refine_current_layout(
+    tree: &mut LayoutTree,
+    file_manager: &mut FileManager,
+    theme: &Theme,
+    no_images: bool,
+    inline_images: bool,
+) {
+    if let Some(file) = file_manager.current_file_mut() {
+        let options = layout_options(file, inline_images);
+        refine_layout(tree, &file.document, theme, &options, &mut file.layout);
+        if no_images {
+            tree.images.clear();
+        }
+    }
+}
+
+/// Lay out every pending block of the current file, keeping the scroll position
+fn complete_layout(
+    tree: &mut LayoutTree,
+    file_manager: &mut FileManager,
+    theme: &Theme,
+    no_images: bool,
+    inline_images: bool,
+) {
+    if let Some(file) = file_manager.current_file_mut() {
+        let options = LayoutOptions {
+            lazy: false,
+            ..layout_options(file, inline_images)
+        };
+        let scroll_y = tree.viewport.scroll_y;
+        *tree = layout_document_cached(
+            &file.document,
+            theme,
+            tree.viewport,
+            &options,
+            &mut file.layout,
+        );
+        tree.viewport
+            .scroll_to_clamped(scroll_y, tree.document_height());
+        if no_images {
+            tree.images.clear();
+        }
+    }
+}
+
+/// Search again after the layout changed. Matches can be in any block, so
+/// pending blocks are laid out first, as `Action::Search` does.
+fn rerun_search(
+    search_state: &mut SearchState,
+    tree: &mut LayoutTree,
+    file_manager: &mut FileManager,
+    theme: &Theme,
+    no_images: bool,
+    inline_images: bool,
+) {
+    if tree.has_pending() {
+        complete_layout(tree, file_manager, theme, no_images, inline_images);
+    }
+    search_state.execute_search(&tree.root);
+}
+
 /// Helper function to recalculate layout for the current file
-/// Returns (viewport, tree) tuple
 fn recalculate_layout(
-    file_manager: &FileManager,
+    file_manager: &mut FileManager,
     terminal: &render::Terminal,
     theme: &Theme,
     file_sidebar_visible: bool,
     no_images: bool,
     inline_images: bool,
-) -> io::Result<(Viewport, LayoutTree)> {
+) -> io::Result<LayoutTree> {
     let size = terminal.size()?;
+    let show_file_sidebar = file_manager.has_multiple_files() && file_sidebar_visible;
     let current_file = file_manager
-        .current_file()
+        .current_file_mut()
         .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "No files open"))?;
 
-    let show_file_sidebar = file_manager.has_multiple_files() && file_sidebar_visible;
     let file_sidebar_width = if show_file_sidebar {
         (size.width * 20) / 100
     } else {
@@ -830,20 +1673,530 @@ fn recalculate_layout(
     };
     let layout_width = size
         .width
-        .saturating_sub(file_sidebar_width + image_sidebar_width);
+        .saturating_sub(file_sidebar_width + image_sidebar_width + render::SCROLLBAR_WIDTH);
     let viewport = Viewport::new(layout_width, size.height.saturating_sub(1));
 
-    let mut tree = layout_document(&current_file.document, theme, viewport, inline_images);
+    let options = layout_options(current_file, inline_images);
+    let mut tree = layout_document_cached(
+        &current_file.document,
+        theme,
+        viewport,
+        &options,
+        &mut current_file.layout,
+    );
     if no_images {
         tree.images.clear();
     }
 
-    Ok((viewport, tree))
+    Ok(tree)
+}
+
+/// The block `za` acts on: the first foldable block (or fold placeholder)
+/// starting on screen, else the heading of the section at the top of the screen
+fn fold_target(tree: &LayoutTree, blocks: &[Block], min_code_lines: usize) -> Option<usize> {
+    let top = tree.viewport.scroll_y;
+    let bottom = top.saturating_add(tree.viewport.height);
+    let on_screen = tree
+        .root
+        .children
+        .iter()
+        .enumerate()
+        .filter(|(_, node)| node.rect.height > 0 && node.rect.y >= top && node.rect.y < bottom)
+        .find_map(|(index, node)| match &node.element {
+            LayoutElement::Folded { block, .. } => Some(*block),
+            _ if is_foldable(blocks, index, min_code_lines) => Some(index),
+            _ => None,
+        });
+
+    on_screen.or_else(|| {
+        let current = tree.block_at(top)?;
+        (0..=current).rev().find(|&index| {
+            matches!(blocks[index], Block::Heading { .. })
+                && section_range(blocks, index).contains(&current)
+        })
+    })
+}
+
+/// The fold a click toggles: a heading, the first row of a callout or
+/// `<details>`, or a fold placeholder
+fn fold_click_target(tree: &LayoutTree, blocks: &[Block], mouse: MouseEvent) -> Option<usize> {
+    if mouse.kind != MouseEventKind::Down(crossterm::event::MouseButton::Left) {
+        return None;
+    }
+    let doc_y = mouse.row + tree.viewport.scroll_y;
+    let index = tree.block_at(doc_y)?;
+    let node = &tree.root.children[index];
+    match &node.element {
+        LayoutElement::Folded { block, .. } => Some(*block),
+        LayoutElement::Heading { .. } if is_foldable(blocks, index, usize::MAX) => Some(index),
+        LayoutElement::Callout { .. } | LayoutElement::Details if doc_y == node.rect.y => {
+            Some(index)
+        }
+        _ => None,
+    }
+}
+
+/// A node of a JSON/YAML tree, from its hit region
+#[derive(Debug, Clone)]
+struct TreeNodeRef {
+    code: String,
+    path: String,
+    folded: bool,
+    /// Document row of the node's first line
+    y: u16,
+}
+
+/// The innermost JSON/YAML tree node covering document `row` (only the
+/// ones that fold if `foldable`)
+fn tree_node_at(tree: &LayoutTree, row: u16, foldable: bool) -> Option<TreeNodeRef> {
+    tree.hit_regions
+        .iter()
+        .filter(|region| region.rect.y <= row && row < region.rect.y + region.rect.height)
+        .filter_map(|region| match &region.element {
+            HitElement::TreeNode {
+                code,
+                path,
+                foldable: can_fold,
+                folded,
+            } if *can_fold || !foldable => Some((
+                region.rect.height,
+                TreeNodeRef {
+                    code: code.clone(),
+                    path: path.clone(),
+                    folded: *folded,
+                    y: region.rect.y,
+                },
+            )),
+            _ => None,
+        })
+        .min_by_key(|(height, _)| *height)
+        .map(|(_, node)| node)
+}
+
+/// The tree node whose first row was clicked, if it folds
+fn tree_click_target(tree: &LayoutTree, mouse: MouseEvent) -> Option<TreeNodeRef> {
+    if mouse.kind != MouseEventKind::Down(crossterm::event::MouseButton::Left) {
+        return None;
+    }
+    let doc_y = mouse.row + tree.viewport.scroll_y;
+    tree_node_at(tree, doc_y, true).filter(|node| node.y == doc_y)
+}
+
+/// What a click folds or unfolds
+enum FoldClick {
+    Block(usize),
+    TreeNode(TreeNodeRef),
 }
 
-enum Action {
-    Quit,
-    Continue,
+/// Fold or unfold a tree node and describe what happened
+fn toggle_tree_node(file: &mut OpenFile, node: &TreeNodeRef) -> String {
+    file.tree_folds.toggle(&node.code, &node.path);
+    let done = if node.folded { "Unfolded" } else { "Folded" };
+    format!("{} {}", done, node.path)
+}
+
+/// Toggle the fold on block `index` and describe what happened
+fn toggle_fold(file: &mut OpenFile, index: usize) -> String {
+    let folded = file.folds.toggle(index);
+    let what = match &file.document.blocks[index] {
+        Block::Heading { content, .. } => format!(
+            "\"{}\"",
+            content
+                .iter()
+                .map(|i| i.to_plain_text())
+                .collect::<String>()
+        ),
+        Block::Callout { .. } => "callout".to_string(),
+        Block::Details { .. } => "details".to_string(),
+        _ => "code block".to_string(),
+    };
+    format!("{} {}", if folded { "Folded" } else { "Unfolded" }, what)
+}
+
+/// Which match to select when searching through folded content
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MatchTarget {
+    First,
+    Next,
+    Prev,
+}
+
+/// Select a search match, unfolding whatever hides it.
+///
+/// The query runs over the fully unfolded document so matches inside
+/// folds count too; if the chosen match is hidden, the fold around it is
+/// opened and the layout rebuilt before the match is selected. Returns
+/// whether anything was unfolded.
+fn reveal_search_match(
+    tree: &mut LayoutTree,
+    file_manager: &mut FileManager,
+    theme: &Theme,
+    search_state: &mut SearchState,
+    target: MatchTarget,
+    no_images: bool,
+    inline_images: bool,
+) -> bool {
+    let Some(file) = file_manager.current_file_mut() else {
+        return false;
+    };
+    // Where a match sits: top-level block, row within the block, column
+    let locate = |tree: &LayoutTree, m: &lumen::search::SearchMatch| {
+        let index = tree.block_at(m.y)?;
+        Some((index, m.y - tree.root.children[index].rect.y, m.x))
+    };
+    let current = search_state.current_match().and_then(|m| locate(tree, m));
+
+    // Everything the screen shows except folds, so rows line up with it
+    let unfolded_options = LayoutOptions {
+        folds: Folds::default(),
+        lazy: false,
+        ..layout_options(file, inline_images)
+    };
+    let full = layout_document_cached(
+        &file.document,
+        theme,
+        tree.viewport,
+        &unfolded_options,
+        &mut file.layout,
+    );
+    let mut everywhere = search_state.clone();
+    everywhere.execute_search(&full.root);
+    let keys: Vec<_> = everywhere
+        .matches
+        .iter()
+        .map(|m| locate(&full, m))
+        .collect();
+    if keys.is_empty() {
+        return false;
+    }
+
+    let position = current.and_then(|current| keys.iter().position(|key| *key == Some(current)));
+    let count = keys.len();
+    let chosen = match (target, position) {
+        (MatchTarget::Next, Some(i)) => (i + 1) % count,
+        (MatchTarget::Prev, Some(i)) => (i + count - 1) % count,
+        (MatchTarget::Prev, None) => count - 1,
+        _ => 0,
+    };
+    let Some((block, row, x)) = keys[chosen] else {
+        return false;
+    };
+
+    let mut unfolded = false;
+    while let Some(fold) = file.folds.hidden_by(&file.document.blocks, block) {
+        file.folds.unfold(fold);
+        unfolded = true;
+    }
+    // Every block is searched, so every block has to be laid out
+    if unfolded || tree.has_pending() {
+        let options = LayoutOptions {
+            lazy: false,
+            ..layout_options(file, inline_images)
+        };
+        let scroll_y = tree.viewport.scroll_y;
+        *tree = layout_document_cached(
+            &file.document,
+            theme,
+            tree.viewport,
+            &options,
+            &mut file.layout,
+        );
+        tree.viewport
+            .scroll_to_clamped(scroll_y, tree.document_height());
+        if no_images {
+            tree.images.clear();
+        }
+    }
+
+    search_state.execute_search(&tree.root);
+    let y = tree.root.children[block].rect.y + row;
+    if let Some(index) = search_state
+        .matches
+        .iter()
+        .position(|m| m.y == y && m.x == x)
+    {
+        search_state.current_index = Some(index);
+    }
+    unfolded
+}
+
+/// The code block whose copy badge was clicked, as `(lang, code)`
+fn copy_badge_at(
+    tree: &LayoutTree,
+    theme: &Theme,
+    mouse: MouseEvent,
+) -> Option<(Option<String>, String)> {
+    if mouse.kind != MouseEventKind::Down(crossterm::event::MouseButton::Left) {
+        return None;
+    }
+    let doc_y = mouse.row + tree.viewport.scroll_y;
+    // CSV blocks drawn as tables have no badge
+    let tables = element_rows(tree, |element| {
+        matches!(element, LayoutElement::Table { .. })
+    });
+    tree.hit_regions
+        .iter()
+        .find_map(|region| match &region.element {
+            HitElement::CodeBlock { lang, code }
+                if region.rect.y == doc_y && !tables.contains(&doc_y) =>
+            {
+                let badge_lang = lang
+                    .as_deref()
+                    .filter(|_| theme.blocks.code_block.show_language_badge);
+                copy_badge_rect(region.rect, badge_lang)
+                    .contains(mouse.column, doc_y)
+                    .then(|| (lang.clone(), code.clone()))
+            }
+            _ => None,
+        })
+}
+
+/// Text to copy for `y` and a short description of it for the status bar.
+///
+/// A visual selection copies the Markdown source of the blocks it touches;
+/// otherwise `y` copies the first code block on screen and `3y` the third
+/// code block in the document.
+fn yank_text(
+    tree: &LayoutTree,
+    file_manager: &FileManager,
+    selection: Option<(u16, u16)>,
+    count: Option<usize>,
+) -> Result<(String, String), String> {
+    if let Some((first, last)) = selection {
+        let file = file_manager.current_file().ok_or("No file open")?;
+        let pieces: Vec<&str> = tree
+            .root
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.rect.y <= last && node.rect.y + node.rect.height > first)
+            .filter_map(|(index, _)| file.block_source(index))
+            .map(|source| source.trim_end())
+            .collect();
+        if pieces.is_empty() {
+            return Err("Nothing to copy in the selection".to_string());
+        }
+        let text = pieces.join("\n\n");
+        let what = format!("{} lines", text.lines().count());
+        return Ok((text, what));
+    }
+
+    let mut code_blocks = tree
+        .hit_regions
+        .iter()
+        .filter_map(|region| match &region.element {
+            HitElement::CodeBlock { lang, code } => Some((region.rect, lang, code)),
+            _ => None,
+        });
+    let found = match count {
+        Some(n) => code_blocks.nth(n.saturating_sub(1)),
+        None => {
+            let top = tree.viewport.scroll_y;
+            let bottom = top + tree.viewport.height;
+            code_blocks.find(|(rect, _, _)| rect.y < bottom && rect.y + rect.height > top)
+        }
+    };
+    match found {
+        Some((_, lang, code)) => Ok((code.clone(), code_block_description(lang.as_deref(), code))),
+        None if count.is_some() => Err(format!("No code block {}", count.unwrap_or(0))),
+        None => Err("No code block on screen".to_string()),
+    }
+}
+
+/// The top rows of nodes whose element matches, e.g. every drawn diagram
+fn element_rows(tree: &LayoutTree, matches: fn(&LayoutElement) -> bool) -> Vec<u16> {
+    fn collect(node: &LayoutNode, matches: fn(&LayoutElement) -> bool, rows: &mut Vec<u16>) {
+        if matches(&node.element) {
+            rows.push(node.rect.y);
+        }
+        for child in &node.children {
+            collect(child, matches, rows);
+        }
+    }
+    let mut rows = Vec::new();
+    collect(&tree.root, matches, &mut rows);
+    rows
+}
+
+/// The source of the diagram `s` flips: the first code block on screen
+/// that is drawn as a diagram (or a CSV table), was flipped, or can be
+/// drawn on request (a JSON tree). Also says whether its renderer in
+/// `options` draws it by default, and whether it can draw this source.
+fn diagram_target(tree: &LayoutTree, options: &LayoutOptions) -> Option<(String, bool, bool)> {
+    let rows = element_rows(tree, |element| {
+        matches!(
+            element,
+            LayoutElement::Diagram { .. } | LayoutElement::Table { .. }
+        )
+    });
+
+    let top = tree.viewport.scroll_y;
+    let bottom = top + tree.viewport.height;
+    tree.hit_regions
+        .iter()
+        .find_map(|region| match &region.element {
+            HitElement::CodeBlock { lang, code }
+                if region.rect.y < bottom && region.rect.y + region.rect.height > top =>
+            {
+                let renderer = FenceInfo::parse(lang.as_deref().unwrap_or(""))
+                    .lang
+                    .and_then(|lang| options.renderers.get(&lang));
+                let drawn_by_default =
+                    renderer.is_none_or(|renderer| renderer.rendered_by_default());
+                (rows.contains(&region.rect.y)
+                    || options.sources.contains(code)
+                    || !drawn_by_default)
+                    .then(|| {
+                        let block = BlockContext::new(region.rect.width);
+                        let drawable = renderer
+                            .is_some_and(|renderer| renderer.render(code, &block).is_some());
+                        (code.clone(), drawn_by_default, drawable)
+                    })
+            }
+            _ => None,
+        })
+}
+
+/// The diff `S` flips: the first diff block on screen, with the width of
+/// its content
+fn diff_target(tree: &LayoutTree) -> Option<(String, u16)> {
+    let top = tree.viewport.scroll_y;
+    let bottom = top + tree.viewport.height;
+    tree.hit_regions
+        .iter()
+        .find_map(|region| match &region.element {
+            HitElement::CodeBlock {
+                lang: Some(lang),
+                code,
+            } if diff::is_diff(lang)
+                && region.rect.y < bottom
+                && region.rect.y + region.rect.height > top =>
+            {
+                Some((code.clone(), region.rect.width.saturating_sub(2)))
+            }
+            _ => None,
+        })
+}
+
+/// e.g. "rust code block (12 lines)"
+fn code_block_description(lang: Option<&str>, code: &str) -> String {
+    let lines = code.lines().count();
+    let plural = if lines == 1 { "" } else { "s" };
+    match lang {
+        Some(lang) => format!("{} code block ({} line{})", lang, lines, plural),
+        None => format!("code block ({} line{})", lines, plural),
+    }
+}
+
+/// Copy text and describe the outcome for the status bar
+fn copy_to_clipboard(text: &str, what: &str) -> String {
+    match lumen::clipboard::copy(text) {
+        Ok(method) => format!("Copied {} via {}", what, method),
+        Err(e) => format!("Copy failed: {}", e),
+    }
+}
+
+/// The `#anchor` of an in-document link that was clicked
+fn anchor_at(tree: &LayoutTree, mouse: MouseEvent) -> Option<String> {
+    if mouse.kind != MouseEventKind::Down(crossterm::event::MouseButton::Left) {
+        return None;
+    }
+    let doc_y = mouse.row + tree.viewport.scroll_y;
+    tree.hit_regions
+        .iter()
+        .find_map(|region| match &region.element {
+            HitElement::Link { url, .. } if region.rect.contains(mouse.column, doc_y) => {
+                url.strip_prefix('#').map(str::to_string)
+            }
+            _ => None,
+        })
+}
+
+/// Jump to the heading an in-document `#anchor` link points at, unfolding
+/// whatever hides it. Returns whether folds changed, or a status message if
+/// no heading has that id.
+fn follow_anchor(
+    tree: &mut LayoutTree,
+    file_manager: &mut FileManager,
+    theme: &Theme,
+    anchor: &str,
+    no_images: bool,
+    inline_images: bool,
+) -> Result<bool, String> {
+    let Some(file) = file_manager.current_file_mut() else {
+        return Ok(false);
+    };
+    let anchor = decode_fragment(anchor);
+
+    // The top-level block with the heading: the heading itself, or the
+    // list, quote, callout or details it is in
+    let Some(index) = file
+        .document
+        .blocks
+        .iter()
+        .position(|block| has_heading(block, &anchor))
+    else {
+        return Err(format!("No heading with id #{}", anchor));
+    };
+    let nested = !matches!(file.document.blocks[index], Block::Heading { .. });
+
+    let mut unfolded = false;
+    while let Some(fold) = file.folds.hidden_by(&file.document.blocks, index) {
+        file.folds.unfold(fold);
+        unfolded = true;
+    }
+    if nested && file.folds.unfold(index) {
+        unfolded = true;
+    }
+    let options = layout_options(file, inline_images);
+    if unfolded {
+        *tree = layout_document_cached(
+            &file.document,
+            theme,
+            tree.viewport,
+            &options,
+            &mut file.layout,
+        );
+    }
+    let y = tree.root.children[index].rect.y;
+    tree.viewport.scroll_to_clamped(y, tree.document_height());
+
+    if nested {
+        // Lay the block out if it was pending, then go to the heading in it
+        refine_layout(tree, &file.document, theme, &options, &mut file.layout);
+        let heading = tree
+            .hit_regions
+            .iter()
+            .find_map(|region| match &region.element {
+                HitElement::Heading { id, .. } if *id == anchor => Some(region.rect.y),
+                _ => None,
+            });
+        if let Some(y) = heading {
+            tree.viewport.scroll_to_clamped(y, tree.document_height());
+        }
+    }
+    if no_images {
+        tree.images.clear();
+    }
+    Ok(unfolded)
+}
+
+/// Whether a block is the heading with this id or contains it
+fn has_heading(block: &Block, anchor: &str) -> bool {
+    match block {
+        Block::Heading { id, .. } => id == anchor,
+        Block::BlockQuote { blocks }
+        | Block::Callout {
+            content: blocks, ..
+        }
+        | Block::Details {
+            content: blocks, ..
+        } => blocks.iter().any(|block| has_heading(block, anchor)),
+        Block::List { items, .. } => items
+            .iter()
+            .any(|item| item.content.iter().any(|block| has_heading(block, anchor))),
+        _ => false,
+    }
 }
 
 fn handle_mouse(mouse: MouseEvent, tree: &mut LayoutTree) -> bool {
@@ -866,27 +2219,11 @@ fn handle_mouse(mouse: MouseEvent, tree: &mut LayoutTree) -> bool {
             // Adjust for scroll position (click_y is viewport-relative)
             let doc_y = click_y + tree.viewport.scroll_y;
 
-            // Check if we clicked on any link
+            // Check if we clicked on any link (anchor links are followed
+            // by `follow_anchor`)
             for region in &tree.hit_regions {
-                if let lumen::layout::HitElement::Link { url, .. } = &region.element {
+                if let lumen::layout::HitElement::Link { .. } = &region.element {
                     if region.rect.contains(click_x, doc_y) {
-                        // Handle internal anchor links
-                        if url.starts_with('#') {
-                            let anchor = &url[1..]; // Remove the '#'
-
-                            // Find the heading with this anchor
-                            for heading_region in &tree.hit_regions {
-                                if let lumen::layout::HitElement::Heading { id, .. } =
-                                    &heading_region.element
-                                {
-                                    if id == anchor {
-                                        // Jump to this heading
-                                        tree.viewport.scroll_y = heading_region.rect.y;
-                                        return true;
-                                    }
-                                }
-                            }
-                        }
                         // For external links, we could open them in a browser,
                         // but for now we'll just ignore them
                         return false;
@@ -899,78 +2236,85 @@ fn handle_mouse(mouse: MouseEvent, tree: &mut LayoutTree) -> bool {
     }
 }
 
-fn handle_key(key: KeyEvent, tree: &mut LayoutTree, search_state: &mut SearchState) -> Action {
+/// Handle scrolling and jump actions; returns whether the view changed.
+///
+/// `count` is the numeric prefix typed before the key (`10j`, `3n`, `5G`).
+fn handle_action(
+    action: Action,
+    count: Option<usize>,
+    tree: &mut LayoutTree,
+    search_state: &mut SearchState,
+) -> bool {
     let doc_height = tree.document_height();
-
-    match key.code {
-        KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
-        KeyCode::Char('j') | KeyCode::Down => {
-            tree.viewport.scroll_by_clamped(1, doc_height);
-            Action::Continue
-        }
-        KeyCode::Char('k') | KeyCode::Up => {
-            tree.viewport.scroll_by_clamped(-1, doc_height);
-            Action::Continue
-        }
-        KeyCode::Char('d') => {
-            tree.viewport
-                .scroll_by_clamped(tree.viewport.height as i16 / 2, doc_height);
-            Action::Continue
-        }
-        KeyCode::Char('u') => {
-            tree.viewport
-                .scroll_by_clamped(-(tree.viewport.height as i16 / 2), doc_height);
-            Action::Continue
-        }
-        KeyCode::PageDown | KeyCode::Char(' ') => {
-            tree.viewport
-                .scroll_by_clamped(tree.viewport.height as i16, doc_height);
-            Action::Continue
-        }
-        KeyCode::PageUp => {
-            tree.viewport
-                .scroll_by_clamped(-(tree.viewport.height as i16), doc_height);
-            Action::Continue
-        }
-        KeyCode::Home | KeyCode::Char('g') => {
-            tree.viewport.scroll_to(0);
-            Action::Continue
-        }
-        KeyCode::End | KeyCode::Char('G') => {
-            tree.viewport
-                .scroll_to_clamped(tree.document_height(), doc_height);
-            Action::Continue
-        }
-        KeyCode::Char('n') => {
+    let times = count.unwrap_or(1);
+    let page = tree.viewport.height as i64;
+
+    match action {
+        Action::ScrollDown => scroll_lines(tree, times as i64),
+        Action::ScrollUp => scroll_lines(tree, -(times as i64)),
+        Action::HalfPageDown => scroll_lines(tree, times as i64 * (page / 2)),
+        Action::HalfPageUp => scroll_lines(tree, -(times as i64 * (page / 2))),
+        Action::PageDown => scroll_lines(tree, times as i64 * page),
+        Action::PageUp => scroll_lines(tree, -(times as i64 * page)),
+        Action::Top | Action::Bottom => {
+            // With a count, go to that line (1-based) instead
+            let target = match (action, count) {
+                (_, Some(line)) => line.saturating_sub(1).min(u16::MAX as usize) as u16,
+                (Action::Top, None) => 0,
+                _ => doc_height,
+            };
+            tree.viewport.scroll_to_clamped(target, doc_height);
+        }
+        Action::GotoPercent => {
+            let Some(percent) = count else {
+                return false;
+            };
+            let max_scroll = doc_height.saturating_sub(tree.viewport.height) as usize;
+            let target = max_scroll * percent.min(100) / 100;
+            tree.viewport.scroll_to_clamped(target as u16, doc_height);
+        }
+        Action::NextMatch => {
             // If we have search results, jump to next match
             if !search_state.matches.is_empty() {
-                search_state.next_match();
+                for _ in 0..times {
+                    search_state.next_match();
+                }
                 if let Some(m) = search_state.current_match() {
                     scroll_to_search_match(tree, m.y, doc_height);
                 }
             } else {
                 // Otherwise jump to next heading
-                jump_to_next_heading(tree, true);
+                for _ in 0..times {
+                    jump_to_next_heading(tree, true);
+                }
             }
-            Action::Continue
         }
-        KeyCode::Char('N') => {
+        Action::PrevMatch => {
             // Jump to previous search match (Shift-N)
             if !search_state.matches.is_empty() {
-                search_state.prev_match();
+                for _ in 0..times {
+                    search_state.prev_match();
+                }
                 if let Some(m) = search_state.current_match() {
                     scroll_to_search_match(tree, m.y, doc_height);
                 }
             }
-            Action::Continue
         }
-        KeyCode::Char('p') => {
-            // Jump to previous heading
-            jump_to_next_heading(tree, false);
-            Action::Continue
+        Action::NextHeading | Action::PrevHeading => {
+            for _ in 0..times {
+                jump_to_next_heading(tree, action == Action::NextHeading);
+            }
         }
-        _ => Action::Continue,
+        _ => return false,
     }
+    true
+}
+
+/// Scroll by a number of lines (negative scrolls up), clamped to the document
+fn scroll_lines(tree: &mut LayoutTree, delta: i64) {
+    let target = (tree.viewport.scroll_y as i64 + delta).clamp(0, u16::MAX as i64) as u16;
+    let doc_height = tree.document_height();
+    tree.viewport.scroll_to_clamped(target, doc_height);
 }
 
 /// Scroll viewport to center a search match with some padding from the top
@@ -1,44 +1,110 @@
 //! Built-in mermaid flowchart renderer — renders mermaid code blocks as ASCII/Unicode art.
 //!
-//! Supports `graph` and `flowchart` diagram types with LR, RL, TD/TB, BT directions.
+//! Supports `graph` and `flowchart` diagram types with LR, RL, TD/TB, BT directions,
+//! `sequenceDiagram`, and `classDiagram`, `stateDiagram` and `erDiagram`, which
+//! share the flowchart layout. `pie`, `gantt` and `timeline` are drawn as text
+//! charts fitted to the width they're laid out at.
 //! Unsupported diagram types fall back to raw code display.
 //!
 //! Inspired by [mermaid-ascii](https://github.com/AlexanderGrooff/mermaid-ascii)
 //! by Alexander Grooff — the original terminal mermaid renderer.
 
-use crate::ir::{Block, Document};
-use std::collections::HashMap;
+use crate::codeblock::{BlockContext, CodeBlockRenderer, DiagramLine, DiagramRole, Rendered};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
 use unicode_width::UnicodeWidthStr;
 
 // ── Data types ──────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum Direction {
+pub(crate) enum Direction {
     LR,
     RL,
     TD,
     BT,
 }
 
+/// Node shape, from the brackets around its label
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum NodeShape {
+    /// `A[text]`
+    Rect,
+    /// `A(text)`
+    Round,
+    /// `A([text])`
+    Stadium,
+    /// `A((text))`
+    Circle,
+    /// `A{text}` (and hexagons, `A{{text}}`)
+    Diamond,
+    /// `A[[text]]`
+    Subroutine,
+    /// `A[/text/]`
+    Parallelogram,
+    /// `A[\text\]`
+    ParallelogramAlt,
+    /// `A>text]`
+    Asymmetric,
+}
+
 #[derive(Debug, Clone)]
-struct Node {
-    id: String,
-    label: String,
+pub(crate) struct Node {
+    pub(crate) id: String,
+    pub(crate) label: String,
+    pub(crate) shape: NodeShape,
+    /// Innermost subgraph the node is in
+    pub(crate) subgraph: Option<usize>,
+}
+
+/// Line style of an edge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum EdgeStyle {
+    /// `-->`, `---`
+    Solid,
+    /// `-.->`, `-.-`
+    Dotted,
+    /// `==>`, `===`
+    Thick,
+    /// `~~~`: affects the layout but isn't drawn
+    Invisible,
+}
+
+impl EdgeStyle {
+    /// Horizontal and vertical line characters
+    fn strokes(self) -> (char, char) {
+        match self {
+            EdgeStyle::Solid | EdgeStyle::Invisible => ('─', '│'),
+            EdgeStyle::Dotted => ('┄', '┆'),
+            EdgeStyle::Thick => ('━', '┃'),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
-struct Edge {
-    from: String,
-    to: String,
-    #[allow(dead_code)]
-    label: Option<String>,
+pub(crate) struct Edge {
+    pub(crate) from: String,
+    pub(crate) to: String,
+    pub(crate) label: Option<String>,
+    pub(crate) style: EdgeStyle,
+    /// Ends in an arrowhead (`-->`) rather than open (`---`)
+    pub(crate) head: bool,
+}
+
+/// `subgraph id [Title] ... end`
+#[derive(Debug, Clone)]
+pub(crate) struct Subgraph {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) parent: Option<usize>,
 }
 
 #[derive(Debug)]
-struct FlowGraph {
-    direction: Direction,
-    nodes: Vec<Node>,
-    edges: Vec<Edge>,
+pub(crate) struct FlowGraph {
+    pub(crate) direction: Direction,
+    pub(crate) nodes: Vec<Node>,
+    pub(crate) edges: Vec<Edge>,
+    pub(crate) subgraphs: Vec<Subgraph>,
 }
 
 // ── Public API ──────────────────────────────────────────────────────────────
@@ -46,6 +112,58 @@ struct FlowGraph {
 /// Render a mermaid code block to ASCII art. Returns `None` if the diagram
 /// type is unsupported or the syntax can't be parsed.
 pub fn render_mermaid(input: &str) -> Option<String> {
+    render_mermaid_width(input, DEFAULT_CHART_WIDTH as u16)
+}
+
+/// Like [`render_mermaid`], fitting charts (pie, gantt, timeline) to
+/// `width` columns; graphs and sequence diagrams take the room they need
+pub fn render_mermaid_width(input: &str, width: u16) -> Option<String> {
+    let lines = render_mermaid_lines(input, width)?;
+    Some(
+        lines
+            .iter()
+            .map(DiagramLine::text)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+/// Like [`render_mermaid_width`], with every part of the drawing tagged
+/// with the [`DiagramRole`] it plays; charts are all
+/// [`DiagramRole::Text`]
+pub fn render_mermaid_lines(input: &str, width: u16) -> Option<Vec<DiagramLine>> {
+    let width = usize::from(width);
+    let chart = |text: String| text.lines().map(DiagramLine::plain).collect();
+    let header = input
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("%%"))?;
+    let keyword = header
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .next()?
+        .to_lowercase();
+    let boxes = match keyword.as_str() {
+        "sequencediagram" => {
+            return parse_sequence(input).map(|diagram| render_sequence(&diagram));
+        }
+        "classdiagram" | "classdiagram-v2" => {
+            parse_class_diagram(input).map(|diagram| (diagram, SQUARE_CORNERS))
+        }
+        "statediagram" | "statediagram-v2" => {
+            parse_state_diagram(input).map(|diagram| (diagram, ROUND_CORNERS))
+        }
+        "erdiagram" => parse_er_diagram(input).map(|diagram| (diagram, SQUARE_CORNERS)),
+        "pie" => return parse_pie(input).map(|pie| chart(render_pie(&pie, width))),
+        "gantt" => return parse_gantt(input).map(|gantt| chart(render_gantt(&gantt, width))),
+        "timeline" => {
+            return parse_timeline(input).map(|timeline| chart(render_timeline(&timeline, width)))
+        }
+        _ => None,
+    };
+    if let Some((diagram, group_corners)) = boxes {
+        return (!diagram.boxes.is_empty()).then(|| render_boxes(&diagram, group_corners));
+    }
+
     let graph = parse_flowchart(input)?;
     if graph.nodes.is_empty() {
         return None;
@@ -53,38 +171,14 @@ pub fn render_mermaid(input: &str) -> Option<String> {
     Some(render_graph(&graph))
 }
 
-/// Walk a parsed document and replace mermaid code blocks with rendered ASCII.
-pub fn transform_mermaid_blocks(document: &mut Document) {
-    for block in &mut document.blocks {
-        transform_block(block);
-    }
-}
+/// Renders ` ```mermaid ` code blocks; registered by default in
+/// [`CodeBlockRenderers`](crate::codeblock::CodeBlockRenderers)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MermaidRenderer;
 
-fn transform_block(block: &mut Block) {
-    match block {
-        Block::CodeBlock { lang, code } => {
-            if lang.as_deref() == Some("mermaid") {
-                if let Some(rendered) = render_mermaid(code) {
-                    *code = rendered;
-                }
-            }
-        }
-        Block::BlockQuote { blocks }
-        | Block::Callout {
-            content: blocks, ..
-        } => {
-            for b in blocks {
-                transform_block(b);
-            }
-        }
-        Block::List { items, .. } => {
-            for item in items {
-                for b in &mut item.content {
-                    transform_block(b);
-                }
-            }
-        }
-        _ => {}
+impl CodeBlockRenderer for MermaidRenderer {
+    fn render(&self, code: &str, block: &BlockContext) -> Option<Rendered> {
+        render_mermaid_lines(code, block.width).map(Rendered::Diagram)
     }
 }
 
@@ -95,6 +189,8 @@ fn parse_flowchart(input: &str) -> Option<FlowGraph> {
     let mut nodes: Vec<Node> = Vec::new();
     let mut edges: Vec<Edge> = Vec::new();
     let mut node_map: HashMap<String, usize> = HashMap::new(); // id → index in nodes
+    let mut subgraphs: Vec<Subgraph> = Vec::new();
+    let mut open_subgraphs: Vec<usize> = Vec::new();
 
     // Flatten semicolons into line breaks, then process line by line
     let input = input.replace(';', "\n");
@@ -106,10 +202,25 @@ fn parse_flowchart(input: &str) -> Option<FlowGraph> {
             continue;
         }
 
-        // Skip subgraph/end/style/classDef for now
         let lower = line.to_lowercase();
-        if lower.starts_with("subgraph")
-            || lower == "end"
+        if lower == "subgraph" || lower.starts_with("subgraph ") {
+            let (id, title) = parse_subgraph_header(line["subgraph".len()..].trim());
+            let id = id.unwrap_or_else(|| format!("subgraph{}", subgraphs.len() + 1));
+            subgraphs.push(Subgraph {
+                id,
+                title,
+                parent: open_subgraphs.last().copied(),
+            });
+            open_subgraphs.push(subgraphs.len() - 1);
+            continue;
+        }
+        if lower == "end" {
+            open_subgraphs.pop();
+            continue;
+        }
+
+        // Skip styling and per-subgraph directions
+        if lower.starts_with("direction ")
             || lower.starts_with("style ")
             || lower.starts_with("classdef ")
             || lower.starts_with("class ")
@@ -124,13 +235,7 @@ fn parse_flowchart(input: &str) -> Option<FlowGraph> {
             if lower.starts_with("graph") || lower.starts_with("flowchart") {
                 let parts: Vec<&str> = line.split_whitespace().collect();
                 if parts.len() >= 2 {
-                    direction = match parts[1].to_uppercase().as_str() {
-                        "LR" => Direction::LR,
-                        "RL" => Direction::RL,
-                        "TD" | "TB" => Direction::TD,
-                        "BT" => Direction::BT,
-                        _ => Direction::TD,
-                    };
+                    direction = parse_direction(parts[1]).unwrap_or(Direction::TD);
                 }
                 found_header = true;
                 continue;
@@ -142,26 +247,77 @@ fn parse_flowchart(input: &str) -> Option<FlowGraph> {
         }
 
         // Parse edges and node definitions from this line
-        parse_edge_line(line, &mut nodes, &mut edges, &mut node_map);
+        let subgraph = open_subgraphs.last().copied();
+        parse_edge_line(line, &mut nodes, &mut edges, &mut node_map, subgraph);
     }
 
     if !found_header && nodes.is_empty() {
         return None;
     }
 
+    connect_subgraph_edges(&mut nodes, &mut edges, &subgraphs);
+
     Some(FlowGraph {
         direction,
         nodes,
         edges,
+        subgraphs,
     })
 }
 
+/// The id and title after `subgraph`: `id [Title]`, `id["Title"]`, `"Title"`
+/// or a bare title that is also the id
+fn parse_subgraph_header(rest: &str) -> (Option<String>, String) {
+    let unquote = |s: &str| s.trim().trim_matches('"').trim().to_string();
+    if let (Some(open), true) = (rest.find('['), rest.ends_with(']')) {
+        let id = rest[..open].trim();
+        let title = unquote(&rest[open + 1..rest.len() - 1]);
+        return ((!id.is_empty()).then(|| id.to_string()), title);
+    }
+    let title = unquote(rest);
+    ((!title.is_empty()).then(|| title.clone()), title)
+}
+
+/// Edges to or from a subgraph id (`A --> backend`) connect to its first
+/// node instead, as there's no drawing an arrow to a whole box
+fn connect_subgraph_edges(nodes: &mut Vec<Node>, edges: &mut [Edge], subgraphs: &[Subgraph]) {
+    let inside = |node: &Node, subgraph: usize| {
+        let mut current = node.subgraph;
+        while let Some(s) = current {
+            if s == subgraph {
+                return true;
+            }
+            current = subgraphs[s].parent;
+        }
+        false
+    };
+    for (s, subgraph) in subgraphs.iter().enumerate() {
+        let stands_for_subgraph = |node: &Node| node.id == subgraph.id && !inside(node, s);
+        if !nodes.iter().any(stands_for_subgraph) {
+            continue;
+        }
+        let Some(first) = nodes.iter().find(|node| inside(node, s)) else {
+            continue;
+        };
+        let first = first.id.clone();
+        for edge in edges.iter_mut() {
+            for end in [&mut edge.from, &mut edge.to] {
+                if *end == subgraph.id {
+                    *end = first.clone();
+                }
+            }
+        }
+        nodes.retain(|node| !stands_for_subgraph(node));
+    }
+}
+
 /// Parse a line that may contain chained edge definitions like `A --> B --> C`
 fn parse_edge_line(
     line: &str,
     nodes: &mut Vec<Node>,
     edges: &mut Vec<Edge>,
     node_map: &mut HashMap<String, usize>,
+    subgraph: Option<usize>,
 ) {
     // Tokenize into alternating node-refs and arrows
     let tokens = tokenize(line);
@@ -171,23 +327,26 @@ fn parse_edge_line(
 
     // Process tokens: NodeRef Arrow NodeRef Arrow NodeRef ...
     let mut prev_node_id: Option<String> = None;
-    let mut pending_label: Option<String> = None;
+    let mut pending_arrow: Option<&Arrow> = None;
 
     for token in &tokens {
         match token {
-            Token::NodeRef { id, label } => {
-                ensure_node(nodes, node_map, id, label.as_deref());
+            Token::NodeRef { id, label, shape } => {
+                ensure_node(nodes, node_map, id, label.as_deref(), *shape, subgraph);
                 if let Some(from) = prev_node_id.take() {
+                    let arrow = pending_arrow.take();
                     edges.push(Edge {
                         from,
                         to: id.clone(),
-                        label: pending_label.take(),
+                        label: arrow.and_then(|a| a.label.clone()),
+                        style: arrow.map_or(EdgeStyle::Solid, |a| a.style),
+                        head: arrow.is_none_or(|a| a.head),
                     });
                 }
                 prev_node_id = Some(id.clone());
             }
-            Token::Arrow { label } => {
-                pending_label = label.clone();
+            Token::Arrow(arrow) => {
+                pending_arrow = Some(arrow);
             }
         }
     }
@@ -198,12 +357,20 @@ fn ensure_node(
     node_map: &mut HashMap<String, usize>,
     id: &str,
     label: Option<&str>,
+    shape: NodeShape,
+    subgraph: Option<usize>,
 ) {
     if let Some(&idx) = node_map.get(id) {
-        // Update label if a better one is provided
+        // Nodes first mentioned outside any subgraph join the one that
+        // lists them
+        if nodes[idx].subgraph.is_none() {
+            nodes[idx].subgraph = subgraph;
+        }
+        // Update label and shape if a better one is provided
         if let Some(lbl) = label {
             if nodes[idx].label == nodes[idx].id {
                 nodes[idx].label = lbl.to_string();
+                nodes[idx].shape = shape;
             }
         }
     } else {
@@ -212,6 +379,8 @@ fn ensure_node(
         nodes.push(Node {
             id: id.to_string(),
             label,
+            shape,
+            subgraph,
         });
     }
 }
@@ -219,15 +388,21 @@ fn ensure_node(
 // ── Tokenizer ───────────────────────────────────────────────────────────────
 
 #[derive(Debug)]
-enum Token {
-    NodeRef { id: String, label: Option<String> },
-    Arrow { label: Option<String> },
+struct Arrow {
+    label: Option<String>,
+    style: EdgeStyle,
+    head: bool,
 }
 
-/// Arrow patterns to search for, ordered longest-first to avoid partial matches
-const ARROW_PATTERNS: &[&str] = &[
-    "-.->", "==>", "-->", "-.-", "===", "---", "~~~", "-->", "->",
-];
+#[derive(Debug)]
+enum Token {
+    NodeRef {
+        id: String,
+        label: Option<String>,
+        shape: NodeShape,
+    },
+    Arrow(Arrow),
+}
 
 fn tokenize(line: &str) -> Vec<Token> {
     let mut tokens = Vec::new();
@@ -240,63 +415,107 @@ fn tokenize(line: &str) -> Vec<Token> {
         }
 
         // Try to match an arrow
-        if let Some((arrow_label, rest)) = try_match_arrow(remaining) {
-            tokens.push(Token::Arrow { label: arrow_label });
+        if let Some((arrow, rest)) = try_match_arrow(remaining) {
+            tokens.push(Token::Arrow(arrow));
             remaining = rest;
             continue;
         }
 
         // Try to match a node reference
-        if let Some((id, label, rest)) = try_match_node_ref(remaining) {
-            tokens.push(Token::NodeRef { id, label });
+        if let Some((id, label, shape, rest)) = try_match_node_ref(remaining) {
+            tokens.push(Token::NodeRef { id, label, shape });
             remaining = rest;
             continue;
         }
 
         // Skip unknown character
-        if let Some(rest) = remaining.get(1..) {
-            remaining = rest;
-        } else {
-            break;
-        }
+        let mut chars = remaining.chars();
+        chars.next();
+        remaining = chars.as_str();
     }
 
     tokens
 }
 
-/// Try to match an arrow at the start of `s`. Returns (optional_label, remaining).
-fn try_match_arrow(s: &str) -> Option<(Option<String>, &str)> {
-    for &pattern in ARROW_PATTERNS {
-        if s.starts_with(pattern) {
-            let rest = &s[pattern.len()..];
-            // Check for pipe-delimited label: -->|label|
-            if let Some(rest) = rest.strip_prefix('|') {
-                if let Some(end) = rest.find('|') {
-                    let label = rest[..end].trim().to_string();
-                    let label = if label.is_empty() { None } else { Some(label) };
-                    return Some((label, &rest[end + 1..]));
-                }
-            }
-            return Some((None, rest));
-        }
+/// Try to match an arrow at the start of `s`: a line of any length (`-->`,
+/// `--->`, `-.->`, `==>`, `---`, `~~~`), optionally with a `|label|` after
+/// it or a label inside it (`-- yes -->`, `== yes ==>`, `-. yes .->`).
+fn try_match_arrow(s: &str) -> Option<(Arrow, &str)> {
+    if let Some((style, head, rest)) = match_line(s) {
+        // Pipe-delimited label: -->|label|
+        let (label, rest) = match rest.trim_start().strip_prefix('|') {
+            Some(inner) => match inner.find('|') {
+                Some(end) => (non_empty(&inner[..end]), &inner[end + 1..]),
+                None => (None, rest),
+            },
+            None => (None, rest),
+        };
+        return Some((Arrow { label, style, head }, rest));
     }
 
-    // Handle "-- label -->" syntax: match `-- <text> -->`
-    if s.starts_with("--") && !s.starts_with("-->") && !s.starts_with("---") {
-        // Look for --> after the label
-        if let Some(end) = s[2..].find("-->") {
-            let label = s[2..2 + end].trim().to_string();
-            let label = if label.is_empty() { None } else { Some(label) };
-            return Some((label, &s[2 + end + 3..]));
+    // Label inside the arrow: `-- text -->`, `== text ==>`, `-. text .->`
+    for (open, style, closes) in [
+        ("--", EdgeStyle::Solid, &["-->", "---"][..]),
+        ("==", EdgeStyle::Thick, &["==>", "==="][..]),
+        ("-.", EdgeStyle::Dotted, &[".->", ".-"][..]),
+    ] {
+        let Some(after) = s.strip_prefix(open) else {
+            continue;
+        };
+        if !after.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let close = closes
+            .iter()
+            .filter_map(|close| after.find(close).map(|at| (at, *close)))
+            .min_by_key(|(at, _)| *at);
+        if let Some((at, close)) = close {
+            let mut rest = &after[at + close.len()..];
+            // Longer closings (`--->`) leave extra line characters
+            rest = rest.trim_start_matches(['-', '=', '.']);
+            let head = close.ends_with('>') || rest.starts_with('>');
+            rest = rest.strip_prefix('>').unwrap_or(rest);
+            let label = non_empty(&after[..at]);
+            return Some((Arrow { label, style, head }, rest));
         }
     }
 
     None
 }
 
+/// A bare arrow line at the start of `s`: its style, whether it has a head,
+/// and what follows it
+fn match_line(s: &str) -> Option<(EdgeStyle, bool, &str)> {
+    let (style, rest) = if let Some(rest) = s.strip_prefix("~~~") {
+        (EdgeStyle::Invisible, rest.trim_start_matches('~'))
+    } else if s.starts_with("==") {
+        (EdgeStyle::Thick, s.trim_start_matches('='))
+    } else if let Some(dots) = s.strip_prefix("-.") {
+        let rest = dots.trim_start_matches('.').strip_prefix('-')?;
+        (EdgeStyle::Dotted, rest)
+    } else if s.starts_with("--") || s.starts_with("->") {
+        (EdgeStyle::Solid, s.trim_start_matches('-'))
+    } else {
+        return None;
+    };
+    // A bare `--` or `==` (as in `-- text -->`) isn't an arrow by itself
+    if !rest.starts_with('>') && s.len() - rest.len() < 3 {
+        return None;
+    }
+    match rest.strip_prefix('>') {
+        Some(rest) => Some((style, true, rest)),
+        None => Some((style, false, rest)),
+    }
+}
+
+fn non_empty(label: &str) -> Option<String> {
+    let label = label.trim();
+    (!label.is_empty()).then(|| label.to_string())
+}
+
 /// Try to match a node reference at the start of `s`.
-/// Returns (id, optional_label, remaining).
-fn try_match_node_ref(s: &str) -> Option<(String, Option<String>, &str)> {
+/// Returns (id, optional_label, shape, remaining).
+fn try_match_node_ref(s: &str) -> Option<(String, Option<String>, NodeShape, &str)> {
     let s = s.trim_start();
     if s.is_empty() {
         return None;
@@ -315,29 +534,44 @@ fn try_match_node_ref(s: &str) -> Option<(String, Option<String>, &str)> {
     let rest = &s[id_end..];
 
     // Check for shape brackets after the ID
-    let (label, rest) = if let Some(r) = rest.strip_prefix("([") {
-        parse_until(r, "])")
+    let (label, shape, rest) = if let Some(r) = rest.strip_prefix("([") {
+        with_shape(parse_until(r, "])"), NodeShape::Stadium)
     } else if let Some(r) = rest.strip_prefix("((") {
-        parse_until(r, "))")
+        with_shape(parse_until(r, "))"), NodeShape::Circle)
     } else if let Some(r) = rest.strip_prefix("[[") {
-        parse_until(r, "]]")
+        with_shape(parse_until(r, "]]"), NodeShape::Subroutine)
     } else if let Some(r) = rest.strip_prefix("[/") {
-        parse_until(r, "/]")
+        with_shape(parse_until(r, "/]"), NodeShape::Parallelogram)
     } else if let Some(r) = rest.strip_prefix("[\\") {
-        parse_until(r, "\\]")
+        with_shape(parse_until(r, "\\]"), NodeShape::ParallelogramAlt)
+    } else if let Some(r) = rest.strip_prefix("{{") {
+        with_shape(parse_until(r, "}}"), NodeShape::Diamond)
     } else if let Some(r) = rest.strip_prefix('[') {
-        parse_until_char(r, ']')
+        with_shape(parse_until_char(r, ']'), NodeShape::Rect)
     } else if let Some(r) = rest.strip_prefix('(') {
-        parse_until_char(r, ')')
+        with_shape(parse_until_char(r, ')'), NodeShape::Round)
     } else if let Some(r) = rest.strip_prefix('{') {
-        parse_until_char(r, '}')
+        with_shape(parse_until_char(r, '}'), NodeShape::Diamond)
     } else if let Some(r) = rest.strip_prefix('>') {
-        parse_until_char(r, ']')
+        with_shape(parse_until_char(r, ']'), NodeShape::Asymmetric)
     } else {
-        (None, rest)
+        (None, NodeShape::Rect, rest)
     };
 
-    Some((id, label, rest))
+    Some((id, label, shape, rest))
+}
+
+/// A parsed label keeps its shape only if the brackets were closed
+fn with_shape(
+    (label, rest): (Option<String>, &str),
+    shape: NodeShape,
+) -> (Option<String>, NodeShape, &str) {
+    let shape = if label.is_some() {
+        shape
+    } else {
+        NodeShape::Rect
+    };
+    (label, shape, rest)
 }
 
 fn parse_until<'a>(s: &'a str, end: &str) -> (Option<String>, &'a str) {
@@ -359,499 +593,3883 @@ fn parse_until_char(s: &str, end: char) -> (Option<String>, &str) {
 }
 
 // ── Layout ──────────────────────────────────────────────────────────────────
+//
+// Flowcharts get a layered (Sugiyama-style) layout:
+//
+// 1. cycles are broken by reversing back-edges (they are still drawn
+//    pointing at their real target);
+// 2. nodes are put in layers by longest path, and an edge spanning several
+//    layers gets a dummy node in each layer it crosses, so that it is
+//    routed between nodes rather than through them;
+// 3. the order within each layer is improved by barycentric sweeps, keeping
+//    the ordering with the fewest crossings, with each subgraph's nodes
+//    kept together;
+// 4. items are placed along their layer as close as possible to what they
+//    connect to, and each subgraph gets a stripe across the layers it spans
+//    that nothing else enters;
+// 5. edges are routed orthogonally through the channels between layers,
+//    each edge that changes column on a track of its own.
+//
+// All of it works along a major axis (from layer to layer: down for TD,
+// right for LR) and a minor axis (along a layer), mapped onto the canvas at
+// the end.
+
+const BOX_HEIGHT: usize = 3;
+const BOX_PAD: usize = 2; // padding inside box on each side
+const ARROW_LEN: usize = 5; // shortest channel between layers (LR)
+const LAYER_GAP_H: usize = 3; // shortest channel between layers (TD)
+const LAYER_GAP_V: usize = 1; // gap between nodes in a layer (+2 for TD)
+const GROUP_PAD: i32 = 2; // subgraph border and the blank line inside it
+const SELF_LOOP: i32 = 2; // room beside a node for an edge to itself
+const ORDER_SWEEPS: usize = 12;
+const PLACE_SWEEPS: usize = 8;
 
-struct NodeLayout {
-    #[allow(dead_code)]
-    col: usize, // layer (column for LR, row for TD)
-    #[allow(dead_code)]
-    row: usize, // position within layer
-    x: usize, // canvas x
-    y: usize, // canvas y
+/// A rectangle on the canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    x: usize,
+    y: usize,
     width: usize,
-    label: String,
+    height: usize,
 }
 
-struct GraphLayout {
-    nodes: Vec<NodeLayout>,
-    node_index: HashMap<String, usize>, // node id → index in nodes
-    canvas_width: usize,
-    canvas_height: usize,
-    direction: Direction,
+/// A node to place, sized in canvas cells
+struct LayerNode {
+    width: usize,
+    height: usize,
+    group: Option<usize>,
 }
 
-const BOX_HEIGHT: usize = 3;
-const BOX_PAD: usize = 2; // padding inside box on each side
-const ARROW_LEN: usize = 5; // length of arrow between nodes
-const LAYER_GAP_H: usize = 3; // vertical gap between rows for TD (room for L-routing)
-const LAYER_GAP_V: usize = 1; // horizontal gap between nodes in same layer
+struct LayerEdge {
+    from: usize,
+    to: usize,
+    label_width: usize,
+}
 
-fn layout_graph(graph: &FlowGraph) -> GraphLayout {
-    let node_ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
-    let id_to_idx: HashMap<&str, usize> = node_ids
-        .iter()
-        .enumerate()
-        .map(|(i, id)| (*id, i))
-        .collect();
+/// A subgraph; its members are the nodes whose `group` is it or one of its
+/// descendants
+struct LayerGroup {
+    parent: Option<usize>,
+    /// Narrowest box that fits its title
+    title_width: usize,
+}
 
-    // Build adjacency lists
-    let n = graph.nodes.len();
-    let mut children: Vec<Vec<usize>> = vec![vec![]; n];
-    let mut has_parent = vec![false; n];
+struct LayeredGraph {
+    direction: Direction,
+    nodes: Vec<LayerNode>,
+    edges: Vec<LayerEdge>,
+    groups: Vec<LayerGroup>,
+    /// Cells at each end of an edge that run straight out of the node,
+    /// leaving room for what's drawn there (1 for a plain arrowhead)
+    end_room: usize,
+}
 
-    for edge in &graph.edges {
-        if let (Some(&from), Some(&to)) = (
-            id_to_idx.get(edge.from.as_str()),
-            id_to_idx.get(edge.to.as_str()),
-        ) {
-            children[from].push(to);
-            has_parent[to] = true;
-        }
-    }
+/// An edge's path as the corners of an orthogonal line, from the cell next
+/// to its source to the cell next to its target (where the arrowhead goes),
+/// and where its label starts
+#[derive(Debug, Clone, Default)]
+struct Route {
+    points: Vec<(usize, usize)>,
+    label: Option<(usize, usize)>,
+}
 
-    // Assign layers via BFS from roots
-    let mut layer_of = vec![0usize; n];
-    let roots: Vec<usize> = (0..n).filter(|&i| !has_parent[i]).collect();
+/// A route's points and label position along (minor, major) axes, before
+/// they are mapped onto the canvas
+type AxisRoute = (Vec<(i32, i32)>, Option<(i32, i32)>);
 
-    // If no roots (cycle), pick node 0
-    let start_nodes = if roots.is_empty() { vec![0] } else { roots };
+struct Placement {
+    nodes: Vec<Rect>,
+    /// Per edge
+    routes: Vec<Route>,
+    /// Per group; `None` for groups without nodes
+    groups: Vec<Option<Rect>>,
+    width: usize,
+    height: usize,
+}
 
-    let mut visited = vec![false; n];
-    let mut queue = std::collections::VecDeque::new();
-    for &root in &start_nodes {
-        queue.push_back(root);
-        visited[root] = true;
-    }
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ItemKind {
+    Node(usize),
+    /// Where an edge crosses a layer
+    Dummy,
+    /// Holds a subgraph's place in a layer it spans without having nodes there
+    Ghost,
+}
 
-    while let Some(node) = queue.pop_front() {
-        for &child in &children[node] {
-            let new_layer = layer_of[node] + 1;
-            if new_layer > layer_of[child] {
-                layer_of[child] = new_layer;
-            }
-            if !visited[child] {
-                visited[child] = true;
-                queue.push_back(child);
-            }
-        }
-    }
+/// Something taking up room in a layer
+struct Item {
+    kind: ItemKind,
+    layer: usize,
+    group: Option<usize>,
+    /// Extent along the layer: the node's box, 1 for dummies
+    size: i32,
+    /// Room needed after the box, for self-loops
+    extra: i32,
+    pos: f64,
+}
 
-    // Handle unvisited nodes (disconnected)
-    for i in 0..n {
-        if !visited[i] {
-            layer_of[i] = 0;
-        }
+impl Item {
+    fn center(&self) -> f64 {
+        self.pos + (self.size / 2) as f64
     }
 
-    // Group nodes by layer, preserving parse order within each layer
-    let num_layers = layer_of.iter().copied().max().unwrap_or(0) + 1;
-    let mut layers: Vec<Vec<usize>> = vec![vec![]; num_layers];
-    for (i, &layer) in layer_of.iter().enumerate() {
-        layers[layer].push(i);
+    fn span(&self) -> i32 {
+        self.size + self.extra
     }
+}
 
-    // Compute display widths for each node
-    let display_widths: Vec<usize> = graph
-        .nodes
-        .iter()
-        .map(|node| UnicodeWidthStr::width(node.label.as_str()) + BOX_PAD * 2 + 2) // +2 for borders
-        .collect();
-
-    // Calculate positions based on direction
-    let is_horizontal = matches!(graph.direction, Direction::LR | Direction::RL);
+/// An edge, or the part of one, between adjacent layers
+struct Link {
+    upper: usize,
+    lower: usize,
+    /// Part of a reversed back-edge
+    reversed: bool,
+}
 
-    let mut node_layouts = Vec::with_capacity(n);
-    let mut node_index = HashMap::new();
+fn layout_layered(graph: &LayeredGraph) -> Placement {
+    let horizontal = matches!(graph.direction, Direction::LR | Direction::RL);
+    let (gap, min_channel) = if horizontal {
+        (LAYER_GAP_V as i32, ARROW_LEN as i32)
+    } else {
+        (LAYER_GAP_V as i32 + 2, LAYER_GAP_H as i32)
+    };
+    let minor_size = |node: &LayerNode| (if horizontal { node.height } else { node.width }) as i32;
+    let major_size = |node: &LayerNode| (if horizontal { node.width } else { node.height }) as i32;
+    let groups = &graph.groups;
+    let n = graph.nodes.len();
 
-    if is_horizontal {
-        // LR/RL: layers are columns, nodes within layer are rows
-        let mut col_widths: Vec<usize> = vec![0; num_layers];
-        let mut rows_per_layer: Vec<usize> = vec![0; num_layers];
+    // 1–2. Break cycles and assign layers; self-loops are left aside
+    let pairs: Vec<(usize, usize)> = graph.edges.iter().map(|e| (e.from, e.to)).collect();
+    let reversed = back_edges(n, &pairs);
+    let dag: Vec<(usize, usize)> = pairs
+        .iter()
+        .zip(&reversed)
+        .filter(|((from, to), _)| from != to)
+        .map(|(&(from, to), &rev)| if rev { (to, from) } else { (from, to) })
+        .collect();
+    let layer_of = assign_layers(n, &dag);
+    let num_layers = layer_of.iter().max().map_or(1, |&last| last + 1);
 
-        for (layer_idx, layer_nodes) in layers.iter().enumerate() {
-            rows_per_layer[layer_idx] = layer_nodes.len();
-            for &node_idx in layer_nodes {
-                col_widths[layer_idx] = col_widths[layer_idx].max(display_widths[node_idx]);
+    let mut items: Vec<Item> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let extra = graph
+                .edges
+                .iter()
+                .filter(|e| e.from == i && e.to == i)
+                .map(|e| {
+                    if horizontal || e.label_width == 0 {
+                        SELF_LOOP
+                    } else {
+                        SELF_LOOP + 1 + e.label_width as i32
+                    }
+                })
+                .max()
+                .unwrap_or(0);
+            Item {
+                kind: ItemKind::Node(i),
+                layer: layer_of[i],
+                group: node.group,
+                size: minor_size(node),
+                extra,
+                pos: 0.0,
             }
-        }
-
-        let max_rows = rows_per_layer.iter().copied().max().unwrap_or(1);
+        })
+        .collect();
 
-        // Calculate x positions for each layer (column)
-        let mut layer_x = vec![0usize; num_layers];
-        let mut x = 0;
-        let layer_order: Vec<usize> = if graph.direction == Direction::RL {
-            (0..num_layers).rev().collect()
+    // Links between adjacent layers, through dummies; `chains` has each
+    // edge's links from the upper layer down
+    let mut links = Vec::new();
+    let mut chains: Vec<Vec<usize>> = vec![Vec::new(); graph.edges.len()];
+    for (e, edge) in graph.edges.iter().enumerate() {
+        if edge.from == edge.to {
+            continue;
+        }
+        let (top, bottom) = if reversed[e] {
+            (edge.to, edge.from)
         } else {
-            (0..num_layers).collect()
+            (edge.from, edge.to)
         };
-
-        for (draw_idx, &layer_idx) in layer_order.iter().enumerate() {
-            layer_x[layer_idx] = x;
-            x += col_widths[layer_idx];
-            if draw_idx < num_layers - 1 {
-                x += ARROW_LEN;
-            }
-        }
-
-        let canvas_width = x;
-        let canvas_height = max_rows * BOX_HEIGHT + (max_rows.saturating_sub(1)) * LAYER_GAP_V;
-
-        for (layer_idx, layer_nodes) in layers.iter().enumerate() {
-            // Center nodes vertically within the column
-            let total_h =
-                layer_nodes.len() * BOX_HEIGHT + layer_nodes.len().saturating_sub(1) * LAYER_GAP_V;
-            let y_offset = (canvas_height.saturating_sub(total_h)) / 2;
-
-            for (row_idx, &node_idx) in layer_nodes.iter().enumerate() {
-                let node = &graph.nodes[node_idx];
-                let w = display_widths[node_idx];
-                // Center node within column width
-                let x = layer_x[layer_idx] + (col_widths[layer_idx].saturating_sub(w)) / 2;
-                let y = y_offset + row_idx * (BOX_HEIGHT + LAYER_GAP_V);
-
-                node_index.insert(node.id.clone(), node_layouts.len());
-                node_layouts.push(NodeLayout {
-                    col: layer_idx,
-                    row: row_idx,
-                    x,
-                    y,
-                    width: w,
-                    label: node.label.clone(),
+        let group = common_group(groups, graph.nodes[top].group, graph.nodes[bottom].group);
+        let mut upper = top;
+        for layer in layer_of[top] + 1..=layer_of[bottom] {
+            let lower = if layer == layer_of[bottom] {
+                bottom
+            } else {
+                items.push(Item {
+                    kind: ItemKind::Dummy,
+                    layer,
+                    group,
+                    size: 1,
+                    extra: 0,
+                    pos: 0.0,
                 });
-            }
+                items.len() - 1
+            };
+            chains[e].push(links.len());
+            links.push(Link {
+                upper,
+                lower,
+                reversed: reversed[e],
+            });
+            upper = lower;
         }
+    }
 
-        GraphLayout {
-            nodes: node_layouts,
-            node_index,
-            canvas_width,
-            canvas_height,
-            direction: graph.direction,
-        }
-    } else {
-        // TD/BT: layers are rows, nodes within layer are columns
-        let mut max_width_per_layer: Vec<usize> = vec![0; num_layers];
-        let mut total_width_per_layer: Vec<usize> = vec![0; num_layers];
-
-        for (layer_idx, layer_nodes) in layers.iter().enumerate() {
-            let total: usize = layer_nodes
-                .iter()
-                .map(|&i| display_widths[i])
-                .sum::<usize>()
-                + layer_nodes.len().saturating_sub(1) * (LAYER_GAP_V + 2);
-            total_width_per_layer[layer_idx] = total;
-            for &node_idx in layer_nodes {
-                max_width_per_layer[layer_idx] =
-                    max_width_per_layer[layer_idx].max(display_widths[node_idx]);
-            }
+    // Layers each subgraph spans, with a ghost wherever it has nothing
+    let mut spans: Vec<Option<(usize, usize)>> = vec![None; groups.len()];
+    let mut present = HashSet::new();
+    for item in &items {
+        for g in ancestry(groups, item.group) {
+            let span = spans[g].get_or_insert((item.layer, item.layer));
+            *span = (span.0.min(item.layer), span.1.max(item.layer));
+            present.insert((g, item.layer));
         }
-
-        let canvas_width = total_width_per_layer.iter().copied().max().unwrap_or(10);
-
-        // Calculate y positions for each layer (row)
-        let mut layer_y = vec![0usize; num_layers];
-        let mut y = 0;
-        let layer_order: Vec<usize> = if graph.direction == Direction::BT {
-            (0..num_layers).rev().collect()
-        } else {
-            (0..num_layers).collect()
+    }
+    let mut deepest_first: Vec<usize> = (0..groups.len()).collect();
+    deepest_first.sort_by_key(|&g| std::cmp::Reverse(ancestry(groups, Some(g)).len()));
+    for &g in &deepest_first {
+        let Some((first, last)) = spans[g] else {
+            continue;
         };
-
-        for (draw_idx, &layer_idx) in layer_order.iter().enumerate() {
-            layer_y[layer_idx] = y;
-            y += BOX_HEIGHT;
-            if draw_idx < num_layers - 1 {
-                y += LAYER_GAP_H;
-            }
-        }
-
-        let canvas_height = y;
-
-        for (layer_idx, layer_nodes) in layers.iter().enumerate() {
-            let total_w = total_width_per_layer[layer_idx];
-            let x_offset = canvas_width.saturating_sub(total_w) / 2;
-
-            let mut x = x_offset;
-            for &node_idx in layer_nodes {
-                let node = &graph.nodes[node_idx];
-                let w = display_widths[node_idx];
-
-                node_index.insert(node.id.clone(), node_layouts.len());
-                node_layouts.push(NodeLayout {
-                    col: layer_idx,
-                    row: 0,
-                    x,
-                    y: layer_y[layer_idx],
-                    width: w,
-                    label: node.label.clone(),
+        for layer in first..=last {
+            if present.insert((g, layer)) {
+                items.push(Item {
+                    kind: ItemKind::Ghost,
+                    layer,
+                    group: Some(g),
+                    size: 0,
+                    extra: 0,
+                    pos: 0.0,
                 });
-                x += w + LAYER_GAP_V + 2;
+                for outer in ancestry(groups, Some(g)) {
+                    present.insert((outer, layer));
+                }
             }
         }
-
-        GraphLayout {
-            nodes: node_layouts,
-            node_index,
-            canvas_width,
-            canvas_height,
-            direction: graph.direction,
-        }
     }
-}
-
-// ── Canvas ──────────────────────────────────────────────────────────────────
-
-struct Canvas {
-    cells: Vec<Vec<char>>,
-    width: usize,
-    height: usize,
-}
 
-impl Canvas {
-    fn new(width: usize, height: usize) -> Self {
-        Self {
-            cells: vec![vec![' '; width]; height],
-            width,
-            height,
-        }
+    // Subgraphs around each item, outermost first
+    let paths: Vec<Vec<usize>> = items
+        .iter()
+        .map(|item| {
+            let mut path = ancestry(groups, item.group);
+            path.reverse();
+            path
+        })
+        .collect();
+    let mut ups = vec![Vec::new(); items.len()];
+    let mut downs = vec![Vec::new(); items.len()];
+    for (k, link) in links.iter().enumerate() {
+        downs[link.upper].push(k);
+        ups[link.lower].push(k);
     }
-
-    fn set(&mut self, x: usize, y: usize, ch: char) {
-        if x < self.width && y < self.height {
-            self.cells[y][x] = ch;
+    // In LR, links on a side of a node share its middle row, unless some
+    // are back-edges: then the node gets taller to give each its own row.
+    // Single-cell nodes (a state diagram's ● and ◆) stay put
+    if horizontal {
+        for (i, item) in items.iter_mut().enumerate().take(n) {
+            if item.size <= 1 {
+                continue;
+            }
+            for side in [&ups[i], &downs[i]] {
+                let mixed = side.iter().any(|&k| links[k].reversed)
+                    && side.iter().any(|&k| !links[k].reversed);
+                if mixed {
+                    item.size = item.size.max(2 * side.len() as i32 + 1);
+                }
+            }
         }
     }
+    let wiring = Wiring {
+        links: &links,
+        ups: &ups,
+        downs: &downs,
+    };
 
-    fn draw_text(&mut self, x: usize, y: usize, text: &str) {
-        let mut cx = x;
-        for ch in text.chars() {
-            self.set(cx, y, ch);
-            cx += unicode_width::UnicodeWidthChar::width(ch).unwrap_or(1);
-        }
+    // 3. Order within layers
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); num_layers];
+    for (i, item) in items.iter().enumerate() {
+        layers[item.layer].push(i);
     }
+    let layers = order_layers(layers, &wiring, &paths, groups.len());
 
-    fn draw_box(&mut self, x: usize, y: usize, width: usize, label: &str) {
-        if width < 2 || y + 2 >= self.height + 1 {
-            return;
-        }
-        // Top border
-        self.set(x, y, '┌');
-        for i in 1..width - 1 {
-            self.set(x + i, y, '─');
+    // 4. Positions along the layers: packed, then pulled towards what each
+    // item connects to, sweeping down and up
+    for layer in &layers {
+        let mut cursor = 0;
+        for &i in layer {
+            items[i].pos = cursor as f64;
+            cursor += items[i].span() + gap;
         }
-        self.set(x + width - 1, y, '┐');
-
-        // Content row — center the label
-        self.set(x, y + 1, '│');
-        let label_w = UnicodeWidthStr::width(label);
-        let inner = width.saturating_sub(2);
-        let pad_left = inner.saturating_sub(label_w) / 2;
-        self.draw_text(x + 1 + pad_left, y + 1, label);
-        self.set(x + width - 1, y + 1, '│');
-
-        // Bottom border
-        self.set(x, y + 2, '└');
-        for i in 1..width - 1 {
-            self.set(x + i, y + 2, '─');
+    }
+    for sweep in 0..PLACE_SWEEPS {
+        let downward = sweep % 2 == 0;
+        for l in sweep_order(num_layers, downward) {
+            let layer = &layers[l];
+            let mut want = Vec::with_capacity(layer.len());
+            let mut weight = Vec::with_capacity(layer.len());
+            let mut spacing = Vec::with_capacity(layer.len());
+            for &i in layer {
+                let side = if downward { &ups[i] } else { &downs[i] };
+                let pulls: Vec<f64> = side
+                    .iter()
+                    .map(|&k| {
+                        let link = &links[k];
+                        let other = if downward { link.upper } else { link.lower };
+                        let own_offset = wiring.port(&items, i, k) as f64 - items[i].pos.round();
+                        wiring.port(&items, other, k) as f64 - own_offset
+                    })
+                    .collect();
+                want.push(if pulls.is_empty() {
+                    items[i].pos
+                } else {
+                    pulls.iter().sum::<f64>() / pulls.len() as f64
+                });
+                let (w, space) = match items[i].kind {
+                    ItemKind::Node(_) => (1.0, items[i].span() + gap),
+                    // Long edges are kept straight where possible
+                    ItemKind::Dummy => (4.0, items[i].span() + gap),
+                    ItemKind::Ghost => (1e-3, 0),
+                };
+                weight.push(w);
+                spacing.push(space as f64);
+            }
+            for (&i, pos) in layer.iter().zip(pav(&want, &weight, &spacing)) {
+                items[i].pos = pos;
+            }
         }
-        self.set(x + width - 1, y + 2, '┘');
     }
+    let min_pos = items
+        .iter()
+        .map(|item| item.pos)
+        .fold(f64::INFINITY, f64::min);
+    let min_pos = if min_pos.is_finite() { min_pos } else { 0.0 };
+    let want: Vec<i32> = items
+        .iter()
+        .map(|item| (item.pos - min_pos).round() as i32)
+        .collect();
 
-    fn draw_harrow(&mut self, x1: usize, x2: usize, y: usize) {
-        if x2 <= x1 + 1 {
-            return;
+    // Subgraph stripes: push items along until every subgraph has the same
+    // extent in all the layers it spans, with nothing else inside it
+    let min_width: Vec<i32> = groups
+        .iter()
+        .map(|group| {
+            if horizontal {
+                0
+            } else {
+                group.title_width as i32
+            }
+        })
+        .collect();
+    let mut left = vec![0i32; groups.len()];
+    let mut right = vec![0i32; groups.len()];
+    for _ in 0..100 {
+        let (mut new_left, mut new_right) = (left.clone(), right.clone());
+        for layer in &layers {
+            let mut cursor = 0;
+            let mut open: Vec<(usize, i32)> = Vec::new();
+            for idx in 0..=layer.len() {
+                let path: &[usize] = layer.get(idx).map_or(&[], |&i| paths[i].as_slice());
+                while let Some(&(g, start)) = open.last() {
+                    if path.get(open.len() - 1) == Some(&g) {
+                        break;
+                    }
+                    open.pop();
+                    let end = (cursor - gap + GROUP_PAD)
+                        .max(right[g])
+                        .max(start + min_width[g]);
+                    new_left[g] = new_left[g].max(start);
+                    new_right[g] = new_right[g].max(end);
+                    cursor = end + gap;
+                }
+                let Some(&i) = layer.get(idx) else {
+                    break;
+                };
+                for &g in &path[open.len()..] {
+                    let start = cursor.max(left[g]);
+                    open.push((g, start));
+                    cursor = start + GROUP_PAD;
+                }
+                let item = &mut items[i];
+                let pos = want[i].max(cursor);
+                item.pos = pos as f64;
+                cursor = pos + item.span();
+                if item.kind != ItemKind::Ghost {
+                    cursor += gap;
+                }
+            }
         }
-        for x in x1..x2 {
-            self.set(x, y, '─');
+        if new_left == left && new_right == right {
+            break;
         }
-        self.set(x2, y, '▶');
+        (left, right) = (new_left, new_right);
     }
 
-    fn draw_harrow_rev(&mut self, x1: usize, x2: usize, y: usize) {
-        if x2 <= x1 + 1 {
-            return;
-        }
-        self.set(x1, y, '◀');
-        for x in x1 + 1..=x2 {
-            self.set(x, y, '─');
+    // 5. Ports and tracks in the channels between layers
+    let ports: Vec<(i32, i32)> = links
+        .iter()
+        .enumerate()
+        .map(|(k, link)| {
+            (
+                wiring.port(&items, link.upper, k),
+                wiring.port(&items, link.lower, k),
+            )
+        })
+        .collect();
+    let mut track = vec![0usize; links.len()];
+    let mut tracks = vec![0usize; num_layers];
+    for (l, count) in tracks.iter_mut().enumerate() {
+        let segments: Vec<(usize, i32, i32)> = links
+            .iter()
+            .enumerate()
+            .filter(|(_, link)| items[link.upper].layer == l)
+            .map(|(k, _)| (k, ports[k].0, ports[k].1))
+            .collect();
+        *count = assign_tracks(&segments, &mut track);
+    }
+    // Labels go by the end of the edge at its source
+    let label_link: Vec<Option<usize>> = chains
+        .iter()
+        .zip(&reversed)
+        .map(|(chain, &rev)| if rev { chain.last() } else { chain.first() }.copied())
+        .collect();
+    let mut label_room = vec![0i32; num_layers];
+    for (edge, link) in graph.edges.iter().zip(&label_link) {
+        if let (Some(k), true) = (link, edge.label_width > 0) {
+            let l = items[links[*k].upper].layer;
+            label_room[l] = label_room[l].max(edge.label_width as i32);
         }
     }
 
-    fn draw_varrow(&mut self, x: usize, y1: usize, y2: usize) {
-        if y2 <= y1 {
-            return;
-        }
-        for y in y1..y2 {
-            self.set(x, y, '│');
+    // Positions along the major axis, with room for subgraph borders (and
+    // for their titles, in LR)
+    let layer_depth: Vec<i32> = layers
+        .iter()
+        .map(|layer| {
+            layer
+                .iter()
+                .filter_map(|&i| match items[i].kind {
+                    ItemKind::Node(node) => Some(major_size(&graph.nodes[node])),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut start_pad = vec![0i32; groups.len()];
+    let mut end_pad = vec![0i32; groups.len()];
+    let mut title_room = vec![0i32; groups.len()];
+    let end_room = graph.end_room.max(1) as i32;
+    let mut layer_start = vec![0i32; num_layers];
+    let mut closing = vec![0i32; num_layers];
+    let mut major_extent;
+    loop {
+        for &g in &deepest_first {
+            let Some((first, last)) = spans[g] else {
+                continue;
+            };
+            let nested = |pads: &[i32], at_end: bool| {
+                (0..groups.len())
+                    .filter(|&c| groups[c].parent == Some(g))
+                    .filter_map(|c| {
+                        let (f, l) = spans[c]?;
+                        (if at_end { l == last } else { f == first }).then_some(pads[c])
+                    })
+                    .max()
+                    .unwrap_or(0)
+            };
+            start_pad[g] = GROUP_PAD + nested(&start_pad, false);
+            end_pad[g] = GROUP_PAD + nested(&end_pad, true) + title_room[g];
+        }
+        let pad_at = |pads: &[i32], layer: usize, at_end: bool| {
+            (0..groups.len())
+                .filter_map(|g| {
+                    let (first, last) = spans[g]?;
+                    (if at_end {
+                        last == layer
+                    } else {
+                        first == layer
+                    })
+                    .then_some(pads[g])
+                })
+                .max()
+                .unwrap_or(0)
+        };
+        let mut cursor = pad_at(&start_pad, 0, false);
+        for l in 0..num_layers {
+            layer_start[l] = cursor;
+            cursor += layer_depth[l];
+            if l + 1 < num_layers {
+                closing[l] = pad_at(&end_pad, l, true);
+                let core = if horizontal {
+                    end_room + tracks[l] as i32 + (label_room[l] + 3).max(end_room + 1)
+                } else {
+                    end_room + tracks[l] as i32 + i32::from(label_room[l] > 0) + end_room
+                };
+                cursor += closing[l] + core.max(min_channel) + pad_at(&start_pad, l + 1, false);
+            }
+        }
+        major_extent = cursor + pad_at(&end_pad, num_layers - 1, true);
+
+        let mut grew = false;
+        if horizontal {
+            for (g, group) in groups.iter().enumerate() {
+                let Some((first, last)) = spans[g] else {
+                    continue;
+                };
+                let extent = layer_start[last] + layer_depth[last] + end_pad[g]
+                    - layer_start[first]
+                    + start_pad[g];
+                if extent < group.title_width as i32 {
+                    title_room[g] += group.title_width as i32 - extent;
+                    grew = true;
+                }
+            }
+        }
+        if !grew {
+            break;
+        }
+    }
+
+    let node_start = |i: usize| {
+        let l = items[i].layer;
+        match items[i].kind {
+            ItemKind::Node(node) => {
+                layer_start[l] + (layer_depth[l] - major_size(&graph.nodes[node])) / 2
+            }
+            _ => layer_start[l],
+        }
+    };
+    let node_end = |i: usize| match items[i].kind {
+        ItemKind::Node(node) => node_start(i) + major_size(&graph.nodes[node]),
+        _ => layer_start[items[i].layer] + layer_depth[items[i].layer],
+    };
+    let after_tracks =
+        |l: usize| layer_start[l] + layer_depth[l] + closing[l] + end_room + tracks[l] as i32;
+
+    // Routes, as (minor, major) points and label position
+    let mut routes: Vec<AxisRoute> = Vec::new();
+    for (e, edge) in graph.edges.iter().enumerate() {
+        if edge.from == edge.to {
+            // A loop beside the node
+            let item = &items[edge.from];
+            let (p, s, m) = (item.pos as i32, item.size, node_start(edge.from));
+            let points = vec![
+                (p + s, m + 1),
+                (p + s + 1, m + 1),
+                (p + s + 1, m + 2),
+                (p + s, m + 2),
+            ];
+            let label = (edge.label_width > 0).then_some(if horizontal {
+                (p + s + 1, m + 4)
+            } else {
+                (p + s + 3, m + 1)
+            });
+            routes.push((points, label));
+            continue;
+        }
+        let mut points = Vec::new();
+        for &k in &chains[e] {
+            let link = &links[k];
+            let l = items[link.upper].layer;
+            let (a, b) = ports[k];
+            let (from, to) = (node_end(link.upper), node_start(link.lower) - 1);
+            let row = layer_start[l] + layer_depth[l] + closing[l] + end_room + track[k] as i32;
+            let corners = if a == b {
+                vec![(a, from), (b, to)]
+            } else {
+                vec![(a, from), (a, row), (b, row), (b, to)]
+            };
+            for point in corners {
+                if points.last() != Some(&point) {
+                    points.push(point);
+                }
+            }
+        }
+        if reversed[e] {
+            points.reverse();
+        }
+        let label = label_link[e].filter(|_| edge.label_width > 0).map(|k| {
+            let l = items[links[k].upper].layer;
+            let b = ports[k].1;
+            if horizontal {
+                // Above the line into the lower layer, or below it if
+                // there's no room above
+                (if b > 0 { b - 1 } else { b + 1 }, after_tracks(l) + 1)
+            } else {
+                (b + 2, after_tracks(l))
+            }
+        });
+        routes.push((points, label));
+    }
+
+    // Map onto the canvas
+    let label_ends = graph
+        .edges
+        .iter()
+        .zip(&routes)
+        .filter_map(|(edge, (_, label))| {
+            let (minor, _) = (*label)?;
+            Some(if horizontal {
+                minor + 1
+            } else {
+                minor + edge.label_width as i32
+            })
+        });
+    let minor_extent = items
+        .iter()
+        .map(|item| item.pos as i32 + item.span())
+        .chain(right.iter().copied())
+        .chain(label_ends)
+        .max()
+        .unwrap_or(0);
+    let flip = matches!(graph.direction, Direction::BT | Direction::RL);
+    let rect = |minor: i32, major: i32, minor_len: i32, major_len: i32| {
+        let major = if flip {
+            major_extent - major - major_len
+        } else {
+            major
+        };
+        let (x, y, width, height) = if horizontal {
+            (major, minor, major_len, minor_len)
+        } else {
+            (minor, major, minor_len, major_len)
+        };
+        Rect {
+            x: x.max(0) as usize,
+            y: y.max(0) as usize,
+            width: width.max(0) as usize,
+            height: height.max(0) as usize,
+        }
+    };
+    let point = |(minor, major): (i32, i32)| {
+        let cell = rect(minor, major, 1, 1);
+        (cell.x, cell.y)
+    };
+
+    let nodes = (0..n)
+        .map(|i| {
+            rect(
+                items[i].pos as i32,
+                node_start(i),
+                items[i].size,
+                major_size(&graph.nodes[i]),
+            )
+        })
+        .collect();
+    let routes = graph
+        .edges
+        .iter()
+        .zip(routes)
+        .map(|(edge, (points, label))| Route {
+            points: points.into_iter().map(point).collect(),
+            // Labels read left to right whichever way the diagram goes
+            label: label.map(|(minor, major)| {
+                let width = if horizontal {
+                    edge.label_width.max(1) as i32
+                } else {
+                    1
+                };
+                let cell = rect(minor, major, 1, width);
+                (cell.x, cell.y)
+            }),
+        })
+        .collect();
+    let group_rects = (0..groups.len())
+        .map(|g| {
+            let (first, last) = spans[g]?;
+            let top = layer_start[first] - start_pad[g];
+            let bottom = layer_start[last] + layer_depth[last] + end_pad[g];
+            Some(rect(left[g], top, right[g] - left[g], bottom - top))
+        })
+        .collect();
+    let (width, height) = if horizontal {
+        (major_extent, minor_extent)
+    } else {
+        (minor_extent, major_extent)
+    };
+    Placement {
+        nodes,
+        routes,
+        groups: group_rects,
+        width: width.max(0) as usize,
+        height: height.max(0) as usize,
+    }
+}
+
+/// Layers in the order a sweep visits them, each placed relative to the one
+/// visited before
+fn sweep_order(num_layers: usize, downward: bool) -> Vec<usize> {
+    if downward {
+        (1..num_layers).collect()
+    } else {
+        (0..num_layers.saturating_sub(1)).rev().collect()
+    }
+}
+
+/// How items connect: links, and each item's links to the layers above
+/// and below
+struct Wiring<'a> {
+    links: &'a [Link],
+    ups: &'a [Vec<usize>],
+    downs: &'a [Vec<usize>],
+}
+
+impl Wiring<'_> {
+    /// Where link `k` meets `item` along the layer. An item's links on one
+    /// side are spread along its box, in the order of their other ends.
+    fn port(&self, items: &[Item], item: usize, k: usize) -> i32 {
+        let outgoing = self.links[k].upper == item;
+        let side = if outgoing {
+            &self.downs[item]
+        } else {
+            &self.ups[item]
+        };
+        let other = |j: usize| {
+            let link = &self.links[j];
+            items[if outgoing { link.lower } else { link.upper }].center()
+        };
+        let it = &items[item];
+        let pos = it.pos.round() as i32;
+        let count = side.len() as i32;
+        if count <= 1 || it.size <= 3 {
+            return pos + it.size / 2;
+        }
+        let rank = side
+            .iter()
+            .filter(|&&j| other(j) < other(k) || (other(j) == other(k) && j < k))
+            .count() as i32;
+        pos + 1 + (it.size - 2) * (2 * rank + 1) / (2 * count)
+    }
+
+    /// Mean position of an item's neighbours on one side, by index
+    fn barycenter(&self, index: &[f64], item: usize, upward: bool) -> Option<f64> {
+        let side = if upward {
+            &self.ups[item]
+        } else {
+            &self.downs[item]
+        };
+        if side.is_empty() {
+            return None;
+        }
+        let sum: f64 = side
+            .iter()
+            .map(|&k| {
+                let link = &self.links[k];
+                index[if upward { link.upper } else { link.lower }]
+            })
+            .sum();
+        Some(sum / side.len() as f64)
+    }
+}
+
+/// Edges that close a cycle, found by depth-first search from the sources
+/// (then from any node not reached yet); reversing them leaves a DAG
+fn back_edges(n: usize, edges: &[(usize, usize)]) -> Vec<bool> {
+    let mut out: Vec<Vec<(usize, usize)>> = vec![Vec::new(); n];
+    let mut has_in = vec![false; n];
+    for (e, &(from, to)) in edges.iter().enumerate() {
+        if from != to {
+            out[from].push((e, to));
+            has_in[to] = true;
+        }
+    }
+    // 0: not visited, 1: on the stack, 2: done
+    let mut state = vec![0u8; n];
+    let mut back = vec![false; edges.len()];
+    for start in (0..n).filter(|&v| !has_in[v]).chain(0..n) {
+        if state[start] != 0 {
+            continue;
+        }
+        state[start] = 1;
+        let mut stack = vec![(start, 0)];
+        while let Some(&(node, next)) = stack.last() {
+            if let Some(&(e, to)) = out[node].get(next) {
+                if let Some(top) = stack.last_mut() {
+                    top.1 += 1;
+                }
+                match state[to] {
+                    0 => {
+                        state[to] = 1;
+                        stack.push((to, 0));
+                    }
+                    1 => back[e] = true,
+                    _ => {}
+                }
+            } else {
+                state[node] = 2;
+                stack.pop();
+            }
+        }
+    }
+    back
+}
+
+/// Layer of each node: the longest path to it from a source, with sources
+/// then moved down next to their highest successor
+fn assign_layers(n: usize, dag: &[(usize, usize)]) -> Vec<usize> {
+    let mut out = vec![Vec::new(); n];
+    let mut indegree = vec![0usize; n];
+    for &(from, to) in dag {
+        out[from].push(to);
+        indegree[to] += 1;
+    }
+    let mut layer = vec![0usize; n];
+    let mut remaining = indegree.clone();
+    let mut queue: std::collections::VecDeque<usize> =
+        (0..n).filter(|&v| indegree[v] == 0).collect();
+    while let Some(v) = queue.pop_front() {
+        for &to in &out[v] {
+            layer[to] = layer[to].max(layer[v] + 1);
+            remaining[to] -= 1;
+            if remaining[to] == 0 {
+                queue.push_back(to);
+            }
+        }
+    }
+    for v in 0..n {
+        if indegree[v] == 0 {
+            if let Some(below) = out[v].iter().map(|&to| layer[to]).min() {
+                layer[v] = below - 1;
+            }
+        }
+    }
+    layer
+}
+
+/// `group` and the groups around it, innermost first
+fn ancestry(groups: &[LayerGroup], group: Option<usize>) -> Vec<usize> {
+    let mut chain = Vec::new();
+    let mut current = group;
+    while let Some(g) = current {
+        if chain.contains(&g) {
+            break;
+        }
+        chain.push(g);
+        current = groups[g].parent;
+    }
+    chain
+}
+
+/// Innermost group containing both `a` and `b`
+fn common_group(groups: &[LayerGroup], a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    let outer = ancestry(groups, a);
+    ancestry(groups, b).into_iter().find(|g| outer.contains(g))
+}
+
+/// Reorder each layer to reduce crossings: barycentric sweeps down and up,
+/// keeping the best ordering seen. Within a layer, a subgraph's items stay
+/// together, and sibling subgraphs are ordered by one rank everywhere so
+/// that their stripes never have to swap.
+fn order_layers(
+    mut layers: Vec<Vec<usize>>,
+    wiring: &Wiring,
+    paths: &[Vec<usize>],
+    group_count: usize,
+) -> Vec<Vec<usize>> {
+    let item_count = paths.len();
+    let index_of = |layers: &[Vec<usize>]| {
+        let mut index = vec![0.0; item_count];
+        for layer in layers {
+            for (i, &item) in layer.iter().enumerate() {
+                index[item] = i as f64;
+            }
+        }
+        index
+    };
+    // A subgraph's rank: where its items' neighbours are, on average
+    let ranks = |index: &[f64]| {
+        let mut sums = vec![(0.0, 0usize); group_count];
+        for item in 0..item_count {
+            let around: Vec<f64> = [true, false]
+                .into_iter()
+                .filter_map(|upward| wiring.barycenter(index, item, upward))
+                .collect();
+            let at = if around.is_empty() {
+                index[item]
+            } else {
+                around.iter().sum::<f64>() / around.len() as f64
+            };
+            for &g in &paths[item] {
+                sums[g].0 += at;
+                sums[g].1 += 1;
+            }
+        }
+        sums.into_iter()
+            .map(|(sum, count)| if count == 0 { 0.0 } else { sum / count as f64 })
+            .collect::<Vec<f64>>()
+    };
+
+    let mut index = index_of(&layers);
+    let rank = ranks(&index);
+    for layer in &mut layers {
+        layer.sort_by(|&a, &b| compare_in_layer(a, b, &index, paths, &rank));
+    }
+    let mut best = layers.clone();
+    let mut best_crossings = count_crossings(&layers, wiring.links, item_count);
+    for sweep in 0..ORDER_SWEEPS {
+        if best_crossings == 0 {
+            break;
+        }
+        let downward = sweep % 2 == 0;
+        index = index_of(&layers);
+        let rank = ranks(&index);
+        for l in sweep_order(layers.len(), downward) {
+            let mut key = index.clone();
+            for &item in &layers[l] {
+                if let Some(center) = wiring.barycenter(&index, item, downward) {
+                    key[item] = center;
+                }
+            }
+            layers[l].sort_by(|&a, &b| compare_in_layer(a, b, &key, paths, &rank));
+            for (i, &item) in layers[l].iter().enumerate() {
+                index[item] = i as f64;
+            }
+        }
+        let crossings = count_crossings(&layers, wiring.links, item_count);
+        if crossings < best_crossings {
+            best = layers.clone();
+            best_crossings = crossings;
+        }
+    }
+    best
+}
+
+/// Order of two items in a layer: where their subgraph paths part, a
+/// subgraph goes by its rank and an item by its key
+fn compare_in_layer(
+    a: usize,
+    b: usize,
+    key: &[f64],
+    paths: &[Vec<usize>],
+    rank: &[f64],
+) -> std::cmp::Ordering {
+    let (path_a, path_b) = (&paths[a], &paths[b]);
+    let common = path_a
+        .iter()
+        .zip(path_b)
+        .take_while(|(x, y)| x == y)
+        .count();
+    let at = |path: &[usize], item: usize| match path.get(common) {
+        Some(&g) => (rank[g], 0, g),
+        None => (key[item], 1, item),
+    };
+    let (ka, kb) = (at(path_a, a), at(path_b, b));
+    ka.0.total_cmp(&kb.0)
+        .then(ka.1.cmp(&kb.1))
+        .then(ka.2.cmp(&kb.2))
+}
+
+fn count_crossings(layers: &[Vec<usize>], links: &[Link], item_count: usize) -> usize {
+    let mut index = vec![0; item_count];
+    let mut layer_of = vec![0; item_count];
+    for (l, layer) in layers.iter().enumerate() {
+        for (i, &item) in layer.iter().enumerate() {
+            index[item] = i;
+            layer_of[item] = l;
+        }
+    }
+    let mut crossings = 0;
+    for (i, a) in links.iter().enumerate() {
+        for b in &links[i + 1..] {
+            if layer_of[a.upper] != layer_of[b.upper] {
+                continue;
+            }
+            let (au, al, bu, bl) = (
+                index[a.upper],
+                index[a.lower],
+                index[b.upper],
+                index[b.lower],
+            );
+            if (au < bu && al > bl) || (au > bu && al < bl) {
+                crossings += 1;
+            }
+        }
+    }
+    crossings
+}
+
+/// Weighted least-squares positions for a row of items that each want to
+/// be at `want[i]` and must keep `spacing[i]` ahead of the next: pool
+/// adjacent violators, on positions less the spacing before them
+fn pav(want: &[f64], weight: &[f64], spacing: &[f64]) -> Vec<f64> {
+    let mut offsets = Vec::with_capacity(want.len());
+    let mut offset = 0.0;
+    for space in spacing {
+        offsets.push(offset);
+        offset += space;
+    }
+    // (weighted sum, total weight, items)
+    let mut pools: Vec<(f64, f64, usize)> = Vec::new();
+    for i in 0..want.len() {
+        pools.push((weight[i] * (want[i] - offsets[i]), weight[i], 1));
+        while pools.len() > 1 {
+            let (sum2, weight2, count2) = pools[pools.len() - 1];
+            let (sum1, weight1, count1) = pools[pools.len() - 2];
+            if sum1 / weight1 <= sum2 / weight2 {
+                break;
+            }
+            pools.pop();
+            if let Some(last) = pools.last_mut() {
+                *last = (sum1 + sum2, weight1 + weight2, count1 + count2);
+            }
+        }
+    }
+    let mut placed = Vec::with_capacity(want.len());
+    for (sum, total, count) in pools {
+        for _ in 0..count {
+            placed.push(sum / total + offsets[placed.len()]);
+        }
+    }
+    placed
+}
+
+/// Give each segment that changes column between two layers a track (a row
+/// of the channel, for TD). Segments whose spans overlap get different
+/// tracks unless they share an end, and a segment leaving a column turns
+/// before one arriving there, so lines never run on top of each other.
+/// Returns the number of tracks.
+fn assign_tracks(segments: &[(usize, i32, i32)], track: &mut [usize]) -> usize {
+    let mut turning: Vec<(usize, i32, i32)> = segments
+        .iter()
+        .copied()
+        .filter(|&(_, a, b)| a != b)
+        .collect();
+    turning.sort_by_key(|&(k, a, b)| (a.min(b), a.max(b), k));
+    let clash = |(_, a1, b1): (usize, i32, i32), (_, a2, b2): (usize, i32, i32)| {
+        a1.min(b1) <= a2.max(b2) && a2.min(b2) <= a1.max(b1) && a1 != a2 && b1 != b2
+    };
+    let free_from =
+        |assigned: &[((usize, i32, i32), usize)], segment: (usize, i32, i32), from: usize| {
+            (from..)
+                .find(|&t| {
+                    !assigned.iter().any(|&(other, used)| {
+                        used == t && other.0 != segment.0 && clash(other, segment)
+                    })
+                })
+                .unwrap_or(from)
+        };
+
+    let mut assigned: Vec<((usize, i32, i32), usize)> = Vec::new();
+    for &segment in &turning {
+        let t = free_from(&assigned, segment, 0);
+        assigned.push((segment, t));
+    }
+    for _ in 0..assigned.len() * assigned.len() {
+        let late = (0..assigned.len())
+            .flat_map(|i| (0..assigned.len()).map(move |j| (i, j)))
+            .find(|&(i, j)| {
+                let ((_, leaving, arrives_at_i), ti) = assigned[i];
+                let ((_, leaves_j, arriving), tj) = assigned[j];
+                // Two segments swapping columns can't both turn first
+                i != j && leaving == arriving && leaves_j != arrives_at_i && ti >= tj
+            });
+        let Some((i, j)) = late else {
+            break;
+        };
+        assigned[j].1 = free_from(&assigned, assigned[j].0, assigned[i].1 + 1);
+    }
+    for &((k, _, _), t) in &assigned {
+        track[k] = t;
+    }
+    assigned.iter().map(|&(_, t)| t + 1).max().unwrap_or(0)
+}
+
+// ── Canvas ──────────────────────────────────────────────────────────────────
+
+const SQUARE_CORNERS: [char; 4] = ['┌', '┐', '└', '┘'];
+const ROUND_CORNERS: [char; 4] = ['╭', '╮', '╰', '╯'];
+
+// Directions lines leave a cell in
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+/// Corners and junctions: ╭ ╮ ╰ ╯ ├ ┤ ┬ ┴ ┼
+const LIGHT_JOINS: [char; 9] = ['╭', '╮', '╰', '╯', '├', '┤', '┬', '┴', '┼'];
+const HEAVY_JOINS: [char; 9] = ['┏', '┓', '┗', '┛', '┣', '┫', '┳', '┻', '╋'];
+
+/// The line character for a cell with lines leaving it in `dirs`
+fn line_glyph(dirs: u8, style: EdgeStyle) -> char {
+    let (horizontal, vertical) = style.strokes();
+    if dirs & (UP | DOWN) == 0 {
+        return horizontal;
+    }
+    if dirs & (LEFT | RIGHT) == 0 {
+        return vertical;
+    }
+    let joins = if style == EdgeStyle::Thick {
+        HEAVY_JOINS
+    } else {
+        LIGHT_JOINS
+    };
+    let index = match (
+        dirs & UP != 0,
+        dirs & DOWN != 0,
+        dirs & LEFT != 0,
+        dirs & RIGHT != 0,
+    ) {
+        (false, true, false, true) => 0,
+        (false, true, true, false) => 1,
+        (true, false, false, true) => 2,
+        (true, false, true, false) => 3,
+        (true, true, false, true) => 4,
+        (true, true, true, false) => 5,
+        (false, true, true, true) => 6,
+        (true, false, true, true) => 7,
+        _ => 8,
+    };
+    joins[index]
+}
+
+/// Directions of the lines in a line character (0 for anything else)
+fn glyph_dirs(ch: char) -> u8 {
+    match ch {
+        '─' | '┄' | '━' => LEFT | RIGHT,
+        '│' | '┆' | '┃' => UP | DOWN,
+        '┌' | '╭' | '┏' => DOWN | RIGHT,
+        '┐' | '╮' | '┓' => DOWN | LEFT,
+        '└' | '╰' | '┗' => UP | RIGHT,
+        '┘' | '╯' | '┛' => UP | LEFT,
+        '├' | '┣' => UP | DOWN | RIGHT,
+        '┤' | '┫' => UP | DOWN | LEFT,
+        '┬' | '┳' => DOWN | LEFT | RIGHT,
+        '┴' | '┻' => UP | LEFT | RIGHT,
+        '┼' | '╋' => UP | DOWN | LEFT | RIGHT,
+        _ => 0,
+    }
+}
+
+/// The arrowhead at the end of a path, pointing along its last segment
+fn arrowhead(points: &[(usize, usize)]) -> Option<((usize, usize), char)> {
+    let &last = points.last()?;
+    let &before = points.iter().rev().find(|&&p| p != last)?;
+    let head = if last.0 > before.0 {
+        '▶'
+    } else if last.0 < before.0 {
+        '◀'
+    } else if last.1 > before.1 {
+        '▼'
+    } else {
+        '▲'
+    };
+    Some((last, head))
+}
+
+/// Character grid a diagram is drawn on; every cell remembers the role of
+/// whatever drew it last (the `pen`)
+struct Canvas {
+    cells: Vec<Vec<char>>,
+    roles: Vec<Vec<DiagramRole>>,
+    pen: DiagramRole,
+    width: usize,
+    height: usize,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            cells: vec![vec![' '; width]; height],
+            roles: vec![vec![DiagramRole::Text; width]; height],
+            pen: DiagramRole::Text,
+            width,
+            height,
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, ch: char) {
+        if x < self.width && y < self.height {
+            self.cells[y][x] = ch;
+            self.roles[y][x] = self.pen;
+        }
+    }
+
+    fn draw_text(&mut self, x: usize, y: usize, text: &str) {
+        let mut cx = x;
+        for ch in text.chars() {
+            self.set(cx, y, ch);
+            cx += unicode_width::UnicodeWidthChar::width(ch).unwrap_or(1);
+        }
+    }
+
+    /// A title on a box's top border, slid right past any edges crossing it
+    /// where there's room
+    fn draw_title(&mut self, rect: Rect, title: &str) {
+        let width = UnicodeWidthStr::width(title);
+        let clear =
+            |x: usize| (x..x + width).all(|cx| cx < self.width && self.cells[rect.y][cx] == '─');
+        let last = (rect.x + rect.width).saturating_sub(width + 2);
+        let x = (rect.x + 2..=last)
+            .find(|&x| clear(x))
+            .unwrap_or(rect.x + 2);
+        self.draw_text(x, rect.y, title);
+    }
+
+    /// A node box, normally 3 rows (taller when edges need more room on
+    /// its sides); its borders depend on the shape:
+    ///
+    /// ```text
+    /// ┌─────┐  ╭─────╮  ╭─────╮   ╭───╮   ╱─────╲  ┌┬───┬┐   ┌─────┐  ┌─────┐
+    /// │  A  │  │  A  │  (  A  )  (  A  )  <  A  >  ││ A ││  ╱  A  ╱  >  A  │
+    /// └─────┘  ╰─────╯  ╰─────╯   ╰───╯   ╲─────╱  └┴───┴┘  └─────┘   └─────┘
+    ///  rect     round   stadium   circle  diamond  subrout.  parallel.  asym.
+    /// ```
+    fn draw_node(&mut self, rect: Rect, label: &str, shape: NodeShape) {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = rect;
+        if width < 4 || height < 3 {
+            return;
+        }
+        let (right, bottom, middle) = (x + width - 1, y + height - 1, y + height / 2);
+        let inner_rows = y + 1..bottom;
+        self.pen = DiagramRole::NodeBorder;
+        match shape {
+            NodeShape::Rect => self.draw_rect(x, y, width, height, SQUARE_CORNERS),
+            NodeShape::Round => self.draw_rect(x, y, width, height, ROUND_CORNERS),
+            NodeShape::Stadium => {
+                self.draw_rect(x, y, width, height, ROUND_CORNERS);
+                for row in inner_rows {
+                    self.set(x, row, '(');
+                    self.set(right, row, ')');
+                }
+            }
+            NodeShape::Circle => {
+                self.draw_rect(x + 1, y, width - 2, height, ROUND_CORNERS);
+                for row in inner_rows {
+                    self.set(x + 1, row, ' ');
+                    self.set(right - 1, row, ' ');
+                    self.set(x, row, '(');
+                    self.set(right, row, ')');
+                }
+            }
+            NodeShape::Diamond => {
+                // Sides slant in towards the top and bottom rows
+                for row in y..=bottom {
+                    let from_middle = row.abs_diff(middle);
+                    if from_middle == 0 {
+                        self.set(x, row, '<');
+                        self.set(right, row, '>');
+                        continue;
+                    }
+                    let inset = from_middle - 1;
+                    let (left_side, right_side) = if row < middle {
+                        ('╱', '╲')
+                    } else {
+                        ('╲', '╱')
+                    };
+                    self.set(x + inset, row, left_side);
+                    self.set(right - inset, row, right_side);
+                    if row == y || row == bottom {
+                        for column in x + inset + 1..right - inset {
+                            self.set(column, row, '─');
+                        }
+                    }
+                }
+            }
+            NodeShape::Subroutine => {
+                self.draw_rect(x, y, width, height, SQUARE_CORNERS);
+                for column in [x + 1, right - 1] {
+                    self.set(column, y, '┬');
+                    for row in inner_rows.clone() {
+                        self.set(column, row, '│');
+                    }
+                    self.set(column, bottom, '┴');
+                }
+            }
+            NodeShape::Parallelogram | NodeShape::ParallelogramAlt => {
+                // The top edge is shifted right (`/`) or left (`\`) of the
+                // bottom one, joined by slanted sides
+                let (top, base, side) = if shape == NodeShape::Parallelogram {
+                    (x + 1, x, '╱')
+                } else {
+                    (x, x + 1, '╲')
+                };
+                for (edge_x, row, [left_corner, right_corner]) in
+                    [(top, y, ['┌', '┐']), (base, bottom, ['└', '┘'])]
+                {
+                    self.set(edge_x, row, left_corner);
+                    for column in edge_x + 1..edge_x + width - 2 {
+                        self.set(column, row, '─');
+                    }
+                    self.set(edge_x + width - 2, row, right_corner);
+                }
+                for row in inner_rows {
+                    self.set(x, row, side);
+                    self.set(right, row, side);
+                }
+            }
+            NodeShape::Asymmetric => {
+                self.draw_rect(x, y, width, height, SQUARE_CORNERS);
+                self.set(x, middle, '>');
+            }
+        }
+
+        // Content row — center the label
+        let label_w = UnicodeWidthStr::width(label);
+        let inner = width.saturating_sub(2);
+        let pad_left = inner.saturating_sub(label_w) / 2;
+        self.pen = DiagramRole::NodeLabel;
+        self.draw_text(x + 1 + pad_left, middle, label);
+    }
+
+    /// Draw an orthogonal line through `points`, joined up with any lines
+    /// already on the canvas (crossings, junctions, subgraph borders)
+    fn draw_path(&mut self, points: &[(usize, usize)], style: EdgeStyle) {
+        let mut dirs: HashMap<(usize, usize), u8> = HashMap::new();
+        for pair in points.windows(2) {
+            let (mut x, mut y) = pair[0];
+            let (x2, y2) = pair[1];
+            while (x, y) != (x2, y2) {
+                let (next, out, back) = if x2 > x {
+                    ((x + 1, y), RIGHT, LEFT)
+                } else if x2 < x {
+                    ((x - 1, y), LEFT, RIGHT)
+                } else if y2 > y {
+                    ((x, y + 1), DOWN, UP)
+                } else {
+                    ((x, y - 1), UP, DOWN)
+                };
+                *dirs.entry((x, y)).or_default() |= out;
+                *dirs.entry(next).or_default() |= back;
+                (x, y) = next;
+            }
+        }
+        for ((x, y), dir) in dirs {
+            if x < self.width && y < self.height {
+                let joined = glyph_dirs(self.cells[y][x]) | dir;
+                self.cells[y][x] = line_glyph(joined, style);
+                self.roles[y][x] = DiagramRole::Edge;
+            }
+        }
+    }
+
+    /// Border of a `width` × `height` rectangle; `corners` are top-left,
+    /// top-right, bottom-left, bottom-right
+    fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize, corners: [char; 4]) {
+        if width < 2 || height < 2 {
+            return;
+        }
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        for column in x + 1..right {
+            self.set(column, y, '─');
+            self.set(column, bottom, '─');
+        }
+        for row in y + 1..bottom {
+            self.set(x, row, '│');
+            self.set(right, row, '│');
+        }
+        self.set(x, y, corners[0]);
+        self.set(right, y, corners[1]);
+        self.set(x, bottom, corners[2]);
+        self.set(right, bottom, corners[3]);
+    }
+
+    /// The drawing's rows without trailing blanks; spaces join the span
+    /// before them
+    fn lines(&self) -> Vec<DiagramLine> {
+        let mut lines: Vec<DiagramLine> = self
+            .cells
+            .iter()
+            .zip(&self.roles)
+            .map(|(cells, roles)| {
+                let end = cells.iter().rposition(|&ch| ch != ' ').map_or(0, |i| i + 1);
+                let mut line = DiagramLine::default();
+                for (&ch, &role) in cells[..end].iter().zip(roles) {
+                    let role = match (ch, line.spans.last()) {
+                        (' ', Some(last)) => last.role,
+                        _ => role,
+                    };
+                    line.push(ch.encode_utf8(&mut [0; 4]), role);
+                }
+                line
+            })
+            .collect();
+        while lines.last().is_some_and(|line| line.spans.is_empty()) {
+            lines.pop();
+        }
+        lines
+    }
+}
+
+// ── Render ───────────────────────────────────────────────────────────────────
+
+/// The edges between known nodes, and the graph handed to the layered
+/// layout with every node and label measured
+fn layered_graph(graph: &FlowGraph) -> (Vec<&Edge>, LayeredGraph) {
+    let index: HashMap<&str, usize> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.id.as_str(), i))
+        .collect();
+    let edges: Vec<&Edge> = graph
+        .edges
+        .iter()
+        .filter(|edge| {
+            index.contains_key(edge.from.as_str()) && index.contains_key(edge.to.as_str())
+        })
+        .collect();
+    let layered = LayeredGraph {
+        direction: graph.direction,
+        nodes: graph
+            .nodes
+            .iter()
+            .map(|node| LayerNode {
+                width: UnicodeWidthStr::width(node.label.as_str()) + BOX_PAD * 2 + 2, // +2 for borders
+                height: BOX_HEIGHT,
+                group: node.subgraph,
+            })
+            .collect(),
+        edges: edges
+            .iter()
+            .map(|edge| LayerEdge {
+                from: index[edge.from.as_str()],
+                to: index[edge.to.as_str()],
+                label_width: edge.label.as_deref().map_or(0, UnicodeWidthStr::width),
+            })
+            .collect(),
+        groups: graph
+            .subgraphs
+            .iter()
+            .map(|subgraph| LayerGroup {
+                parent: subgraph.parent,
+                // ┌─ Title ─┐
+                title_width: UnicodeWidthStr::width(subgraph.title.as_str()) + 6,
+            })
+            .collect(),
+        end_room: 1,
+    };
+    (edges, layered)
+}
+
+pub(crate) fn render_graph(graph: &FlowGraph) -> Vec<DiagramLine> {
+    let (edges, layered) = layered_graph(graph);
+    let placement = layout_layered(&layered);
+
+    let mut canvas = Canvas::new(placement.width.max(1), placement.height.max(1));
+
+    // Subgraphs first, then edges (crossing their borders), subgraph titles,
+    // arrowheads and labels, with nodes on top
+    canvas.pen = DiagramRole::NodeBorder;
+    for rect in placement.groups.iter().flatten() {
+        canvas.draw_rect(rect.x, rect.y, rect.width, rect.height, SQUARE_CORNERS);
+    }
+    let drawn: Vec<(&Edge, &Route)> = edges
+        .iter()
+        .copied()
+        .zip(&placement.routes)
+        .filter(|(edge, _)| edge.style != EdgeStyle::Invisible)
+        .collect();
+    for (edge, route) in &drawn {
+        canvas.draw_path(&route.points, edge.style);
+    }
+    canvas.pen = DiagramRole::NodeLabel;
+    for (subgraph, rect) in graph.subgraphs.iter().zip(&placement.groups) {
+        if let (Some(rect), false) = (rect, subgraph.title.is_empty()) {
+            canvas.draw_title(*rect, &format!(" {} ", subgraph.title));
+        }
+    }
+    canvas.pen = DiagramRole::Arrowhead;
+    for (edge, route) in &drawn {
+        if let (true, Some(((x, y), head))) = (edge.head, arrowhead(&route.points)) {
+            canvas.set(x, y, head);
+        }
+    }
+    canvas.pen = DiagramRole::EdgeLabel;
+    for (edge, route) in &drawn {
+        if let (Some(label), Some((x, y))) = (&edge.label, route.label) {
+            canvas.draw_text(x, y, label);
+        }
+    }
+    for (node, rect) in graph.nodes.iter().zip(&placement.nodes) {
+        canvas.draw_node(*rect, &node.label, node.shape);
+    }
+
+    canvas.lines()
+}
+
+// ── Sequence diagrams ───────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+struct Participant {
+    id: String,
+    label: String,
+    /// Declared with `actor` (drawn with rounded corners)
+    actor: bool,
+}
+
+/// End of a message line: `->>` arrow, `-x` cross, `-)` open (async), `->` none
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArrowHead {
+    None,
+    Filled,
+    Cross,
+    Open,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Message {
+    from: usize,
+    to: usize,
+    label: String,
+    dashed: bool,
+    head: ArrowHead,
+    /// `->>+`: activate the receiver
+    activate: bool,
+    /// `-->>-`: deactivate the sender
+    deactivate: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NotePlacement {
+    LeftOf(usize),
+    RightOf(usize),
+    Over(usize, usize),
+}
+
+/// A part of a `loop`/`alt`/`opt`/`par` frame: the frame itself, or one of
+/// its `else`/`and` branches
+#[derive(Debug, Clone, PartialEq)]
+struct FrameSection {
+    keyword: String,
+    label: String,
+    events: Vec<SeqEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SeqEvent {
+    Message(Message),
+    Note {
+        placement: NotePlacement,
+        lines: Vec<String>,
+    },
+    Activate(usize),
+    Deactivate(usize),
+    Frame(Vec<FrameSection>),
+}
+
+#[derive(Debug, Default)]
+struct SequenceDiagram {
+    participants: Vec<Participant>,
+    events: Vec<SeqEvent>,
+}
+
+/// Frame keywords, and the keyword that starts another section of them
+const FRAME_KEYWORDS: &[(&str, Option<&str>)] = &[
+    ("loop", None),
+    ("alt", Some("else")),
+    ("opt", None),
+    ("par", Some("and")),
+    ("critical", Some("option")),
+    ("break", None),
+];
+
+/// Blocks closed by `end`; `rect` and `box` only colour things in mermaid,
+/// so they are parsed but not drawn
+enum OpenBlock {
+    Frame(Vec<FrameSection>),
+    Group,
+}
+
+fn message_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        // from, arrow, activation marker, to, optional `: label`
+        Regex::new(
+            r"^([^\s:+-][^:]*?)\s*(-->>|->>|-->|->|--x|-x|--\)|-\))\s*([+-]?)\s*([^:]+?)\s*(?::\s*(.*))?$",
+        )
+        .expect("valid message pattern")
+    })
+}
+
+fn parse_sequence(input: &str) -> Option<SequenceDiagram> {
+    let mut lines = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("%%"));
+    let header = lines.next()?;
+    if !header.eq_ignore_ascii_case("sequenceDiagram") {
+        return None;
+    }
+
+    let mut diagram = SequenceDiagram::default();
+    let mut stack: Vec<OpenBlock> = Vec::new();
+    let mut autonumber: Option<usize> = None;
+
+    for line in lines {
+        let (word, rest) = split_keyword(line);
+        // `create participant X`: participants appear when first used anyway
+        let (word, rest) = if word.eq_ignore_ascii_case("create") {
+            split_keyword(rest)
+        } else {
+            (word, rest)
+        };
+        let keyword = word.to_lowercase();
+
+        let event = match keyword.as_str() {
+            "participant" | "actor" => {
+                let (id, label) = match rest.split_once(" as ") {
+                    Some((id, label)) => (id.trim(), label.trim()),
+                    None => (rest, rest),
+                };
+                let index = participant_index(&mut diagram.participants, id);
+                diagram.participants[index].label = label.to_string();
+                diagram.participants[index].actor = keyword == "actor";
+                continue;
+            }
+            "autonumber" => {
+                autonumber = Some(1);
+                continue;
+            }
+            "activate" => SeqEvent::Activate(participant_index(&mut diagram.participants, rest)),
+            "deactivate" => {
+                SeqEvent::Deactivate(participant_index(&mut diagram.participants, rest))
+            }
+            "note" => match parse_note(rest, &mut diagram.participants) {
+                Some(note) => note,
+                None => continue,
+            },
+            "end" => {
+                if let Some(OpenBlock::Frame(sections)) = stack.pop() {
+                    push_event(&mut stack, &mut diagram.events, SeqEvent::Frame(sections));
+                }
+                continue;
+            }
+            "rect" | "box" => {
+                stack.push(OpenBlock::Group);
+                continue;
+            }
+            "title" | "destroy" | "links" | "link" | "properties" | "details" => continue,
+            _ => {
+                if FRAME_KEYWORDS.iter().any(|(frame, _)| *frame == keyword) {
+                    stack.push(OpenBlock::Frame(vec![FrameSection {
+                        keyword,
+                        label: rest.to_string(),
+                        events: Vec::new(),
+                    }]));
+                    continue;
+                }
+                if let Some(sections) = innermost_frame(&mut stack) {
+                    let section_keyword = FRAME_KEYWORDS
+                        .iter()
+                        .find(|(frame, _)| *frame == sections[0].keyword)
+                        .and_then(|(_, section)| *section);
+                    if section_keyword == Some(keyword.as_str()) {
+                        sections.push(FrameSection {
+                            keyword,
+                            label: rest.to_string(),
+                            events: Vec::new(),
+                        });
+                        continue;
+                    }
+                }
+                match parse_message(line, &mut diagram.participants) {
+                    Some(mut message) => {
+                        if let Some(number) = autonumber.as_mut() {
+                            message.label = format!("{}. {}", number, message.label)
+                                .trim_end()
+                                .to_string();
+                            *number += 1;
+                        }
+                        SeqEvent::Message(message)
+                    }
+                    None => continue,
+                }
+            }
+        };
+        push_event(&mut stack, &mut diagram.events, event);
+    }
+
+    // Frames left open at the end close there
+    while let Some(block) = stack.pop() {
+        if let OpenBlock::Frame(sections) = block {
+            push_event(&mut stack, &mut diagram.events, SeqEvent::Frame(sections));
+        }
+    }
+
+    if diagram.participants.is_empty() {
+        return None;
+    }
+    Some(diagram)
+}
+
+/// First word of a line and the (trimmed) rest
+fn split_keyword(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (line, ""),
+    }
+}
+
+/// Index of a participant, adding it if this is its first appearance
+fn participant_index(participants: &mut Vec<Participant>, id: &str) -> usize {
+    let id = id.trim();
+    if let Some(index) = participants.iter().position(|p| p.id == id) {
+        return index;
+    }
+    participants.push(Participant {
+        id: id.to_string(),
+        label: id.to_string(),
+        actor: false,
+    });
+    participants.len() - 1
+}
+
+fn innermost_frame(stack: &mut [OpenBlock]) -> Option<&mut Vec<FrameSection>> {
+    stack.iter_mut().rev().find_map(|block| match block {
+        OpenBlock::Frame(sections) => Some(sections),
+        OpenBlock::Group => None,
+    })
+}
+
+/// Add an event to the innermost open frame, or to the diagram
+fn push_event(stack: &mut [OpenBlock], events: &mut Vec<SeqEvent>, event: SeqEvent) {
+    match innermost_frame(stack).and_then(|sections| sections.last_mut()) {
+        Some(section) => section.events.push(event),
+        None => events.push(event),
+    }
+}
+
+/// `right of A: text`, `left of A: text`, `over A: text`, `over A,B: text`
+fn parse_note(rest: &str, participants: &mut Vec<Participant>) -> Option<SeqEvent> {
+    let (place, text) = rest.split_once(':')?;
+    let (position, who) = split_keyword(place.trim());
+    let placement = match position.to_lowercase().as_str() {
+        "right" | "left" => {
+            let who = who.strip_prefix("of")?.trim();
+            let index = participant_index(participants, who);
+            if position.eq_ignore_ascii_case("right") {
+                NotePlacement::RightOf(index)
+            } else {
+                NotePlacement::LeftOf(index)
+            }
+        }
+        "over" => {
+            let mut ids = who.split(',');
+            let first = participant_index(participants, ids.next()?);
+            let last = match ids.next() {
+                Some(id) => participant_index(participants, id),
+                None => first,
+            };
+            NotePlacement::Over(first, last)
+        }
+        _ => return None,
+    };
+    Some(SeqEvent::Note {
+        placement,
+        lines: text_lines(text),
+    })
+}
+
+/// Split a label on mermaid's `<br>` line breaks
+fn text_lines(text: &str) -> Vec<String> {
+    let mut text = text.trim().to_string();
+    for br in ["<br/>", "<br />", "<br>", "<BR>", "<BR/>"] {
+        text = text.replace(br, "\n");
+    }
+    text.lines().map(|line| line.trim().to_string()).collect()
+}
+
+fn parse_message(line: &str, participants: &mut Vec<Participant>) -> Option<Message> {
+    let captures = message_pattern().captures(line)?;
+    let arrow = &captures[2];
+    let marker = &captures[3];
+    let from = participant_index(participants, &captures[1]);
+    let to = participant_index(participants, &captures[4]);
+    let head = match arrow.trim_start_matches('-') {
+        ">>" => ArrowHead::Filled,
+        "x" => ArrowHead::Cross,
+        ")" => ArrowHead::Open,
+        _ => ArrowHead::None,
+    };
+    Some(Message {
+        from,
+        to,
+        label: captures
+            .get(5)
+            .map_or(String::new(), |label| text_lines(label.as_str()).join(" ")),
+        dashed: arrow.starts_with("--"),
+        head,
+        activate: marker == "+",
+        deactivate: marker == "-",
+    })
+}
+
+// Layout: participants are columns, events stack downwards. Everything is
+// first placed as drawing operations in signed coordinates (notes and
+// frames may stick out left of the first participant), then drawn.
+
+/// Columns between neighbouring participant boxes
+const SEQ_GAP: i32 = 3;
+/// Row where events start, below the participant boxes and one blank row
+const SEQ_FIRST_ROW: i32 = BOX_HEIGHT as i32 + 1;
+
+#[derive(Debug)]
+enum SeqOp {
+    Lifeline {
+        x: i32,
+        top: i32,
+        bottom: i32,
+    },
+    Activation {
+        x: i32,
+        top: i32,
+        bottom: i32,
+    },
+    Frame {
+        x: i32,
+        width: i32,
+        top: i32,
+        bottom: i32,
+        title: String,
+        dividers: Vec<(i32, String)>,
+    },
+    Note {
+        x: i32,
+        y: i32,
+        width: i32,
+        lines: Vec<String>,
+    },
+    Arrow {
+        from: i32,
+        to: i32,
+        y: i32,
+        dashed: bool,
+        head: ArrowHead,
+    },
+    SelfArrow {
+        x: i32,
+        y: i32,
+        dashed: bool,
+        head: ArrowHead,
+    },
+    Text {
+        x: i32,
+        y: i32,
+        text: String,
+    },
+    Participant {
+        x: i32,
+        y: i32,
+        width: i32,
+        label: String,
+        actor: bool,
+    },
+    /// Where a lifeline meets a participant box
+    Joint {
+        x: i32,
+        y: i32,
+        ch: char,
+    },
+}
+
+impl SeqOp {
+    /// Columns covered, for sizing frames and the canvas
+    fn x_range(&self) -> Option<(i32, i32)> {
+        match self {
+            SeqOp::Frame { x, width, .. }
+            | SeqOp::Note { x, width, .. }
+            | SeqOp::Participant { x, width, .. } => Some((*x, x + width - 1)),
+            SeqOp::Arrow { from, to, .. } => Some((*from.min(to), *from.max(to))),
+            SeqOp::SelfArrow { x, .. } => Some((*x, x + 3)),
+            SeqOp::Text { x, text, .. } => Some((*x, x + text_width(text) - 1)),
+            SeqOp::Lifeline { .. } | SeqOp::Activation { .. } | SeqOp::Joint { .. } => None,
+        }
+    }
+
+    /// Drawing order: lifelines under everything, boxes on top
+    fn layer(&self) -> u8 {
+        match self {
+            SeqOp::Lifeline { .. } => 0,
+            SeqOp::Activation { .. } => 1,
+            SeqOp::Frame { .. } => 2,
+            SeqOp::Note { .. } | SeqOp::Arrow { .. } | SeqOp::SelfArrow { .. } => 3,
+            SeqOp::Text { .. } | SeqOp::Participant { .. } => 4,
+            SeqOp::Joint { .. } => 5,
+        }
+    }
+
+    fn draw(&self, canvas: &mut Canvas, offset: i32) {
+        let at = |x: i32| (x + offset).max(0) as usize;
+        let row = |y: i32| y.max(0) as usize;
+        match self {
+            SeqOp::Lifeline { x, top, bottom } | SeqOp::Activation { x, top, bottom } => {
+                let (ch, pen) = if matches!(self, SeqOp::Lifeline { .. }) {
+                    ('│', DiagramRole::Edge)
+                } else {
+                    ('┃', DiagramRole::NodeBorder)
+                };
+                canvas.pen = pen;
+                for y in *top..=*bottom {
+                    canvas.set(at(*x), row(y), ch);
+                }
+            }
+            SeqOp::Frame {
+                x,
+                width,
+                top,
+                bottom,
+                title,
+                dividers,
+            } => {
+                let height = (bottom - top + 1) as usize;
+                canvas.pen = DiagramRole::NodeBorder;
+                canvas.draw_rect(at(*x), row(*top), *width as usize, height, SQUARE_CORNERS);
+                for (y, _) in dividers {
+                    canvas.set(at(*x), row(*y), '├');
+                    for column in x + 1..x + width - 1 {
+                        canvas.set(at(column), row(*y), '┄');
+                    }
+                    canvas.set(at(x + width - 1), row(*y), '┤');
+                }
+                canvas.pen = DiagramRole::NodeLabel;
+                canvas.draw_text(at(x + 2), row(*top), title);
+                for (y, title) in dividers {
+                    canvas.draw_text(at(x + 2), row(*y), title);
+                }
+            }
+            SeqOp::Note { x, y, width, lines } => {
+                let height = lines.len() + 2;
+                // Notes hide the lifelines behind them
+                canvas.pen = DiagramRole::NodeBorder;
+                for r in 0..height {
+                    for c in 0..*width as usize {
+                        canvas.set(at(*x) + c, row(*y) + r, ' ');
+                    }
+                }
+                canvas.draw_rect(at(*x), row(*y), *width as usize, height, SQUARE_CORNERS);
+                canvas.pen = DiagramRole::NodeLabel;
+                for (i, line) in lines.iter().enumerate() {
+                    canvas.draw_text(at(x + 2), row(*y) + 1 + i, line);
+                }
+            }
+            SeqOp::Arrow {
+                from,
+                to,
+                y,
+                dashed,
+                head,
+            } => {
+                let line = if *dashed { '┄' } else { '─' };
+                let (start, end, tip, ch) = if to > from {
+                    let ch = match head {
+                        ArrowHead::None => line,
+                        ArrowHead::Filled => '▶',
+                        ArrowHead::Cross => '×',
+                        ArrowHead::Open => '▷',
+                    };
+                    (from + 1, to - 1, to - 1, ch)
+                } else {
+                    let ch = match head {
+                        ArrowHead::None => line,
+                        ArrowHead::Filled => '◀',
+                        ArrowHead::Cross => '×',
+                        ArrowHead::Open => '◁',
+                    };
+                    (to + 1, from - 1, to + 1, ch)
+                };
+                canvas.pen = DiagramRole::Edge;
+                for x in start..=end {
+                    canvas.set(at(x), row(*y), line);
+                }
+                if *head != ArrowHead::None {
+                    canvas.pen = DiagramRole::Arrowhead;
+                }
+                canvas.set(at(tip), row(*y), ch);
+            }
+            SeqOp::SelfArrow { x, y, dashed, head } => {
+                let (line, side) = if *dashed {
+                    ('┄', '┆')
+                } else {
+                    ('─', '│')
+                };
+                let tip = match head {
+                    ArrowHead::None => line,
+                    ArrowHead::Filled => '◀',
+                    ArrowHead::Cross => '×',
+                    ArrowHead::Open => '◁',
+                };
+                canvas.pen = DiagramRole::Edge;
+                canvas.set(at(x + 1), row(*y), line);
+                canvas.set(at(x + 2), row(*y), line);
+                canvas.set(at(x + 3), row(*y), '┐');
+                canvas.set(at(x + 3), row(y + 1), side);
+                canvas.set(at(x + 2), row(y + 2), line);
+                canvas.set(at(x + 3), row(y + 2), '┘');
+                if *head != ArrowHead::None {
+                    canvas.pen = DiagramRole::Arrowhead;
+                }
+                canvas.set(at(x + 1), row(y + 2), tip);
+            }
+            SeqOp::Text { x, y, text } => {
+                canvas.pen = DiagramRole::EdgeLabel;
+                canvas.draw_text(at(*x), row(*y), text);
+            }
+            SeqOp::Participant {
+                x,
+                y,
+                width,
+                label,
+                actor,
+            } => {
+                let corners = if *actor {
+                    ROUND_CORNERS
+                } else {
+                    SQUARE_CORNERS
+                };
+                canvas.pen = DiagramRole::NodeBorder;
+                canvas.draw_rect(at(*x), row(*y), *width as usize, BOX_HEIGHT, corners);
+                let pad = (width - 2 - text_width(label)).max(0) / 2;
+                canvas.pen = DiagramRole::NodeLabel;
+                canvas.draw_text(at(x + 1 + pad), row(*y) + 1, label);
+            }
+            SeqOp::Joint { x, y, ch } => {
+                canvas.pen = DiagramRole::NodeBorder;
+                canvas.set(at(*x), row(*y), *ch);
+            }
+        }
+    }
+}
+
+fn text_width(text: &str) -> i32 {
+    UnicodeWidthStr::width(text) as i32
+}
+
+/// Walks the events top to bottom, collecting drawing operations
+struct SequenceLayout {
+    centers: Vec<i32>,
+    ops: Vec<SeqOp>,
+    /// Next free row
+    y: i32,
+    /// Start rows of each participant's open activations
+    activations: Vec<Vec<i32>>,
+}
+
+impl SequenceLayout {
+    fn events(&mut self, events: &[SeqEvent]) {
+        for event in events {
+            match event {
+                SeqEvent::Message(message) => self.message(message),
+                SeqEvent::Note { placement, lines } => self.note(*placement, lines),
+                SeqEvent::Activate(p) => self.activations[*p].push(self.y),
+                SeqEvent::Deactivate(p) => self.deactivate(*p, self.y - 1),
+                SeqEvent::Frame(sections) => self.frame(sections),
+            }
+        }
+    }
+
+    fn deactivate(&mut self, participant: usize, bottom: i32) {
+        if let Some(top) = self.activations[participant].pop() {
+            self.ops.push(SeqOp::Activation {
+                x: self.centers[participant],
+                top,
+                bottom: bottom.max(top),
+            });
+        }
+    }
+
+    fn message(&mut self, message: &Message) {
+        let from = self.centers[message.from];
+        let to = self.centers[message.to];
+        let arrow_row = if message.from == message.to {
+            // A loop out to the right and back
+            self.ops.push(SeqOp::SelfArrow {
+                x: from,
+                y: self.y,
+                dashed: message.dashed,
+                head: message.head,
+            });
+            if !message.label.is_empty() {
+                self.ops.push(SeqOp::Text {
+                    x: from + 5,
+                    y: self.y,
+                    text: message.label.clone(),
+                });
+            }
+            self.y += 3;
+            self.y - 1
+        } else {
+            // Label centred above the arrow
+            if !message.label.is_empty() {
+                self.ops.push(SeqOp::Text {
+                    x: (from + to) / 2 - text_width(&message.label) / 2,
+                    y: self.y,
+                    text: message.label.clone(),
+                });
+                self.y += 1;
+            }
+            self.ops.push(SeqOp::Arrow {
+                from,
+                to,
+                y: self.y,
+                dashed: message.dashed,
+                head: message.head,
+            });
+            self.y += 1;
+            self.y - 1
+        };
+        if message.activate {
+            self.activations[message.to].push(arrow_row);
+        }
+        if message.deactivate {
+            self.deactivate(message.from, arrow_row);
+        }
+    }
+
+    fn note(&mut self, placement: NotePlacement, lines: &[String]) {
+        let mut width = lines.iter().map(|l| text_width(l)).max().unwrap_or(0) + 4;
+        let x = match placement {
+            NotePlacement::RightOf(p) => self.centers[p] + 2,
+            NotePlacement::LeftOf(p) => self.centers[p] - 1 - width,
+            NotePlacement::Over(a, b) => {
+                let left = self.centers[a].min(self.centers[b]);
+                let right = self.centers[a].max(self.centers[b]);
+                width = width.max(right - left + 5);
+                (left + right) / 2 - width / 2
+            }
+        };
+        self.ops.push(SeqOp::Note {
+            x,
+            y: self.y,
+            width,
+            lines: lines.to_vec(),
+        });
+        self.y += lines.len() as i32 + 2;
+    }
+
+    fn frame(&mut self, sections: &[FrameSection]) {
+        let title = |section: &FrameSection| {
+            if section.label.is_empty() {
+                format!("[{}]", section.keyword)
+            } else {
+                format!("[{}] {}", section.keyword, section.label)
+            }
+        };
+        let top = self.y;
+        let first_op = self.ops.len();
+        self.y += 1;
+        let mut dividers = Vec::new();
+        for (i, section) in sections.iter().enumerate() {
+            if i > 0 {
+                dividers.push((self.y, title(section)));
+                self.y += 1;
+            }
+            self.events(&section.events);
+        }
+        let bottom = self.y;
+        self.y += 1;
+
+        // Wrap whatever the frame contains, or every participant if nothing
+        let (left, right) = self.ops[first_op..]
+            .iter()
+            .filter_map(SeqOp::x_range)
+            .reduce(|(a, b), (c, d)| (a.min(c), b.max(d)))
+            .unwrap_or((self.centers[0], self.centers[self.centers.len() - 1]));
+        let title = title(&sections[0]);
+        let widest = dividers
+            .iter()
+            .map(|(_, t)| text_width(t))
+            .chain([text_width(&title)])
+            .max()
+            .unwrap_or(0);
+        let width = (right - left + 5).max(widest + 4);
+        self.ops.push(SeqOp::Frame {
+            x: left - 2,
+            width,
+            top,
+            bottom,
+            title,
+            dividers,
+        });
+    }
+}
+
+/// Participant box widths and lifeline columns, spaced so that labels,
+/// self-messages and notes fit between neighbours
+fn participant_columns(diagram: &SequenceDiagram) -> (Vec<i32>, Vec<i32>) {
+    let widths: Vec<i32> = diagram
+        .participants
+        .iter()
+        .map(|p| text_width(&p.label) + BOX_PAD as i32 * 2 + 2)
+        .collect();
+    let mut centers: Vec<i32> = Vec::with_capacity(widths.len());
+    for (i, width) in widths.iter().enumerate() {
+        centers.push(match i {
+            0 => width / 2,
+            _ => centers[i - 1] + (widths[i - 1] - widths[i - 1] / 2) + SEQ_GAP + width / 2,
+        });
+    }
+
+    fn spacing(events: &[SeqEvent], count: usize, needs: &mut Vec<(usize, usize, i32)>) {
+        for event in events {
+            match event {
+                SeqEvent::Message(m) if m.from == m.to => {
+                    if m.from + 1 < count {
+                        needs.push((m.from, m.from + 1, text_width(&m.label) + 7));
+                    }
+                }
+                SeqEvent::Message(m) => {
+                    let (left, right) = (m.from.min(m.to), m.from.max(m.to));
+                    needs.push((left, right, text_width(&m.label) + 4));
+                }
+                SeqEvent::Note { placement, lines } => {
+                    let width = lines.iter().map(|l| text_width(l)).max().unwrap_or(0) + 4;
+                    match *placement {
+                        NotePlacement::RightOf(p) if p + 1 < count => {
+                            needs.push((p, p + 1, width + 3))
+                        }
+                        NotePlacement::LeftOf(p) if p > 0 => needs.push((p - 1, p, width + 3)),
+                        _ => {}
+                    }
+                }
+                SeqEvent::Frame(sections) => {
+                    for section in sections {
+                        spacing(&section.events, count, needs);
+                    }
+                }
+                SeqEvent::Activate(_) | SeqEvent::Deactivate(_) => {}
+            }
+        }
+    }
+    let mut needs = Vec::new();
+    spacing(&diagram.events, centers.len(), &mut needs);
+    // Widening a gap only ever moves participants apart, so one pass will do
+    for (left, right, distance) in needs {
+        let short = distance - (centers[right] - centers[left]);
+        if short > 0 {
+            for center in &mut centers[right..] {
+                *center += short;
+            }
+        }
+    }
+    (widths, centers)
+}
+
+fn render_sequence(diagram: &SequenceDiagram) -> Vec<DiagramLine> {
+    let (widths, centers) = participant_columns(diagram);
+    let count = centers.len();
+    let mut layout = SequenceLayout {
+        centers,
+        ops: Vec::new(),
+        y: SEQ_FIRST_ROW,
+        activations: vec![Vec::new(); count],
+    };
+    layout.events(&diagram.events);
+    for p in 0..count {
+        while !layout.activations[p].is_empty() {
+            layout.deactivate(p, layout.y - 1);
+        }
+    }
+
+    // Participants again at the bottom, lifelines in between
+    let bottom = layout.y + 1;
+    for (p, participant) in diagram.participants.iter().enumerate() {
+        let center = layout.centers[p];
+        for y in [0, bottom] {
+            layout.ops.push(SeqOp::Participant {
+                x: center - widths[p] / 2,
+                y,
+                width: widths[p],
+                label: participant.label.clone(),
+                actor: participant.actor,
+            });
+        }
+        layout.ops.push(SeqOp::Lifeline {
+            x: center,
+            top: BOX_HEIGHT as i32,
+            bottom: bottom - 1,
+        });
+        layout.ops.push(SeqOp::Joint {
+            x: center,
+            y: BOX_HEIGHT as i32 - 1,
+            ch: '┬',
+        });
+        layout.ops.push(SeqOp::Joint {
+            x: center,
+            y: bottom,
+            ch: '┴',
+        });
+    }
+
+    let (left, right) = layout
+        .ops
+        .iter()
+        .filter_map(SeqOp::x_range)
+        .reduce(|(a, b), (c, d)| (a.min(c), b.max(d)))
+        .unwrap_or((0, 0));
+    let mut canvas = Canvas::new(
+        (right - left + 1) as usize,
+        (bottom + BOX_HEIGHT as i32) as usize,
+    );
+    for layer in 0..=5 {
+        for op in layout.ops.iter().filter(|op| op.layer() == layer) {
+            op.draw(&mut canvas, -left);
+        }
+    }
+    canvas.lines()
+}
+
+// ── Class, state and ER diagrams ────────────────────────────────────────────
+//
+// All three are boxes joined by lines, so they share the flowchart layout:
+// each box is measured, placed by `layout_layered` and drawn with its
+// compartments; what differs is how the lines end.
+
+/// How a box is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BoxKind {
+    /// Classes and entities
+    Square,
+    /// States
+    Round,
+    /// `[*]` where a transition starts: ●
+    Start,
+    /// `[*]` where a transition ends: ◉
+    End,
+    /// `<<choice>>`: ◆
+    Choice,
+    /// `<<fork>>` and `<<join>>`: a bar across the flow
+    Bar,
+}
+
+#[derive(Debug, Clone)]
+struct DiagramBox {
+    id: String,
+    /// Centred lines at the top: the name, under any `«annotation»`
+    title: Vec<String>,
+    /// Left-aligned compartments below the title, each under a rule
+    sections: Vec<Vec<String>>,
+    kind: BoxKind,
+    group: Option<usize>,
+}
+
+impl DiagramBox {
+    /// Width and height on the canvas
+    fn size(&self, direction: Direction) -> (usize, usize) {
+        let across = matches!(direction, Direction::LR | Direction::RL);
+        match self.kind {
+            BoxKind::Start | BoxKind::End | BoxKind::Choice => (1, 1),
+            BoxKind::Bar if across => (1, 5),
+            BoxKind::Bar => (9, 1),
+            BoxKind::Square | BoxKind::Round => {
+                let width = self
+                    .title
+                    .iter()
+                    .chain(self.sections.iter().flatten())
+                    .map(|line| UnicodeWidthStr::width(line.as_str()))
+                    .max()
+                    .unwrap_or(0);
+                let rows = self.title.len()
+                    + self
+                        .sections
+                        .iter()
+                        .map(|section| section.len() + 1)
+                        .sum::<usize>();
+                (width + BOX_PAD * 2 + 2, rows + 2)
+            }
+        }
+    }
+}
+
+/// How a line ends at a box
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EndMarker {
+    None,
+    /// ▶ association, dependency and transitions
+    Arrow,
+    /// ▷ inheritance and realization
+    Triangle,
+    /// ◆ composition
+    Diamond,
+    /// ◇ aggregation
+    HollowDiamond,
+    /// A crow's foot for many or a bar for one, next to the box, then ○ if
+    /// there may be none or a second bar if not
+    Cardinality {
+        many: bool,
+        optional: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Relation {
+    from: String,
+    to: String,
+    label: Option<String>,
+    style: EdgeStyle,
+    from_end: EndMarker,
+    to_end: EndMarker,
+    /// Multiplicities written by each end (`A "1" --> "*" B`)
+    from_text: Option<String>,
+    to_text: Option<String>,
+}
+
+impl Relation {
+    fn new(from: &str, to: &str, label: Option<&str>) -> Self {
+        Relation {
+            from: from.to_string(),
+            to: to.to_string(),
+            label: label.and_then(|label| non_empty(label.trim_matches('"'))),
+            style: EdgeStyle::Solid,
+            from_end: EndMarker::None,
+            to_end: EndMarker::None,
+            from_text: None,
+            to_text: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BoxDiagram {
+    direction: Direction,
+    boxes: Vec<DiagramBox>,
+    relations: Vec<Relation>,
+    /// Namespaces and composite states
+    groups: Vec<Subgraph>,
+}
+
+impl BoxDiagram {
+    fn new() -> Self {
+        BoxDiagram {
+            direction: Direction::TD,
+            boxes: Vec::new(),
+            relations: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+
+    /// The box with `id`, added if it's new. Like flowchart nodes, boxes
+    /// first mentioned outside any group join the first one that lists them
+    fn ensure(&mut self, id: &str, kind: BoxKind, group: Option<usize>) -> usize {
+        if let Some(index) = self.boxes.iter().position(|b| b.id == id) {
+            let existing = &mut self.boxes[index];
+            if existing.group.is_none() {
+                existing.group = group;
+            }
+            return index;
+        }
+        self.boxes.push(DiagramBox {
+            id: id.to_string(),
+            title: if matches!(kind, BoxKind::Square | BoxKind::Round) {
+                vec![id.to_string()]
+            } else {
+                Vec::new()
+            },
+            sections: Vec::new(),
+            kind,
+            group,
+        });
+        self.boxes.len() - 1
+    }
+
+    /// Drop the empty compartments at the bottom of each box (an empty one
+    /// above a full one stays, as in a class with methods but no fields)
+    fn trim_sections(&mut self) {
+        for b in &mut self.boxes {
+            while b.sections.last().is_some_and(Vec::is_empty) {
+                b.sections.pop();
+            }
+        }
+    }
+}
+
+/// The direction after `graph`/`flowchart`/`direction`
+pub(crate) fn parse_direction(word: &str) -> Option<Direction> {
+    match word.to_uppercase().as_str() {
+        "LR" => Some(Direction::LR),
+        "RL" => Some(Direction::RL),
+        "TD" | "TB" => Some(Direction::TD),
+        "BT" => Some(Direction::BT),
+        _ => None,
+    }
+}
+
+/// Lines that only style a diagram
+fn is_styling(lower: &str) -> bool {
+    [
+        "style ",
+        "classdef ",
+        "cssclass ",
+        "click ",
+        "link ",
+        "callback ",
+    ]
+    .iter()
+    .any(|keyword| lower.starts_with(keyword))
+}
+
+// ── Class diagrams ──────────────────────────────────────────────────────────
+
+/// `A "1" <|-- "*" B : label`; an `o` end must be spaced off its class name
+fn class_relation_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r#"^([\w~`.]+)\s*(?:"([^"]*)"\s*)?(<\||\*|\so|<)?(--|\.\.)(\|>|\*|>|o\s)?\s*(?:"([^"]*)"\s*)?([\w~`.]+)\s*(?::\s*(.*))?$"#,
+        )
+        .unwrap()
+    })
+}
+
+fn parse_class_diagram(input: &str) -> Option<BoxDiagram> {
+    let mut lines = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("%%"));
+    let header = lines.next()?.to_lowercase();
+    if !header.starts_with("classdiagram") {
+        return None;
+    }
+
+    let mut diagram = BoxDiagram::new();
+    let mut namespaces: Vec<usize> = Vec::new();
+    // The class whose `{ ... }` body is open
+    let mut body: Option<usize> = None;
+
+    for line in lines {
+        let lower = line.to_lowercase();
+        let group = namespaces.last().copied();
+        if let Some(class) = body {
+            if line == "}" {
+                body = None;
+            } else if let Some(annotation) = annotation(line) {
+                diagram.boxes[class].title.insert(0, annotation);
+            } else {
+                add_member(&mut diagram.boxes[class], line);
+            }
+            continue;
+        }
+        if line == "}" {
+            namespaces.pop();
+            continue;
+        }
+        if let Some(word) = lower.strip_prefix("direction ") {
+            diagram.direction = parse_direction(word.trim()).unwrap_or(diagram.direction);
+            continue;
+        }
+        if lower.starts_with("note") || is_styling(&lower) {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("namespace ") {
+            let id = rest.trim_end_matches('{').trim().to_string();
+            diagram.groups.push(Subgraph {
+                title: id.clone(),
+                id,
+                parent: group,
+            });
+            namespaces.push(diagram.groups.len() - 1);
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("class ") {
+            let (rest, opens) = match rest.trim().strip_suffix('{') {
+                Some(rest) => (rest.trim(), true),
+                None => (rest.trim(), false),
+            };
+            let rest = rest.split(":::").next().unwrap_or(rest);
+            let (id, label) = match rest.split_once('[') {
+                Some((id, label)) => (id.trim(), Some(label.trim_end_matches(']'))),
+                None => (rest, None),
+            };
+            let class = diagram.ensure(&class_name(id), BoxKind::Square, group);
+            if let Some(label) = label {
+                let label = label.trim_matches('"').to_string();
+                *diagram.boxes[class].title.last_mut().unwrap() = label;
+            }
+            if opens {
+                body = Some(class);
+            }
+            continue;
+        }
+        if let Some(caps) = class_relation_pattern().captures(line) {
+            let end = |marker: &str| match marker.trim() {
+                "<|" | "|>" => EndMarker::Triangle,
+                "*" => EndMarker::Diamond,
+                "o" => EndMarker::HollowDiamond,
+                "<" | ">" => EndMarker::Arrow,
+                _ => EndMarker::None,
+            };
+            let text = |i: usize| caps.get(i).and_then(|m| non_empty(m.as_str()));
+            let (from, to) = (class_name(&caps[1]), class_name(&caps[7]));
+            diagram.ensure(&from, BoxKind::Square, group);
+            diagram.ensure(&to, BoxKind::Square, group);
+            let mut relation = Relation::new(&from, &to, caps.get(8).map(|m| m.as_str()));
+            relation.style = if &caps[4] == ".." {
+                EdgeStyle::Dotted
+            } else {
+                EdgeStyle::Solid
+            };
+            relation.from_end = end(caps.get(3).map_or("", |m| m.as_str()));
+            relation.to_end = end(caps.get(5).map_or("", |m| m.as_str()));
+            relation.from_text = text(2);
+            relation.to_text = text(6);
+            // Parents and wholes go above (or before) what they're made of
+            let structural = |end: EndMarker| {
+                matches!(
+                    end,
+                    EndMarker::Triangle | EndMarker::Diamond | EndMarker::HollowDiamond
+                )
+            };
+            if structural(relation.to_end) && !structural(relation.from_end) {
+                std::mem::swap(&mut relation.from, &mut relation.to);
+                std::mem::swap(&mut relation.from_end, &mut relation.to_end);
+                std::mem::swap(&mut relation.from_text, &mut relation.to_text);
+            }
+            diagram.relations.push(relation);
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("<<") {
+            if let Some((annotation, id)) = rest.split_once(">>") {
+                let class = diagram.ensure(&class_name(id.trim()), BoxKind::Square, group);
+                diagram.boxes[class]
+                    .title
+                    .insert(0, format!("«{}»", annotation.trim()));
+            }
+            continue;
+        }
+        if let Some((id, member)) = line.split_once(':') {
+            let class = diagram.ensure(&class_name(id.trim()), BoxKind::Square, group);
+            add_member(&mut diagram.boxes[class], member.trim());
+        }
+    }
+
+    diagram.trim_sections();
+    Some(diagram)
+}
+
+/// `<<interface>>` as `«interface»`
+fn annotation(line: &str) -> Option<String> {
+    let inner = line.strip_prefix("<<")?.strip_suffix(">>")?;
+    Some(format!("«{}»", inner.trim()))
+}
+
+/// `List~T~` as `List<T>`
+fn class_name(text: &str) -> String {
+    let mut open = false;
+    text.chars()
+        .map(|ch| match ch {
+            '~' => {
+                open = !open;
+                if open {
+                    '<'
+                } else {
+                    '>'
+                }
+            }
+            _ => ch,
+        })
+        .filter(|&ch| ch != '`')
+        .collect()
+}
+
+/// A field goes in the first compartment and a method, with its
+/// parentheses, in the second; `$` and `*` classifiers are dropped
+fn add_member(class: &mut DiagramBox, member: &str) {
+    let member = member.trim().trim_end_matches(['$', '*']);
+    if member.is_empty() {
+        return;
+    }
+    if class.sections.len() < 2 {
+        class.sections.resize(2, Vec::new());
+    }
+    let section = usize::from(member.contains('('));
+    class.sections[section].push(class_name(member));
+}
+
+// ── State diagrams ──────────────────────────────────────────────────────────
+
+/// `A --> B : label`
+fn transition_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(\S+?)\s*-->\s*([^\s:]+)\s*(?::\s*(.*))?$").unwrap())
+}
+
+/// The id of `[*]` in a composite state (or at the top), which is a
+/// different pseudo-state at the start of a transition than at its end
+fn pseudo_state(start: bool, scope: Option<usize>) -> String {
+    let scope = scope.map_or(0, |s| s + 1);
+    if start {
+        format!("[*]start{scope}")
+    } else {
+        format!("[*]end{scope}")
+    }
+}
+
+fn parse_state_diagram(input: &str) -> Option<BoxDiagram> {
+    let mut lines = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("%%"));
+    let header = lines.next()?.to_lowercase();
+    if !header.starts_with("statediagram") {
+        return None;
+    }
+
+    let mut diagram = BoxDiagram::new();
+    let mut composites: Vec<usize> = Vec::new();
+    let mut in_note = false;
+    let state_id = |text: &str| text.split(":::").next().unwrap_or(text).trim().to_string();
+
+    for line in lines {
+        let lower = line.to_lowercase();
+        let scope = composites.last().copied();
+        if in_note {
+            in_note = lower != "end note";
+            continue;
+        }
+        if lower.starts_with("note ") {
+            in_note = !line.contains(':');
+            continue;
+        }
+        if line == "}" {
+            composites.pop();
+            continue;
+        }
+        if let Some(word) = lower.strip_prefix("direction ") {
+            if scope.is_none() {
+                diagram.direction = parse_direction(word.trim()).unwrap_or(diagram.direction);
+            }
+            continue;
+        }
+        if line == "--" || lower.starts_with("hide ") || lower.starts_with("scale ") {
+            continue;
+        }
+        if is_styling(&lower) || lower.starts_with("class ") {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("state ") {
+            let (rest, opens) = match rest.trim().strip_suffix('{') {
+                Some(rest) => (rest.trim(), true),
+                None => (rest.trim(), false),
+            };
+            // `state "Description" as Id` or `state Id <<choice>>`
+            let (title, rest) = match rest.strip_prefix('"').and_then(|r| r.split_once('"')) {
+                Some((title, rest)) => (Some(title.to_string()), rest.trim()),
+                None => (None, rest),
+            };
+            let rest = rest.strip_prefix("as ").unwrap_or(rest).trim();
+            let (id, kind) = match rest.split_once("<<") {
+                Some((id, stereotype)) => {
+                    let kind = match stereotype.trim_end_matches(">>").trim() {
+                        "choice" => BoxKind::Choice,
+                        "fork" | "join" => BoxKind::Bar,
+                        _ => BoxKind::Round,
+                    };
+                    (state_id(id), kind)
+                }
+                None => (state_id(rest), BoxKind::Round),
+            };
+            let (id, description) = match id.split_once(':') {
+                Some((id, description)) => (id.trim().to_string(), Some(description.trim())),
+                None => (id, None),
+            };
+            if opens {
+                diagram.groups.push(Subgraph {
+                    title: title.unwrap_or_else(|| id.clone()),
+                    id,
+                    parent: scope,
+                });
+                composites.push(diagram.groups.len() - 1);
+                continue;
+            }
+            let state = diagram.ensure(&id, kind, scope);
+            diagram.boxes[state].kind = kind;
+            if kind != BoxKind::Round {
+                diagram.boxes[state].title.clear();
+            } else if let Some(title) = title {
+                diagram.boxes[state].title = vec![title];
+            }
+            if let Some(description) = description {
+                add_description(&mut diagram.boxes[state], description);
+            }
+            continue;
+        }
+        if let Some(caps) = transition_pattern().captures(line) {
+            let end = |text: &str, start: bool| {
+                if text == "[*]" {
+                    let kind = if start { BoxKind::Start } else { BoxKind::End };
+                    (pseudo_state(start, scope), kind)
+                } else {
+                    (state_id(text), BoxKind::Round)
+                }
+            };
+            let (from, from_kind) = end(&caps[1], true);
+            let (to, to_kind) = end(&caps[2], false);
+            diagram.ensure(&from, from_kind, scope);
+            diagram.ensure(&to, to_kind, scope);
+            let mut relation = Relation::new(&from, &to, caps.get(3).map(|m| m.as_str()));
+            relation.to_end = EndMarker::Arrow;
+            diagram.relations.push(relation);
+            continue;
+        }
+        if let Some((id, description)) = line.split_once(':') {
+            let state = diagram.ensure(&state_id(id), BoxKind::Round, scope);
+            add_description(&mut diagram.boxes[state], description.trim());
+            continue;
+        }
+        diagram.ensure(&state_id(line), BoxKind::Round, scope);
+    }
+
+    connect_composite_states(&mut diagram);
+    diagram.trim_sections();
+    Some(diagram)
+}
+
+fn add_description(state: &mut DiagramBox, description: &str) {
+    if description.is_empty() {
+        return;
+    }
+    if state.sections.is_empty() {
+        state.sections.push(Vec::new());
+    }
+    state.sections[0].push(description.to_string());
+}
+
+/// Transitions into a composite state go to its `[*]` start and those out
+/// of it leave from its `[*]` end (or its first state without one), as
+/// there's no drawing an arrow to a whole box
+fn connect_composite_states(diagram: &mut BoxDiagram) {
+    let groups = &diagram.groups;
+    let inside = |b: &DiagramBox, group: usize| ancestry_of(groups, b.group).contains(&group);
+    for (g, group) in groups.iter().enumerate() {
+        let pick = |start: bool| {
+            let pseudo = pseudo_state(start, Some(g));
+            diagram
+                .boxes
+                .iter()
+                .find(|b| b.id == pseudo)
+                .or_else(|| {
+                    diagram
+                        .boxes
+                        .iter()
+                        .find(|b| inside(b, g) && !matches!(b.kind, BoxKind::Start | BoxKind::End))
+                })
+                .map(|b| b.id.clone())
+        };
+        let (entry, exit) = (pick(true), pick(false));
+        for relation in &mut diagram.relations {
+            if let (true, Some(entry)) = (relation.to == group.id, &entry) {
+                relation.to = entry.clone();
+            }
+            if let (true, Some(exit)) = (relation.from == group.id, &exit) {
+                relation.from = exit.clone();
+            }
+        }
+    }
+    let ids: HashSet<&str> = groups.iter().map(|group| group.id.as_str()).collect();
+    diagram.boxes.retain(|b| !ids.contains(b.id.as_str()));
+}
+
+/// `group` and the groups around it
+fn ancestry_of(groups: &[Subgraph], group: Option<usize>) -> Vec<usize> {
+    let mut chain = Vec::new();
+    let mut current = group;
+    while let Some(g) = current {
+        chain.push(g);
+        current = groups[g].parent;
+    }
+    chain
+}
+
+// ── ER diagrams ─────────────────────────────────────────────────────────────
+
+/// `CUSTOMER ||--o{ ORDER : places`
+fn er_relation_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"^([\w-]+)\s*(\|o|\|\||\}o|\}\|)(--|\.\.)(o\||\|\||o\{|\|\{)\s*([\w-]+)\s*(?::\s*(.*))?$",
+        )
+        .unwrap()
+    })
+}
+
+/// `type name PK,FK "comment"`
+fn er_attribute_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r#"^(\S+)\s+(\S+)(?:\s+((?:PK|FK|UK)(?:\s*,\s*(?:PK|FK|UK))*))?(?:\s+"([^"]*)")?$"#,
+        )
+        .unwrap()
+    })
+}
+
+fn parse_er_diagram(input: &str) -> Option<BoxDiagram> {
+    let mut lines = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("%%"));
+    let header = lines.next()?.to_lowercase();
+    if !header.starts_with("erdiagram") {
+        return None;
+    }
+
+    let mut diagram = BoxDiagram::new();
+    // Attribute columns per entity, lined up once they're all read
+    let mut attributes: HashMap<usize, Vec<Vec<String>>> = HashMap::new();
+    let mut body: Option<usize> = None;
+
+    for line in lines {
+        let lower = line.to_lowercase();
+        if let Some(entity) = body {
+            if line == "}" {
+                body = None;
+            } else if let Some(caps) = er_attribute_pattern().captures(line) {
+                let column = |i: usize| caps.get(i).map_or("", |m| m.as_str());
+                attributes.entry(entity).or_default().push(vec![
+                    column(1).to_string(),
+                    column(2).to_string(),
+                    column(3).replace(' ', ""),
+                    caps.get(4)
+                        .map_or(String::new(), |m| format!("\"{}\"", m.as_str())),
+                ]);
+            }
+            continue;
+        }
+        if let Some(word) = lower.strip_prefix("direction ") {
+            diagram.direction = parse_direction(word.trim()).unwrap_or(diagram.direction);
+            continue;
+        }
+        if is_styling(&lower) || lower.starts_with("class ") {
+            continue;
+        }
+        if let Some(caps) = er_relation_pattern().captures(line) {
+            let cardinality = |marker: &str| EndMarker::Cardinality {
+                many: marker.contains(['{', '}']),
+                optional: marker.contains('o'),
+            };
+            diagram.ensure(&caps[1], BoxKind::Square, None);
+            diagram.ensure(&caps[5], BoxKind::Square, None);
+            let mut relation = Relation::new(&caps[1], &caps[5], caps.get(6).map(|m| m.as_str()));
+            relation.style = if &caps[3] == ".." {
+                EdgeStyle::Dotted
+            } else {
+                EdgeStyle::Solid
+            };
+            relation.from_end = cardinality(&caps[2]);
+            relation.to_end = cardinality(&caps[4]);
+            diagram.relations.push(relation);
+            continue;
+        }
+        let (id, opens) = match line.strip_suffix('{') {
+            Some(id) => (id.trim(), true),
+            None => (line, false),
+        };
+        if id
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-')
+        {
+            let entity = diagram.ensure(id, BoxKind::Square, None);
+            if opens {
+                body = Some(entity);
+            }
+        }
+    }
+
+    for (entity, rows) in attributes {
+        let widths: Vec<usize> = (0..4)
+            .map(|column| {
+                rows.iter()
+                    .map(|row| UnicodeWidthStr::width(row[column].as_str()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let lines = rows
+            .iter()
+            .map(|row| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .filter(|(_, &width)| width > 0)
+                    .map(|(cell, &width)| {
+                        let pad = width - UnicodeWidthStr::width(cell.as_str());
+                        format!("{cell}{}", " ".repeat(pad))
+                    })
+                    .collect();
+                cells.join("  ").trim_end().to_string()
+            })
+            .collect();
+        diagram.boxes[entity].sections = vec![lines];
+    }
+    Some(diagram)
+}
+
+// ── Box diagram rendering ───────────────────────────────────────────────────
+
+impl Canvas {
+    /// A class, state or entity box: its title centred above compartments
+    /// of left-aligned lines, each under a rule
+    ///
+    /// ```text
+    /// ┌───────────────┐  ╭────────╮   ●  ◉  ◆  ━━━━━━━━━
+    /// │    Animal     │  │  Idle  │
+    /// ├───────────────┤  ╰────────╯
+    /// │  +int age     │
+    /// ├───────────────┤
+    /// │  +isMammal()  │
+    /// └───────────────┘
+    /// ```
+    fn draw_box(&mut self, rect: Rect, diagram_box: &DiagramBox) {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = rect;
+        let (mid_x, mid_y) = (x + width / 2, y + height / 2);
+        self.pen = DiagramRole::NodeBorder;
+        let corners = match diagram_box.kind {
+            BoxKind::Start => return self.set(mid_x, mid_y, '●'),
+            BoxKind::End => return self.set(mid_x, mid_y, '◉'),
+            BoxKind::Choice => return self.set(mid_x, mid_y, '◆'),
+            BoxKind::Bar => {
+                let bar = if width >= height { '━' } else { '┃' };
+                for row in y..y + height {
+                    for column in x..x + width {
+                        self.set(column, row, bar);
+                    }
+                }
+                return;
+            }
+            BoxKind::Square => SQUARE_CORNERS,
+            BoxKind::Round => ROUND_CORNERS,
+        };
+        self.draw_rect(x, y, width, height, corners);
+
+        // A lone title sits in the middle of boxes grown for their edges
+        let mut row = if diagram_box.sections.is_empty() {
+            y + (height - diagram_box.title.len()) / 2
+        } else {
+            y + 1
+        };
+        self.pen = DiagramRole::NodeLabel;
+        for line in &diagram_box.title {
+            let line_width = UnicodeWidthStr::width(line.as_str());
+            self.draw_text(x + (width - line_width) / 2, row, line);
+            row += 1;
+        }
+        for section in &diagram_box.sections {
+            self.pen = DiagramRole::NodeBorder;
+            self.set(x, row, '├');
+            for column in x + 1..x + width - 1 {
+                self.set(column, row, '─');
+            }
+            self.set(x + width - 1, row, '┤');
+            self.pen = DiagramRole::NodeLabel;
+            row += 1;
+            for line in section {
+                self.draw_text(x + 1 + BOX_PAD, row, line);
+                row += 1;
+            }
+        }
+    }
+
+    /// How a line ends at the box after the last of `points`
+    fn draw_end(&mut self, points: &[(usize, usize)], marker: EndMarker) {
+        let (Some(&(x, y)), Some(&before)) = (
+            points.last(),
+            points
+                .iter()
+                .rev()
+                .find(|&&p| p != points[points.len() - 1]),
+        ) else {
+            return;
+        };
+        // One step towards the box
+        let (dx, dy) = (
+            (x as i32 - before.0 as i32).signum(),
+            (y as i32 - before.1 as i32).signum(),
+        );
+        let pick = |right, left, down, up| match (dx, dy) {
+            (1, _) => right,
+            (-1, _) => left,
+            (_, 1) => down,
+            _ => up,
+        };
+        self.pen = DiagramRole::Arrowhead;
+        let ch = match marker {
+            EndMarker::None => return,
+            EndMarker::Arrow => pick('▶', '◀', '▼', '▲'),
+            EndMarker::Triangle => pick('▷', '◁', '▽', '△'),
+            EndMarker::Diamond => '◆',
+            EndMarker::HollowDiamond => '◇',
+            EndMarker::Cardinality { many, optional } => {
+                let far = if optional { '○' } else { '┼' };
+                self.set((x as i32 - dx) as usize, (y as i32 - dy) as usize, far);
+                if many {
+                    pick('<', '>', '⋀', '⋁')
+                } else {
+                    '┼'
+                }
+            }
+        };
+        self.set(x, y, ch);
+    }
+
+    /// A multiplicity beside the last of `points`, off the line and clear
+    /// of the box it meets
+    fn draw_end_text(&mut self, points: &[(usize, usize)], text: &str) {
+        let (Some(&(x, y)), Some(&(before_x, _))) = (
+            points.last(),
+            points
+                .iter()
+                .rev()
+                .find(|&&p| p != points[points.len() - 1]),
+        ) else {
+            return;
+        };
+        let width = UnicodeWidthStr::width(text);
+        self.pen = DiagramRole::EdgeLabel;
+        if before_x == x {
+            self.draw_text(x + 2, y, text);
+        } else if before_x < x {
+            self.draw_text((x + 1).saturating_sub(width), y.saturating_sub(1), text);
+        } else {
+            self.draw_text(x, y.saturating_sub(1), text);
+        }
+    }
+}
+
+fn render_boxes(diagram: &BoxDiagram, group_corners: [char; 4]) -> Vec<DiagramLine> {
+    let index: HashMap<&str, usize> = diagram
+        .boxes
+        .iter()
+        .enumerate()
+        .map(|(i, b)| (b.id.as_str(), i))
+        .collect();
+    let relations: Vec<&Relation> = diagram
+        .relations
+        .iter()
+        .filter(|r| index.contains_key(r.from.as_str()) && index.contains_key(r.to.as_str()))
+        .collect();
+    let cardinalities = relations.iter().any(|r| {
+        [r.from_end, r.to_end]
+            .iter()
+            .any(|end| matches!(end, EndMarker::Cardinality { .. }))
+    });
+    let layered = LayeredGraph {
+        direction: diagram.direction,
+        nodes: diagram
+            .boxes
+            .iter()
+            .map(|b| {
+                let (width, height) = b.size(diagram.direction);
+                LayerNode {
+                    width,
+                    height,
+                    group: b.group,
+                }
+            })
+            .collect(),
+        edges: relations
+            .iter()
+            .map(|r| LayerEdge {
+                from: index[r.from.as_str()],
+                to: index[r.to.as_str()],
+                label_width: r.label.as_deref().map_or(0, UnicodeWidthStr::width),
+            })
+            .collect(),
+        groups: diagram
+            .groups
+            .iter()
+            .map(|group| LayerGroup {
+                parent: group.parent,
+                title_width: UnicodeWidthStr::width(group.title.as_str()) + 6,
+            })
+            .collect(),
+        // Cardinalities take two cells
+        end_room: if cardinalities { 2 } else { 1 },
+    };
+    let placement = layout_layered(&layered);
+
+    let mut canvas = Canvas::new(placement.width.max(1), placement.height.max(1));
+    canvas.pen = DiagramRole::NodeBorder;
+    for rect in placement.groups.iter().flatten() {
+        canvas.draw_rect(rect.x, rect.y, rect.width, rect.height, group_corners);
+    }
+    for (relation, route) in relations.iter().zip(&placement.routes) {
+        canvas.draw_path(&route.points, relation.style);
+    }
+    canvas.pen = DiagramRole::NodeLabel;
+    for (group, rect) in diagram.groups.iter().zip(&placement.groups) {
+        if let (Some(rect), false) = (rect, group.title.is_empty()) {
+            canvas.draw_title(*rect, &format!(" {} ", group.title));
+        }
+    }
+    for (relation, route) in relations.iter().zip(&placement.routes) {
+        let backwards: Vec<(usize, usize)> = route.points.iter().rev().copied().collect();
+        canvas.draw_end(&route.points, relation.to_end);
+        canvas.draw_end(&backwards, relation.from_end);
+        if let Some(text) = &relation.to_text {
+            canvas.draw_end_text(&route.points, text);
+        }
+        if let Some(text) = &relation.from_text {
+            canvas.draw_end_text(&backwards, text);
+        }
+        if let (Some(label), Some((x, y))) = (&relation.label, route.label) {
+            canvas.pen = DiagramRole::EdgeLabel;
+            canvas.draw_text(x, y, label);
+        }
+    }
+    for (b, rect) in diagram.boxes.iter().zip(&placement.nodes) {
+        canvas.draw_box(*rect, b);
+    }
+    canvas.lines()
+}
+
+// ── Charts: pie, gantt and timeline ─────────────────────────────────────────
+//
+// These aren't drawn on a canvas: they're rows of text fitted to the width
+// the block is laid out at.
+
+/// Width charts are fitted to when the caller doesn't give one
+const DEFAULT_CHART_WIDTH: usize = 80;
+/// Narrowest bar area of a pie or gantt chart
+const MIN_BAR_WIDTH: usize = 10;
+
+/// Break `text` into lines at most `width` columns wide, at spaces where
+/// possible
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let needed = UnicodeWidthStr::width(line.as_str())
+            + usize::from(!line.is_empty())
+            + UnicodeWidthStr::width(word);
+        if needed > width && !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        for ch in word.chars() {
+            let ch_width = unicode_width::UnicodeWidthChar::width(ch).unwrap_or(0);
+            if UnicodeWidthStr::width(line.as_str()) + ch_width > width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            line.push(ch);
         }
-        self.set(x, y2, '▼');
     }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
 
-    fn draw_varrow_up(&mut self, x: usize, y1: usize, y2: usize) {
-        if y2 <= y1 {
-            return;
+/// `text` cut to `width` columns, ending in `…` if anything was cut
+fn truncate_text(text: &str, width: usize) -> String {
+    if UnicodeWidthStr::width(text) <= width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    for ch in text.chars() {
+        let ch_width = unicode_width::UnicodeWidthChar::width(ch).unwrap_or(0);
+        if UnicodeWidthStr::width(out.as_str()) + ch_width + 1 > width {
+            break;
         }
-        self.set(x, y1, '▲');
-        for y in y1 + 1..=y2 {
-            self.set(x, y, '│');
+        out.push(ch);
+    }
+    out.push('…');
+    out
+}
+
+/// `text` padded with spaces to `width` columns
+fn pad_text(text: &str, width: usize) -> String {
+    let pad = width.saturating_sub(UnicodeWidthStr::width(text));
+    format!("{text}{}", " ".repeat(pad))
+}
+
+/// The title of a chart, from `title ...` or the header line
+fn chart_title(line: &str) -> Option<String> {
+    let rest = line.strip_prefix("title")?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace))
+        .then(|| rest.trim().to_string())
+        .filter(|title| !title.is_empty())
+}
+
+#[derive(Debug, PartialEq)]
+struct PieChart {
+    title: Option<String>,
+    /// `showData`: write each slice's value as well as its share
+    show_data: bool,
+    slices: Vec<(String, f64)>,
+}
+
+fn parse_pie(input: &str) -> Option<PieChart> {
+    let mut lines = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("%%"));
+    let header = lines.next()?;
+    let rest = header.strip_prefix("pie")?.trim();
+    let show_data = rest.starts_with("showData");
+    let mut chart = PieChart {
+        title: chart_title(rest.trim_start_matches("showData").trim()),
+        show_data,
+        slices: Vec::new(),
+    };
+    for line in lines {
+        if let Some(title) = chart_title(line) {
+            chart.title = Some(title);
+        } else if let Some((label, value)) = line.rsplit_once(':') {
+            let Ok(value) = value.trim().parse::<f64>() else {
+                continue;
+            };
+            if value >= 0.0 {
+                let label = label.trim().trim_matches('"').to_string();
+                chart.slices.push((label, value));
+            }
         }
     }
+    (!chart.slices.is_empty()).then_some(chart)
+}
 
-    fn render(&self) -> String {
-        self.cells
-            .iter()
-            .map(|row| {
-                let s: String = row.iter().collect();
-                s.trim_end().to_string()
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
-            .trim_end_matches('\n')
-            .to_string()
+/// A bar `length` eighths of a cell long, in whole and partial blocks
+fn eighths_bar(length: usize) -> String {
+    const PARTIAL: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+    let mut bar = "█".repeat(length / 8);
+    if let partial @ 1.. = length % 8 {
+        bar.push(PARTIAL[partial]);
     }
+    bar
 }
 
-// ── Render ───────────────────────────────────────────────────────────────────
+/// A horizontal bar per slice, as long as its share of the largest
+///
+/// ```text
+/// Pets adopted
+/// Dogs  ████████████████████████████  386  50.3%
+/// Cats  ███████████████████▊           85  11.1%
+/// ```
+fn render_pie(chart: &PieChart, width: usize) -> String {
+    let total: f64 = chart.slices.iter().map(|(_, value)| value).sum();
+    let largest = chart
+        .slices
+        .iter()
+        .map(|&(_, value)| value)
+        .fold(0.0, f64::max);
+    let label_width = chart
+        .slices
+        .iter()
+        .map(|(label, _)| UnicodeWidthStr::width(label.as_str()))
+        .max()
+        .unwrap_or(0)
+        .min(width / 3);
+    let values: Vec<String> = chart
+        .slices
+        .iter()
+        .map(|&(_, value)| format!("{value}"))
+        .collect();
+    let value_width = values.iter().map(String::len).max().unwrap_or(0);
+    // "  386  50.3%"
+    let suffix_width = if chart.show_data { value_width + 2 } else { 0 } + 8;
+    let bar_width = width
+        .saturating_sub(label_width + 2 + suffix_width)
+        .max(MIN_BAR_WIDTH);
 
-fn render_graph(graph: &FlowGraph) -> String {
-    let layout = layout_graph(graph);
+    let mut lines: Vec<String> = chart.title.iter().cloned().collect();
+    for ((label, value), shown) in chart.slices.iter().zip(&values) {
+        let share = if total > 0.0 { value / total } else { 0.0 };
+        let length = if largest > 0.0 {
+            ((value / largest) * (bar_width * 8) as f64).round() as usize
+        } else {
+            0
+        };
+        let mut line = format!(
+            "{}  {}",
+            pad_text(&truncate_text(label, label_width), label_width),
+            pad_text(
+                &eighths_bar(length.max(usize::from(*value > 0.0))),
+                bar_width
+            )
+        );
+        if chart.show_data {
+            line.push_str(&format!("  {shown:>value_width$}"));
+        }
+        line.push_str(&format!("  {:>5.1}%", share * 100.0));
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+// Gantt charts keep times as fractional days since 1970-01-01
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
 
-    let mut canvas = Canvas::new(layout.canvas_width.max(1), layout.canvas_height.max(1));
+/// The year, month and day of a day count from [`days_from_civil`]
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
 
-    // Draw all nodes
-    for nl in &layout.nodes {
-        canvas.draw_box(nl.x, nl.y, nl.width, &nl.label);
+/// A pattern for dates written in a `dateFormat` (`YYYY-MM-DD`, `DD.MM.YYYY
+/// HH:mm`, ...), capturing each field under its token's name
+fn date_pattern(format: &str) -> Option<Regex> {
+    const TOKENS: [(&str, &str); 9] = [
+        ("YYYY", r"(?P<YYYY>\d{4})"),
+        ("YY", r"(?P<YY>\d{2})"),
+        ("MM", r"(?P<MM>\d{1,2})"),
+        ("M", r"(?P<MM>\d{1,2})"),
+        ("DD", r"(?P<DD>\d{1,2})"),
+        ("D", r"(?P<DD>\d{1,2})"),
+        ("HH", r"(?P<HH>\d{1,2})"),
+        ("mm", r"(?P<mm>\d{1,2})"),
+        ("ss", r"(?P<ss>\d{1,2})"),
+    ];
+    let mut pattern = String::from("^");
+    let mut rest = format.trim();
+    while !rest.is_empty() {
+        if let Some((token, group)) = TOKENS.iter().find(|(token, _)| rest.starts_with(token)) {
+            pattern.push_str(group);
+            rest = &rest[token.len()..];
+        } else {
+            let ch = rest.chars().next()?;
+            pattern.push_str(&regex::escape(&ch.to_string()));
+            rest = &rest[ch.len_utf8()..];
+        }
     }
+    pattern.push('$');
+    Regex::new(&pattern).ok()
+}
 
-    // Draw edges
-    let is_horizontal = matches!(layout.direction, Direction::LR | Direction::RL);
+fn parse_date(text: &str, pattern: &Regex) -> Option<f64> {
+    let caps = pattern.captures(text.trim())?;
+    let field = |name: &str| caps.name(name).and_then(|m| m.as_str().parse::<u32>().ok());
+    let year = match (field("YYYY"), field("YY")) {
+        (Some(year), _) => i64::from(year),
+        (None, Some(year)) => 2000 + i64::from(year),
+        (None, None) => 1970,
+    };
+    let (month, day) = (field("MM").unwrap_or(1), field("DD").unwrap_or(1));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let seconds =
+        field("HH").unwrap_or(0) * 3600 + field("mm").unwrap_or(0) * 60 + field("ss").unwrap_or(0);
+    Some(days_from_civil(year, month, day) as f64 + f64::from(seconds) / 86_400.0)
+}
 
-    for edge in &graph.edges {
-        let from_idx = layout.node_index.get(&edge.from);
-        let to_idx = layout.node_index.get(&edge.to);
+/// `3d`, `1.5w`, `12h`, ... in days
+fn parse_duration(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let split = text.find(|ch: char| !ch.is_ascii_digit() && ch != '.')?;
+    let value: f64 = text[..split].parse().ok()?;
+    let unit = match &text[split..] {
+        "ms" => 1.0 / 86_400_000.0,
+        "s" => 1.0 / 86_400.0,
+        "m" => 1.0 / 1440.0,
+        "h" => 1.0 / 24.0,
+        "d" => 1.0,
+        "w" => 7.0,
+        "M" => 30.0,
+        "y" => 365.0,
+        _ => return None,
+    };
+    Some(value * unit)
+}
 
-        if let (Some(&fi), Some(&ti)) = (from_idx, to_idx) {
-            let from = &layout.nodes[fi];
-            let to = &layout.nodes[ti];
+/// How long a task takes, the way it'd be written in the chart
+fn format_duration(days: f64) -> String {
+    if days >= 1.0 || days == 0.0 {
+        let days = (days * 10.0).round() / 10.0;
+        format!("{days}d")
+    } else {
+        format!("{}h", (days * 24.0).round())
+    }
+}
 
-            if is_horizontal {
-                // Horizontal arrow: from right edge of source to left edge of target
-                let from_right = from.x + from.width;
-                let from_mid_y = from.y + 1; // middle row of box
-                let to_left = to.x;
-                let to_mid_y = to.y + 1;
+#[derive(Debug, Clone, Default, PartialEq)]
+struct GanttTask {
+    name: String,
+    id: Option<String>,
+    start: f64,
+    end: f64,
+    done: bool,
+    active: bool,
+    crit: bool,
+    milestone: bool,
+}
 
-                if from_mid_y == to_mid_y {
-                    // Same row — straight horizontal arrow
-                    if layout.direction == Direction::RL {
-                        if to.x + to.width < from.x {
-                            canvas.draw_harrow_rev(
-                                to.x + to.width,
-                                from.x.saturating_sub(1),
-                                from_mid_y,
-                            );
-                        }
-                    } else if from_right < to_left {
-                        canvas.draw_harrow(from_right, to_left.saturating_sub(1), from_mid_y);
-                    }
-                } else {
-                    // Different rows — L-shaped route
-                    let mid_x = if layout.direction == Direction::RL {
-                        to.x + to.width + (from.x.saturating_sub(to.x + to.width)) / 2
-                    } else {
-                        from_right + (to_left.saturating_sub(from_right)) / 2
-                    };
+#[derive(Debug, PartialEq)]
+struct GanttChart {
+    title: Option<String>,
+    /// Whether dates have times of day, so the axis shows them
+    timed: bool,
+    /// Tasks under each `section` (`None` before the first)
+    sections: Vec<(Option<String>, Vec<GanttTask>)>,
+}
 
-                    // Horizontal from source to mid
-                    for x in from_right..=mid_x {
-                        canvas.set(x, from_mid_y, '─');
-                    }
-                    // Vertical from source_y to target_y
-                    let (y_start, y_end) = if from_mid_y < to_mid_y {
-                        (from_mid_y, to_mid_y)
-                    } else {
-                        (to_mid_y, from_mid_y)
-                    };
-                    for y in y_start..=y_end {
-                        canvas.set(mid_x, y, '│');
-                    }
-                    // Corner at source
-                    if from_mid_y < to_mid_y {
-                        canvas.set(mid_x, from_mid_y, '┐');
-                        canvas.set(mid_x, to_mid_y, '└');
-                    } else {
-                        canvas.set(mid_x, from_mid_y, '┘');
-                        canvas.set(mid_x, to_mid_y, '┌');
-                    }
-                    // Horizontal from mid to target
-                    for x in mid_x + 1..to_left {
-                        canvas.set(x, to_mid_y, '─');
-                    }
-                    if to_left > 0 {
-                        canvas.set(to_left.saturating_sub(1), to_mid_y, '▶');
-                    }
-                }
+fn parse_gantt(input: &str) -> Option<GanttChart> {
+    let mut lines = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("%%"));
+    if lines.next()? != "gantt" {
+        return None;
+    }
+    let mut format = "YYYY-MM-DD".to_string();
+    let mut pattern = date_pattern(&format)?;
+    let mut chart = GanttChart {
+        title: None,
+        timed: false,
+        sections: Vec::new(),
+    };
+    let mut previous_end: Option<f64> = None;
+    let mut ends: HashMap<String, (f64, f64)> = HashMap::new();
+
+    for line in lines {
+        if let Some(title) = chart_title(line) {
+            chart.title = Some(title);
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("dateFormat") {
+            format = rest.trim().to_string();
+            pattern = date_pattern(&format)?;
+            chart.timed = format.contains("HH");
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("section") {
+            chart.sections.push((non_empty(name.trim()), Vec::new()));
+            continue;
+        }
+        let Some((name, meta)) = line.split_once(':') else {
+            // axisFormat, excludes, todayMarker, tickInterval, ...
+            continue;
+        };
+
+        let mut task = GanttTask {
+            name: name.trim().to_string(),
+            ..GanttTask::default()
+        };
+        let mut fields: Vec<&str> = meta.split(',').map(str::trim).collect();
+        while let Some(&tag) = fields.first() {
+            match tag {
+                "done" => task.done = true,
+                "active" => task.active = true,
+                "crit" => task.crit = true,
+                "milestone" => task.milestone = true,
+                _ => break,
+            }
+            fields.remove(0);
+        }
+        // `end`, `start, end` or `id, start, end`
+        if fields.len() >= 3 {
+            task.id = non_empty(fields.remove(0));
+        }
+        let start_field = (fields.len() >= 2).then(|| fields.remove(0));
+        let end_field = fields.first().copied().unwrap_or("");
+
+        let start = match start_field {
+            Some(field) => match field.strip_prefix("after ") {
+                Some(ids) => ids
+                    .split_whitespace()
+                    .filter_map(|id| ends.get(id).map(|&(_, end)| end))
+                    .reduce(f64::max),
+                None => parse_date(field, &pattern),
+            },
+            None => previous_end,
+        };
+        let start = start.or(previous_end).unwrap_or(0.0);
+        let end = if let Some(id) = end_field.strip_prefix("until ") {
+            ends.get(id.trim()).map(|&(start, _)| start)
+        } else {
+            parse_date(end_field, &pattern)
+                .or_else(|| parse_duration(end_field).map(|days| start + days))
+        };
+        task.start = start;
+        task.end = end.unwrap_or(start).max(start);
+
+        if let Some(id) = &task.id {
+            ends.insert(id.clone(), (task.start, task.end));
+        }
+        previous_end = Some(task.end);
+        if chart.sections.is_empty() {
+            chart.sections.push((None, Vec::new()));
+        }
+        chart.sections.last_mut().unwrap().1.push(task);
+    }
+    chart.sections.retain(|(_, tasks)| !tasks.is_empty());
+    (!chart.sections.is_empty()).then_some(chart)
+}
+
+/// A date (or time, for timed charts) on the axis
+fn format_day(day: f64, timed: bool) -> String {
+    let whole = day.floor();
+    let (year, month, date) = civil_from_days(whole as i64);
+    if timed {
+        let minutes = ((day - whole) * 1440.0).round() as u32;
+        format!(
+            "{month:02}-{date:02} {:02}:{:02}",
+            minutes / 60,
+            minutes % 60
+        )
+    } else {
+        format!("{year}-{month:02}-{date:02}")
+    }
+}
+
+/// Tasks as bars along a date axis, grouped by section
+///
+/// ```text
+/// Release plan
+///                   2024-01-01       2024-01-11        2024-01-21
+///                   ├────────────────┼─────────────────┼─────────┤
+/// Design
+///   Research        █████████ 5d
+///   Mockups                  ░░░░░░░ 4d
+/// Launch
+///   Release                           ◆
+/// ```
+///
+/// Done tasks are `░`, active ones `▒`, critical ones `▓`, milestones `◆`
+fn render_gantt(chart: &GanttChart, width: usize) -> String {
+    let tasks = chart.sections.iter().flat_map(|(_, tasks)| tasks);
+    let first = tasks
+        .clone()
+        .map(|task| task.start)
+        .fold(f64::INFINITY, f64::min);
+    let last = tasks
+        .clone()
+        .map(|task| task.end)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let span = (last - first).max(1.0 / 24.0);
+
+    let indent = if chart.sections.iter().any(|(name, _)| name.is_some()) {
+        2
+    } else {
+        0
+    };
+    let label_width = tasks
+        .clone()
+        .map(|task| UnicodeWidthStr::width(task.name.as_str()) + indent)
+        .max()
+        .unwrap_or(0)
+        .min(width / 3)
+        .max(indent);
+    let durations: Vec<String> = tasks
+        .clone()
+        .map(|task| format_duration(task.end - task.start))
+        .collect();
+    let duration_width = durations.iter().map(String::len).max().unwrap_or(0);
+    let bar_width = width
+        .saturating_sub(label_width + 2 + duration_width + 1)
+        .max(MIN_BAR_WIDTH);
+    let column = |day: f64| (((day - first) / span) * bar_width as f64).round() as usize;
+    let margin = " ".repeat(label_width + 2);
+
+    let mut lines: Vec<String> = chart.title.iter().cloned().collect();
+
+    // Axis: dates centred over ticks, with room between them, except at
+    // the ends where they line up with the bars
+    let label_len = format_day(first, chart.timed).len();
+    let intervals = (bar_width * 2 / (label_len * 3 + 4)).max(1);
+    let ticks: Vec<usize> = (0..=intervals)
+        .map(|i| (i * (bar_width - 1)) / intervals)
+        .collect();
+    let mut dates = String::new();
+    let mut ruler: Vec<char> = vec!['─'; bar_width];
+    for (i, &tick) in ticks.iter().enumerate() {
+        ruler[tick] = match i {
+            0 => '├',
+            _ if i == intervals => '┤',
+            _ => '┼',
+        };
+        let date = format_day(
+            first + span * tick as f64 / (bar_width - 1) as f64,
+            chart.timed,
+        );
+        let at = match i {
+            0 => 0,
+            _ if i == intervals => (tick + 1).saturating_sub(date.len()),
+            _ => tick.saturating_sub(date.len() / 2),
+        };
+        let used = dates.chars().count();
+        if at >= used + usize::from(used > 0) && at + date.len() <= bar_width {
+            dates.push_str(&" ".repeat(at - used));
+            dates.push_str(&date);
+        }
+    }
+    lines.push(format!("{margin}{dates}"));
+    lines.push(format!("{margin}{}", ruler.into_iter().collect::<String>()));
+
+    let mut durations = durations.iter();
+    for (name, tasks) in &chart.sections {
+        if let Some(name) = name {
+            lines.push(truncate_text(name, width));
+        }
+        for task in tasks {
+            let duration = durations.next().unwrap();
+            let label = pad_text(
+                &truncate_text(&task.name, label_width.saturating_sub(indent)),
+                label_width - indent,
+            );
+            let start = column(task.start).min(bar_width - 1);
+            let mut line = format!("{}{label}  {}", " ".repeat(indent), " ".repeat(start));
+            if task.milestone {
+                line.push('◆');
             } else {
-                // Vertical arrow: from bottom of source to top of target
-                let from_bottom = from.y + BOX_HEIGHT;
-                let from_mid_x = from.x + from.width / 2;
-                let to_top = to.y;
-                let to_mid_x = to.x + to.width / 2;
-
-                if from_mid_x == to_mid_x {
-                    // Same column — straight vertical arrow
-                    if layout.direction == Direction::BT {
-                        if to.y + BOX_HEIGHT < from.y {
-                            canvas.draw_varrow_up(
-                                from_mid_x,
-                                to.y + BOX_HEIGHT,
-                                from.y.saturating_sub(1),
-                            );
-                        }
-                    } else if from_bottom < to_top {
-                        canvas.draw_varrow(from_mid_x, from_bottom, to_top.saturating_sub(1));
-                    }
+                let fill = if task.crit {
+                    '▓'
+                } else if task.active {
+                    '▒'
+                } else if task.done {
+                    '░'
                 } else {
-                    // Different columns — L-shaped route (down, across, down)
-                    let mid_y = from_bottom + (to_top.saturating_sub(from_bottom)) / 2;
+                    '█'
+                };
+                let end = column(task.end).clamp(start + 1, bar_width);
+                line.extend(std::iter::repeat_n(fill, end - start));
+                line.push(' ');
+                line.push_str(duration);
+            }
+            lines.push(line);
+        }
+    }
+    lines.join("\n")
+}
 
-                    // Vertical from source bottom to mid
-                    for y in from_bottom..mid_y {
-                        canvas.set(from_mid_x, y, '│');
-                    }
-                    // Horizontal from source_x to target_x at mid_y
-                    let (x_start, x_end) = if from_mid_x < to_mid_x {
-                        (from_mid_x, to_mid_x)
-                    } else {
-                        (to_mid_x, from_mid_x)
-                    };
-                    for x in x_start..=x_end {
-                        canvas.set(x, mid_y, '─');
-                    }
-                    // Corners
-                    if from_mid_x < to_mid_x {
-                        canvas.set(from_mid_x, mid_y, '╰');
-                        canvas.set(to_mid_x, mid_y, '╮');
-                    } else {
-                        canvas.set(from_mid_x, mid_y, '╯');
-                        canvas.set(to_mid_x, mid_y, '╭');
-                    }
-                    // Vertical from mid to target
-                    for y in mid_y + 1..to_top.saturating_sub(1) {
-                        canvas.set(to_mid_x, y, '│');
-                    }
-                    if to_top > 0 {
-                        canvas.set(to_mid_x, to_top.saturating_sub(1), '▼');
-                    }
-                    if to_top > 0 {
-                        canvas.set(to_mid_x, to_top.saturating_sub(1), '▼');
-                    }
+/// A period and its events
+type TimelinePeriod = (String, Vec<String>);
+
+#[derive(Debug, PartialEq)]
+struct Timeline {
+    title: Option<String>,
+    /// Periods under each `section` (`None` before the first)
+    sections: Vec<(Option<String>, Vec<TimelinePeriod>)>,
+}
+
+fn parse_timeline(input: &str) -> Option<Timeline> {
+    let mut lines = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("%%"));
+    let header = lines.next()?;
+    let rest = header.strip_prefix("timeline")?.trim();
+    let mut timeline = Timeline {
+        title: chart_title(rest),
+        sections: vec![(None, Vec::new())],
+    };
+    for line in lines {
+        if let Some(title) = chart_title(line) {
+            timeline.title = Some(title);
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("section") {
+            timeline.sections.push((non_empty(name.trim()), Vec::new()));
+            continue;
+        }
+        let mut parts = line.split(':').map(str::trim);
+        let period = parts.next().unwrap_or("");
+        let events = parts.filter(|event| !event.is_empty()).map(str::to_string);
+        let periods = &mut timeline.sections.last_mut().unwrap().1;
+        match periods.last_mut() {
+            // `: event` carries on the period above
+            Some((_, existing)) if period.is_empty() => existing.extend(events),
+            _ if period.is_empty() => {}
+            _ => periods.push((period.to_string(), events.collect())),
+        }
+    }
+    timeline
+        .sections
+        .retain(|(name, periods)| name.is_some() || !periods.is_empty());
+    timeline
+        .sections
+        .iter()
+        .any(|(_, periods)| !periods.is_empty())
+        .then_some(timeline)
+}
+
+/// Periods down the left of a line, each with its events wrapped beside it
+///
+/// ```text
+/// Social media
+///
+/// ── 2000s ──
+/// 2002 ●─ LinkedIn
+///      │
+/// 2004 ●─ Facebook
+///      │  Google
+/// ```
+fn render_timeline(timeline: &Timeline, width: usize) -> String {
+    let periods = timeline.sections.iter().flat_map(|(_, periods)| periods);
+    let period_width = periods
+        .clone()
+        .map(|(period, _)| UnicodeWidthStr::width(period.as_str()))
+        .max()
+        .unwrap_or(0)
+        .min(width / 3);
+    let event_width = width.saturating_sub(period_width + 4).max(MIN_BAR_WIDTH);
+    let spine = format!("{} │", " ".repeat(period_width));
+
+    let mut lines: Vec<String> = Vec::new();
+    if let Some(title) = &timeline.title {
+        lines.extend(wrap_text(title, width));
+        lines.push(String::new());
+    }
+    let mut first = true;
+    for (name, periods) in &timeline.sections {
+        if let Some(name) = name {
+            if !first {
+                lines.push(spine.clone());
+            }
+            lines.push(format!(
+                "── {} ──",
+                truncate_text(name, width.saturating_sub(6))
+            ));
+            first = true;
+        }
+        for (period, events) in periods {
+            if !first {
+                lines.push(spine.clone());
+            }
+            first = false;
+            let period = pad_text(&truncate_text(period, period_width), period_width);
+            let wrapped: Vec<String> = events
+                .iter()
+                .flat_map(|event| wrap_text(event, event_width))
+                .collect();
+            if wrapped.is_empty() {
+                lines.push(format!("{period} ●"));
+            }
+            for (i, event) in wrapped.iter().enumerate() {
+                if i == 0 {
+                    lines.push(format!("{period} ●─ {event}"));
+                } else {
+                    lines.push(format!("{spine}  {event}"));
                 }
             }
         }
     }
-
-    canvas.render()
+    lines.join("\n")
 }
 
 // ── Tests ───────────────────────────────────────────────────────────────────
@@ -888,6 +4506,430 @@ mod tests {
         assert_eq!(graph.edges[1].label, Some("No".to_string()));
     }
 
+    #[test]
+    fn test_parse_shapes_and_edge_styles() {
+        let input = "graph LR\n    A([Go]) -.-> B{Ok?}\n    B == yes ==> C[[Sub]]\n    \
+                     B -- no --- D((End))\n    C ---> E[/In/] ~~~ F[\\Out\\]";
+        let graph = parse_flowchart(input).unwrap();
+        let shapes: Vec<NodeShape> = graph.nodes.iter().map(|n| n.shape).collect();
+        assert_eq!(
+            shapes,
+            [
+                NodeShape::Stadium,
+                NodeShape::Diamond,
+                NodeShape::Subroutine,
+                NodeShape::Circle,
+                NodeShape::Parallelogram,
+                NodeShape::ParallelogramAlt,
+            ]
+        );
+        assert_eq!(graph.nodes[1].label, "Ok?");
+
+        let edges: Vec<(EdgeStyle, bool, Option<&str>)> = graph
+            .edges
+            .iter()
+            .map(|e| (e.style, e.head, e.label.as_deref()))
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                (EdgeStyle::Dotted, true, None),
+                (EdgeStyle::Thick, true, Some("yes")),
+                (EdgeStyle::Solid, false, Some("no")),
+                (EdgeStyle::Solid, true, None),
+                (EdgeStyle::Invisible, false, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_shapes_styles_and_labels() {
+        let output = render_mermaid("graph LR\n    A(Start) ==>|go| B{Ok?} -.- C([Done])").unwrap();
+        assert_eq!(
+            output,
+            "╭─────────╮  go  ╱───────╲     ╭────────╮\n\
+             │  Start  │━━━━━▶<  Ok?  >┄┄┄┄┄(  Done  )\n\
+             ╰─────────╯      ╲───────╱     ╰────────╯"
+        );
+
+        let output = render_mermaid("graph TD\n    A[[Load]] -->|ok| B((Exec))").unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "┌┬──────┬┐");
+        assert_eq!(lines[1], "││ Load ││");
+        assert_eq!(lines[4], "     │ ok");
+        assert!(lines.contains(&"(  Exec  )"));
+    }
+
+    fn place(input: &str) -> (FlowGraph, Placement) {
+        let graph = parse_flowchart(input).unwrap();
+        let placement = layout_layered(&layered_graph(&graph).1);
+        (graph, placement)
+    }
+
+    fn contains(outer: &Rect, inner: &Rect) -> bool {
+        outer.x < inner.x
+            && outer.y < inner.y
+            && inner.x + inner.width < outer.x + outer.width
+            && inner.y + inner.height < outer.y + outer.height
+    }
+
+    #[test]
+    fn test_back_edges() {
+        assert_eq!(
+            back_edges(3, &[(0, 1), (1, 2), (2, 0)]),
+            vec![false, false, true]
+        );
+        assert_eq!(back_edges(3, &[(0, 1), (0, 2), (1, 2)]), vec![false; 3]);
+        // Self-loops are left for the caller
+        assert_eq!(back_edges(1, &[(0, 0)]), vec![false]);
+    }
+
+    #[test]
+    fn test_cycle_drawn_back_into_upper_node() {
+        let (_, placement) = place("graph TD\n    A --> B --> C --> A");
+        let a = placement.nodes[0];
+        assert!(placement.nodes[1].y > a.y && placement.nodes[2].y > placement.nodes[1].y);
+        // C --> A climbs back up and arrives under A
+        let back = &placement.routes[2];
+        let &(x, y) = back.points.last().unwrap();
+        assert_eq!(y, a.y + a.height);
+        assert!((a.x..a.x + a.width).contains(&x));
+
+        let output = render_mermaid("graph TD\n    A --> B --> C --> A").unwrap();
+        assert_eq!(output.matches('▲').count(), 1);
+        assert_eq!(output.matches('▼').count(), 2);
+    }
+
+    #[test]
+    fn test_crossings_reduced() {
+        // Declared so that the second layer starts out as C, D and the
+        // edges cross; the layout swaps them
+        let (_, placement) =
+            place("graph TD\n    A\n    B\n    C\n    D\n    A --> D\n    B --> C");
+        assert!(placement.nodes[0].x < placement.nodes[1].x);
+        assert!(placement.nodes[3].x < placement.nodes[2].x);
+        let output =
+            render_mermaid("graph TD\n    A\n    B\n    C\n    D\n    A --> D\n    B --> C")
+                .unwrap();
+        assert!(!output.contains('┼'));
+    }
+
+    #[test]
+    fn test_routes_are_orthogonal_and_avoid_nodes() {
+        for input in [
+            "graph TD\n    A --> B --> C\n    A --> C",
+            "graph LR\n    A --> B --> C --> D\n    A --> D\n    D --> A",
+        ] {
+            let (_, placement) = place(input);
+            for route in &placement.routes {
+                for pair in route.points.windows(2) {
+                    let ((x1, y1), (x2, y2)) = (pair[0], pair[1]);
+                    assert!(x1 == x2 || y1 == y2, "{input}: diagonal segment");
+                    for node in &placement.nodes {
+                        let cells: Vec<(usize, usize)> = if x1 == x2 {
+                            (y1.min(y2)..=y1.max(y2)).map(|y| (x1, y)).collect()
+                        } else {
+                            (x1.min(x2)..=x1.max(x2)).map(|x| (x, y1)).collect()
+                        };
+                        assert!(
+                            !cells
+                                .iter()
+                                .any(|&(x, y)| (node.x..node.x + node.width).contains(&x)
+                                    && (node.y..node.y + node.height).contains(&y)),
+                            "{input}: route through a node"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_subgraphs() {
+        let graph = parse_flowchart(
+            "graph TD\n    subgraph api [Backend API]\n        A --> B\n        \
+             subgraph Storage\n            C\n        end\n    end\n    D --> api",
+        )
+        .unwrap();
+        assert_eq!(graph.subgraphs.len(), 2);
+        assert_eq!(graph.subgraphs[0].id, "api");
+        assert_eq!(graph.subgraphs[0].title, "Backend API");
+        assert_eq!(graph.subgraphs[1].title, "Storage");
+        assert_eq!(graph.subgraphs[1].parent, Some(0));
+        let group = |id: &str| graph.nodes.iter().find(|n| n.id == id).unwrap().subgraph;
+        assert_eq!(group("A"), Some(0));
+        assert_eq!(group("C"), Some(1));
+        assert_eq!(group("D"), None);
+        // The edge to the subgraph lands on its first node
+        assert!(graph.nodes.iter().all(|n| n.id != "api"));
+        assert_eq!(graph.edges[1].to, "A");
+    }
+
+    #[test]
+    fn test_subgraph_box_encloses_members() {
+        let input = "graph LR\n    subgraph one [Group One]\n        A --> B\n    end\n    C --> A";
+        let (_, placement) = place(input);
+        let group = placement.groups[0].unwrap();
+        assert!(contains(&group, &placement.nodes[0]));
+        assert!(contains(&group, &placement.nodes[1]));
+        let c = placement.nodes[2];
+        assert!(c.x + c.width <= group.x || c.y + c.height <= group.y);
+
+        let output = render_mermaid(input).unwrap();
+        assert!(output.contains("┌─ Group One ─"));
+    }
+
+    #[test]
+    fn test_parse_class_diagram() {
+        let diagram = parse_class_diagram(
+            "classDiagram\n    Duck --|> Animal\n    class Animal {\n        <<abstract>>\n        \
+             +int age\n        +eat()$\n    }\n    Animal : +List~Food~ diet\n    \
+             Pond \"1\" o-- \"*\" Duck : lives in\n    Zoo --> Animal",
+        )
+        .unwrap();
+        let animal = &diagram.boxes[1];
+        assert_eq!(animal.title, vec!["«abstract»", "Animal"]);
+        assert_eq!(
+            animal.sections,
+            vec![vec!["+int age", "+List<Food> diet"], vec!["+eat()"]]
+        );
+        // Parents and wholes come first, whichever way the arrow was written
+        let rel = &diagram.relations;
+        assert_eq!(
+            (rel[0].from.as_str(), rel[0].to.as_str()),
+            ("Animal", "Duck")
+        );
+        assert_eq!(
+            (rel[0].from_end, rel[0].to_end),
+            (EndMarker::Triangle, EndMarker::None)
+        );
+        assert_eq!(rel[1].from_end, EndMarker::HollowDiamond);
+        assert_eq!(rel[1].from_text.as_deref(), Some("1"));
+        assert_eq!(rel[1].to_text.as_deref(), Some("*"));
+        assert_eq!(rel[1].label.as_deref(), Some("lives in"));
+        assert_eq!(
+            (rel[2].from.as_str(), rel[2].to_end),
+            ("Zoo", EndMarker::Arrow)
+        );
+    }
+
+    #[test]
+    fn test_render_class_diagram() {
+        let output = render_mermaid(
+            "classDiagram\n    Duck --|> Animal\n    class Animal {\n        +int age\n        +eat()\n    }",
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            "┌────────────┐\n\
+             │   Animal   │\n\
+             ├────────────┤\n\
+             │  +int age  │\n\
+             ├────────────┤\n\
+             │  +eat()    │\n\
+             └────────────┘\n       \
+             △\n       \
+             │\n       \
+             │\n  \
+             ┌────────┐\n  \
+             │  Duck  │\n  \
+             └────────┘"
+        );
+    }
+
+    #[test]
+    fn test_parse_state_diagram() {
+        let diagram = parse_state_diagram(
+            "stateDiagram-v2\n    [*] --> Active\n    state Active {\n        [*] --> On\n        \
+             On --> Off : toggle\n    }\n    Active --> Done\n    state \"All done\" as Done\n    \
+             Done : cleaned up\n    state pick <<choice>>\n    Done --> [*]",
+        )
+        .unwrap();
+        let find = |id: &str| diagram.boxes.iter().find(|b| b.id == id).unwrap();
+        assert_eq!(diagram.groups[0].title, "Active");
+        assert_eq!(find("On").group, Some(0));
+        assert_eq!(find("Done").title, vec!["All done"]);
+        assert_eq!(find("Done").sections, vec![vec!["cleaned up"]]);
+        assert_eq!(find("pick").kind, BoxKind::Choice);
+        // The composite itself isn't a box: transitions into it reach its
+        // own start, and those out of it leave from its first state
+        assert!(diagram.boxes.iter().all(|b| b.id != "Active"));
+        let rel = &diagram.relations;
+        assert_eq!(rel[0].to, pseudo_state(true, Some(0)));
+        assert_eq!(rel[3].from, "On");
+        assert_eq!(rel[2].label.as_deref(), Some("toggle"));
+        assert_eq!(find(&pseudo_state(false, None)).kind, BoxKind::End);
+    }
+
+    #[test]
+    fn test_render_state_diagram() {
+        let output =
+            render_mermaid("stateDiagram-v2\n    [*] --> Idle\n    Idle --> [*] : done").unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0].trim(), "●");
+        assert_eq!(lines[5], "│  Idle  │");
+        assert_eq!(lines[4], "╭────────╮");
+        assert_eq!(lines[8].trim(), "│ done");
+        assert_eq!(lines.last().unwrap().trim(), "◉");
+
+        let output =
+            render_mermaid("stateDiagram\n    state Busy {\n        Work --> Rest\n    }").unwrap();
+        assert!(output.starts_with("╭─ Busy ─"));
+    }
+
+    #[test]
+    fn test_er_diagram() {
+        let input = "erDiagram\n    CUSTOMER ||--o{ ORDER : places\n    \
+                     ORDER }|..|| SHIPMENT : \"ships in\"\n    CUSTOMER {\n        \
+                     string name PK \"full name\"\n        int age\n    }";
+        let diagram = parse_er_diagram(input).unwrap();
+        assert_eq!(
+            diagram.boxes[0].sections,
+            vec![vec!["string  name  PK  \"full name\"", "int     age"]]
+        );
+        let rel = &diagram.relations;
+        assert_eq!(
+            (rel[0].from_end, rel[0].to_end),
+            (
+                EndMarker::Cardinality {
+                    many: false,
+                    optional: false
+                },
+                EndMarker::Cardinality {
+                    many: true,
+                    optional: true
+                }
+            )
+        );
+        assert_eq!(rel[1].style, EdgeStyle::Dotted);
+        assert_eq!(rel[1].label.as_deref(), Some("ships in"));
+
+        let output = render_mermaid("erDiagram\n    A ||--o{ B : has").unwrap();
+        let column: String = output
+            .lines()
+            .skip(3)
+            .take(5)
+            .map(|line| line.chars().nth(3).unwrap())
+            .collect();
+        assert_eq!(column, "┼┼│○⋀");
+    }
+
+    #[test]
+    fn test_render_pie() {
+        let input = "pie showData title Pets\n    \"Dogs\" : 30\n    \"Cats\" : 10";
+        assert_eq!(
+            render_mermaid_width(input, 40).unwrap(),
+            "Pets\n\
+             Dogs  ██████████████████████  30   75.0%\n\
+             Cats  ███████▍                10   25.0%"
+        );
+        assert!(parse_pie("pie\n    title Empty").is_none());
+    }
+
+    #[test]
+    fn test_calendar_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        for days in [-800, 0, 59, 11_016, 19_782, 40_000] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+        let pattern = date_pattern("DD.MM.YYYY HH:mm").unwrap();
+        assert_eq!(parse_date("02.01.1970 12:00", &pattern), Some(1.5));
+        assert_eq!(parse_duration("2w"), Some(14.0));
+        assert_eq!(parse_duration("36h"), Some(1.5));
+    }
+
+    #[test]
+    fn test_parse_gantt() {
+        let chart = parse_gantt(
+            "gantt\n    title Plan\n    dateFormat YYYY-MM-DD\n    excludes weekends\n    \
+             section Build\n    Design :done, a1, 2024-01-01, 5d\n    Code :active, a2, after a1, 2024-01-16\n    \
+             Test :crit, 1w\n    section Ship\n    Freeze :a3, 2024-01-03, until a2\n    \
+             Launch :milestone, m1, after a2, 0d",
+        )
+        .unwrap();
+        let day = |d: u32| days_from_civil(2024, 1, d) as f64;
+        assert_eq!(chart.title.as_deref(), Some("Plan"));
+        assert_eq!(chart.sections[0].0.as_deref(), Some("Build"));
+        let build = &chart.sections[0].1;
+        assert_eq!(
+            (build[0].start, build[0].end, build[0].done),
+            (day(1), day(6), true)
+        );
+        assert_eq!(
+            (build[1].start, build[1].end, build[1].active),
+            (day(6), day(16), true)
+        );
+        // No start: straight after the task before
+        assert_eq!(
+            (build[2].start, build[2].end, build[2].crit),
+            (day(16), day(23), true)
+        );
+        let ship = &chart.sections[1].1;
+        assert_eq!((ship[0].start, ship[0].end), (day(3), day(6)));
+        assert!(ship[1].milestone);
+        assert_eq!(ship[1].start, day(16));
+    }
+
+    #[test]
+    fn test_render_gantt_fits_width() {
+        let input = "gantt\n    dateFormat YYYY-MM-DD\n    section A\n    \
+                     First task :t1, 2024-03-01, 10d\n    Second :after t1, 5d\n    \
+                     Done :milestone, 2024-03-16, 0d";
+        for width in [40, 60, 120] {
+            let output = render_mermaid_width(input, width).unwrap();
+            let lines: Vec<&str> = output.lines().collect();
+            for line in &lines {
+                assert!(
+                    UnicodeWidthStr::width(*line) <= width as usize,
+                    "{line:?} > {width}"
+                );
+            }
+            assert!(lines[0].trim_start().starts_with("2024-03-01"));
+            assert!(lines[0].ends_with("2024-03-16"));
+            assert!(lines[1].trim_start().starts_with('├') && lines[1].ends_with('┤'));
+            assert_eq!(lines[2], "A");
+            assert!(lines[3].starts_with("  First task  █") && lines[3].ends_with(" 10d"));
+            // The second task starts where the first ends
+            let bar = |line: &str| {
+                let start = line.chars().position(|c| c == '█').unwrap();
+                (start, start + line.chars().filter(|&c| c == '█').count())
+            };
+            assert_eq!(bar(lines[4]).0, bar(lines[3]).1);
+            assert!(lines[5].ends_with('◆'));
+        }
+    }
+
+    #[test]
+    fn test_render_gantt_narrow_width() {
+        let input = "gantt\n    section A\n    Task :2024-03-01, 3d";
+        for width in 0..8 {
+            let output = render_mermaid_width(input, width).unwrap();
+            assert!(output.lines().any(|line| line.contains('█')), "{width}");
+        }
+    }
+
+    #[test]
+    fn test_render_timeline() {
+        let input = "timeline\n    title History\n    2002 : LinkedIn\n    2004 : Facebook\n         : Google\n    \
+                     section Later\n    2006 : Twitter and many more";
+        assert_eq!(
+            render_mermaid_width(input, 20).unwrap(),
+            "History\n\
+             \n\
+             2002 ●─ LinkedIn\n     \
+             │\n\
+             2004 ●─ Facebook\n     \
+             │  Google\n     \
+             │\n\
+             ── Later ──\n\
+             2006 ●─ Twitter and\n     \
+             │  many more"
+        );
+    }
+
     #[test]
     fn test_render_simple_lr() {
         let input = "graph LR\n    A --> B --> C";
@@ -959,4 +5001,129 @@ mod tests {
         assert!(output.contains("Process Data"));
         assert!(output.contains("Done"));
     }
+
+    #[test]
+    fn test_diagram_roles() {
+        let lines = render_mermaid_lines("graph LR\n    A[Alpha] -->|go| B[Beta]", 80).unwrap();
+        let role_of = |needle: &str| {
+            lines
+                .iter()
+                .flat_map(|line| &line.spans)
+                .find(|span| span.text.contains(needle))
+                .map(|span| span.role)
+        };
+        assert_eq!(role_of("Alpha"), Some(DiagramRole::NodeLabel));
+        assert_eq!(role_of("┌"), Some(DiagramRole::NodeBorder));
+        assert_eq!(role_of("go"), Some(DiagramRole::EdgeLabel));
+        assert_eq!(role_of("▶"), Some(DiagramRole::Arrowhead));
+        assert!(lines
+            .iter()
+            .flat_map(|line| &line.spans)
+            .any(|span| span.role == DiagramRole::Edge && span.text.contains('─')));
+        // The plain rendering is the same drawing
+        let text: Vec<String> = lines.iter().map(DiagramLine::text).collect();
+        assert_eq!(
+            text.join("\n"),
+            render_mermaid("graph LR\n    A[Alpha] -->|go| B[Beta]").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_sequence_diagram() {
+        let input = "sequenceDiagram\n    participant A as Alice\n    actor B\n    A->>+B: Hello\n    B-->>-A: Hi\n    A-)C: async\n    C-xA\n    Note right of B: thinking<br>hard\n    Note over A,C: shared\n    alt ok\n        A->>A: self\n    else failed\n        loop retry\n            A->B: again\n        end\n    end";
+        let diagram = parse_sequence(input).unwrap();
+        let ids: Vec<(&str, &str, bool)> = diagram
+            .participants
+            .iter()
+            .map(|p| (p.id.as_str(), p.label.as_str(), p.actor))
+            .collect();
+        assert_eq!(
+            ids,
+            vec![("A", "Alice", false), ("B", "B", true), ("C", "C", false)]
+        );
+
+        let SeqEvent::Message(hello) = &diagram.events[0] else {
+            panic!("expected a message");
+        };
+        assert_eq!(
+            (hello.from, hello.to, hello.label.as_str()),
+            (0, 1, "Hello")
+        );
+        assert!(hello.activate && !hello.dashed && hello.head == ArrowHead::Filled);
+        let SeqEvent::Message(hi) = &diagram.events[1] else {
+            panic!("expected a message");
+        };
+        assert!(hi.deactivate && hi.dashed);
+        assert!(matches!(&diagram.events[2], SeqEvent::Message(m) if m.head == ArrowHead::Open));
+        assert!(
+            matches!(&diagram.events[3], SeqEvent::Message(m) if m.head == ArrowHead::Cross && m.label.is_empty())
+        );
+        assert_eq!(
+            diagram.events[4],
+            SeqEvent::Note {
+                placement: NotePlacement::RightOf(1),
+                lines: vec!["thinking".to_string(), "hard".to_string()],
+            }
+        );
+        assert!(matches!(
+            &diagram.events[5],
+            SeqEvent::Note {
+                placement: NotePlacement::Over(0, 2),
+                ..
+            }
+        ));
+
+        let SeqEvent::Frame(sections) = &diagram.events[6] else {
+            panic!("expected the alt frame");
+        };
+        let keywords: Vec<(&str, &str)> = sections
+            .iter()
+            .map(|s| (s.keyword.as_str(), s.label.as_str()))
+            .collect();
+        assert_eq!(keywords, vec![("alt", "ok"), ("else", "failed")]);
+        assert!(
+            matches!(&sections[1].events[0], SeqEvent::Frame(inner) if inner[0].keyword == "loop")
+        );
+    }
+
+    #[test]
+    fn test_render_sequence_messages() {
+        let input = "sequenceDiagram\n    participant A as Alice\n    actor B as Bob\n    A->>B: Hello Bob\n    B-->>A: Hi";
+        let expected = "\
+┌─────────┐   ╭───────╮
+│  Alice  │   │  Bob  │
+└────┬────┘   ╰───┬───╯
+     │            │
+     │ Hello Bob  │
+     │───────────▶│
+     │    Hi      │
+     │◀┄┄┄┄┄┄┄┄┄┄┄│
+     │            │
+┌────┴────┐   ╭───┴───╮
+│  Alice  │   │  Bob  │
+└─────────┘   ╰───────╯";
+        assert_eq!(render_mermaid(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_sequence_frames_notes_and_activations() {
+        let input = "sequenceDiagram\n    autonumber\n    Client->>+Server: GET /users\n    Note right of Server: validate\n    loop Every row\n        Server->>DB: SELECT\n    end\n    alt found\n        Server-->>Client: 200\n    else missing\n        Server-->>Client: 404\n    end\n    Server->>Server: log\n    deactivate Server";
+        let output = render_mermaid(input).unwrap();
+        for part in [
+            "1. GET /users",
+            "│ validate │",
+            "┌─[loop] Every row",
+            "[alt] found",
+            "├┄[else] missing┄",
+            "┃──┐ 5. log",
+            "┃◀─┘",
+        ] {
+            assert!(output.contains(part), "missing {:?} in\n{}", part, output);
+        }
+        // The activation bar ends with `deactivate`
+        let lines: Vec<&str> = output.lines().collect();
+        let last_bar = lines.iter().rposition(|line| line.contains('┃')).unwrap();
+        let self_return = lines.iter().position(|line| line.contains("┃◀─┘")).unwrap();
+        assert_eq!(last_bar, self_return);
+    }
 }
@@ -0,0 +1,280 @@
+//! CSV and TSV data → `Block::Table`
+//!
+//! Used for ```` ```csv ```` / ```` ```tsv ```` fences and for opening a
+//! `.csv` or `.tsv` file directly. Fields follow RFC 4180: a field that
+//! starts with `"` runs to the closing quote, `""` is a literal quote, and
+//! delimiters and newlines inside quotes belong to the field.
+
+use crate::codeblock::{BlockContext, CodeBlockRenderer, Rendered};
+use crate::ir::{Alignment, Block, Document, Inline, Metadata, TableCell};
+
+/// The field delimiter for a fence language or file extension
+pub fn delimiter_for(lang: &str) -> Option<char> {
+    match lang.to_ascii_lowercase().as_str() {
+        "csv" => Some(','),
+        "tsv" => Some('\t'),
+        _ => None,
+    }
+}
+
+/// Split delimited text into records. Blank lines are skipped.
+pub fn parse_records(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    // Whether the current field began with a quote, and whether we are
+    // still between its quotes (where delimiters and newlines are text)
+    let mut quoted = false;
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\r' if chars.peek() == Some(&'\n') => {}
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() && !quoted => {
+                quoted = true;
+                in_quotes = true;
+            }
+            c if c == delimiter => {
+                record.push(std::mem::take(&mut field));
+                quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                end_record(&mut records, &mut record, &mut field, quoted);
+                quoted = false;
+            }
+            _ => field.push(c),
+        }
+    }
+    end_record(&mut records, &mut record, &mut field, quoted);
+    records
+}
+
+fn end_record(
+    records: &mut Vec<Vec<String>>,
+    record: &mut Vec<String>,
+    field: &mut String,
+    quoted: bool,
+) {
+    if record.is_empty() && field.trim().is_empty() && !quoted {
+        field.clear();
+        return;
+    }
+    record.push(std::mem::take(field));
+    records.push(std::mem::take(record));
+}
+
+/// Delimited text as a table, or `None` if it holds no records.
+///
+/// The first record is the header when it has more than one record, none
+/// of its fields are empty and none look like numbers. Columns whose
+/// values are all numbers are right-aligned. Short records are padded so
+/// every row has the same number of cells.
+pub fn csv_table(text: &str, delimiter: char) -> Option<Block> {
+    let mut records = parse_records(text, delimiter);
+    if records.is_empty() {
+        return None;
+    }
+    let columns = records.iter().map(Vec::len).max().unwrap_or(0);
+    for record in &mut records {
+        record.resize(columns, String::new());
+    }
+
+    let has_header = records.len() > 1
+        && records[0]
+            .iter()
+            .all(|field| !field.trim().is_empty() && !is_numeric(field));
+    let header = if has_header {
+        records.remove(0)
+    } else {
+        Vec::new()
+    };
+
+    let alignment = (0..columns)
+        .map(|column| {
+            let mut values = records
+                .iter()
+                .map(|record| record[column].trim())
+                .filter(|value| !value.is_empty())
+                .peekable();
+            if values.peek().is_some() && values.all(is_numeric) {
+                Alignment::Right
+            } else {
+                Alignment::None
+            }
+        })
+        .collect();
+
+    Some(Block::Table {
+        headers: header.iter().map(|field| cell(field)).collect(),
+        rows: records
+            .iter()
+            .map(|record| record.iter().map(|field| cell(field)).collect())
+            .collect(),
+        alignment,
+    })
+}
+
+/// Renders ` ```csv ` and ` ```tsv ` code blocks as tables, splitting
+/// fields at the delimiter it holds; registered by default in
+/// [`CodeBlockRenderers`](crate::codeblock::CodeBlockRenderers)
+#[derive(Debug, Clone, Copy)]
+pub struct CsvRenderer(pub char);
+
+impl CodeBlockRenderer for CsvRenderer {
+    fn render(&self, code: &str, _block: &BlockContext) -> Option<Rendered> {
+        match csv_table(code, self.0)? {
+            Block::Table {
+                headers,
+                rows,
+                alignment,
+            } => Some(Rendered::Table {
+                headers,
+                rows,
+                alignment,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A document holding just the table for a `.csv` or `.tsv` file. The
+/// table's source range runs from its first record to its last, so
+/// yanking it copies the data without the blank lines around it.
+pub fn parse_csv(text: &str, delimiter: char) -> Document {
+    let blocks: Vec<Block> = csv_table(text, delimiter).into_iter().collect();
+    let first = text.len() - text.trim_start().len();
+    let start = text[..first].rfind('\n').map_or(0, |newline| newline + 1);
+    let source_ranges = blocks
+        .iter()
+        .map(|_| start..text.trim_end().len())
+        .collect();
+    Document {
+        metadata: Metadata::default(),
+        blocks,
+        source_ranges,
+    }
+}
+
+/// A field as cell content; newlines inside quotes become line breaks
+fn cell(field: &str) -> TableCell {
+    let mut content = Vec::new();
+    for (i, line) in field.trim().split('\n').enumerate() {
+        if i > 0 {
+            content.push(Inline::LineBreak);
+        }
+        if !line.is_empty() {
+            content.push(Inline::Text(line.to_string()));
+        }
+    }
+    TableCell { content }
+}
+
+/// Whether a field reads as a number: `42`, `-3.5`, `1,024`, `6.02e23`,
+/// `12%` or `$9.99`
+fn is_numeric(field: &str) -> bool {
+    let field = field.trim();
+    let field = field.strip_suffix('%').unwrap_or(field);
+    let field = field
+        .strip_prefix(['$', '€', '£', '¥'])
+        .unwrap_or(field)
+        .replace([',', '_'], "");
+    field.bytes().any(|b| b.is_ascii_digit())
+        && field
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'))
+        && field.parse::<f64>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(cell: &TableCell) -> String {
+        cell.content
+            .iter()
+            .map(|inline| match inline {
+                Inline::Text(s) => s.as_str(),
+                Inline::LineBreak => "\n",
+                _ => "",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quoted_fields_and_embedded_newlines() {
+        let records = parse_records(
+            "name,quote\r\n\"Smith, J\",\"said \"\"hi\"\"\nthen left\"\n\nlast,\n",
+            ',',
+        );
+        assert_eq!(
+            records,
+            vec![
+                vec!["name", "quote"],
+                vec!["Smith, J", "said \"hi\"\nthen left"],
+                vec!["last", ""],
+            ]
+        );
+        assert_eq!(
+            parse_records("a\tb,c\n\"x\ty\"\tz", '\t'),
+            vec![vec!["a", "b,c"], vec!["x\ty", "z"]]
+        );
+    }
+
+    #[test]
+    fn test_header_detection_and_numeric_alignment() {
+        let Some(Block::Table {
+            headers,
+            rows,
+            alignment,
+        }) = csv_table("item,price,qty\napple,$1.50,3\npear,\"1,024\"\n", ',')
+        else {
+            panic!("expected a table");
+        };
+        assert_eq!(
+            headers.iter().map(text).collect::<Vec<_>>(),
+            ["item", "price", "qty"]
+        );
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].len(), 3, "short records are padded");
+        assert_eq!(
+            alignment,
+            [Alignment::None, Alignment::Right, Alignment::Right]
+        );
+
+        // A numeric first record is data, not a header
+        let Some(Block::Table { headers, rows, .. }) = csv_table("1,2\n3,4", ',') else {
+            panic!("expected a table");
+        };
+        assert!(headers.is_empty());
+        assert_eq!(rows.len(), 2);
+
+        let Some(Block::Table { rows, .. }) = csv_table("name,note\nann,\"x\ny\"", ',') else {
+            panic!("expected a table");
+        };
+        assert_eq!(text(&rows[0][1]), "x\ny");
+
+        assert_eq!(csv_table("\n\n", ','), None);
+    }
+
+    #[test]
+    fn test_csv_document_covers_its_records() {
+        let text = "\n  a,b\n1,2\n\n";
+        let doc = parse_csv(text, ',');
+        assert_eq!(doc.source_ranges.len(), doc.blocks.len());
+        assert_eq!(&text[doc.source_ranges[0].clone()], "  a,b\n1,2");
+        assert!(parse_csv("\n", ',').source_ranges.is_empty());
+    }
+}
@@ -0,0 +1,800 @@
+//! Raw HTML to IR conversion for a small, safe subset of HTML
+//!
+//! Inline tags (`<kbd>`, `<b>`, `<br>`, `<img>`, `<sub>`/`<sup>`, ...) become
+//! inlines and block HTML (`<details>`, `<table>`, `<p>`, `<h1>`, ...) becomes
+//! blocks. Tags outside the subset are kept as dimmed source
+//! (`Inline::Html`) rather than dropped.
+
+use crate::ir::{Alignment, Block, Inline, TableCell};
+
+/// A single HTML tag, e.g. `<img src="a.png" alt="A">` or `</kbd>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HtmlTag {
+    /// Lowercase tag name
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    /// `</name>`
+    pub closing: bool,
+    /// `<name/>`
+    pub self_closing: bool,
+}
+
+impl HtmlTag {
+    /// Parse a complete tag (`<...>`); comments, doctypes and anything
+    /// malformed give `None`
+    pub fn parse(raw: &str) -> Option<Self> {
+        let inner = raw.trim().strip_prefix('<')?.strip_suffix('>')?;
+        let (closing, inner) = match inner.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, inner),
+        };
+        let (self_closing, inner) = match inner.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, inner),
+        };
+
+        let name_end = inner
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .unwrap_or(inner.len());
+        let name = &inner[..name_end];
+        if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        Some(Self {
+            name: name.to_ascii_lowercase(),
+            attrs: parse_attrs(&inner[name_end..])?,
+            closing,
+            self_closing,
+        })
+    }
+
+    /// Value of an attribute (names are case-insensitive)
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// `key="value" key='value' key=value key` → pairs; `None` if malformed
+fn parse_attrs(mut rest: &str) -> Option<Vec<(String, String)>> {
+    let mut attrs = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Some(attrs);
+        }
+        let key_end = rest
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(rest.len());
+        let key = rest[..key_end].to_ascii_lowercase();
+        if key.is_empty() || key.contains(['"', '\'', '<', '>']) {
+            return None;
+        }
+        rest = rest[key_end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, remaining) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let end = after[1..].find(quote)? + 1;
+                        (&after[1..end], &after[end + 1..])
+                    }
+                    _ => {
+                        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                        (&after[..end], &after[end..])
+                    }
+                };
+                rest = remaining;
+                decode_entities(value)
+            }
+            None => String::new(),
+        };
+        attrs.push((key, value));
+    }
+}
+
+/// Decode the character references that show up in hand-written HTML
+pub(crate) fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let ch = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                "copy" => Some('©'),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "hellip" => Some('…'),
+                _ => {
+                    let number = entity.strip_prefix('#')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => number.parse().ok(),
+                    };
+                    code.and_then(char::from_u32)
+                }
+            };
+            ch.map(|ch| (ch, end))
+        });
+        match decoded {
+            Some((ch, end)) => {
+                out.push(ch);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whether raw HTML is only a comment (`<!-- ... -->`), which is dropped
+pub(crate) fn is_comment(raw: &str) -> bool {
+    let raw = raw.trim();
+    raw.starts_with("<!--") && raw.ends_with("-->")
+}
+
+/// Inline for a tag without content: `<br>` and `<img>`
+pub(crate) fn void_inline(tag: &HtmlTag) -> Option<Inline> {
+    if tag.closing {
+        return None;
+    }
+    match tag.name.as_str() {
+        "br" => Some(Inline::LineBreak),
+        "img" => Some(Inline::Image {
+            url: tag.attr("src")?.to_string(),
+            alt: tag.attr("alt").unwrap_or_default().to_string(),
+            title: tag.attr("title").map(str::to_string),
+        }),
+        _ => None,
+    }
+}
+
+/// Inline tags whose content is kept, with or without styling
+pub(crate) fn is_inline_container(name: &str) -> bool {
+    matches!(
+        name,
+        "kbd"
+            | "b"
+            | "strong"
+            | "i"
+            | "em"
+            | "s"
+            | "del"
+            | "strike"
+            | "code"
+            | "tt"
+            | "samp"
+            | "a"
+            | "sub"
+            | "sup"
+            | "span"
+            | "u"
+            | "ins"
+            | "mark"
+            | "small"
+            | "abbr"
+            | "cite"
+            | "font"
+    )
+}
+
+/// An inline tag that has been opened but not closed yet
+#[derive(Debug)]
+pub(crate) struct OpenTag {
+    pub tag: HtmlTag,
+    raw: String,
+    /// Inlines before the tag
+    saved: Vec<Inline>,
+}
+
+impl OpenTag {
+    /// Open `tag`; inlines so far are set aside until it closes
+    pub fn new(tag: HtmlTag, raw: &str, current: &mut Vec<Inline>) -> Self {
+        Self {
+            tag,
+            raw: raw.to_string(),
+            saved: std::mem::take(current),
+        }
+    }
+
+    /// Closed by its end tag: `current` (the tag's content) is wrapped
+    pub fn close(self, current: &mut Vec<Inline>) {
+        let content = std::mem::take(current);
+        *current = self.saved;
+        current.extend(wrap_inline(&self.tag, content));
+    }
+
+    /// Never closed: the tag is shown as source and its content kept as is
+    pub fn abandon(self, current: &mut Vec<Inline>) {
+        let content = std::mem::take(current);
+        *current = self.saved;
+        current.push(Inline::Html(self.raw));
+        current.extend(content);
+    }
+}
+
+/// Inlines for the content of an inline container tag
+fn wrap_inline(tag: &HtmlTag, content: Vec<Inline>) -> Vec<Inline> {
+    let plain = || -> String { content.iter().map(Inline::to_plain_text).collect() };
+    match tag.name.as_str() {
+        "kbd" => vec![Inline::Kbd(plain().trim().to_string())],
+        "b" | "strong" => vec![Inline::Strong(content)],
+        "i" | "em" | "cite" => vec![Inline::Emphasis(content)],
+        "s" | "del" | "strike" => vec![Inline::Strikethrough(content)],
+        "code" | "tt" | "samp" => vec![Inline::Code(plain())],
+        "sub" => vec![Inline::Text(script(&plain(), false))],
+        "sup" => vec![Inline::Text(script(&plain(), true))],
+        "a" => match tag.attr("href") {
+            Some(url) => vec![Inline::Link {
+                url: url.to_string(),
+                title: tag.attr("title").map(str::to_string),
+                text: content,
+            }],
+            None => content,
+        },
+        _ => content,
+    }
+}
+
+/// Unicode super- or subscript for `text`, or `^(text)` / `_(text)` if
+/// some character has none
+fn script(text: &str, superscript: bool) -> String {
+    const SUPER: &[(char, char)] = &[
+        ('0', '⁰'),
+        ('1', '¹'),
+        ('2', '²'),
+        ('3', '³'),
+        ('4', '⁴'),
+        ('5', '⁵'),
+        ('6', '⁶'),
+        ('7', '⁷'),
+        ('8', '⁸'),
+        ('9', '⁹'),
+        ('+', '⁺'),
+        ('-', '⁻'),
+        ('=', '⁼'),
+        ('(', '⁽'),
+        (')', '⁾'),
+        ('i', 'ⁱ'),
+        ('n', 'ⁿ'),
+    ];
+    const SUB: &[(char, char)] = &[
+        ('0', '₀'),
+        ('1', '₁'),
+        ('2', '₂'),
+        ('3', '₃'),
+        ('4', '₄'),
+        ('5', '₅'),
+        ('6', '₆'),
+        ('7', '₇'),
+        ('8', '₈'),
+        ('9', '₉'),
+        ('+', '₊'),
+        ('-', '₋'),
+        ('=', '₌'),
+        ('(', '₍'),
+        (')', '₎'),
+        ('a', 'ₐ'),
+        ('e', 'ₑ'),
+        ('h', 'ₕ'),
+        ('k', 'ₖ'),
+        ('l', 'ₗ'),
+        ('m', 'ₘ'),
+        ('n', 'ₙ'),
+        ('o', 'ₒ'),
+        ('p', 'ₚ'),
+        ('s', 'ₛ'),
+        ('t', 'ₜ'),
+        ('x', 'ₓ'),
+    ];
+    let table = if superscript { SUPER } else { SUB };
+    let mapped: Option<String> = text
+        .chars()
+        .map(|c| table.iter().find(|(from, _)| *from == c).map(|(_, to)| *to))
+        .collect();
+    match mapped {
+        Some(mapped) if !mapped.is_empty() => mapped,
+        _ => format!("{}({})", if superscript { '^' } else { '_' }, text),
+    }
+}
+
+/// Piece of raw HTML: a tag (with its source) or decoded text
+#[derive(Debug)]
+enum Token<'a> {
+    Tag(HtmlTag, &'a str),
+    Text(String),
+}
+
+/// Split HTML into tags and text. Comments and doctypes are dropped; a `<`
+/// that doesn't start a tag is text.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let skip = if rest.starts_with("<!--") {
+            Some(rest.find("-->").map_or(rest.len(), |end| end + 3))
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            Some(rest.find('>').map_or(rest.len(), |end| end + 1))
+        } else {
+            None
+        };
+        if let Some(skip) = skip {
+            rest = &rest[skip..];
+            continue;
+        }
+
+        let tag = tag_end(rest).and_then(|end| {
+            let raw = &rest[..end];
+            HtmlTag::parse(raw).map(|tag| (tag, raw))
+        });
+        match tag {
+            Some((tag, raw)) => {
+                if !text.is_empty() {
+                    tokens.push(Token::Text(decode_entities(&std::mem::take(&mut text))));
+                }
+                rest = &rest[raw.len()..];
+                tokens.push(Token::Tag(tag, raw));
+            }
+            None => {
+                text.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        tokens.push(Token::Text(decode_entities(&text)));
+    }
+    tokens
+}
+
+/// Byte offset just past the `>` ending the tag at the start of `html`,
+/// skipping `>` inside quoted attribute values
+fn tag_end(html: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in html.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(index + 1),
+            (None, '<') => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Inline content being built from tokens, with its open inline tags
+#[derive(Default)]
+struct Inlines {
+    current: Vec<Inline>,
+    open: Vec<OpenTag>,
+}
+
+impl Inlines {
+    fn text(&mut self, text: &str) {
+        // Source line breaks are just whitespace in HTML
+        let text = text.replace(['\n', '\r'], " ");
+        if !text.is_empty() {
+            self.current.push(Inline::Text(text));
+        }
+    }
+
+    /// Add an inline tag; anything outside the subset stays visible as source
+    fn tag(&mut self, tag: HtmlTag, raw: &str) {
+        if let Some(inline) = void_inline(&tag) {
+            self.current.push(inline);
+        } else if !is_inline_container(&tag.name) || tag.self_closing {
+            self.current.push(Inline::Html(raw.to_string()));
+        } else if !tag.closing {
+            self.open.push(OpenTag::new(tag, raw, &mut self.current));
+        } else if let Some(depth) = self.open.iter().rposition(|open| open.tag.name == tag.name) {
+            while self.open.len() > depth + 1 {
+                let inner = self.open.pop().expect("open tag above depth");
+                inner.abandon(&mut self.current);
+            }
+            let open = self.open.pop().expect("matched open tag");
+            open.close(&mut self.current);
+        } else {
+            self.current.push(Inline::Html(raw.to_string()));
+        }
+    }
+
+    /// Everything so far, closing any tags left open; `None` if it's only
+    /// whitespace
+    fn take(&mut self) -> Option<Vec<Inline>> {
+        while let Some(open) = self.open.pop() {
+            open.abandon(&mut self.current);
+        }
+        let inlines = std::mem::take(&mut self.current);
+        let blank = inlines
+            .iter()
+            .all(|inline| matches!(inline, Inline::Text(text) if text.trim().is_empty()));
+        (!blank).then_some(inlines)
+    }
+}
+
+/// Result of converting an HTML block. A `<details>` opened in one block
+/// and closed in a later one (with Markdown in between) comes out as a
+/// start and an end marker.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum HtmlItem {
+    Block(Block),
+    DetailsStart { summary: Vec<Inline>, open: bool },
+    DetailsEnd,
+}
+
+/// A `<details>` element being converted
+struct DetailsFrame {
+    summary: Vec<Inline>,
+    open: bool,
+    blocks: Vec<Block>,
+}
+
+impl DetailsFrame {
+    fn into_block(self) -> Block {
+        Block::Details {
+            summary: if self.summary.is_empty() {
+                vec![Inline::Text("Details".to_string())]
+            } else {
+                self.summary
+            },
+            open: self.open,
+            content: self.blocks,
+        }
+    }
+}
+
+/// Convert an HTML block into IR
+pub(crate) fn html_block(html: &str) -> Vec<HtmlItem> {
+    let tokens = tokenize(html);
+    let mut items = Vec::new();
+    let mut frames: Vec<DetailsFrame> = Vec::new();
+    let mut inlines = Inlines::default();
+    // Level and `id` of the heading whose inlines are being collected
+    let mut heading: Option<(u8, String)> = None;
+    let mut in_summary = false;
+
+    fn push(block: Block, frames: &mut [DetailsFrame], items: &mut Vec<HtmlItem>) {
+        match frames.last_mut() {
+            Some(frame) => frame.blocks.push(block),
+            None => items.push(HtmlItem::Block(block)),
+        }
+    }
+
+    fn flush(inlines: &mut Inlines, frames: &mut [DetailsFrame], items: &mut Vec<HtmlItem>) {
+        if let Some(content) = inlines.take() {
+            let content = trim_inlines(content);
+            push(Block::Paragraph { content }, frames, items);
+        }
+    }
+
+    let mut index = 0;
+    while index < tokens.len() {
+        let (tag, raw) = match &tokens[index] {
+            Token::Text(text) => {
+                inlines.text(text);
+                index += 1;
+                continue;
+            }
+            Token::Tag(tag, raw) => (tag, *raw),
+        };
+        index += 1;
+
+        match tag.name.as_str() {
+            "details" if !tag.closing => {
+                flush(&mut inlines, &mut frames, &mut items);
+                frames.push(DetailsFrame {
+                    summary: Vec::new(),
+                    open: tag.attr("open").is_some(),
+                    blocks: Vec::new(),
+                });
+            }
+            "details" => {
+                flush(&mut inlines, &mut frames, &mut items);
+                match frames.pop() {
+                    Some(frame) => push(frame.into_block(), &mut frames, &mut items),
+                    None => items.push(HtmlItem::DetailsEnd),
+                }
+            }
+            "summary" if !frames.is_empty() => {
+                if tag.closing {
+                    let summary = inlines.take().unwrap_or_default();
+                    if let Some(frame) = frames.last_mut() {
+                        frame.summary = trim_inlines(summary);
+                    }
+                    in_summary = false;
+                } else {
+                    flush(&mut inlines, &mut frames, &mut items);
+                    in_summary = true;
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" if !in_summary => {
+                if !tag.closing {
+                    flush(&mut inlines, &mut frames, &mut items);
+                    let id = tag.attr("id").unwrap_or_default().to_string();
+                    heading = tag.name[1..].parse().ok().map(|level| (level, id));
+                } else if let Some((level, id)) = heading.take() {
+                    let content = trim_inlines(inlines.take().unwrap_or_default());
+                    push(
+                        Block::Heading { level, content, id },
+                        &mut frames,
+                        &mut items,
+                    );
+                } else {
+                    inlines.tag(tag.clone(), raw);
+                }
+            }
+            "table" if !tag.closing => {
+                flush(&mut inlines, &mut frames, &mut items);
+                let end = table_end(&tokens, index);
+                push(table(&tokens[index..end]), &mut frames, &mut items);
+                index = (end + 1).min(tokens.len());
+            }
+            "hr" => {
+                flush(&mut inlines, &mut frames, &mut items);
+                push(Block::HorizontalRule, &mut frames, &mut items);
+            }
+            "p" | "div" | "center" | "section" | "article" | "header" | "footer" | "main"
+            | "nav" | "aside" | "figure" | "figcaption" | "picture" | "source" => {
+                if !in_summary && heading.is_none() {
+                    flush(&mut inlines, &mut frames, &mut items);
+                }
+            }
+            _ => inlines.tag(tag.clone(), raw),
+        }
+    }
+
+    flush(&mut inlines, &mut frames, &mut items);
+    // Unclosed `<details>`: the Markdown that follows goes inside
+    for frame in frames {
+        items.push(HtmlItem::DetailsStart {
+            summary: if frame.summary.is_empty() {
+                vec![Inline::Text("Details".to_string())]
+            } else {
+                frame.summary
+            },
+            open: frame.open,
+        });
+        items.extend(frame.blocks.into_iter().map(HtmlItem::Block));
+    }
+    items
+}
+
+/// Index of the `</table>` matching a `<table>` whose content starts at
+/// `start` (or the end of the tokens)
+fn table_end(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        if let Token::Tag(tag, _) = token {
+            if tag.name == "table" {
+                if !tag.closing {
+                    depth += 1;
+                } else if depth == 0 {
+                    return index;
+                } else {
+                    depth -= 1;
+                }
+            }
+        }
+    }
+    tokens.len()
+}
+
+/// `<table>` content → `Block::Table`. The first row is the header if it
+/// is in `<thead>` or made of `<th>` cells.
+fn table(tokens: &[Token]) -> Block {
+    let mut rows: Vec<Vec<TableCell>> = Vec::new();
+    let mut alignment: Vec<Alignment> = Vec::new();
+    let mut header_row = None;
+    let mut in_head = false;
+    let mut row: Option<(Vec<TableCell>, bool)> = None;
+    let mut cell: Option<Inlines> = None;
+
+    fn end_cell(cell: &mut Option<Inlines>, row: &mut Option<(Vec<TableCell>, bool)>) {
+        if let Some(mut inlines) = cell.take() {
+            let content = trim_inlines(inlines.take().unwrap_or_default());
+            row.get_or_insert_with(|| (Vec::new(), true))
+                .0
+                .push(TableCell { content });
+        }
+    }
+
+    let mut end_row = |row: &mut Option<(Vec<TableCell>, bool)>, in_head: bool| {
+        if let Some((cells, all_th)) = row.take() {
+            if rows.is_empty() && header_row.is_none() && (in_head || all_th) && !cells.is_empty() {
+                header_row = Some(cells);
+            } else {
+                rows.push(cells);
+            }
+        }
+    };
+
+    for token in tokens {
+        match token {
+            Token::Text(text) => {
+                if let Some(inlines) = cell.as_mut() {
+                    inlines.text(text);
+                }
+            }
+            Token::Tag(tag, raw) => match tag.name.as_str() {
+                "thead" => in_head = !tag.closing,
+                "tr" => {
+                    end_cell(&mut cell, &mut row);
+                    end_row(&mut row, in_head);
+                    if !tag.closing {
+                        row = Some((Vec::new(), true));
+                    }
+                }
+                "th" | "td" => {
+                    end_cell(&mut cell, &mut row);
+                    if !tag.closing {
+                        let (cells, all_th) = row.get_or_insert_with(|| (Vec::new(), true));
+                        *all_th &= tag.name == "th";
+                        if alignment.len() <= cells.len() {
+                            alignment.push(match tag.attr("align") {
+                                Some("left") => Alignment::Left,
+                                Some("center") => Alignment::Center,
+                                Some("right") => Alignment::Right,
+                                _ => Alignment::None,
+                            });
+                        }
+                        cell = Some(Inlines::default());
+                    }
+                }
+                "tbody" | "tfoot" | "caption" | "colgroup" | "col" => {}
+                _ => {
+                    if let Some(inlines) = cell.as_mut() {
+                        inlines.tag(tag.clone(), raw);
+                    }
+                }
+            },
+        }
+    }
+    end_cell(&mut cell, &mut row);
+    end_row(&mut row, in_head);
+
+    // Markdown tables are rectangular; pad short rows
+    let headers = header_row.unwrap_or_default();
+    let columns = rows
+        .iter()
+        .map(Vec::len)
+        .chain([headers.len()])
+        .max()
+        .unwrap_or(0);
+    let pad = |mut cells: Vec<TableCell>| {
+        cells.resize(
+            columns,
+            TableCell {
+                content: Vec::new(),
+            },
+        );
+        cells
+    };
+    alignment.resize(columns, Alignment::None);
+    Block::Table {
+        headers: if headers.is_empty() {
+            headers
+        } else {
+            pad(headers)
+        },
+        rows: rows.into_iter().map(pad).collect(),
+        alignment,
+    }
+}
+
+/// Drop whitespace-only text at the start and end of inline content
+fn trim_inlines(mut inlines: Vec<Inline>) -> Vec<Inline> {
+    let blank = |inline: &Inline| matches!(inline, Inline::Text(text) if text.trim().is_empty());
+    while inlines.last().is_some_and(blank) {
+        inlines.pop();
+    }
+    let leading = inlines.iter().take_while(|inline| blank(inline)).count();
+    inlines.drain(..leading);
+    inlines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tag_attributes() {
+        let tag =
+            HtmlTag::parse(r#"<IMG src="logo.png" alt='The "logo"' width=40 hidden />"#).unwrap();
+        assert_eq!(tag.name, "img");
+        assert!(tag.self_closing && !tag.closing);
+        assert_eq!(tag.attr("src"), Some("logo.png"));
+        assert_eq!(tag.attr("alt"), Some("The \"logo\""));
+        assert_eq!(tag.attr("width"), Some("40"));
+        assert_eq!(tag.attr("hidden"), Some(""));
+
+        assert!(HtmlTag::parse("</kbd>").unwrap().closing);
+        assert_eq!(HtmlTag::parse("<!-- note -->"), None);
+        assert_eq!(HtmlTag::parse("< 3"), None);
+    }
+
+    #[test]
+    fn test_entities_and_scripts() {
+        assert_eq!(
+            decode_entities("a &lt;b&gt; &amp; &#169; &#x41; &bogus;"),
+            "a <b> & © A &bogus;"
+        );
+        assert_eq!(script("2", true), "²");
+        assert_eq!(script("n+1", true), "ⁿ⁺¹");
+        assert_eq!(script("2", false), "₂");
+        assert_eq!(script("max", true), "^(max)");
+    }
+
+    #[test]
+    fn test_table_with_header_row() {
+        let items = html_block(
+            "<table>\n<tr><th>Key</th><th align=\"right\">Count</th></tr>\n\
+             <tr><td><kbd>q</kbd></td><td>1</td></tr>\n<tr><td>only</td></tr>\n</table>\n",
+        );
+        let [HtmlItem::Block(Block::Table {
+            headers,
+            rows,
+            alignment,
+        })] = items.as_slice()
+        else {
+            panic!("expected a table, got {:?}", items);
+        };
+        assert_eq!(headers.len(), 2);
+        assert_eq!(alignment, &[Alignment::None, Alignment::Right]);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][0].content, vec![Inline::Kbd("q".to_string())]);
+        assert!(rows[1][1].content.is_empty(), "short rows are padded");
+    }
+
+    #[test]
+    fn test_unclosed_details_and_unknown_tags() {
+        let items = html_block("<details open>\n<summary>More <b>info</b></summary>\n");
+        assert_eq!(
+            items,
+            vec![HtmlItem::DetailsStart {
+                summary: vec![
+                    Inline::Text("More ".to_string()),
+                    Inline::Strong(vec![Inline::Text("info".to_string())]),
+                ],
+                open: true,
+            }]
+        );
+        assert_eq!(html_block("</details>\n"), vec![HtmlItem::DetailsEnd]);
+
+        let items = html_block("<video src=\"a.mp4\">Fallback</video>\n");
+        let [HtmlItem::Block(Block::Paragraph { content })] = items.as_slice() else {
+            panic!("expected a paragraph, got {:?}", items);
+        };
+        assert_eq!(
+            content,
+            &vec![
+                Inline::Html("<video src=\"a.mp4\">".to_string()),
+                Inline::Text("Fallback".to_string()),
+                Inline::Html("</video>".to_string()),
+            ]
+        );
+    }
+}
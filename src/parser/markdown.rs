@@ -1,9 +1,12 @@
 //! Markdown to IR conversion using pulldown-cmark
 
+use super::html::{self, HtmlItem, HtmlTag, OpenTag};
+use super::slug::{assign_heading_ids, Slugger};
 use crate::ir::{Alignment, Block, CalloutKind, Document, Inline, ListItem, TableCell};
 use pulldown_cmark::{
-    Alignment as CMarkAlignment, Event, HeadingLevel, Options, Parser, Tag, TagEnd,
+    Alignment as CMarkAlignment, Event, HeadingLevel, OffsetIter, Options, Parser, Tag, TagEnd,
 };
+use std::ops::Range;
 
 /// Parse a Markdown string into a Lumen Document
 pub fn parse_markdown(markdown: &str) -> Document {
@@ -15,7 +18,7 @@ pub fn parse_markdown(markdown: &str) -> Document {
 
     let parser = Parser::new_ext(markdown, options);
     let mut converter = MarkdownConverter::new();
-    converter.process_events(parser);
+    converter.process_events(parser.into_offset_iter());
     converter.finish()
 }
 
@@ -25,6 +28,11 @@ struct MarkdownConverter {
     block_stack: Vec<BlockContext>,
     inline_stack: Vec<InlineContext>,
     current_inlines: Vec<Inline>,
+    /// Source range of the event being processed
+    event: Range<usize>,
+    /// Where the top-level block just completed began, if not in the event
+    /// that completed it (a `<details>` spanning several HTML blocks)
+    block_start: Option<usize>,
 }
 
 /// Context for nested block elements
@@ -33,6 +41,8 @@ enum BlockContext {
     Paragraph,
     Heading {
         level: u8,
+        /// Explicit `{#id}`
+        id: Option<String>,
     },
     BlockQuote {
         blocks: Vec<Block>,
@@ -59,6 +69,17 @@ enum BlockContext {
     TableHead,
     TableRow,
     TableCell,
+    /// Raw HTML block, converted when it ends
+    HtmlBlock {
+        html: String,
+    },
+    /// `<details>` whose content is Markdown between HTML blocks
+    Details {
+        summary: Vec<Inline>,
+        open: bool,
+        blocks: Vec<Block>,
+        start: usize,
+    },
 }
 
 /// Context for nested inline elements
@@ -78,6 +99,8 @@ enum InlineContext {
         title: Option<String>,
         text: Vec<Inline>,
     },
+    /// Inline HTML tag (`<kbd>`, `<b>`, ...) waiting for its end tag
+    Html(OpenTag),
 }
 
 impl MarkdownConverter {
@@ -87,11 +110,15 @@ impl MarkdownConverter {
             block_stack: Vec::new(),
             inline_stack: Vec::new(),
             current_inlines: Vec::new(),
+            event: 0..0,
+            block_start: None,
         }
     }
 
-    fn process_events(&mut self, parser: Parser) {
-        for event in parser {
+    fn process_events(&mut self, events: OffsetIter) {
+        for (event, range) in events {
+            let block_count = self.document.blocks.len();
+            self.event = range.clone();
             match event {
                 Event::Start(tag) => self.handle_start_tag(tag),
                 Event::End(tag_end) => self.handle_end_tag(tag_end),
@@ -100,9 +127,14 @@ impl MarkdownConverter {
                 Event::SoftBreak => self.current_inlines.push(Inline::SoftBreak),
                 Event::HardBreak => self.current_inlines.push(Inline::LineBreak),
                 Event::Rule => self.document.blocks.push(Block::HorizontalRule),
-                Event::Html(_) | Event::InlineHtml(_) => {
-                    // Skip raw HTML for now (could support in future)
+                Event::Html(html) => {
+                    if let Some(BlockContext::HtmlBlock { html: block }) =
+                        self.block_stack.last_mut()
+                    {
+                        block.push_str(&html);
+                    }
                 }
+                Event::InlineHtml(html) => self.handle_inline_html(&html),
                 Event::FootnoteReference(_)
                 | Event::TaskListMarker(_)
                 | Event::InlineMath(_)
@@ -110,6 +142,13 @@ impl MarkdownConverter {
                     // Skip for now
                 }
             }
+
+            // A top-level block is completed by its End event (or is a
+            // single event, like a rule), whose range covers all of it
+            let start = self.block_start.take().unwrap_or(range.start);
+            for _ in block_count..self.document.blocks.len() {
+                self.document.source_ranges.push(start..range.end);
+            }
         }
     }
 
@@ -118,7 +157,7 @@ impl MarkdownConverter {
             Tag::Paragraph => {
                 self.block_stack.push(BlockContext::Paragraph);
             }
-            Tag::Heading { level, .. } => {
+            Tag::Heading { level, id, .. } => {
                 let level = match level {
                     HeadingLevel::H1 => 1,
                     HeadingLevel::H2 => 2,
@@ -127,7 +166,10 @@ impl MarkdownConverter {
                     HeadingLevel::H5 => 5,
                     HeadingLevel::H6 => 6,
                 };
-                self.block_stack.push(BlockContext::Heading { level });
+                self.block_stack.push(BlockContext::Heading {
+                    level,
+                    id: id.map(|id| id.to_string()),
+                });
             }
             Tag::BlockQuote(_) => {
                 self.block_stack
@@ -254,7 +296,12 @@ impl MarkdownConverter {
                     text: saved,
                 }); // Temp use Link context
             }
-            Tag::FootnoteDefinition(_) | Tag::HtmlBlock | Tag::MetadataBlock(_) => {
+            Tag::HtmlBlock => {
+                self.block_stack.push(BlockContext::HtmlBlock {
+                    html: String::new(),
+                });
+            }
+            Tag::FootnoteDefinition(_) | Tag::MetadataBlock(_) => {
                 // Skip for now
             }
             Tag::DefinitionList | Tag::DefinitionListTitle | Tag::DefinitionListDefinition => {
@@ -264,6 +311,20 @@ impl MarkdownConverter {
     }
 
     fn handle_end_tag(&mut self, tag_end: TagEnd) {
+        // Inline HTML tags still open when a Markdown element ends were
+        // never closed inside it
+        while let Some(InlineContext::Html(_)) = self.inline_stack.last() {
+            if let Some(InlineContext::Html(open)) = self.inline_stack.pop() {
+                open.abandon(&mut self.current_inlines);
+            }
+        }
+        // Likewise a `<details>` opened inside a list item or quote
+        if !matches!(tag_end, TagEnd::HtmlBlock) {
+            while matches!(self.block_stack.last(), Some(BlockContext::Details { .. })) {
+                self.close_details();
+            }
+        }
+
         match tag_end {
             TagEnd::Paragraph => {
                 if let Some(BlockContext::Paragraph) = self.block_stack.pop() {
@@ -274,9 +335,11 @@ impl MarkdownConverter {
                 }
             }
             TagEnd::Heading(_) => {
-                if let Some(BlockContext::Heading { level }) = self.block_stack.pop() {
+                if let Some(BlockContext::Heading { level, id }) = self.block_stack.pop() {
                     let content = std::mem::take(&mut self.current_inlines);
-                    self.push_block(Block::Heading { level, content });
+                    // Slugs are assigned once the whole document is parsed
+                    let id = id.unwrap_or_default();
+                    self.push_block(Block::Heading { level, content, id });
                 }
             }
             TagEnd::BlockQuote(_) => {
@@ -437,7 +500,12 @@ impl MarkdownConverter {
                     self.current_inlines = text;
                 }
             }
-            TagEnd::FootnoteDefinition | TagEnd::HtmlBlock | TagEnd::MetadataBlock(_) => {
+            TagEnd::HtmlBlock => {
+                if let Some(BlockContext::HtmlBlock { html }) = self.block_stack.pop() {
+                    self.handle_html_block(&html);
+                }
+            }
+            TagEnd::FootnoteDefinition | TagEnd::MetadataBlock(_) => {
                 // Skip for now
             }
             TagEnd::DefinitionList
@@ -460,12 +528,101 @@ impl MarkdownConverter {
         self.current_inlines.push(Inline::Code(code.to_string()));
     }
 
+    /// Inline HTML arrives one tag at a time: `<kbd>`, `Ctrl`, `</kbd>`
+    fn handle_inline_html(&mut self, raw: &str) {
+        let Some(tag) = HtmlTag::parse(raw) else {
+            if !html::is_comment(raw) {
+                self.current_inlines.push(Inline::Html(raw.to_string()));
+            }
+            return;
+        };
+
+        if let Some(inline) = html::void_inline(&tag) {
+            self.current_inlines.push(inline);
+        } else if !html::is_inline_container(&tag.name) || tag.self_closing {
+            self.current_inlines.push(Inline::Html(raw.to_string()));
+        } else if !tag.closing {
+            let open = OpenTag::new(tag, raw, &mut self.current_inlines);
+            self.inline_stack.push(InlineContext::Html(open));
+        } else {
+            // Close the matching tag, unless a Markdown element opened since
+            let depth = self
+                .inline_stack
+                .iter()
+                .rev()
+                .take_while(|ctx| matches!(ctx, InlineContext::Html(_)))
+                .position(
+                    |ctx| matches!(ctx, InlineContext::Html(open) if open.tag.name == tag.name),
+                );
+            let Some(depth) = depth else {
+                self.current_inlines.push(Inline::Html(raw.to_string()));
+                return;
+            };
+            for level in 0..=depth {
+                if let Some(InlineContext::Html(open)) = self.inline_stack.pop() {
+                    if level == depth {
+                        open.close(&mut self.current_inlines);
+                    } else {
+                        open.abandon(&mut self.current_inlines);
+                    }
+                }
+            }
+        }
+    }
+
+    /// A complete HTML block; `<details>` may open here and close in a
+    /// later block, with Markdown in between
+    fn handle_html_block(&mut self, html: &str) {
+        for item in html::html_block(html) {
+            match item {
+                HtmlItem::Block(block) => self.push_block(block),
+                HtmlItem::DetailsStart { summary, open } => {
+                    self.block_stack.push(BlockContext::Details {
+                        summary,
+                        open,
+                        blocks: Vec::new(),
+                        start: self.event.start,
+                    });
+                }
+                HtmlItem::DetailsEnd => {
+                    if matches!(self.block_stack.last(), Some(BlockContext::Details { .. })) {
+                        self.close_details();
+                    } else {
+                        self.push_block(Block::Paragraph {
+                            content: vec![Inline::Html("</details>".to_string())],
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    fn close_details(&mut self) {
+        if let Some(BlockContext::Details {
+            summary,
+            open,
+            blocks,
+            start,
+        }) = self.block_stack.pop()
+        {
+            if self.block_stack.is_empty() {
+                self.block_start = Some(start);
+            }
+            self.push_block(Block::Details {
+                summary,
+                open,
+                content: blocks,
+            });
+        }
+    }
+
     fn push_block(&mut self, block: Block) {
         // Check if we're inside a nested block context
         if let Some(ctx) = self.block_stack.last_mut() {
             match ctx {
                 BlockContext::BlockQuote { blocks } => blocks.push(block),
                 BlockContext::ListItem { blocks, .. } => blocks.push(block),
+                BlockContext::Details { blocks, .. } => blocks.push(block),
                 _ => self.document.blocks.push(block),
             }
         } else {
@@ -473,68 +630,96 @@ impl MarkdownConverter {
         }
     }
 
-    /// Try to parse a blockquote as a GitHub-style callout
-    /// Syntax: > [!NOTE] or > [!WARNING] etc.
+    /// Try to parse a blockquote as a callout.
+    /// Syntax: `> [!NOTE]`, `> [!tip] Custom title`, or `> [!faq]- Folded`
     fn try_parse_callout(blocks: &[Block]) -> Option<Block> {
-        if blocks.is_empty() {
+        let Some(Block::Paragraph { content }) = blocks.first() else {
             return None;
-        }
+        };
 
-        // Check if first block is a paragraph starting with [!TYPE]
-        if let Block::Paragraph { content } = &blocks[0] {
-            if let Some(Inline::Text(text)) = content.first() {
-                let trimmed = text.trim_start();
-                if trimmed.starts_with("[!") {
-                    // Extract the callout type
-                    if let Some(end_idx) = trimmed.find(']') {
-                        let type_str = &trimmed[2..end_idx];
-                        let kind = match type_str.to_uppercase().as_str() {
-                            "NOTE" => CalloutKind::Note,
-                            "WARNING" => CalloutKind::Warning,
-                            "IMPORTANT" => CalloutKind::Important,
-                            "TIP" => CalloutKind::Tip,
-                            "CAUTION" => CalloutKind::Caution,
-                            _ => return None, // Unknown callout type
-                        };
-
-                        // Remove the [!TYPE] marker from the first paragraph
-                        let mut remaining_blocks = blocks.to_vec();
-                        if let Block::Paragraph { content } = &mut remaining_blocks[0] {
-                            if let Some(Inline::Text(text)) = content.first_mut() {
-                                // Remove [!TYPE] and any following whitespace
-                                let after_marker = &trimmed[end_idx + 1..].trim_start();
-                                *text = after_marker.to_string();
-
-                                // If the text is now empty, remove it
-                                if text.is_empty() {
-                                    content.remove(0);
-                                }
-
-                                // If paragraph is now empty, remove it
-                                if content.is_empty() {
-                                    remaining_blocks.remove(0);
-                                }
-                            }
-                        }
+        // pulldown-cmark may split `[!NOTE]` across several text events
+        let lead_len = content
+            .iter()
+            .take_while(|inline| matches!(inline, Inline::Text(_)))
+            .count();
+        let lead: String = content[..lead_len]
+            .iter()
+            .map(|inline| inline.to_plain_text())
+            .collect();
+        let (kind, folded, after_marker) = parse_callout_marker(&lead)?;
 
-                        return Some(Block::Callout {
-                            kind,
-                            title: None, // Could extract from after [!TYPE] if desired
-                            content: remaining_blocks,
-                        });
-                    }
-                }
-            }
+        // The rest of the marker's line is the title, later lines the body
+        let mut inlines = Vec::new();
+        if !after_marker.is_empty() {
+            inlines.push(Inline::Text(after_marker.to_string()));
+        }
+        inlines.extend(content[lead_len..].iter().cloned());
+        let body = match inlines
+            .iter()
+            .position(|inline| matches!(inline, Inline::SoftBreak | Inline::LineBreak))
+        {
+            Some(line_end) => inlines.split_off(line_end)[1..].to_vec(),
+            None => Vec::new(),
+        };
+        let title: String = inlines
+            .iter()
+            .map(|inline| inline.to_plain_text())
+            .collect();
+        let title = title.trim();
+
+        let mut remaining_blocks = blocks[1..].to_vec();
+        if !body.is_empty() {
+            remaining_blocks.insert(0, Block::Paragraph { content: body });
         }
 
-        None
+        Some(Block::Callout {
+            kind,
+            title: (!title.is_empty()).then(|| title.to_string()),
+            folded,
+            content: remaining_blocks,
+        })
     }
 
-    fn finish(self) -> Document {
+    fn finish(mut self) -> Document {
+        // A `<details>` left open runs to the end of the document
+        let block_count = self.document.blocks.len();
+        while matches!(self.block_stack.last(), Some(BlockContext::Details { .. })) {
+            self.close_details();
+        }
+        if self.document.blocks.len() > block_count {
+            let start = self.block_start.take().unwrap_or(self.event.start);
+            self.document.source_ranges.push(start..self.event.end);
+        }
+        assign_heading_ids(&mut self.document.blocks, &mut Slugger::default());
         self.document
     }
 }
 
+/// Split `[!type]+ rest` into the callout kind, its fold marker and the rest
+/// of the line. Type names are a single word: `[!see also]` is not a callout.
+fn parse_callout_marker(text: &str) -> Option<(CalloutKind, Option<bool>, &str)> {
+    let rest = text.trim_start().strip_prefix("[!")?;
+    let end = rest.find(']')?;
+    let name = &rest[..end];
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+
+    let rest = &rest[end + 1..];
+    let (folded, rest) = if let Some(rest) = rest.strip_prefix('-') {
+        (Some(true), rest)
+    } else if let Some(rest) = rest.strip_prefix('+') {
+        (Some(false), rest)
+    } else {
+        (None, rest)
+    };
+    Some((CalloutKind::from_name(name), folded, rest.trim_start()))
+}
+
 /// Fix pulldown-cmark's incorrect parsing of nested lists with labels.
 ///
 /// When markdown has:
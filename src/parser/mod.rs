@@ -1,8 +1,29 @@
 //! Markdown parser that converts Markdown to Lumen IR
 
+mod csv;
+mod html;
 mod markdown;
+mod slug;
 
+pub use csv::{csv_table, delimiter_for, parse_csv, parse_records, CsvRenderer};
 pub use markdown::parse_markdown;
+pub use slug::{decode_fragment, github_slug, Slugger};
+
+use crate::ir::Document;
+use std::path::Path;
+
+/// Parse a file's contents by its extension: `.csv` and `.tsv` files
+/// become a single table, anything else is Markdown
+pub fn parse_file(path: &Path, source: &str) -> Document {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(delimiter_for)
+    {
+        Some(delimiter) => parse_csv(source, delimiter),
+        None => parse_markdown(source),
+    }
+}
 
 #[cfg(test)]
 mod tests;
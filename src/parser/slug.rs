@@ -0,0 +1,139 @@
+//! Heading anchor ids, computed the way GitHub does
+//!
+//! A heading's id is its text lowercased, with punctuation and symbols
+//! (emoji included) removed and each space turned into `-`. Repeated ids
+//! get `-1`, `-2`, ... appended, so `[see](#setup-1)` reaches the second
+//! "Setup" heading.
+
+use crate::ir::Block;
+use std::collections::HashMap;
+
+/// GitHub's slug for heading text: `"What's new in v2.0?"` → `whats-new-in-v20`
+pub fn github_slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Hands out unique heading ids for one document
+#[derive(Debug, Default)]
+pub struct Slugger {
+    /// Id → how many times its slug has been repeated
+    occurrences: HashMap<String, usize>,
+}
+
+impl Slugger {
+    /// Unique id for heading text: its slug, or the slug with `-1`, `-2`, ...
+    /// appended if already taken
+    pub fn slug(&mut self, text: &str) -> String {
+        let base = github_slug(text);
+        let mut slug = base.clone();
+        while self.occurrences.contains_key(&slug) {
+            let count = self.occurrences.entry(base.clone()).or_insert(0);
+            *count += 1;
+            slug = format!("{}-{}", base, count);
+        }
+        self.occurrences.insert(slug.clone(), 0);
+        slug
+    }
+
+    /// Mark an explicit id (`{#id}`) as taken
+    pub fn reserve(&mut self, id: &str) {
+        self.occurrences.entry(id.to_string()).or_insert(0);
+    }
+}
+
+/// Give every heading without an explicit id its slug, in document order
+pub(crate) fn assign_heading_ids(blocks: &mut [Block], slugger: &mut Slugger) {
+    for block in blocks {
+        match block {
+            Block::Heading { content, id, .. } => {
+                if id.is_empty() {
+                    let text: String = content.iter().map(|i| i.to_plain_text()).collect();
+                    *id = slugger.slug(&text);
+                } else {
+                    slugger.reserve(id);
+                }
+            }
+            Block::BlockQuote { blocks }
+            | Block::Callout {
+                content: blocks, ..
+            }
+            | Block::Details {
+                content: blocks, ..
+            } => assign_heading_ids(blocks, slugger),
+            Block::List { items, .. } => {
+                for item in items {
+                    assign_heading_ids(&mut item.content, slugger);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Link fragments may be percent-encoded (`#caf%C3%A9`); decode them to
+/// compare with heading ids
+pub fn decode_fragment(fragment: &str) -> String {
+    let bytes = fragment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| fragment.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_slug() {
+        assert_eq!(github_slug("Getting Started"), "getting-started");
+        assert_eq!(github_slug("What's new in v2.0?"), "whats-new-in-v20");
+        assert_eq!(github_slug("C++ & Rust"), "c--rust");
+        assert_eq!(github_slug("🚀 Launch"), "-launch");
+        assert_eq!(github_slug("snake_case and-dash"), "snake_case-and-dash");
+        assert_eq!(github_slug("Café Ünïcode"), "café-ünïcode");
+    }
+
+    #[test]
+    fn test_slugger_disambiguates_repeats() {
+        let mut slugger = Slugger::default();
+        assert_eq!(slugger.slug("Setup"), "setup");
+        assert_eq!(slugger.slug("Setup"), "setup-1");
+        assert_eq!(slugger.slug("Setup"), "setup-2");
+        // A heading whose own slug is already taken
+        assert_eq!(slugger.slug("Setup 1"), "setup-1-1");
+
+        slugger.reserve("usage");
+        assert_eq!(slugger.slug("Usage"), "usage-1");
+    }
+
+    #[test]
+    fn test_decode_fragment() {
+        assert_eq!(decode_fragment("caf%C3%A9"), "café");
+        assert_eq!(decode_fragment("100%-done"), "100%-done");
+    }
+}
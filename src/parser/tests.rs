@@ -1,7 +1,7 @@
 //! Parser tests
 
 use super::parse_markdown;
-use crate::ir::{Block, Inline};
+use crate::ir::{Block, CalloutKind, Inline};
 
 #[test]
 fn test_parse_simple_paragraph() {
@@ -29,7 +29,7 @@ fn test_parse_heading() {
     assert_eq!(doc.blocks.len(), 2);
 
     match &doc.blocks[0] {
-        Block::Heading { level, content } => {
+        Block::Heading { level, content, .. } => {
             assert_eq!(*level, 1);
             assert_eq!(content.len(), 1);
         }
@@ -195,3 +195,246 @@ fn test_parse_table() {
         _ => panic!("Expected Table block"),
     }
 }
+
+#[test]
+fn test_source_ranges_cover_top_level_blocks() {
+    let markdown = "# Title\n\n```sh\ncargo test\n```\n\n- a\n- b\n\n---\n\n> [!NOTE]\n> Hi\n";
+    let doc = parse_markdown(markdown);
+
+    assert_eq!(doc.source_ranges.len(), doc.blocks.len());
+    let sources: Vec<&str> = doc
+        .source_ranges
+        .iter()
+        .map(|range| markdown[range.clone()].trim_end())
+        .collect();
+    assert_eq!(
+        sources,
+        vec![
+            "# Title",
+            "```sh\ncargo test\n```",
+            "- a\n- b",
+            "---",
+            "> [!NOTE]\n> Hi"
+        ]
+    );
+}
+
+#[test]
+fn test_parse_github_callout() {
+    let doc = parse_markdown("> [!NOTE]\n> Useful information.");
+
+    match &doc.blocks[0] {
+        Block::Callout {
+            kind,
+            title,
+            folded,
+            content,
+        } => {
+            assert_eq!(*kind, CalloutKind::Note);
+            assert_eq!(*title, None);
+            assert_eq!(*folded, None);
+            assert_eq!(
+                content,
+                &vec![Block::Paragraph {
+                    content: vec![Inline::Text("Useful information.".to_string())]
+                }]
+            );
+        }
+        other => panic!("Expected Callout block, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_callout_title_alias_and_fold() {
+    let doc = parse_markdown("> [!faq]- Why *fold* it?\n> Because it is long.");
+
+    match &doc.blocks[0] {
+        Block::Callout {
+            kind,
+            title,
+            folded,
+            content,
+        } => {
+            assert_eq!(*kind, CalloutKind::Question);
+            assert_eq!(title.as_deref(), Some("Why fold it?"));
+            assert_eq!(*folded, Some(true));
+            assert_eq!(content.len(), 1);
+        }
+        other => panic!("Expected Callout block, got {:?}", other),
+    }
+
+    let doc = parse_markdown("> [!Recipe]+ Pancakes");
+    match &doc.blocks[0] {
+        Block::Callout {
+            kind,
+            title,
+            folded,
+            content,
+        } => {
+            assert_eq!(*kind, CalloutKind::Custom("recipe".to_string()));
+            assert_eq!(title.as_deref(), Some("Pancakes"));
+            assert_eq!(*folded, Some(false));
+            assert!(content.is_empty());
+        }
+        other => panic!("Expected Callout block, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_bracketed_text_is_not_a_callout() {
+    let doc = parse_markdown("> [!see also] the docs");
+    assert!(matches!(doc.blocks[0], Block::BlockQuote { .. }));
+}
+
+#[test]
+fn test_parse_inline_html() {
+    let doc = parse_markdown(
+        "Press <kbd>Ctrl</kbd>+<kbd>C</kbd>, see <b>x<sup>2</sup></b><br>H<sub>2</sub>O <blink>!</blink>",
+    );
+    let Block::Paragraph { content } = &doc.blocks[0] else {
+        panic!("Expected Paragraph block, got {:?}", doc.blocks[0]);
+    };
+    let text = |s: &str| Inline::Text(s.to_string());
+    assert_eq!(
+        content,
+        &vec![
+            text("Press "),
+            Inline::Kbd("Ctrl".to_string()),
+            text("+"),
+            Inline::Kbd("C".to_string()),
+            text(", see "),
+            Inline::Strong(vec![text("x"), text("²")]),
+            Inline::LineBreak,
+            text("H"),
+            text("₂"),
+            text("O "),
+            Inline::Html("<blink>".to_string()),
+            text("!"),
+            Inline::Html("</blink>".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_unclosed_inline_html_stays_visible() {
+    let doc = parse_markdown("**<kbd>Esc**</kbd> <!-- hidden -->");
+    let Block::Paragraph { content } = &doc.blocks[0] else {
+        panic!("Expected Paragraph block, got {:?}", doc.blocks[0]);
+    };
+    assert_eq!(
+        content,
+        &vec![
+            Inline::Strong(vec![
+                Inline::Html("<kbd>".to_string()),
+                Inline::Text("Esc".to_string()),
+            ]),
+            Inline::Html("</kbd>".to_string()),
+            Inline::Text(" ".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_details_around_markdown() {
+    let markdown = "Intro\n\n<details>\n<summary>Show <b>more</b></summary>\n\n\
+                    Hidden **text**.\n\n- a\n- b\n\n</details>\n\nAfter";
+    let doc = parse_markdown(markdown);
+
+    assert_eq!(doc.blocks.len(), 3);
+    assert_eq!(doc.source_ranges.len(), 3);
+    match &doc.blocks[1] {
+        Block::Details {
+            summary,
+            open,
+            content,
+        } => {
+            assert_eq!(
+                summary
+                    .iter()
+                    .map(|i| i.to_plain_text())
+                    .collect::<String>(),
+                "Show more"
+            );
+            assert!(!open);
+            assert!(matches!(content[0], Block::Paragraph { .. }));
+            assert!(matches!(content[1], Block::List { .. }));
+        }
+        other => panic!("Expected Details block, got {:?}", other),
+    }
+    let source = &markdown[doc.source_ranges[1].clone()];
+    assert!(source.starts_with("<details>") && source.trim_end().ends_with("</details>"));
+}
+
+#[test]
+fn test_parse_html_blocks() {
+    let doc = parse_markdown(
+        "<p align=\"center\">\n  <img src=\"logo.png\" alt=\"Logo\" width=\"80\">\n</p>\n\n\
+         <table>\n<tr><th>Key</th><th>Action</th></tr>\n<tr><td><kbd>q</kbd></td><td>Quit</td></tr>\n</table>\n\n\
+         <details open><summary>Open</summary>Inline body</details>\n",
+    );
+
+    assert_eq!(doc.blocks.len(), 3);
+    let Block::Paragraph { content } = &doc.blocks[0] else {
+        panic!("Expected Paragraph block, got {:?}", doc.blocks[0]);
+    };
+    assert!(content.iter().any(|i| matches!(
+        i,
+        Inline::Image { url, alt, .. } if url == "logo.png" && alt == "Logo"
+    )));
+
+    let Block::Table { headers, rows, .. } = &doc.blocks[1] else {
+        panic!("Expected Table block, got {:?}", doc.blocks[1]);
+    };
+    assert_eq!(headers.len(), 2);
+    assert_eq!(rows[0][0].content, vec![Inline::Kbd("q".to_string())]);
+
+    let Block::Details { open, content, .. } = &doc.blocks[2] else {
+        panic!("Expected Details block, got {:?}", doc.blocks[2]);
+    };
+    assert!(open);
+    assert_eq!(content.len(), 1);
+}
+
+#[test]
+fn test_heading_ids_are_github_slugs() {
+    let doc = parse_markdown(
+        "# What's New?\n\n## Setup\n\n> ## Setup\n\n## Setup {#custom-setup}\n\n\
+         <h2 id=\"raw\">Raw</h2>\n\n## 🚀 Launch `v2.0`",
+    );
+    let mut ids = Vec::new();
+    fn collect(blocks: &[Block], ids: &mut Vec<String>) {
+        for block in blocks {
+            match block {
+                Block::Heading { id, .. } => ids.push(id.clone()),
+                Block::BlockQuote { blocks } => collect(blocks, ids),
+                _ => {}
+            }
+        }
+    }
+    collect(&doc.blocks, &mut ids);
+    assert_eq!(
+        ids,
+        vec![
+            "whats-new",
+            "setup",
+            "setup-1",
+            "custom-setup",
+            "raw",
+            "-launch-v20"
+        ]
+    );
+}
+
+#[test]
+fn test_parse_file_reads_csv_as_a_table() {
+    use super::parse_file;
+    use std::path::Path;
+
+    let doc = parse_file(Path::new("data.tsv"), "name\tqty\npen\t3\n");
+    assert!(matches!(
+        doc.blocks.as_slice(),
+        [Block::Table { headers, rows, .. }] if headers.len() == 2 && rows.len() == 1
+    ));
+    let doc = parse_file(Path::new("notes.md"), "name\tqty");
+    assert!(matches!(doc.blocks.as_slice(), [Block::Paragraph { .. }]));
+}
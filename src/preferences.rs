@@ -1,6 +1,7 @@
 //! User preferences management
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
 use std::path::PathBuf;
@@ -18,23 +19,96 @@ pub struct Preferences {
     /// Whether file sidebar was visible
     #[serde(default = "default_true")]
     pub file_sidebar_visible: bool,
+
+    /// Whether marks (`Ma` / `'a`) are saved across sessions
+    #[serde(default)]
+    pub persist_marks: bool,
+
+    /// Key binding overrides: key sequence → action name (see `keymap`),
+    /// kept as written so saving never rewrites them; read them with
+    /// [`Preferences::key_overrides`]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_keys",
+        skip_serializing_if = "serde_yaml::Mapping::is_empty"
+    )]
+    pub keys: serde_yaml::Mapping,
+
+    /// Code blocks longer than this many lines can be folded (`za`)
+    #[serde(default = "default_fold_code_lines")]
+    pub fold_code_lines: usize,
 }
 
 fn default_true() -> bool {
     true
 }
 
+/// The `keys:` section as written, so one bad entry doesn't lose the whole
+/// config; an empty `keys:` is no overrides
+fn deserialize_keys<'de, D>(deserializer: D) -> Result<serde_yaml::Mapping, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<serde_yaml::Mapping>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// A scalar key or action as text
+fn key_text(value: &serde_yaml::Value) -> Result<String, &'static str> {
+    match value {
+        serde_yaml::Value::Null => Err("nothing"),
+        serde_yaml::Value::Bool(b) => Ok(b.to_string()),
+        serde_yaml::Value::Number(n) => Ok(n.to_string()),
+        serde_yaml::Value::String(s) => Ok(s.clone()),
+        serde_yaml::Value::Sequence(_) => Err("a list"),
+        serde_yaml::Value::Mapping(_) => Err("a map"),
+        serde_yaml::Value::Tagged(tagged) => key_text(&tagged.value),
+    }
+}
+
+fn default_fold_code_lines() -> usize {
+    crate::layout::fold::DEFAULT_FOLD_CODE_LINES
+}
+
 impl Default for Preferences {
     fn default() -> Self {
         Self {
             theme: "docs".to_string(),
             mouse_enabled: false,
             file_sidebar_visible: true,
+            persist_marks: false,
+            keys: serde_yaml::Mapping::new(),
+            fold_code_lines: default_fold_code_lines(),
         }
     }
 }
 
 impl Preferences {
+    /// Key binding overrides for [`Keymap`](crate::keymap::Keymap), and a
+    /// warning for each entry that isn't a key and an action name. Entries
+    /// without an action (`gg:`) are skipped.
+    pub fn key_overrides(&self) -> (BTreeMap<String, String>, Vec<String>) {
+        let mut overrides = BTreeMap::new();
+        let mut warnings = Vec::new();
+        for (sequence, action) in &self.keys {
+            if action.is_null() {
+                continue;
+            }
+            match (key_text(sequence), key_text(action)) {
+                (Ok(sequence), Ok(action)) => {
+                    overrides.insert(sequence, action);
+                }
+                (Err(got), _) => {
+                    warnings.push(format!("key binding: expected a key sequence, got {}", got))
+                }
+                (Ok(sequence), Err(got)) => warnings.push(format!(
+                    "key binding '{}': expected an action name, got {}",
+                    sequence, got
+                )),
+            }
+        }
+        (overrides, warnings)
+    }
+
     /// Get the path to the preferences directory (~/.lumen)
     pub fn config_dir() -> Option<PathBuf> {
         dirs::home_dir().map(|home| home.join(".lumen"))
@@ -109,6 +183,7 @@ mod tests {
         assert_eq!(prefs.theme, "docs");
         assert!(!prefs.mouse_enabled);
         assert!(prefs.file_sidebar_visible);
+        assert_eq!(prefs.fold_code_lines, 10);
     }
 
     #[test]
@@ -117,6 +192,9 @@ mod tests {
             theme: "dracula".to_string(),
             mouse_enabled: true,
             file_sidebar_visible: false,
+            persist_marks: true,
+            keys: serde_yaml::Mapping::new(),
+            fold_code_lines: 20,
         };
 
         let yaml = serde_yaml::to_string(&prefs).unwrap();
@@ -125,5 +203,44 @@ mod tests {
         assert_eq!(deserialized.theme, "dracula");
         assert!(deserialized.mouse_enabled);
         assert!(!deserialized.file_sidebar_visible);
+        assert!(deserialized.persist_marks);
+        assert_eq!(deserialized.fold_code_lines, 20);
+    }
+
+    #[test]
+    fn test_keys_section() {
+        let yaml = "theme: docs\nkeys:\n  gg: top\n  Ctrl-e: scroll_down\n";
+        let prefs: Preferences = serde_yaml::from_str(yaml).unwrap();
+        let (overrides, warnings) = prefs.key_overrides();
+
+        assert_eq!(overrides.get("gg").map(String::as_str), Some("top"));
+        assert_eq!(
+            overrides.get("Ctrl-e").map(String::as_str),
+            Some("scroll_down")
+        );
+        assert!(warnings.is_empty());
+
+        // Entries without an action are skipped and other bad values are
+        // warned about; the rest of the file still loads
+        let yaml = "theme: nord\nkeys:\n  gg:\n  j: [a, b]\n  k: scroll_up\n";
+        let prefs: Preferences = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(prefs.theme, "nord");
+        let (overrides, warnings) = prefs.key_overrides();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(
+            warnings,
+            vec!["key binding 'j': expected an action name, got a list"]
+        );
+        // ...and saving writes them back as they were
+        let saved: Preferences =
+            serde_yaml::from_str(&serde_yaml::to_string(&prefs).unwrap()).unwrap();
+        assert_eq!(saved.keys, prefs.keys);
+        assert!(saved.keys["gg"].is_null());
+        let prefs: Preferences = serde_yaml::from_str("theme: nord\nkeys:\n").unwrap();
+        assert!(prefs.keys.is_empty());
+
+        // Empty overrides are not written back
+        let yaml = serde_yaml::to_string(&Preferences::default()).unwrap();
+        assert!(!yaml.contains("keys"));
     }
 }
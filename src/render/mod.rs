@@ -1,6 +1,11 @@
 //! Terminal rendering
 
-use crate::layout::{ImageReference, LayoutElement, LayoutNode, LayoutTree, Line, TextSegment};
+use crate::codeblock::{DiagramLine, DiagramRole};
+use crate::keymap::{Action, Keymap};
+use crate::layout::code::{copy_badge_rect, CONTINUATION_MARKER, COPY_BADGE};
+use crate::layout::{
+    CodeLine, ImageReference, LayoutElement, LayoutNode, LayoutTree, Line, TextSegment,
+};
 use crate::search::SearchState;
 use crate::theme::{BorderStyle, Color, FontStyle, FontWeight, Theme};
 use ratatui::{
@@ -19,6 +24,9 @@ use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub type Terminal = RatatuiTerminal<CrosstermBackend<io::Stdout>>;
 
+/// Columns reserved at the right edge of the document for the scrollbar
+pub const SCROLLBAR_WIDTH: u16 = 1;
+
 /// Initialize terminal for rendering
 pub fn init_terminal() -> io::Result<Terminal> {
     crossterm::terminal::enable_raw_mode()?;
@@ -60,11 +68,16 @@ pub fn restore_terminal(terminal: &mut Terminal) -> io::Result<()> {
 /// * `tree` - Positioned layout tree from `layout_document`
 /// * `theme` - Theme for colors, borders, and styling
 /// * `show_help` - Whether to show the help menu overlay
+/// * `keymap` - Active key bindings (the help menu is generated from them)
 /// * `search_state` - Current search state (query, matches, selection)
 /// * `file_manager` - File manager with open files
 /// * `show_file_sidebar` - Whether to show the file navigation sidebar
 /// * `file_jump_mode` - Whether file jump mode is active (entering file number)
 /// * `file_jump_buffer` - Buffer containing the file number being entered
+/// * `selected_link_index` - Link selected with link navigation, if any
+/// * `status_message` - Transient message shown on the right of the status bar
+/// * `pending_input` - Count prefix / partial key sequence typed so far (e.g. `10`)
+/// * `selection` - First and last document row of the visual line selection
 ///
 /// # Returns
 ///
@@ -74,7 +87,7 @@ pub fn restore_terminal(terminal: &mut Terminal) -> io::Result<()> {
 ///
 /// ```no_run
 /// use lumen::render::{init_terminal, render};
-/// use lumen::{parse_markdown, layout_document, Theme, FileManager};
+/// use lumen::{parse_markdown, layout_document, Keymap, Theme, FileManager};
 /// use lumen::layout::Viewport;
 /// use lumen::search::SearchState;
 ///
@@ -86,15 +99,17 @@ pub fn restore_terminal(terminal: &mut Terminal) -> io::Result<()> {
 /// let tree = layout_document(&doc, &theme, viewport, false);
 /// let mut file_manager = FileManager::new();
 /// let search_state = SearchState::new();
+/// let keymap = Keymap::default();
 ///
-/// render(&mut terminal, &tree, &theme, false, &search_state,
-///        &file_manager, false, false, "", None, None).unwrap();
+/// render(&mut terminal, &tree, &theme, false, &keymap, &search_state,
+///        &file_manager, false, false, "", None, None, "", None).unwrap();
 /// ```
 pub fn render(
     terminal: &mut Terminal,
     tree: &LayoutTree,
     theme: &Theme,
     show_help: bool,
+    keymap: &Keymap,
     search_state: &SearchState,
     file_manager: &crate::FileManager,
     show_file_sidebar: bool,
@@ -102,6 +117,8 @@ pub fn render(
     file_jump_buffer: &str,
     selected_link_index: Option<usize>,
     status_message: Option<&str>,
+    pending_input: &str,
+    selection: Option<(u16, u16)>,
 ) -> io::Result<()> {
     terminal.draw(|frame| {
         let area = frame.area();
@@ -162,6 +179,11 @@ pub fn render(
             );
         }
 
+        if let Some((first, last)) = selection {
+            render_selection(frame, theme, scroll_y, content_area, first, last);
+        }
+        render_scrollbar(frame, tree, theme, content_area);
+
         // Render file sidebar if present
         if let Some(file_sidebar) = file_sidebar_area {
             render_file_sidebar(frame, file_manager, file_sidebar, theme);
@@ -173,19 +195,17 @@ pub fn render(
         }
 
         // Render status bar (use full area width)
-        render_status_bar(
-            frame,
-            tree,
-            area,
-            search_state,
-            file_jump_mode,
-            file_jump_buffer,
-            status_message,
-        );
+        let status = StatusBar {
+            file_jump: file_jump_mode.then_some(file_jump_buffer),
+            message: status_message,
+            pending_input,
+            selection,
+        };
+        render_status_bar(frame, tree, theme, area, search_state, &status);
 
         // Render help menu if active
         if show_help {
-            render_help_menu(frame, area);
+            render_help_menu(frame, area, theme, keymap);
         }
     })?;
     Ok(())
@@ -208,8 +228,10 @@ fn render_node(
     let is_container = matches!(
         node.element,
         LayoutElement::Callout { .. }
+            | LayoutElement::Details
             | LayoutElement::BlockQuote
             | LayoutElement::CodeBlock { .. }
+            | LayoutElement::Diagram { .. }
             | LayoutElement::List { .. }
             | LayoutElement::ListItem { .. }
             | LayoutElement::Table { .. }
@@ -260,19 +282,41 @@ fn render_node(
                 );
             }
         }
-        LayoutElement::CodeBlock { lang, lines } => {
+        LayoutElement::CodeBlock {
+            lang,
+            lines,
+            gutter_width,
+            line_numbers,
+        } => {
+            let place = CodeBlockPlace {
+                x: node.rect.x + x_offset,
+                display_y,
+                width: node.rect.width,
+                area,
+                node_y: node.rect.y,
+                scroll_y,
+            };
             render_code_block(
                 frame,
                 lang,
                 lines,
+                *gutter_width,
+                *line_numbers,
                 theme,
-                node.rect.x + x_offset,
+                &place,
+                search_state,
+            );
+        }
+        LayoutElement::Diagram { lang, lines } => {
+            let place = CodeBlockPlace {
+                x: node.rect.x + x_offset,
                 display_y,
-                node.rect.width,
+                width: node.rect.width,
                 area,
-                node.rect.y,
+                node_y: node.rect.y,
                 scroll_y,
-            );
+            };
+            render_diagram(frame, lang, lines, theme, &place, search_state);
         }
         LayoutElement::List { .. } => {
             for child in &node.children {
@@ -380,16 +424,8 @@ fn render_node(
             }
         }
         LayoutElement::Callout { kind } => {
-            use crate::ir::CalloutKind;
-
             // Get style for this callout type
-            let callout_style = match kind {
-                CalloutKind::Note => &theme.blocks.callout.note,
-                CalloutKind::Warning => &theme.blocks.callout.warning,
-                CalloutKind::Important => &theme.blocks.callout.important,
-                CalloutKind::Tip => &theme.blocks.callout.tip,
-                CalloutKind::Caution => &theme.blocks.callout.caution,
-            };
+            let callout_style = theme.blocks.callout.style_for(kind);
 
             // Calculate visible portion of callout
             let visible_start_y = if node.rect.y < scroll_y {
@@ -754,18 +790,32 @@ fn render_node(
 
             frame.render_widget(Paragraph::new(hr_text), hr_area);
         }
+        LayoutElement::Folded { summary, .. } => {
+            if !summary.is_empty() {
+                let style = Style::default()
+                    .fg(to_ratatui_color(theme.colors.muted))
+                    .add_modifier(Modifier::ITALIC);
+                let summary_area = ratatui::layout::Rect {
+                    x: node.rect.x + x_offset,
+                    y: display_y,
+                    width: area.width.saturating_sub(node.rect.x + x_offset),
+                    height: 1,
+                };
+                frame.render_widget(
+                    Paragraph::new(RatatuiText::from(Span::styled(summary.as_str(), style))),
+                    summary_area,
+                );
+            }
+        }
         LayoutElement::Image { path, alt_text } => {
             // Render inline image
-            render_inline_image(
-                frame,
-                path,
-                alt_text,
-                node.rect.x + x_offset,
-                display_y,
-                node.rect.width,
-                node.rect.height,
-                area,
-            );
+            let image_area = ratatui::layout::Rect {
+                x: node.rect.x + x_offset,
+                y: display_y,
+                width: node.rect.width,
+                height: node.rect.height,
+            };
+            render_inline_image(frame, path, alt_text, image_area, area, theme);
         }
         _ => {
             // Render children for other types
@@ -858,11 +908,18 @@ fn render_paragraph(
         }
 
         // Get search matches for this line
-        let line_matches: Vec<&crate::search::SearchMatch> = search_state
+        // (a match can contribute runs to several rows, so collect its spans)
+        let mut line_matches: Vec<(&crate::search::MatchSpan, bool)> = search_state
             .matches
             .iter()
-            .filter(|m| m.y == line_y_in_doc)
+            .enumerate()
+            .flat_map(|(idx, m)| {
+                let is_current = search_state.current_index == Some(idx);
+                m.spans_on_row(line_y_in_doc)
+                    .map(move |span| (span, is_current))
+            })
             .collect();
+        line_matches.sort_by_key(|(span, _)| span.x);
 
         // If no matches on this line, render normally
         if line_matches.is_empty() {
@@ -889,7 +946,7 @@ fn render_paragraph(
                 let seg_end = current_x + seg_display_width;
                 let mut last_col: usize = 0; // display columns consumed within segment
 
-                for match_ref in &line_matches {
+                for &(match_ref, is_current) in &line_matches {
                     let match_start = match_ref.x;
                     let match_end = match_ref.x + match_ref.length as u16;
 
@@ -917,20 +974,16 @@ fn render_paragraph(
                         // Add highlighted match
                         if overlap_end_col > overlap_start_col {
                             let match_text = &seg.text[overlap_start_byte..overlap_end_byte];
-                            let is_current = search_state
-                                .current_index
-                                .map(|idx| search_state.matches[idx] == **match_ref)
-                                .unwrap_or(false);
-
+                            let ui = theme.ui_colors();
                             let highlight_style = if is_current {
                                 Style::default()
-                                    .bg(RatatuiColor::Yellow)
-                                    .fg(RatatuiColor::Black)
+                                    .bg(to_ratatui_color(ui.search_current_bg))
+                                    .fg(to_ratatui_color(ui.search_current_fg))
                                     .add_modifier(Modifier::BOLD)
                             } else {
                                 Style::default()
-                                    .bg(RatatuiColor::DarkGray)
-                                    .fg(RatatuiColor::White)
+                                    .bg(to_ratatui_color(ui.search_match_bg))
+                                    .fg(to_ratatui_color(ui.search_match_fg))
                             };
 
                             spans.push(Span::styled(match_text.to_string(), highlight_style));
@@ -1005,23 +1058,239 @@ fn segment_to_style(seg: &TextSegment, theme: &Theme) -> Style {
     }
 }
 
-fn render_code_block(
-    frame: &mut ratatui::Frame,
-    lang: &Option<String>,
-    lines: &[String],
-    theme: &Theme,
+/// Where a code block or diagram is drawn: its column, width and first
+/// row on screen, the area it is clipped to, and its first row in the
+/// document with the scroll offset
+#[derive(Debug, Clone, Copy)]
+struct CodeBlockPlace {
     x: u16,
     display_y: u16,
     width: u16,
     area: ratatui::layout::Rect,
     node_y: u16,
     scroll_y: u16,
+}
+
+fn render_code_block(
+    frame: &mut ratatui::Frame,
+    lang: &Option<String>,
+    lines: &[CodeLine],
+    gutter_width: u16,
+    line_numbers: bool,
+    theme: &Theme,
+    place: &CodeBlockPlace,
+    search_state: &SearchState,
 ) {
+    let CodeBlockPlace {
+        x,
+        area,
+        node_y,
+        scroll_y,
+        ..
+    } = *place;
     let code_style = &theme.blocks.code_block;
     let style = Style::default()
         .fg(to_ratatui_color(code_style.foreground))
         .bg(to_ratatui_color(code_style.background));
 
+    let actual_width = render_code_frame(frame, lang, lines.len() as u16, theme, place);
+
+    // Calculate which lines are visible
+    // Code block content starts at node_y + 1 (after top border)
+    let content_start_y = node_y + 1;
+
+    // Determine starting line index based on scroll position
+    let start_line = if content_start_y < scroll_y {
+        (scroll_y - content_start_y) as usize
+    } else {
+        0
+    };
+
+    // Render code lines
+    for (i, line) in lines.iter().enumerate().skip(start_line) {
+        let line_y_in_doc = content_start_y + i as u16;
+
+        // Skip if line is above viewport
+        if line_y_in_doc < scroll_y {
+            continue;
+        }
+
+        // Stop if line is below viewport
+        if line_y_in_doc >= scroll_y + area.height {
+            break;
+        }
+
+        let display_line_y = line_y_in_doc - scroll_y;
+        let content_width = actual_width.saturating_sub(2) as usize;
+
+        let row_style = if line.highlighted {
+            style.bg(to_ratatui_color(
+                code_style
+                    .highlight_background
+                    .unwrap_or(theme.colors.muted),
+            ))
+        } else if line.error {
+            style.fg(to_ratatui_color(theme.colors.error))
+        } else {
+            style
+        };
+
+        // Gutter: right-aligned line number, `↪` on wrapped rows
+        let mut spans = Vec::new();
+        if gutter_width > 0 {
+            let number_width = gutter_width.saturating_sub(1) as usize;
+            let gutter = match (line.continuation, line_numbers) {
+                _ if line.error => " ".repeat(gutter_width as usize),
+                (true, _) => format!("{:>number_width$} ", CONTINUATION_MARKER),
+                (false, true) => format!("{:>number_width$} ", line.number),
+                (false, false) => " ".repeat(gutter_width as usize),
+            };
+            let gutter_fg = if line.highlighted {
+                theme.colors.accent
+            } else {
+                theme.colors.muted
+            };
+            spans.push(Span::styled(
+                gutter,
+                row_style.fg(to_ratatui_color(gutter_fg)),
+            ));
+        }
+
+        // Pad line with spaces to fill the full width so background extends across
+        let text_width = content_width.saturating_sub(gutter_width as usize);
+        let padded_line = format!("{:width$}", line.text, width = text_width);
+        let text_x = x + 1 + gutter_width;
+        spans.extend(highlight_code_row(
+            padded_line,
+            text_x,
+            line_y_in_doc,
+            row_style,
+            theme,
+            search_state,
+        ));
+
+        let para = Paragraph::new(RatatuiText::from(ratatui::text::Line::from(spans)));
+
+        let line_area = ratatui::layout::Rect {
+            x: x + 1,
+            y: display_line_y,
+            width: actual_width.saturating_sub(2),
+            height: 1,
+        };
+
+        frame.render_widget(para, line_area);
+    }
+}
+
+/// A rendered diagram: the code block frame around rows whose parts are
+/// coloured by their [`DiagramRole`]
+fn render_diagram(
+    frame: &mut ratatui::Frame,
+    lang: &Option<String>,
+    lines: &[DiagramLine],
+    theme: &Theme,
+    place: &CodeBlockPlace,
+    search_state: &SearchState,
+) {
+    let CodeBlockPlace {
+        x,
+        area,
+        node_y,
+        scroll_y,
+        ..
+    } = *place;
+    let code_style = &theme.blocks.code_block;
+    let style = Style::default()
+        .fg(to_ratatui_color(code_style.foreground))
+        .bg(to_ratatui_color(code_style.background));
+    let colors = theme.diagram_colors();
+
+    let actual_width = render_code_frame(frame, lang, lines.len() as u16, theme, place);
+    let content_width = actual_width.saturating_sub(2);
+
+    let content_start_y = node_y + 1;
+    for (i, line) in lines.iter().enumerate() {
+        let doc_y = content_start_y + i as u16;
+        if doc_y < scroll_y {
+            continue;
+        }
+        if doc_y >= scroll_y + area.height {
+            break;
+        }
+
+        let mut spans = Vec::new();
+        let mut column = 0;
+        for span in &line.spans {
+            let fg = match span.role {
+                DiagramRole::Text => code_style.foreground,
+                DiagramRole::NodeBorder => colors.node_border,
+                DiagramRole::NodeLabel => colors.node_label,
+                DiagramRole::Edge => colors.edge,
+                DiagramRole::Arrowhead => colors.arrowhead,
+                DiagramRole::EdgeLabel => colors.edge_label,
+                DiagramRole::Added | DiagramRole::AddedWord => colors.added,
+                DiagramRole::Removed | DiagramRole::RemovedWord => colors.removed,
+                DiagramRole::HunkHeader => colors.hunk_header,
+                DiagramRole::FileHeader => colors.file_header,
+            };
+            let span_style = match span.role {
+                // Changed words stand out from the rest of their line
+                DiagramRole::AddedWord | DiagramRole::RemovedWord => style
+                    .fg(to_ratatui_color(code_style.background))
+                    .bg(to_ratatui_color(fg)),
+                DiagramRole::HunkHeader | DiagramRole::FileHeader => {
+                    style.fg(to_ratatui_color(fg)).add_modifier(Modifier::BOLD)
+                }
+                _ => style.fg(to_ratatui_color(fg)),
+            };
+            spans.extend(highlight_code_row(
+                span.text.clone(),
+                x + 1 + column,
+                doc_y,
+                span_style,
+                theme,
+                search_state,
+            ));
+            column += UnicodeWidthStr::width(span.text.as_str()) as u16;
+        }
+        // Pad so the background extends across
+        if column < content_width {
+            spans.push(Span::styled(
+                " ".repeat((content_width - column) as usize),
+                style,
+            ));
+        }
+
+        frame.render_widget(
+            Paragraph::new(RatatuiText::from(ratatui::text::Line::from(spans))),
+            ratatui::layout::Rect {
+                x: x + 1,
+                y: doc_y - scroll_y,
+                width: content_width,
+                height: 1,
+            },
+        );
+    }
+}
+
+/// Borders, language badge and copy badge of a code block with `rows`
+/// rows of content; returns the width it was drawn at
+fn render_code_frame(
+    frame: &mut ratatui::Frame,
+    lang: &Option<String>,
+    rows: u16,
+    theme: &Theme,
+    place: &CodeBlockPlace,
+) -> u16 {
+    let CodeBlockPlace {
+        x,
+        display_y,
+        width,
+        area,
+        node_y,
+        scroll_y,
+    } = *place;
+    let code_style = &theme.blocks.code_block;
     let border_fg = to_ratatui_color(code_style.foreground);
     let border_style = Style::default()
         .fg(border_fg)
@@ -1032,7 +1301,7 @@ fn render_code_block(
 
     // Calculate block boundaries in document coordinates
     let block_start = node_y;
-    let block_end = node_y + lines.len() as u16 + 2; // +2 for top and bottom borders
+    let block_end = node_y + rows + 2; // +2 for top and bottom borders
 
     // Draw top border if visible
     if block_start >= scroll_y && block_start < scroll_y + area.height {
@@ -1116,63 +1385,124 @@ fn render_code_block(
         }
     }
 
-    // Calculate which lines are visible
-    // Code block content starts at node_y + 1 (after top border)
-    let content_start_y = node_y + 1;
-
-    // Determine starting line index based on scroll position
-    let start_line = if content_start_y < scroll_y {
-        (scroll_y - content_start_y) as usize
-    } else {
-        0
-    };
+    // Copy badge, clickable with the mouse (see `copy_badge_rect`)
+    if node_y >= scroll_y && display_y < area.height {
+        let badge_lang = lang.as_deref().filter(|_| code_style.show_language_badge);
+        let block_rect = crate::layout::Rectangle::new(x, display_y, actual_width, 1);
+        let badge = copy_badge_rect(block_rect, badge_lang);
+        if badge.width > 0 {
+            let badge_span = Span::styled(
+                COPY_BADGE,
+                Style::default().fg(to_ratatui_color(theme.colors.muted)),
+            );
+            frame.render_widget(
+                Paragraph::new(RatatuiText::from(badge_span)),
+                ratatui::layout::Rect {
+                    x: badge.x,
+                    y: badge.y,
+                    width: badge.width,
+                    height: 1,
+                },
+            );
+        }
+    }
 
-    // Render code lines
-    for (i, line) in lines.iter().enumerate().skip(start_line) {
-        let line_y_in_doc = content_start_y + i as u16;
+    actual_width
+}
 
-        // Skip if line is above viewport
-        if line_y_in_doc < scroll_y {
+/// Split a code row into spans, highlighting search matches that fall on it
+fn highlight_code_row(
+    text: String,
+    text_x: u16,
+    doc_y: u16,
+    style: Style,
+    theme: &Theme,
+    search_state: &SearchState,
+) -> Vec<Span<'static>> {
+    let text_end = text_x.saturating_add(UnicodeWidthStr::width(text.as_str()) as u16);
+    let mut row_matches: Vec<(&crate::search::MatchSpan, bool)> = search_state
+        .matches
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, m)| {
+            let is_current = search_state.current_index == Some(idx);
+            m.spans_on_row(doc_y).map(move |span| (span, is_current))
+        })
+        .filter(|(span, _)| span.x >= text_x && span.x < text_end)
+        .collect();
+    if row_matches.is_empty() {
+        return vec![Span::styled(text, style)];
+    }
+    row_matches.sort_by_key(|(span, _)| span.x);
+
+    let ui = theme.ui_colors();
+    let mut spans = Vec::new();
+    let mut last_col = 0;
+    for (span, is_current) in row_matches {
+        let start_col = span.x.saturating_sub(text_x) as usize;
+        let end_col = start_col + span.length;
+        if start_col < last_col {
             continue;
         }
-
-        // Stop if line is below viewport
-        if line_y_in_doc >= scroll_y + area.height {
-            break;
+        let start = display_col_to_byte_idx(&text, start_col);
+        let end = display_col_to_byte_idx(&text, end_col);
+        let last = display_col_to_byte_idx(&text, last_col);
+        if start > last {
+            spans.push(Span::styled(text[last..start].to_string(), style));
         }
-
-        let display_line_y = line_y_in_doc - scroll_y;
-
-        // Pad line with spaces to fill the full width so background extends across
-        let content_width = actual_width.saturating_sub(2) as usize;
-        let padded_line = format!("{:width$}", line, width = content_width);
-
-        let span = Span::styled(padded_line, style);
-        let line_text = RatatuiText::from(span);
-        let para = Paragraph::new(line_text);
-
-        let line_area = ratatui::layout::Rect {
-            x: x + 1,
-            y: display_line_y,
-            width: actual_width.saturating_sub(2),
-            height: 1,
+        let highlight = if is_current {
+            Style::default()
+                .bg(to_ratatui_color(ui.search_current_bg))
+                .fg(to_ratatui_color(ui.search_current_fg))
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+                .bg(to_ratatui_color(ui.search_match_bg))
+                .fg(to_ratatui_color(ui.search_match_fg))
         };
-
-        frame.render_widget(para, line_area);
+        spans.push(Span::styled(text[start..end].to_string(), highlight));
+        last_col = end_col;
     }
+    let last = display_col_to_byte_idx(&text, last_col);
+    spans.push(Span::styled(text[last..].to_string(), style));
+    spans
+}
+
+/// What the status bar shows besides the search and the position
+struct StatusBar<'a> {
+    /// The file number typed so far, while jumping to a file
+    file_jump: Option<&'a str>,
+    message: Option<&'a str>,
+    /// Keys typed towards a multi-key binding or count
+    pending_input: &'a str,
+    /// Visual line selection, as (first, last) rows
+    selection: Option<(u16, u16)>,
 }
 
 fn render_status_bar(
     frame: &mut ratatui::Frame,
     tree: &LayoutTree,
+    theme: &Theme,
     area: ratatui::layout::Rect,
     search_state: &SearchState,
-    file_jump_mode: bool,
-    file_jump_buffer: &str,
-    status_message: Option<&str>,
+    status: &StatusBar,
 ) {
+    let StatusBar {
+        file_jump,
+        message: status_message,
+        pending_input,
+        selection,
+    } = *status;
+    let ui = theme.ui_colors();
+    let bar_style = Style::default()
+        .bg(to_ratatui_color(ui.status_bar_bg))
+        .fg(to_ratatui_color(ui.status_bar_fg));
+    let message_style = Style::default()
+        .bg(to_ratatui_color(ui.status_message_bg))
+        .fg(to_ratatui_color(ui.status_message_fg));
+
     // If file jump mode is active, show file jump prompt
-    if file_jump_mode {
+    if let Some(file_jump_buffer) = file_jump {
         let prompt_text = if file_jump_buffer.is_empty() {
             ":".to_string()
         } else {
@@ -1181,9 +1511,7 @@ fn render_status_bar(
 
         let prompt_span = Span::styled(
             format!("{} (Enter file number)", prompt_text),
-            Style::default()
-                .bg(RatatuiColor::Blue)
-                .fg(RatatuiColor::White),
+            message_style,
         );
 
         let prompt_bar_area = ratatui::layout::Rect {
@@ -1205,7 +1533,9 @@ fn render_status_bar(
             format!("/{}", search_state.needle)
         };
 
-        let match_info = if search_state.matches.is_empty() && !search_state.needle.is_empty() {
+        let match_info = if let Some(error) = &search_state.error {
+            format!(" (invalid regex: {})", error)
+        } else if search_state.matches.is_empty() && !search_state.needle.is_empty() {
             " (no matches)".to_string()
         } else if let Some(idx) = search_state.current_index {
             format!(" [{}/{}]", idx + 1, search_state.matches.len())
@@ -1215,12 +1545,27 @@ fn render_status_bar(
 
         let full_text = format!("{}{}", search_text, match_info);
 
-        let search_span = Span::styled(
-            full_text,
-            Style::default()
-                .bg(RatatuiColor::Yellow)
-                .fg(RatatuiColor::Black),
-        );
+        // Active mode on the right, with the toggles that change it
+        let mode_text = format!(" {}  ^R regex  ^W word ", search_state.mode_label());
+        let padding = (area.width as usize)
+            .saturating_sub(UnicodeWidthStr::width(full_text.as_str()))
+            .saturating_sub(UnicodeWidthStr::width(mode_text.as_str()));
+
+        let prompt_style = Style::default()
+            .bg(to_ratatui_color(ui.search_prompt_bg))
+            .fg(to_ratatui_color(ui.search_prompt_fg));
+        let search_span = ratatui::text::Line::from(vec![
+            Span::styled(
+                full_text,
+                if search_state.error.is_some() {
+                    prompt_style.fg(to_ratatui_color(theme.colors.error))
+                } else {
+                    prompt_style
+                },
+            ),
+            Span::styled(" ".repeat(padding), prompt_style),
+            Span::styled(mode_text, prompt_style.add_modifier(Modifier::DIM)),
+        ]);
 
         let search_bar_area = ratatui::layout::Rect {
             x: 0,
@@ -1262,7 +1607,7 @@ fn render_status_bar(
     let position = format!("Lines {}-{}/{} ", top_line, bottom_line, doc_height);
 
     // Show search match count if we have matches
-    let search_info = if !search_state.matches.is_empty() {
+    let mut search_info = if !search_state.matches.is_empty() {
         if let Some(idx) = search_state.current_index {
             format!(" Search: {}/{} ", idx + 1, search_state.matches.len())
         } else {
@@ -1272,6 +1617,15 @@ fn render_status_bar(
         String::new()
     };
 
+    if let Some((first, last)) = selection {
+        search_info.push_str(&format!(" VISUAL LINE {} ", last - first + 1));
+    }
+
+    // Count prefix / partial key sequence, like vim's showcmd
+    if !pending_input.is_empty() {
+        search_info.push_str(&format!(" {} ", pending_input));
+    }
+
     let right_text = if let Some(msg) = status_message {
         format!(" {} ", msg)
     } else {
@@ -1295,28 +1649,15 @@ fn render_status_bar(
         let msg_part = &full_status[msg_start..];
 
         let spans = vec![
-            Span::styled(
-                before.to_string(),
-                Style::default()
-                    .bg(RatatuiColor::DarkGray)
-                    .fg(RatatuiColor::White),
-            ),
+            Span::styled(before.to_string(), bar_style),
             Span::styled(
                 msg_part.to_string(),
-                Style::default()
-                    .bg(RatatuiColor::Blue)
-                    .fg(RatatuiColor::White)
-                    .add_modifier(Modifier::BOLD),
+                message_style.add_modifier(Modifier::BOLD),
             ),
         ];
         RatatuiText::from(ratatui::text::Line::from(spans))
     } else {
-        let span = Span::styled(
-            full_status,
-            Style::default()
-                .bg(RatatuiColor::DarkGray)
-                .fg(RatatuiColor::White),
-        );
+        let span = Span::styled(full_status, bar_style);
         RatatuiText::from(span)
     };
 
@@ -1330,51 +1671,42 @@ fn render_status_bar(
     frame.render_widget(Paragraph::new(status_span), status_area);
 }
 
-fn render_help_menu(frame: &mut ratatui::Frame, area: ratatui::layout::Rect) {
-    let help_text = vec![
-        "LUMEN - Keyboard Shortcuts",
-        "",
-        "Navigation:",
-        "  j / ↓        Scroll down one line",
-        "  k / ↑        Scroll up one line",
-        "  d            Scroll down half page",
-        "  u            Scroll up half page",
-        "  Space        Scroll down one page",
-        "  PageDown     Scroll down one page",
-        "  PageUp       Scroll up one page",
-        "  g / Home     Go to top of document",
-        "  G / End      Go to bottom of document",
-        "",
-        "Header Navigation:",
-        "  n            Jump to next heading",
-        "  p            Jump to previous heading",
-        "",
-        "Link Navigation:",
-        "  a            Cycle through links",
-        "  Enter        Follow selected link",
-        "",
-        "File Navigation:",
-        "  Tab          Switch to next file",
-        "  Shift+Tab    Switch to previous file",
-        "  1-9          Jump to file 1-9",
-        "  :N           Jump to file N (any number)",
-        "",
-        "Search:",
-        "  /            Start search",
-        "  n            Next match (when searching)",
-        "  N            Previous match",
-        "  Esc          Clear search results",
-        "",
-        "Other:",
-        "  t            Cycle through themes",
-        "  f            Toggle file sidebar",
-        "  r            Reload current file",
-        "  m            Toggle mouse mode",
-        "  h            Toggle this help menu",
-        "  q / Esc      Quit",
-        "",
-        "Press 'h' or Esc to close this menu",
-    ];
+fn render_help_menu(
+    frame: &mut ratatui::Frame,
+    area: ratatui::layout::Rect,
+    theme: &Theme,
+    keymap: &Keymap,
+) {
+    let sections = keymap.help_sections();
+    let key_width = sections
+        .iter()
+        .flat_map(|(_, entries)| entries.iter())
+        .map(|(keys, _)| UnicodeWidthStr::width(keys.as_str()))
+        .max()
+        .unwrap_or(0)
+        .max(12);
+
+    let mut help_text = vec!["LUMEN - Keyboard Shortcuts".to_string(), String::new()];
+    for (title, entries) in &sections {
+        help_text.push(format!("{}:", title));
+        for (keys, description) in entries {
+            let padding = key_width - UnicodeWidthStr::width(keys.as_str());
+            help_text.push(format!("  {}{} {}", keys, " ".repeat(padding), description));
+        }
+        // Count prefixes are not bindings, so list them separately
+        if *title == "Navigation" {
+            help_text.push(format!(
+                "  {:<key_width$} Count prefix: 10j, 5G, 50%, 3n",
+                "N"
+            ));
+        }
+        help_text.push(String::new());
+    }
+    let close_keys = keymap.keys_for(Action::ToggleHelp);
+    help_text.push(match close_keys.first() {
+        Some(key) => format!("Press '{}' or Esc to close this menu", key),
+        None => "Press Esc to close this menu".to_string(),
+    });
 
     // Calculate centered position
     let width = 60u16.min(area.width);
@@ -1391,10 +1723,11 @@ fn render_help_menu(frame: &mut ratatui::Frame, area: ratatui::layout::Rect) {
     };
 
     // Create bordered block
+    let ui = theme.ui_colors();
     let block = Block::default()
         .borders(Borders::ALL)
-        .border_style(Style::default().fg(RatatuiColor::Cyan))
-        .style(Style::default().bg(RatatuiColor::Black));
+        .border_style(Style::default().fg(to_ratatui_color(ui.help_border)))
+        .style(Style::default().bg(to_ratatui_color(ui.help_bg)));
 
     // Create text
     let text: Vec<ratatui::text::Line> = help_text
@@ -1402,28 +1735,28 @@ fn render_help_menu(frame: &mut ratatui::Frame, area: ratatui::layout::Rect) {
         .map(|line| {
             if line.starts_with("LUMEN") {
                 ratatui::text::Line::from(Span::styled(
-                    *line,
+                    line.as_str(),
                     Style::default()
-                        .fg(RatatuiColor::Cyan)
+                        .fg(to_ratatui_color(ui.help_title))
                         .add_modifier(Modifier::BOLD),
                 ))
             } else if line.ends_with(':') {
                 ratatui::text::Line::from(Span::styled(
-                    *line,
+                    line.as_str(),
                     Style::default()
-                        .fg(RatatuiColor::Yellow)
+                        .fg(to_ratatui_color(ui.help_section))
                         .add_modifier(Modifier::BOLD),
                 ))
             } else {
-                ratatui::text::Line::from(*line)
+                ratatui::text::Line::from(line.as_str())
             }
         })
         .collect();
 
     let paragraph = Paragraph::new(text).block(block).style(
         Style::default()
-            .fg(RatatuiColor::White)
-            .bg(RatatuiColor::Black),
+            .fg(to_ratatui_color(ui.help_fg))
+            .bg(to_ratatui_color(ui.help_bg)),
     );
 
     // Clear the area first to prevent transparency
@@ -1498,14 +1831,86 @@ fn text_segment_to_span<'a>(
     Span::styled(segment.text.as_str(), style)
 }
 
+/// Tint the rows of the visual line selection, keeping their text
+fn render_selection(
+    frame: &mut ratatui::Frame,
+    theme: &Theme,
+    scroll_y: u16,
+    area: ratatui::layout::Rect,
+    first: u16,
+    last: u16,
+) {
+    let style = Style::default().bg(to_ratatui_color(theme.ui_colors().selection_bg));
+    // The bottom row belongs to the status bar
+    let visible_end = scroll_y.saturating_add(area.height.saturating_sub(1));
+    for doc_y in first.max(scroll_y)..=last.min(visible_end.saturating_sub(1)) {
+        let row = ratatui::layout::Rect {
+            x: area.x,
+            y: area.y + doc_y - scroll_y,
+            width: area.width.saturating_sub(SCROLLBAR_WIDTH),
+            height: 1,
+        };
+        frame.buffer_mut().set_style(row, style);
+    }
+}
+
+/// Draw the scrollbar in the rightmost column of the document area
+///
+/// The layout leaves [`SCROLLBAR_WIDTH`] columns free for it, and it is only
+/// drawn when the document is taller than the viewport.
+fn render_scrollbar(
+    frame: &mut ratatui::Frame,
+    tree: &LayoutTree,
+    theme: &Theme,
+    area: ratatui::layout::Rect,
+) {
+    let doc_height = tree.document_height();
+    let viewport_height = tree.viewport.height;
+    // The bottom row belongs to the status bar
+    let track_height = area.height.saturating_sub(1);
+    if doc_height <= viewport_height || track_height == 0 || area.width == 0 {
+        return;
+    }
+
+    let thumb_height = ((track_height as u32 * viewport_height as u32) / doc_height as u32)
+        .clamp(1, track_height as u32) as u16;
+    let max_scroll = doc_height - viewport_height;
+    let scroll_y = tree.viewport.scroll_y.min(max_scroll);
+    let thumb_top =
+        ((track_height - thumb_height) as u32 * scroll_y as u32 / max_scroll as u32) as u16;
+
+    let ui = theme.ui_colors();
+    let track_style = Style::default().fg(to_ratatui_color(ui.scrollbar_track));
+    let thumb_style = Style::default().fg(to_ratatui_color(ui.scrollbar_thumb));
+
+    let x = area.x + area.width - SCROLLBAR_WIDTH;
+    for y in 0..track_height {
+        let in_thumb = y >= thumb_top && y < thumb_top + thumb_height;
+        let span = if in_thumb {
+            Span::styled("┃", thumb_style)
+        } else {
+            Span::styled("│", track_style)
+        };
+        let cell = ratatui::layout::Rect {
+            x,
+            y: area.y + y,
+            width: SCROLLBAR_WIDTH,
+            height: 1,
+        };
+        frame.render_widget(Paragraph::new(RatatuiText::from(span)), cell);
+    }
+}
+
 fn render_file_sidebar(
     frame: &mut ratatui::Frame,
     file_manager: &crate::FileManager,
     area: ratatui::layout::Rect,
-    _theme: &Theme,
+    theme: &Theme,
 ) {
+    let ui = theme.ui_colors();
+
     // Draw sidebar border on the right side
-    let border_style = Style::default().fg(RatatuiColor::DarkGray);
+    let border_style = Style::default().fg(to_ratatui_color(ui.sidebar_border));
     for y in 0..area.height {
         let border_span = Span::styled("│", border_style);
         let border_area = ratatui::layout::Rect {
@@ -1521,7 +1926,7 @@ fn render_file_sidebar(
     let title_span = Span::styled(
         " Open Files ",
         Style::default()
-            .fg(RatatuiColor::Cyan)
+            .fg(to_ratatui_color(ui.sidebar_title))
             .add_modifier(Modifier::BOLD),
     );
     let title_area = ratatui::layout::Rect {
@@ -1557,10 +1962,10 @@ fn render_file_sidebar(
 
         let file_style = if is_current {
             Style::default()
-                .fg(RatatuiColor::Yellow)
+                .fg(to_ratatui_color(ui.sidebar_selection))
                 .add_modifier(Modifier::BOLD)
         } else {
-            Style::default().fg(RatatuiColor::White)
+            Style::default().fg(to_ratatui_color(ui.sidebar_text))
         };
 
         let file_span = Span::styled(file_text, file_style);
@@ -1581,10 +1986,11 @@ fn render_image_sidebar(
     images: &[ImageReference],
     scroll_y: u16,
     area: ratatui::layout::Rect,
-    _theme: &Theme,
+    theme: &Theme,
 ) {
-    // Draw sidebar border
-    let border_style = Style::default().fg(RatatuiColor::DarkGray);
+    // Draw sidebar border; captions share its muted colour
+    let muted = to_ratatui_color(theme.ui_colors().sidebar_border);
+    let border_style = Style::default().fg(muted);
     for y in 0..area.height {
         let border_span = Span::styled("│", border_style);
         let border_area = ratatui::layout::Rect {
@@ -1639,7 +2045,7 @@ fn render_image_sidebar(
                 frame.render_stateful_widget(image_widget, image_area, &mut protocol);
             } else {
                 // Fallback: show alt text if image can't be rendered
-                render_image_fallback(frame, &image.alt_text, image_area);
+                render_image_fallback(frame, &image.alt_text, image_area, muted);
             }
 
             // Render caption immediately below image (no gap)
@@ -1648,9 +2054,7 @@ fn render_image_sidebar(
                 let caption_text = format!("[IMAGE: {}]", image.alt_text);
                 let caption_span = Span::styled(
                     caption_text,
-                    Style::default()
-                        .fg(RatatuiColor::DarkGray)
-                        .add_modifier(Modifier::ITALIC),
+                    Style::default().fg(muted).add_modifier(Modifier::ITALIC),
                 );
                 let caption_area = ratatui::layout::Rect {
                     x: area.x + 2,
@@ -1674,7 +2078,7 @@ fn render_image_sidebar(
                 width: area.width.saturating_sub(3),
                 height: 3,
             };
-            render_image_fallback(frame, &image.alt_text, fallback_area);
+            render_image_fallback(frame, &image.alt_text, fallback_area, muted);
             next_available_y = actual_y + 4; // 3 for text + 1 gap
         }
     }
@@ -1709,22 +2113,34 @@ fn create_image_protocol(
     Ok(protocol)
 }
 
-fn render_image_fallback(frame: &mut ratatui::Frame, alt_text: &str, area: ratatui::layout::Rect) {
+fn render_image_fallback(
+    frame: &mut ratatui::Frame,
+    alt_text: &str,
+    area: ratatui::layout::Rect,
+    color: RatatuiColor,
+) {
     let fallback_text = format!("[{}]", alt_text);
-    let span = Span::styled(fallback_text, Style::default().fg(RatatuiColor::DarkGray));
+    let span = Span::styled(fallback_text, Style::default().fg(color));
     frame.render_widget(Paragraph::new(RatatuiText::from(span)), area);
 }
 
+/// An image in the document at `rect`, captioned in the theme's muted
+/// chrome colour
 fn render_inline_image(
     frame: &mut ratatui::Frame,
     path: &str,
     alt_text: &str,
-    x: u16,
-    y: u16,
-    width: u16,
-    height: u16,
+    rect: ratatui::layout::Rect,
     area: ratatui::layout::Rect,
+    theme: &Theme,
 ) {
+    let ratatui::layout::Rect {
+        x,
+        y,
+        width,
+        height,
+    } = rect;
+    let muted = to_ratatui_color(theme.ui_colors().sidebar_border);
     // Skip if image is off-screen
     if y >= area.height {
         return;
@@ -1748,7 +2164,7 @@ fn render_inline_image(
             frame.render_stateful_widget(image_widget, image_area, &mut protocol);
         } else {
             // Fallback: show alt text if image can't be rendered
-            render_image_fallback(frame, alt_text, image_area);
+            render_image_fallback(frame, alt_text, image_area, muted);
         }
 
         // Render caption below image
@@ -1757,9 +2173,7 @@ fn render_inline_image(
             let caption_text = format!("[IMAGE: {}]", alt_text);
             let caption_span = Span::styled(
                 caption_text,
-                Style::default()
-                    .fg(RatatuiColor::DarkGray)
-                    .add_modifier(Modifier::ITALIC),
+                Style::default().fg(muted).add_modifier(Modifier::ITALIC),
             );
             let caption_area = ratatui::layout::Rect {
                 x,
@@ -1780,7 +2194,7 @@ fn render_inline_image(
             width,
             height: available_height.min(3),
         };
-        render_image_fallback(frame, alt_text, fallback_area);
+        render_image_fallback(frame, alt_text, fallback_area, muted);
     }
 }
 
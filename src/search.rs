@@ -1,15 +1,48 @@
 //! Search functionality for finding text in documents
 
-use crate::layout::{LayoutElement, LayoutNode, Line};
+use crate::codeblock::DiagramRole;
+use crate::layout::{LayoutElement, LayoutNode};
+use regex::{Regex, RegexBuilder};
 use unicode_width::UnicodeWidthStr;
 
 /// Search match position in the document
+///
+/// `y`, `x` and `length` describe where the match starts (used for
+/// scrolling); `spans` lists every highlighted run, since a match can cross
+/// styled segments and wrapped lines.
 #[derive(Debug, Clone, PartialEq)]
 pub struct SearchMatch {
-    pub y: u16,        // Document y position
-    pub x: u16,        // X position in line
-    pub length: usize, // Match length
-    pub text: String,  // Matched text (for context)
+    pub y: u16,                // Document y position
+    pub x: u16,                // X position in line
+    pub length: usize,         // Match length on the first row
+    pub text: String,          // Matched text (for context)
+    pub spans: Vec<MatchSpan>, // Highlighted runs, one per row touched
+}
+
+/// A highlighted run of a match on a single row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchSpan {
+    pub y: u16,
+    pub x: u16,
+    pub length: usize,
+}
+
+impl SearchMatch {
+    /// Highlighted runs of this match on document row `y`
+    pub fn spans_on_row(&self, y: u16) -> impl Iterator<Item = &MatchSpan> {
+        self.spans.iter().filter(move |span| span.y == y)
+    }
+}
+
+/// How the query decides between case-sensitive and case-insensitive matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    /// Case-sensitive only if the query contains an uppercase letter
+    Smart,
+    /// Forced by `\c` in the query
+    Insensitive,
+    /// Forced by `\C` in the query
+    Sensitive,
 }
 
 /// Search state
@@ -19,6 +52,9 @@ pub struct SearchState {
     pub matches: Vec<SearchMatch>,    // All matches found
     pub current_index: Option<usize>, // Currently selected match
     pub active: bool,                 // Whether search mode is active
+    pub regex: bool,                  // Treat the query as a regular expression
+    pub whole_word: bool,             // Only match whole words
+    pub error: Option<String>,        // Why the last query could not be compiled
 }
 
 impl Default for SearchState {
@@ -34,6 +70,9 @@ impl SearchState {
             matches: Vec::new(),
             current_index: None,
             active: false,
+            regex: false,
+            whole_word: false,
+            error: None,
         }
     }
 
@@ -50,11 +89,112 @@ impl SearchState {
     }
 
     pub fn deactivate(&mut self) {
-        // Clear everything and exit search
+        // Clear everything and exit search (modes are kept for the next search)
         self.active = false;
         self.needle.clear();
         self.matches.clear();
         self.current_index = None;
+        self.error = None;
+    }
+
+    /// Toggle regex mode (Ctrl-R in the search prompt)
+    pub fn toggle_regex(&mut self) {
+        self.regex = !self.regex;
+    }
+
+    /// Toggle whole-word mode (Ctrl-W in the search prompt)
+    pub fn toggle_whole_word(&mut self) {
+        self.whole_word = !self.whole_word;
+    }
+
+    /// Query with `\c` / `\C` removed, and the case mode they select
+    pub fn parse_needle(&self) -> (String, CaseMode) {
+        let mut pattern = String::with_capacity(self.needle.len());
+        let mut case = CaseMode::Smart;
+        let mut chars = self.needle.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.peek() {
+                    Some('c') => {
+                        case = CaseMode::Insensitive;
+                        chars.next();
+                        continue;
+                    }
+                    Some('C') => {
+                        case = CaseMode::Sensitive;
+                        chars.next();
+                        continue;
+                    }
+                    Some(&next) => {
+                        // Keep other escapes intact (`\\c` is a literal backslash + c)
+                        pattern.push(c);
+                        pattern.push(next);
+                        chars.next();
+                        continue;
+                    }
+                    None => {}
+                }
+            }
+            pattern.push(c);
+        }
+
+        (pattern, case)
+    }
+
+    /// Whether the current query matches case-sensitively
+    pub fn is_case_sensitive(&self) -> bool {
+        let (pattern, case) = self.parse_needle();
+        match case {
+            CaseMode::Sensitive => true,
+            CaseMode::Insensitive => false,
+            CaseMode::Smart => has_uppercase(&pattern, self.regex),
+        }
+    }
+
+    /// Short description of the active mode for the search prompt
+    pub fn mode_label(&self) -> String {
+        let (_, case) = self.parse_needle();
+        let mut parts = Vec::new();
+        if self.regex {
+            parts.push("regex");
+        }
+        if self.whole_word {
+            parts.push("word");
+        }
+        parts.push(match (case, self.is_case_sensitive()) {
+            (CaseMode::Smart, true) => "smart-case: Aa",
+            (CaseMode::Smart, false) => "smart-case: aa",
+            (_, true) => "match case",
+            (_, false) => "ignore case",
+        });
+        parts.join(", ")
+    }
+
+    /// Compile the query into a matcher according to the active modes
+    pub fn build_matcher(&self) -> Result<Regex, String> {
+        let (pattern, _) = self.parse_needle();
+        let mut pattern = if self.regex {
+            pattern
+        } else {
+            regex::escape(&pattern)
+        };
+        if self.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.is_case_sensitive())
+            .build()
+            .map_err(|e| match e {
+                regex::Error::Syntax(msg) => msg
+                    .lines()
+                    .last()
+                    .unwrap_or("invalid regex")
+                    .trim_start_matches("error: ")
+                    .to_string(),
+                other => other.to_string(),
+            })
     }
 
     pub fn add_char(&mut self, c: char) {
@@ -65,17 +205,27 @@ impl SearchState {
         self.needle.pop();
     }
 
+    /// Run the query over a layout tree.
+    ///
+    /// An invalid regex leaves no matches and sets `error`; search mode
+    /// stays active so the query can be fixed.
     pub fn execute_search(&mut self, root: &LayoutNode) {
         self.matches.clear();
         self.current_index = None;
+        self.error = None;
 
         if self.needle.is_empty() {
             return;
         }
 
-        // Search case-insensitively
-        let needle_lower = self.needle.to_lowercase();
-        search_node(root, &needle_lower, &mut self.matches);
+        let matcher = match self.build_matcher() {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        };
+        search_node(root, &matcher, &mut self.matches);
 
         // Select first match if any
         if !self.matches.is_empty() {
@@ -117,70 +267,196 @@ impl SearchState {
 }
 
 /// Recursively search through layout nodes
-fn search_node(node: &LayoutNode, needle: &str, matches: &mut Vec<SearchMatch>) {
+fn search_node(node: &LayoutNode, matcher: &Regex, matches: &mut Vec<SearchMatch>) {
     match &node.element {
         LayoutElement::Heading { text, .. } => {
-            search_text(text, needle, node.rect.x, node.rect.y, matches);
+            search_text(text, matcher, node.rect.x, node.rect.y, matches);
         }
         LayoutElement::Paragraph { lines } => {
+            // A paragraph is searched as one logical string so that phrases
+            // spanning styles (`**bold** text`) or wrap points still match
+            let mut logical = LogicalText::default();
             for (line_idx, line) in lines.iter().enumerate() {
                 let y = node.rect.y + line_idx as u16;
-                search_line(line, needle, node.rect.x, y, matches);
+                logical.push_break();
+                let mut current_x = node.rect.x;
+                for segment in &line.segments {
+                    logical.push(&segment.text, current_x, y);
+                    current_x += UnicodeWidthStr::width(segment.text.as_str()) as u16;
+                }
             }
+            logical.search(matcher, matches);
         }
-        LayoutElement::CodeBlock { lines, .. } => {
-            for (line_idx, line_text) in lines.iter().enumerate() {
+        LayoutElement::CodeBlock {
+            lines,
+            gutter_width,
+            ..
+        } => {
+            // Each source line is one logical string, even when wrapped
+            let x = node.rect.x + 1 + gutter_width;
+            let mut logical = LogicalText::default();
+            for (row_idx, row) in lines.iter().enumerate() {
+                if !row.continuation {
+                    logical.search(matcher, matches);
+                    logical = LogicalText::default();
+                }
                 // Code blocks have padding, so y is offset by 1
-                let y = node.rect.y + 1 + line_idx as u16;
-                search_text(line_text, needle, node.rect.x + 1, y, matches);
+                let y = node.rect.y + 1 + row_idx as u16;
+                logical.push(&row.text, x, y);
+            }
+            logical.search(matcher, matches);
+        }
+        LayoutElement::Diagram { lines, .. } => {
+            // Only the words in a diagram: labels, not the lines drawn
+            // between them. Neighbouring words are searched together, so
+            // a match may run across a diff's changed words.
+            for (row_idx, row) in lines.iter().enumerate() {
+                let y = node.rect.y + 1 + row_idx as u16;
+                let mut x = node.rect.x + 1;
+                let mut logical = LogicalText::default();
+                for span in &row.spans {
+                    if matches!(
+                        span.role,
+                        DiagramRole::NodeBorder | DiagramRole::Edge | DiagramRole::Arrowhead
+                    ) {
+                        logical.search(matcher, matches);
+                        logical = LogicalText::default();
+                    } else {
+                        logical.push(&span.text, x, y);
+                    }
+                    x += UnicodeWidthStr::width(span.text.as_str()) as u16;
+                }
+                logical.search(matcher, matches);
             }
         }
         _ => {}
     }
 
-    // Recursively search children
+    // Recursively search children (list items, table cells, callouts...)
     for child in &node.children {
-        search_node(child, needle, matches);
+        search_node(child, matcher, matches);
     }
 }
 
-/// Search within a line of text segments
-fn search_line(line: &Line, needle: &str, x: u16, y: u16, matches: &mut Vec<SearchMatch>) {
-    let mut current_x = x;
-    for segment in &line.segments {
-        search_text(&segment.text, needle, current_x, y, matches);
-        current_x += UnicodeWidthStr::width(segment.text.as_str()) as u16;
-    }
+/// Search for matches in a single run of text at a given position
+fn search_text(text: &str, matcher: &Regex, x: u16, y: u16, matches: &mut Vec<SearchMatch>) {
+    let mut logical = LogicalText::default();
+    logical.push(text, x, y);
+    logical.search(matcher, matches);
 }
 
-/// Search for needle in text at given position
-fn search_text(text: &str, needle: &str, x: u16, y: u16, matches: &mut Vec<SearchMatch>) {
-    let text_lower = text.to_lowercase();
-    let mut start = 0;
-
-    while let Some(pos) = text_lower[start..].find(needle) {
-        let abs_pos = start + pos;
-        let match_end = abs_pos + needle.len();
-
-        // Convert byte positions (from find) to display column widths
-        let display_offset = UnicodeWidthStr::width(&text_lower[..abs_pos]);
-        let match_display_width = UnicodeWidthStr::width(&text_lower[abs_pos..match_end]);
+/// The text of a block as the reader sees it, with the screen position of
+/// every piece it was assembled from
+#[derive(Debug, Default)]
+struct LogicalText {
+    text: String,
+    runs: Vec<TextRun>,
+}
 
-        // Extract matched text from original (preserving case)
-        let matched_text = if match_end <= text.len() {
-            text[abs_pos..match_end].to_string()
-        } else {
-            text_lower[abs_pos..match_end].to_string()
-        };
+/// A byte range of a [`LogicalText`] drawn starting at (`x`, `y`)
+#[derive(Debug)]
+struct TextRun {
+    start: usize,
+    end: usize,
+    x: u16,
+    y: u16,
+}
 
-        matches.push(SearchMatch {
+impl LogicalText {
+    fn push(&mut self, text: &str, x: u16, y: u16) {
+        if text.is_empty() {
+            return;
+        }
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.runs.push(TextRun {
+            start,
+            end: self.text.len(),
+            x,
             y,
-            x: x + display_offset as u16,
-            length: match_display_width,
-            text: matched_text,
         });
-        start = abs_pos + 1;
     }
+
+    /// Join a wrapped line: wrapping consumed the space, so put one back
+    fn push_break(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with(char::is_whitespace) {
+            self.text.push(' ');
+        }
+    }
+
+    fn search(&self, matcher: &Regex, matches: &mut Vec<SearchMatch>) {
+        for found in matcher.find_iter(&self.text) {
+            // Empty matches (e.g. `a*`) cannot be highlighted or navigated to
+            if found.is_empty() {
+                continue;
+            }
+
+            let spans = self.spans(found.start(), found.end());
+            let Some(first) = spans.first().copied() else {
+                // The match only covers an inserted line break
+                continue;
+            };
+
+            matches.push(SearchMatch {
+                y: first.y,
+                x: first.x,
+                length: first.length,
+                text: found.as_str().to_string(),
+                spans,
+            });
+        }
+    }
+
+    /// Map a byte range of the logical text back to highlighted runs,
+    /// merging pieces that sit next to each other on the same row
+    fn spans(&self, start: usize, end: usize) -> Vec<MatchSpan> {
+        let mut spans: Vec<MatchSpan> = Vec::new();
+        for run in &self.runs {
+            if run.end <= start || run.start >= end {
+                continue;
+            }
+            let from = start.max(run.start);
+            let to = end.min(run.end);
+
+            // Convert byte positions to display column widths
+            let x = run.x + UnicodeWidthStr::width(&self.text[run.start..from]) as u16;
+            let length = UnicodeWidthStr::width(&self.text[from..to]);
+            if length == 0 {
+                continue;
+            }
+
+            match spans.last_mut() {
+                Some(last) if last.y == run.y && last.x + last.length as u16 == x => {
+                    last.length += length;
+                }
+                _ => spans.push(MatchSpan {
+                    y: run.y,
+                    x,
+                    length,
+                }),
+            }
+        }
+        spans
+    }
+}
+
+/// Whether a query contains an uppercase letter (ignoring regex escapes like `\W`)
+fn has_uppercase(pattern: &str, regex: bool) -> bool {
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        if regex && c == '\\' {
+            escaped = true;
+            continue;
+        }
+        if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
 }
 
 #[cfg(test)]
@@ -197,18 +473,21 @@ mod tests {
                 x: 0,
                 length: 4,
                 text: "test".to_string(),
+                spans: Vec::new(),
             },
             SearchMatch {
                 y: 5,
                 x: 10,
                 length: 4,
                 text: "test".to_string(),
+                spans: Vec::new(),
             },
             SearchMatch {
                 y: 10,
                 x: 20,
                 length: 4,
                 text: "test".to_string(),
+                spans: Vec::new(),
             },
         ];
 
@@ -229,10 +508,158 @@ mod tests {
     #[test]
     fn test_search_text() {
         let mut matches = Vec::new();
-        search_text("Hello world, hello universe", "hello", 0, 0, &mut matches);
+        let matcher = RegexBuilder::new("hello")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        search_text("Hello world, hello universe", &matcher, 0, 0, &mut matches);
 
         assert_eq!(matches.len(), 2);
         assert_eq!(matches[0].x, 0);
         assert_eq!(matches[1].x, 13);
     }
+
+    #[test]
+    fn test_smart_case() {
+        let mut state = SearchState::new();
+        state.needle = "foo".to_string();
+        assert!(!state.is_case_sensitive());
+
+        state.needle = "Foo::bar".to_string();
+        assert!(state.is_case_sensitive());
+
+        // \c forces case-insensitive, \C forces case-sensitive
+        state.needle = "Foo\\c".to_string();
+        assert!(!state.is_case_sensitive());
+        assert_eq!(state.parse_needle().0, "Foo");
+        state.needle = "\\Cfoo".to_string();
+        assert!(state.is_case_sensitive());
+    }
+
+    #[test]
+    fn test_regex_escapes_do_not_trigger_smart_case() {
+        let mut state = SearchState::new();
+        state.regex = true;
+        state.needle = r"TODO\(\w+\)".to_string();
+        assert!(state.is_case_sensitive());
+
+        state.needle = r"\W\d+".to_string();
+        assert!(!state.is_case_sensitive());
+    }
+
+    #[test]
+    fn test_build_matcher_modes() {
+        let mut state = SearchState::new();
+        state.needle = "a.b".to_string();
+        let literal = state.build_matcher().unwrap();
+        assert!(literal.is_match("a.b"));
+        assert!(!literal.is_match("axb"));
+
+        state.regex = true;
+        assert!(state.build_matcher().unwrap().is_match("axb"));
+
+        state.regex = false;
+        state.whole_word = true;
+        state.needle = "test".to_string();
+        let word = state.build_matcher().unwrap();
+        assert!(word.is_match("a test here"));
+        assert!(!word.is_match("testing"));
+    }
+
+    #[test]
+    fn test_invalid_regex_reports_error() {
+        let mut state = SearchState::new();
+        state.activate();
+        state.regex = true;
+        state.needle = "TODO(".to_string();
+
+        let node = LayoutNode {
+            id: 0,
+            rect: crate::layout::Rectangle::new(0, 0, 80, 1),
+            element: LayoutElement::Document,
+            children: Vec::new(),
+            style: crate::layout::ComputedStyle::default(),
+        };
+        state.execute_search(&node);
+
+        assert!(state.error.is_some());
+        assert!(state.active);
+        assert!(state.matches.is_empty());
+    }
+
+    fn paragraph(lines: &[&[&str]]) -> LayoutNode {
+        use crate::layout::{Line, TextStyle};
+        let lines = lines
+            .iter()
+            .map(|segments| {
+                let mut line = Line::new();
+                for text in *segments {
+                    line.add_segment(text.to_string(), TextStyle::default());
+                }
+                line
+            })
+            .collect();
+        LayoutNode {
+            id: 0,
+            rect: crate::layout::Rectangle::new(2, 10, 40, 2),
+            element: LayoutElement::Paragraph { lines },
+            children: Vec::new(),
+            style: crate::layout::ComputedStyle::default(),
+        }
+    }
+
+    fn find(node: &LayoutNode, needle: &str) -> Vec<SearchMatch> {
+        let matcher = Regex::new(needle).unwrap();
+        let mut matches = Vec::new();
+        search_node(node, &matcher, &mut matches);
+        matches
+    }
+
+    #[test]
+    fn test_match_across_segments_is_one_span() {
+        let node = paragraph(&[&["Press ", "Ctrl", " twice"]]);
+        let matches = find(&node, "Ctrl twice");
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].spans,
+            vec![MatchSpan {
+                y: 10,
+                x: 8,
+                length: 10
+            }]
+        );
+    }
+
+    #[test]
+    fn test_match_across_wrapped_lines() {
+        let node = paragraph(&[&["the quick brown"], &["fox jumps"]]);
+        let matches = find(&node, "brown fox");
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].text, "brown fox");
+        assert_eq!((matches[0].y, matches[0].x, matches[0].length), (10, 12, 5));
+        assert_eq!(
+            matches[0].spans,
+            vec![
+                MatchSpan {
+                    y: 10,
+                    x: 12,
+                    length: 5
+                },
+                MatchSpan {
+                    y: 11,
+                    x: 2,
+                    length: 3
+                },
+            ]
+        );
+        assert_eq!(matches[0].spans_on_row(11).count(), 1);
+    }
+
+    #[test]
+    fn test_inserted_break_alone_is_not_a_match() {
+        let node = paragraph(&[&["end"], &["start"]]);
+        assert!(find(&node, r"\s").is_empty());
+    }
 }
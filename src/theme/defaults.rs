@@ -93,6 +93,9 @@ pub fn docs_theme() -> Theme {
                 }),
                 padding: (1, 2),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::rgb(180, 180, 200),
@@ -151,7 +154,33 @@ pub fn docs_theme() -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
         },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::rgb(220, 220, 220)),
+            status_bar_bg: Some(Color::rgb(50, 50, 50)),
+            status_message_fg: Some(Color::rgb(30, 30, 30)),
+            status_message_bg: Some(Color::rgb(100, 180, 255)),
+            sidebar_title: Some(Color::rgb(100, 180, 255)),
+            sidebar_text: Some(Color::rgb(220, 220, 220)),
+            sidebar_selection: Some(Color::rgb(255, 200, 100)),
+            sidebar_border: Some(Color::rgb(100, 100, 100)),
+            search_prompt_fg: Some(Color::rgb(30, 30, 30)),
+            search_prompt_bg: Some(Color::rgb(255, 200, 100)),
+            search_match_fg: Some(Color::rgb(220, 220, 220)),
+            search_match_bg: Some(Color::rgb(70, 70, 70)),
+            search_current_fg: Some(Color::rgb(30, 30, 30)),
+            search_current_bg: Some(Color::rgb(255, 200, 100)),
+            help_fg: Some(Color::rgb(220, 220, 220)),
+            help_bg: Some(Color::rgb(30, 30, 30)),
+            help_border: Some(Color::rgb(100, 180, 255)),
+            help_title: Some(Color::rgb(100, 180, 255)),
+            help_section: Some(Color::rgb(255, 200, 100)),
+            selection_bg: Some(Color::rgb(70, 70, 70)),
+            scrollbar_track: Some(Color::rgb(50, 50, 50)),
+            scrollbar_thumb: Some(Color::rgb(100, 100, 100)),
+        },
+        diagram: Default::default(),
     }
 }
 
@@ -235,6 +264,9 @@ pub fn neon_theme() -> Theme {
                 }),
                 padding: (1, 2),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::rgb(200, 200, 255),
@@ -293,7 +325,33 @@ pub fn neon_theme() -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
+        },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::rgb(240, 240, 255)),
+            status_bar_bg: Some(Color::rgb(30, 20, 50)),
+            status_message_fg: Some(Color::rgb(10, 10, 20)),
+            status_message_bg: Some(Color::rgb(0, 255, 255)),
+            sidebar_title: Some(Color::rgb(0, 255, 255)),
+            sidebar_text: Some(Color::rgb(240, 240, 255)),
+            sidebar_selection: Some(Color::rgb(255, 255, 0)),
+            sidebar_border: Some(Color::rgb(100, 100, 120)),
+            search_prompt_fg: Some(Color::rgb(10, 10, 20)),
+            search_prompt_bg: Some(Color::rgb(255, 255, 0)),
+            search_match_fg: Some(Color::rgb(240, 240, 255)),
+            search_match_bg: Some(Color::rgb(60, 40, 90)),
+            search_current_fg: Some(Color::rgb(10, 10, 20)),
+            search_current_bg: Some(Color::rgb(255, 255, 0)),
+            help_fg: Some(Color::rgb(240, 240, 255)),
+            help_bg: Some(Color::rgb(10, 10, 20)),
+            help_border: Some(Color::rgb(0, 255, 255)),
+            help_title: Some(Color::rgb(0, 255, 255)),
+            help_section: Some(Color::rgb(255, 255, 0)),
+            selection_bg: Some(Color::rgb(60, 40, 90)),
+            scrollbar_track: Some(Color::rgb(30, 20, 50)),
+            scrollbar_thumb: Some(Color::rgb(100, 100, 120)),
         },
+        diagram: Default::default(),
     }
 }
 
@@ -370,6 +428,9 @@ pub fn minimal_theme() -> Theme {
                 }),
                 padding: (0, 1),
                 show_language_badge: false,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::Ansi(AnsiColor::BrightBlack),
@@ -423,7 +484,33 @@ pub fn minimal_theme() -> Theme {
                 show_url: UrlDisplayMode::Inline,
             },
             strikethrough: TextStyle::default(),
+            kbd: None,
+        },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::Ansi(AnsiColor::Black)),
+            status_bar_bg: Some(Color::Ansi(AnsiColor::White)),
+            status_message_fg: Some(Color::Ansi(AnsiColor::Black)),
+            status_message_bg: Some(Color::Ansi(AnsiColor::BrightWhite)),
+            sidebar_title: Some(Color::Ansi(AnsiColor::BrightWhite)),
+            sidebar_text: Some(Color::Ansi(AnsiColor::White)),
+            sidebar_selection: Some(Color::Ansi(AnsiColor::BrightWhite)),
+            sidebar_border: Some(Color::Ansi(AnsiColor::BrightBlack)),
+            search_prompt_fg: Some(Color::Ansi(AnsiColor::Black)),
+            search_prompt_bg: Some(Color::Ansi(AnsiColor::White)),
+            search_match_fg: Some(Color::Ansi(AnsiColor::White)),
+            search_match_bg: Some(Color::Ansi(AnsiColor::BrightBlack)),
+            search_current_fg: Some(Color::Ansi(AnsiColor::Black)),
+            search_current_bg: Some(Color::Ansi(AnsiColor::BrightWhite)),
+            help_fg: Some(Color::Ansi(AnsiColor::White)),
+            help_bg: Some(Color::Ansi(AnsiColor::Black)),
+            help_border: Some(Color::Ansi(AnsiColor::BrightBlack)),
+            help_title: Some(Color::Ansi(AnsiColor::BrightWhite)),
+            help_section: Some(Color::Ansi(AnsiColor::White)),
+            selection_bg: Some(Color::Ansi(AnsiColor::BrightBlack)),
+            scrollbar_track: Some(Color::Ansi(AnsiColor::Black)),
+            scrollbar_thumb: Some(Color::Ansi(AnsiColor::BrightBlack)),
         },
+        diagram: Default::default(),
     }
 }
 
@@ -506,6 +593,9 @@ pub fn dracula_theme() -> Theme {
                 }),
                 padding: (1, 2),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::rgb(189, 147, 249),
@@ -564,7 +654,33 @@ pub fn dracula_theme() -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
         },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::rgb(248, 248, 242)),
+            status_bar_bg: Some(Color::rgb(68, 71, 90)),
+            status_message_fg: Some(Color::rgb(40, 42, 54)),
+            status_message_bg: Some(Color::rgb(189, 147, 249)),
+            sidebar_title: Some(Color::rgb(189, 147, 249)),
+            sidebar_text: Some(Color::rgb(248, 248, 242)),
+            sidebar_selection: Some(Color::rgb(255, 121, 198)),
+            sidebar_border: Some(Color::rgb(98, 114, 164)),
+            search_prompt_fg: Some(Color::rgb(40, 42, 54)),
+            search_prompt_bg: Some(Color::rgb(255, 121, 198)),
+            search_match_fg: Some(Color::rgb(248, 248, 242)),
+            search_match_bg: Some(Color::rgb(68, 71, 90)),
+            search_current_fg: Some(Color::rgb(40, 42, 54)),
+            search_current_bg: Some(Color::rgb(255, 121, 198)),
+            help_fg: Some(Color::rgb(248, 248, 242)),
+            help_bg: Some(Color::rgb(40, 42, 54)),
+            help_border: Some(Color::rgb(189, 147, 249)),
+            help_title: Some(Color::rgb(189, 147, 249)),
+            help_section: Some(Color::rgb(255, 121, 198)),
+            selection_bg: Some(Color::rgb(68, 71, 90)),
+            scrollbar_track: Some(Color::rgb(68, 71, 90)),
+            scrollbar_thumb: Some(Color::rgb(98, 114, 164)),
+        },
+        diagram: Default::default(),
     }
 }
 
@@ -647,6 +763,9 @@ pub fn monokai_theme() -> Theme {
                 }),
                 padding: (1, 2),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::rgb(230, 219, 116),
@@ -705,7 +824,33 @@ pub fn monokai_theme() -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
+        },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::rgb(248, 248, 242)),
+            status_bar_bg: Some(Color::rgb(62, 61, 50)),
+            status_message_fg: Some(Color::rgb(39, 40, 34)),
+            status_message_bg: Some(Color::rgb(102, 217, 239)),
+            sidebar_title: Some(Color::rgb(102, 217, 239)),
+            sidebar_text: Some(Color::rgb(248, 248, 242)),
+            sidebar_selection: Some(Color::rgb(230, 219, 116)),
+            sidebar_border: Some(Color::rgb(117, 113, 94)),
+            search_prompt_fg: Some(Color::rgb(39, 40, 34)),
+            search_prompt_bg: Some(Color::rgb(230, 219, 116)),
+            search_match_fg: Some(Color::rgb(248, 248, 242)),
+            search_match_bg: Some(Color::rgb(73, 72, 62)),
+            search_current_fg: Some(Color::rgb(39, 40, 34)),
+            search_current_bg: Some(Color::rgb(230, 219, 116)),
+            help_fg: Some(Color::rgb(248, 248, 242)),
+            help_bg: Some(Color::rgb(39, 40, 34)),
+            help_border: Some(Color::rgb(102, 217, 239)),
+            help_title: Some(Color::rgb(102, 217, 239)),
+            help_section: Some(Color::rgb(230, 219, 116)),
+            selection_bg: Some(Color::rgb(73, 72, 62)),
+            scrollbar_track: Some(Color::rgb(62, 61, 50)),
+            scrollbar_thumb: Some(Color::rgb(117, 113, 94)),
         },
+        diagram: Default::default(),
     }
 }
 
@@ -788,6 +933,9 @@ pub fn solarized_theme() -> Theme {
                 }),
                 padding: (1, 2),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::rgb(147, 161, 161),
@@ -846,7 +994,33 @@ pub fn solarized_theme() -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
         },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::rgb(147, 161, 161)),
+            status_bar_bg: Some(Color::rgb(7, 54, 66)),
+            status_message_fg: Some(Color::rgb(0, 43, 54)),
+            status_message_bg: Some(Color::rgb(38, 139, 210)),
+            sidebar_title: Some(Color::rgb(38, 139, 210)),
+            sidebar_text: Some(Color::rgb(147, 161, 161)),
+            sidebar_selection: Some(Color::rgb(181, 137, 0)),
+            sidebar_border: Some(Color::rgb(88, 110, 117)),
+            search_prompt_fg: Some(Color::rgb(0, 43, 54)),
+            search_prompt_bg: Some(Color::rgb(181, 137, 0)),
+            search_match_fg: Some(Color::rgb(147, 161, 161)),
+            search_match_bg: Some(Color::rgb(7, 54, 66)),
+            search_current_fg: Some(Color::rgb(0, 43, 54)),
+            search_current_bg: Some(Color::rgb(181, 137, 0)),
+            help_fg: Some(Color::rgb(147, 161, 161)),
+            help_bg: Some(Color::rgb(0, 43, 54)),
+            help_border: Some(Color::rgb(38, 139, 210)),
+            help_title: Some(Color::rgb(38, 139, 210)),
+            help_section: Some(Color::rgb(181, 137, 0)),
+            selection_bg: Some(Color::rgb(7, 54, 66)),
+            scrollbar_track: Some(Color::rgb(7, 54, 66)),
+            scrollbar_thumb: Some(Color::rgb(88, 110, 117)),
+        },
+        diagram: Default::default(),
     }
 }
 
@@ -929,6 +1103,9 @@ pub fn gruvbox_theme() -> Theme {
                 }),
                 padding: (1, 2),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::rgb(184, 187, 38),
@@ -987,7 +1164,33 @@ pub fn gruvbox_theme() -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
+        },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::rgb(235, 219, 178)),
+            status_bar_bg: Some(Color::rgb(80, 73, 69)),
+            status_message_fg: Some(Color::rgb(40, 40, 40)),
+            status_message_bg: Some(Color::rgb(251, 184, 108)),
+            sidebar_title: Some(Color::rgb(251, 184, 108)),
+            sidebar_text: Some(Color::rgb(235, 219, 178)),
+            sidebar_selection: Some(Color::rgb(250, 189, 47)),
+            sidebar_border: Some(Color::rgb(146, 131, 116)),
+            search_prompt_fg: Some(Color::rgb(40, 40, 40)),
+            search_prompt_bg: Some(Color::rgb(250, 189, 47)),
+            search_match_fg: Some(Color::rgb(235, 219, 178)),
+            search_match_bg: Some(Color::rgb(102, 92, 84)),
+            search_current_fg: Some(Color::rgb(40, 40, 40)),
+            search_current_bg: Some(Color::rgb(250, 189, 47)),
+            help_fg: Some(Color::rgb(235, 219, 178)),
+            help_bg: Some(Color::rgb(40, 40, 40)),
+            help_border: Some(Color::rgb(251, 184, 108)),
+            help_title: Some(Color::rgb(251, 184, 108)),
+            help_section: Some(Color::rgb(250, 189, 47)),
+            selection_bg: Some(Color::rgb(102, 92, 84)),
+            scrollbar_track: Some(Color::rgb(80, 73, 69)),
+            scrollbar_thumb: Some(Color::rgb(146, 131, 116)),
         },
+        diagram: Default::default(),
     }
 }
 
@@ -1070,6 +1273,9 @@ pub fn nord_theme() -> Theme {
                 }),
                 padding: (1, 2),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::rgb(229, 233, 240),
@@ -1128,7 +1334,33 @@ pub fn nord_theme() -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
+        },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::rgb(216, 222, 233)),
+            status_bar_bg: Some(Color::rgb(59, 66, 82)),
+            status_message_fg: Some(Color::rgb(46, 52, 64)),
+            status_message_bg: Some(Color::rgb(136, 192, 208)),
+            sidebar_title: Some(Color::rgb(136, 192, 208)),
+            sidebar_text: Some(Color::rgb(216, 222, 233)),
+            sidebar_selection: Some(Color::rgb(235, 203, 139)),
+            sidebar_border: Some(Color::rgb(76, 86, 106)),
+            search_prompt_fg: Some(Color::rgb(46, 52, 64)),
+            search_prompt_bg: Some(Color::rgb(235, 203, 139)),
+            search_match_fg: Some(Color::rgb(216, 222, 233)),
+            search_match_bg: Some(Color::rgb(67, 76, 94)),
+            search_current_fg: Some(Color::rgb(46, 52, 64)),
+            search_current_bg: Some(Color::rgb(235, 203, 139)),
+            help_fg: Some(Color::rgb(216, 222, 233)),
+            help_bg: Some(Color::rgb(46, 52, 64)),
+            help_border: Some(Color::rgb(136, 192, 208)),
+            help_title: Some(Color::rgb(136, 192, 208)),
+            help_section: Some(Color::rgb(235, 203, 139)),
+            selection_bg: Some(Color::rgb(67, 76, 94)),
+            scrollbar_track: Some(Color::rgb(59, 66, 82)),
+            scrollbar_thumb: Some(Color::rgb(76, 86, 106)),
         },
+        diagram: Default::default(),
     }
 }
 
@@ -1211,6 +1443,9 @@ pub fn tokyo_night_theme() -> Theme {
                 }),
                 padding: (1, 2),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::rgb(169, 177, 214),
@@ -1269,7 +1504,33 @@ pub fn tokyo_night_theme() -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
         },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::rgb(169, 177, 214)),
+            status_bar_bg: Some(Color::rgb(41, 46, 66)),
+            status_message_fg: Some(Color::rgb(26, 27, 38)),
+            status_message_bg: Some(Color::rgb(125, 207, 255)),
+            sidebar_title: Some(Color::rgb(125, 207, 255)),
+            sidebar_text: Some(Color::rgb(169, 177, 214)),
+            sidebar_selection: Some(Color::rgb(224, 175, 104)),
+            sidebar_border: Some(Color::rgb(86, 95, 137)),
+            search_prompt_fg: Some(Color::rgb(26, 27, 38)),
+            search_prompt_bg: Some(Color::rgb(224, 175, 104)),
+            search_match_fg: Some(Color::rgb(169, 177, 214)),
+            search_match_bg: Some(Color::rgb(51, 59, 91)),
+            search_current_fg: Some(Color::rgb(26, 27, 38)),
+            search_current_bg: Some(Color::rgb(224, 175, 104)),
+            help_fg: Some(Color::rgb(169, 177, 214)),
+            help_bg: Some(Color::rgb(26, 27, 38)),
+            help_border: Some(Color::rgb(125, 207, 255)),
+            help_title: Some(Color::rgb(125, 207, 255)),
+            help_section: Some(Color::rgb(224, 175, 104)),
+            selection_bg: Some(Color::rgb(51, 59, 91)),
+            scrollbar_track: Some(Color::rgb(41, 46, 66)),
+            scrollbar_thumb: Some(Color::rgb(86, 95, 137)),
+        },
+        diagram: Default::default(),
     }
 }
 
@@ -1352,6 +1613,9 @@ pub fn catppuccin_theme() -> Theme {
                 }),
                 padding: (1, 2),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: Color::rgb(186, 194, 222),
@@ -1410,7 +1674,33 @@ pub fn catppuccin_theme() -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
+        },
+        ui: UiStyles {
+            status_bar_fg: Some(Color::rgb(205, 214, 244)),
+            status_bar_bg: Some(Color::rgb(49, 50, 68)),
+            status_message_fg: Some(Color::rgb(30, 30, 46)),
+            status_message_bg: Some(Color::rgb(137, 180, 250)),
+            sidebar_title: Some(Color::rgb(137, 180, 250)),
+            sidebar_text: Some(Color::rgb(205, 214, 244)),
+            sidebar_selection: Some(Color::rgb(249, 226, 175)),
+            sidebar_border: Some(Color::rgb(108, 112, 134)),
+            search_prompt_fg: Some(Color::rgb(30, 30, 46)),
+            search_prompt_bg: Some(Color::rgb(249, 226, 175)),
+            search_match_fg: Some(Color::rgb(205, 214, 244)),
+            search_match_bg: Some(Color::rgb(69, 71, 90)),
+            search_current_fg: Some(Color::rgb(30, 30, 46)),
+            search_current_bg: Some(Color::rgb(249, 226, 175)),
+            help_fg: Some(Color::rgb(205, 214, 244)),
+            help_bg: Some(Color::rgb(30, 30, 46)),
+            help_border: Some(Color::rgb(137, 180, 250)),
+            help_title: Some(Color::rgb(137, 180, 250)),
+            help_section: Some(Color::rgb(249, 226, 175)),
+            selection_bg: Some(Color::rgb(69, 71, 90)),
+            scrollbar_track: Some(Color::rgb(49, 50, 68)),
+            scrollbar_thumb: Some(Color::rgb(108, 112, 134)),
         },
+        diagram: Default::default(),
     }
 }
 
@@ -231,4 +231,82 @@ mod tests {
         assert!(theme.validate().is_empty());
         assert_eq!(theme.spacing.paragraph_spacing, 20);
     }
+
+    #[test]
+    fn test_ui_section_is_optional() {
+        let mut yaml = docs_theme().to_yaml().unwrap();
+        let ui_start = yaml.find("\nui:").unwrap();
+        yaml.truncate(ui_start + 1);
+
+        let theme = Theme::from_yaml(&yaml).unwrap();
+        assert!(theme.ui.status_bar_bg.is_none());
+
+        // Missing slots are derived from the palette
+        let ui = theme.ui_colors();
+        assert_eq!(ui.status_bar_fg, theme.colors.foreground);
+        assert_eq!(ui.sidebar_title, theme.colors.primary);
+        assert_eq!(ui.search_current_bg, theme.colors.accent);
+    }
+
+    #[test]
+    fn test_diagram_colors_fall_back_to_palette() {
+        let mut theme = docs_theme();
+        let diagram = theme.diagram_colors();
+        assert_eq!(diagram.node_border, theme.colors.primary);
+        assert_eq!(diagram.arrowhead, theme.colors.accent);
+
+        theme.diagram.edge = Some(Color::rgb(1, 2, 3));
+        let theme = Theme::from_yaml(&theme.to_yaml().unwrap()).unwrap();
+        assert_eq!(theme.diagram_colors().edge, Color::rgb(1, 2, 3));
+        assert_eq!(theme.diagram_colors().edge_label, theme.colors.secondary);
+    }
+
+    #[test]
+    fn test_callout_styles_for_extended_and_custom_kinds() {
+        use crate::ir::CalloutKind;
+
+        let mut theme = docs_theme();
+        let recipe = CalloutStyle {
+            icon: "🍳".to_string(),
+            ..theme.blocks.callout.tip.clone()
+        };
+        theme
+            .blocks
+            .callout
+            .custom
+            .insert("recipe".to_string(), recipe);
+        let theme = Theme::from_yaml(&theme.to_yaml().unwrap()).unwrap();
+        let callouts = &theme.blocks.callout;
+
+        let custom = callouts.style_for(&CalloutKind::from_name("Recipe"));
+        assert_eq!(custom.icon, "🍳");
+        assert_eq!(custom.color, callouts.tip.color);
+
+        // Obsidian kinds borrow a built-in colour with their own icon
+        let bug = callouts.style_for(&CalloutKind::Bug);
+        assert_eq!(bug.color, callouts.caution.color);
+        assert_ne!(bug.icon, callouts.caution.icon);
+
+        // Unknown kinds look like notes
+        let unknown = callouts.style_for(&CalloutKind::Custom("misc".to_string()));
+        assert_eq!(unknown.icon, callouts.note.icon);
+    }
+
+    #[test]
+    fn test_builtin_themes_define_ui() {
+        for name in Theme::builtin_names() {
+            let theme = Theme::builtin(name).unwrap();
+            let ui = &theme.ui;
+            assert!(
+                ui.status_bar_fg.is_some()
+                    && ui.status_bar_bg.is_some()
+                    && ui.sidebar_selection.is_some()
+                    && ui.search_prompt_bg.is_some()
+                    && ui.help_bg.is_some()
+                    && ui.scrollbar_thumb.is_some(),
+                "{} has no ui section",
+                name
+            );
+        }
+    }
 }
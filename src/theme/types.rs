@@ -1,7 +1,10 @@
 //! Theme type definitions
 
 use super::color::Color;
+use crate::ir::CalloutKind;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 
 /// Complete theme definition
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +26,12 @@ pub struct Theme {
     pub blocks: BlockStyles,
     /// Inline element styles
     pub inlines: InlineStyles,
+    /// Application chrome (status bar, sidebars, prompts, help overlay)
+    #[serde(default)]
+    pub ui: UiStyles,
+    /// Rendered diagrams (mermaid)
+    #[serde(default)]
+    pub diagram: DiagramStyles,
 }
 
 fn default_version() -> String {
@@ -221,12 +230,42 @@ pub struct CodeBlockStyle {
     pub padding: (u16, u16),
     #[serde(default = "default_true")]
     pub show_language_badge: bool,
+    /// How lines wider than the block are shown
+    #[serde(default)]
+    pub wrap: CodeWrap,
+    /// Number source lines in a gutter
+    #[serde(default)]
+    pub line_numbers: bool,
+    /// Background of lines listed in the fence info (```` ```rust {3,7-9} ````);
+    /// defaults to the muted palette color
+    #[serde(default)]
+    pub highlight_background: Option<Color>,
 }
 
 fn default_true() -> bool {
     true
 }
 
+/// Treatment of code lines wider than the code block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeWrap {
+    /// Soft-wrap onto continuation rows marked with `↪`
+    #[default]
+    Wrap,
+    /// Cut off at the block's right edge
+    Clip,
+}
+
+impl CodeWrap {
+    pub fn toggled(self) -> Self {
+        match self {
+            CodeWrap::Wrap => CodeWrap::Clip,
+            CodeWrap::Clip => CodeWrap::Wrap,
+        }
+    }
+}
+
 /// Block quote style
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockQuoteStyle {
@@ -247,6 +286,42 @@ pub struct CalloutStyles {
     pub important: CalloutStyle,
     pub tip: CalloutStyle,
     pub caution: CalloutStyle,
+    /// Styles for other kinds by lowercase name (`bug`, `recipe`, ...).
+    /// Obsidian kinds missing here borrow one of the five styles above.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, CalloutStyle>,
+}
+
+impl CalloutStyles {
+    /// Style for a callout kind, falling back to the closest built-in style
+    /// (with its own icon) for kinds the theme doesn't define
+    pub fn style_for(&self, kind: &CalloutKind) -> Cow<'_, CalloutStyle> {
+        let (base, icon) = match kind {
+            CalloutKind::Note => return Cow::Borrowed(&self.note),
+            CalloutKind::Warning => return Cow::Borrowed(&self.warning),
+            CalloutKind::Important => return Cow::Borrowed(&self.important),
+            CalloutKind::Tip => return Cow::Borrowed(&self.tip),
+            CalloutKind::Caution => return Cow::Borrowed(&self.caution),
+            _ if self.custom.contains_key(kind.name()) => {
+                return Cow::Borrowed(&self.custom[kind.name()]);
+            }
+            CalloutKind::Abstract => (&self.note, "📋"),
+            CalloutKind::Info => (&self.note, "ℹ"),
+            CalloutKind::Todo => (&self.note, "☐"),
+            CalloutKind::Success => (&self.tip, "✔"),
+            CalloutKind::Question => (&self.warning, "?"),
+            CalloutKind::Failure => (&self.caution, "✘"),
+            CalloutKind::Danger => (&self.caution, "⚡"),
+            CalloutKind::Bug => (&self.caution, "🐞"),
+            CalloutKind::Example => (&self.important, "☰"),
+            CalloutKind::Quote => (&self.note, "❝"),
+            CalloutKind::Custom(_) => return Cow::Borrowed(&self.note),
+        };
+        Cow::Owned(CalloutStyle {
+            icon: icon.to_string(),
+            ..base.clone()
+        })
+    }
 }
 
 /// Individual callout style
@@ -293,6 +368,7 @@ impl Default for CalloutStyles {
                 background: Some(Color::Ansi256(52)), // Dark red bg
                 border_color: Color::Ansi256(202),
             },
+            custom: BTreeMap::new(),
         }
     }
 }
@@ -389,6 +465,142 @@ pub enum BorderSide {
     Left,
 }
 
+/// Colors for the application chrome around the document
+///
+/// Every slot is optional; [`Theme::ui_colors`] derives missing ones from the
+/// [`ColorPalette`], so older theme files keep working.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UiStyles {
+    /// Status bar text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_bar_fg: Option<Color>,
+    /// Status bar background
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_bar_bg: Option<Color>,
+    /// Transient status messages ("Mark 'a' set", theme changes)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_message_fg: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_message_bg: Option<Color>,
+    /// File sidebar title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidebar_title: Option<Color>,
+    /// File sidebar entries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidebar_text: Option<Color>,
+    /// The current file in the sidebar
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidebar_selection: Option<Color>,
+    /// Border between the sidebars and the document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidebar_border: Option<Color>,
+    /// The `/` search prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_prompt_fg: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_prompt_bg: Option<Color>,
+    /// Search matches other than the current one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_match_fg: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_match_bg: Option<Color>,
+    /// The current search match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_current_fg: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_current_bg: Option<Color>,
+    /// Help overlay text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help_fg: Option<Color>,
+    /// Help overlay background
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help_bg: Option<Color>,
+    /// Help overlay border
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help_border: Option<Color>,
+    /// Help overlay title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help_title: Option<Color>,
+    /// Help overlay section headings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help_section: Option<Color>,
+    /// Rows selected in visual line mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selection_bg: Option<Color>,
+    /// Scrollbar track
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrollbar_track: Option<Color>,
+    /// Scrollbar thumb
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrollbar_thumb: Option<Color>,
+}
+
+/// [`UiStyles`] with every slot resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UiColors {
+    pub status_bar_fg: Color,
+    pub status_bar_bg: Color,
+    pub status_message_fg: Color,
+    pub status_message_bg: Color,
+    pub sidebar_title: Color,
+    pub sidebar_text: Color,
+    pub sidebar_selection: Color,
+    pub sidebar_border: Color,
+    pub search_prompt_fg: Color,
+    pub search_prompt_bg: Color,
+    pub search_match_fg: Color,
+    pub search_match_bg: Color,
+    pub search_current_fg: Color,
+    pub search_current_bg: Color,
+    pub help_fg: Color,
+    pub help_bg: Color,
+    pub help_border: Color,
+    pub help_title: Color,
+    pub help_section: Color,
+    pub selection_bg: Color,
+    pub scrollbar_track: Color,
+    pub scrollbar_thumb: Color,
+}
+
+/// Colors for the parts of rendered diagrams
+///
+/// Every slot is optional; [`Theme::diagram_colors`] derives missing ones
+/// from the [`ColorPalette`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiagramStyles {
+    /// Outlines of nodes, boxes, subgraphs and frames
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_border: Option<Color>,
+    /// Text inside nodes and boxes, subgraph titles and notes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_label: Option<Color>,
+    /// Lines between nodes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge: Option<Color>,
+    /// Arrowheads and relationship ends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrowhead: Option<Color>,
+    /// Text on or beside lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge_label: Option<Color>,
+}
+
+/// [`DiagramStyles`] with every slot resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiagramColors {
+    pub node_border: Color,
+    pub node_label: Color,
+    pub edge: Color,
+    pub arrowhead: Color,
+    pub edge_label: Color,
+    /// Added diff lines, from the palette's `success`
+    pub added: Color,
+    /// Removed diff lines, from the palette's `error`
+    pub removed: Color,
+    pub hunk_header: Color,
+    pub file_header: Color,
+}
+
 /// Inline element styles
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InlineStyles {
@@ -398,6 +610,9 @@ pub struct InlineStyles {
     pub link: LinkStyle,
     #[serde(default)]
     pub strikethrough: TextStyle,
+    /// `<kbd>` keycaps; defaults to bold inline code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kbd: Option<TextStyle>,
 }
 
 /// Text styling
@@ -527,6 +742,57 @@ impl Theme {
         errors
     }
 
+    /// Chrome colors, falling back to the palette for unset slots
+    pub fn ui_colors(&self) -> UiColors {
+        let ui = &self.ui;
+        let c = &self.colors;
+        UiColors {
+            status_bar_fg: ui.status_bar_fg.unwrap_or(c.foreground),
+            status_bar_bg: ui
+                .status_bar_bg
+                .unwrap_or(self.blocks.code_block.background),
+            status_message_fg: ui.status_message_fg.unwrap_or(c.background),
+            status_message_bg: ui.status_message_bg.unwrap_or(c.primary),
+            sidebar_title: ui.sidebar_title.unwrap_or(c.primary),
+            sidebar_text: ui.sidebar_text.unwrap_or(c.foreground),
+            sidebar_selection: ui.sidebar_selection.unwrap_or(c.accent),
+            sidebar_border: ui.sidebar_border.unwrap_or(c.muted),
+            search_prompt_fg: ui.search_prompt_fg.unwrap_or(c.background),
+            search_prompt_bg: ui.search_prompt_bg.unwrap_or(c.accent),
+            search_match_fg: ui.search_match_fg.unwrap_or(c.foreground),
+            search_match_bg: ui.search_match_bg.unwrap_or(c.muted),
+            search_current_fg: ui.search_current_fg.unwrap_or(c.background),
+            search_current_bg: ui.search_current_bg.unwrap_or(c.accent),
+            help_fg: ui.help_fg.unwrap_or(c.foreground),
+            help_bg: ui.help_bg.unwrap_or(c.background),
+            help_border: ui.help_border.unwrap_or(c.primary),
+            help_title: ui.help_title.unwrap_or(c.primary),
+            help_section: ui.help_section.unwrap_or(c.accent),
+            selection_bg: ui.selection_bg.unwrap_or(self.blocks.code_block.background),
+            scrollbar_track: ui.scrollbar_track.unwrap_or(c.muted),
+            scrollbar_thumb: ui.scrollbar_thumb.unwrap_or(c.secondary),
+        }
+    }
+
+    /// Diagram colors, falling back to the palette for unset slots
+    pub fn diagram_colors(&self) -> DiagramColors {
+        let diagram = &self.diagram;
+        let c = &self.colors;
+        DiagramColors {
+            node_border: diagram.node_border.unwrap_or(c.primary),
+            node_label: diagram
+                .node_label
+                .unwrap_or(self.blocks.code_block.foreground),
+            edge: diagram.edge.unwrap_or(c.muted),
+            arrowhead: diagram.arrowhead.unwrap_or(c.accent),
+            edge_label: diagram.edge_label.unwrap_or(c.secondary),
+            added: c.success,
+            removed: c.error,
+            hunk_header: c.accent,
+            file_header: c.primary,
+        }
+    }
+
     /// Clamp spacing values to reasonable bounds, fixing invalid themes in-place.
     pub fn clamp_spacing(&mut self) {
         const MAX: u16 = 20;
@@ -345,6 +345,30 @@ fn bg_color(colors: &VimColors, group: &str) -> Option<Color> {
         .and_then(|c| hex_to_color(c))
 }
 
+/// Foreground and background of a highlight group, honouring `gui=reverse`
+fn group_colors(
+    colors: &VimColors,
+    group: &str,
+    default_fg: Color,
+    default_bg: Color,
+) -> (Color, Color) {
+    let fg = fg_color(colors, group).unwrap_or(default_fg);
+    let bg = bg_color(colors, group).unwrap_or(default_bg);
+    let reversed = colors
+        .groups
+        .get(group)
+        .and_then(|g| g.gui.as_deref())
+        .is_some_and(|gui| {
+            let gui = gui.to_lowercase();
+            gui.contains("reverse") || gui.contains("inverse")
+        });
+    if reversed {
+        (bg, fg)
+    } else {
+        (fg, bg)
+    }
+}
+
 /// Darken a color by a factor (0.0 = black, 1.0 = unchanged)
 fn darken(color: Color, factor: f32) -> Color {
     match color {
@@ -426,7 +450,19 @@ fn build_theme(colors: &VimColors, fallback_name: &str) -> Theme {
         .or_else(|| bg_color(colors, "ColorColumn"))
         .unwrap_or_else(|| darken(bg, 0.8));
 
-    let _visual_bg = bg_color(colors, "Visual").unwrap_or(Color::Rgb(60, 60, 100));
+    let visual_bg = bg_color(colors, "Visual").unwrap_or(Color::Rgb(60, 60, 100));
+
+    // Chrome: vim's own status line, splits, popup menu and search groups
+    let (status_fg, status_bg) = group_colors(colors, "StatusLine", fg, code_bg);
+    let (message_fg, message_bg) = group_colors(colors, "PmenuSel", bg, primary);
+    let (search_fg, search_bg) = group_colors(colors, "Search", bg, warning_color);
+    let (current_fg, current_bg) = group_colors(colors, "IncSearch", bg, accent);
+    let split = fg_color(colors, "VertSplit")
+        .or_else(|| fg_color(colors, "WinSeparator"))
+        .unwrap_or(muted);
+    let (help_fg, help_bg) = group_colors(colors, "Pmenu", fg, bg);
+    let scrollbar_track = bg_color(colors, "PmenuSbar").unwrap_or(code_bg);
+    let scrollbar_thumb = bg_color(colors, "PmenuThumb").unwrap_or(muted);
 
     // Build heading styles using a hierarchy of vim groups
     let h1_color = fg_color(colors, "Title").unwrap_or(primary);
@@ -521,6 +557,9 @@ fn build_theme(colors: &VimColors, fallback_name: &str) -> Theme {
                 }),
                 padding: (1, 1),
                 show_language_badge: true,
+                wrap: CodeWrap::Wrap,
+                line_numbers: false,
+                highlight_background: None,
             },
             blockquote: BlockQuoteStyle {
                 color: muted,
@@ -578,6 +617,7 @@ fn build_theme(colors: &VimColors, fallback_name: &str) -> Theme {
                     background: Some(blend(error_color, bg, 0.9)),
                     border_color: error_border,
                 },
+                custom: Default::default(),
             },
         },
         inlines: InlineStyles {
@@ -610,7 +650,33 @@ fn build_theme(colors: &VimColors, fallback_name: &str) -> Theme {
                 weight: FontWeight::Normal,
                 style: FontStyle::Normal,
             },
+            kbd: None,
         },
+        ui: UiStyles {
+            status_bar_fg: Some(status_fg),
+            status_bar_bg: Some(status_bg),
+            status_message_fg: Some(message_fg),
+            status_message_bg: Some(message_bg),
+            sidebar_title: Some(h1_color),
+            sidebar_text: Some(fg),
+            sidebar_selection: Some(accent),
+            sidebar_border: Some(split),
+            search_prompt_fg: Some(search_fg),
+            search_prompt_bg: Some(search_bg),
+            search_match_fg: Some(fg),
+            search_match_bg: Some(visual_bg),
+            search_current_fg: Some(current_fg),
+            search_current_bg: Some(current_bg),
+            help_fg: Some(help_fg),
+            help_bg: Some(help_bg),
+            help_border: Some(split),
+            help_title: Some(h1_color),
+            help_section: Some(keyword_color),
+            selection_bg: Some(visual_bg),
+            scrollbar_track: Some(scrollbar_track),
+            scrollbar_thumb: Some(scrollbar_thumb),
+        },
+        diagram: Default::default(),
     }
 }
 
@@ -674,6 +740,24 @@ hi Visual guibg=#264f78
         ));
     }
 
+    #[test]
+    fn test_build_theme_ui_from_vim_groups() {
+        let vim = r#"
+hi Normal guifg=#d4d4d4 guibg=#1e1e1e
+hi StatusLine guifg=#3c3836 guibg=#ebdbb2 gui=reverse
+hi Search guifg=#000000 guibg=#fabd2f
+hi Visual guibg=#264f78
+"#;
+        let colors = parse_vim_colorscheme(vim);
+        let ui = build_theme(&colors, "ui").ui_colors();
+
+        // gui=reverse swaps the group's colors, as vim draws them
+        assert_eq!(ui.status_bar_fg, Color::Rgb(0xeb, 0xdb, 0xb2));
+        assert_eq!(ui.status_bar_bg, Color::Rgb(0x3c, 0x38, 0x36));
+        assert_eq!(ui.search_prompt_bg, Color::Rgb(0xfa, 0xbd, 0x2f));
+        assert_eq!(ui.search_match_bg, Color::Rgb(0x26, 0x4f, 0x78));
+    }
+
     #[test]
     fn test_hex_to_color() {
         assert!(matches!(
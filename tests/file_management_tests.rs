@@ -1,6 +1,6 @@
 //! Integration tests for multi-file management
 
-use lumen::{parse_markdown, FileManager};
+use lumen::{parse_markdown, FileManager, SavedMarks};
 use std::path::PathBuf;
 
 #[test]
@@ -106,3 +106,62 @@ fn test_scroll_position_persistence() {
     manager.prev_file();
     assert_eq!(manager.get_scroll_position(), 42);
 }
+
+#[test]
+fn test_marks_are_per_file() {
+    let mut manager = FileManager::new();
+
+    manager.add_file(PathBuf::from("/file1.md"), parse_markdown("# File 1"));
+    manager.add_file(PathBuf::from("/file2.md"), parse_markdown("# File 2"));
+
+    manager.set_mark('a', 30);
+    assert_eq!(manager.get_mark('a'), Some(30));
+
+    // Marks do not leak into other files
+    manager.next_file();
+    assert_eq!(manager.get_mark('a'), None);
+    manager.set_mark('a', 7);
+
+    manager.prev_file();
+    assert_eq!(manager.get_mark('a'), Some(30));
+}
+
+#[test]
+fn test_marks_round_trip_through_saved_marks() {
+    let mut manager = FileManager::new();
+    manager.add_file(PathBuf::from("/file1.md"), parse_markdown("# File 1"));
+    manager.set_mark('x', 12);
+
+    let mut saved = SavedMarks::default();
+    manager.store_marks(&mut saved);
+
+    let yaml = serde_yaml::to_string(&saved).unwrap();
+    let loaded: SavedMarks = serde_yaml::from_str(&yaml).unwrap();
+
+    let mut restored = FileManager::new();
+    restored.add_file(PathBuf::from("/file1.md"), parse_markdown("# File 1"));
+    restored.restore_marks(&loaded);
+    assert_eq!(restored.get_mark('x'), Some(12));
+}
+
+#[test]
+fn test_block_source_returns_markdown_of_top_level_blocks() {
+    let source = "# Title\n\nSome *styled* text.\n\n```rust\nfn main() {}\n```\n";
+    let mut manager = FileManager::new();
+    manager.add_file_with_source(
+        PathBuf::from("/file1.md"),
+        source.to_string(),
+        parse_markdown(source),
+    );
+
+    let file = manager.current_file().unwrap();
+    assert_eq!(file.block_source(0), Some("# Title\n"));
+    assert_eq!(file.block_source(1), Some("Some *styled* text.\n"));
+    assert_eq!(file.block_source(2), Some("```rust\nfn main() {}\n```"));
+    assert_eq!(file.block_source(3), None);
+
+    // Without the source nothing can be yanked as Markdown
+    manager.add_file(PathBuf::from("/file2.md"), parse_markdown(source));
+    manager.next_file();
+    assert_eq!(manager.current_file().unwrap().block_source(0), None);
+}
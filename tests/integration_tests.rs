@@ -152,3 +152,54 @@ fn test_complex_markdown() {
         .iter()
         .any(|b| matches!(b, Block::HorizontalRule)));
 }
+
+#[test]
+fn test_toc_anchor_links_resolve() {
+    use lumen::ir::Inline;
+    use lumen::parser::decode_fragment;
+
+    let markdown = load_fixture("anchors.md");
+    let doc = parse_markdown(&markdown);
+
+    let ids: Vec<&str> = doc
+        .blocks
+        .iter()
+        .filter_map(|b| match b {
+            Block::Heading { id, .. } => Some(id.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    fn links(blocks: &[Block], out: &mut Vec<String>) {
+        for block in blocks {
+            match block {
+                Block::Paragraph { content } => {
+                    for inline in content {
+                        if let Inline::Link { url, .. } = inline {
+                            out.push(url.clone());
+                        }
+                    }
+                }
+                Block::List { items, .. } => {
+                    for item in items {
+                        links(&item.content, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut urls = Vec::new();
+    links(&doc.blocks, &mut urls);
+    assert_eq!(urls.len(), 8);
+
+    for url in urls {
+        let anchor = decode_fragment(url.strip_prefix('#').unwrap());
+        assert!(
+            ids.contains(&anchor.as_str()),
+            "{} does not resolve; heading ids: {:?}",
+            url,
+            ids
+        );
+    }
+}
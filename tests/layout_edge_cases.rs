@@ -191,3 +191,81 @@ fn test_inline_vs_sidebar_images() {
     // In inline mode, images are rendered differently
     assert!(tree_inline.document_height() > 0);
 }
+
+#[test]
+fn test_code_block_wrap_and_clip() {
+    use lumen::layout::LayoutElement;
+    use lumen::search::SearchState;
+    use lumen::theme::CodeWrap;
+
+    let long_line = format!("let value = {}; // marker", "x".repeat(40));
+    let markdown = format!("```rust {{2}}\nfn main() {{\n{}\n}}\n```\n", long_line);
+    let doc = parse_markdown(&markdown);
+    let mut theme = Theme::builtin("docs").unwrap();
+    theme.blocks.code_block.line_numbers = true;
+    let viewport = Viewport::new(30, 24);
+
+    let tree = layout_document(&doc, &theme, viewport, false);
+    let code = &tree.root.children[0];
+    let LayoutElement::CodeBlock {
+        lang,
+        lines,
+        gutter_width,
+        ..
+    } = &code.element
+    else {
+        panic!("expected a code block");
+    };
+    assert_eq!(lang.as_deref(), Some("rust"));
+    assert_eq!(*gutter_width, 2);
+    assert!(lines.len() > 3, "long line should wrap");
+    assert!(lines.iter().all(|row| row.text.chars().count() <= 26));
+    let wrapped: Vec<_> = lines.iter().filter(|row| row.number == 2).collect();
+    assert!(wrapped.len() > 1 && wrapped.iter().all(|row| row.highlighted));
+    assert!(wrapped[1..].iter().all(|row| row.continuation));
+
+    // A match straddling the wrap point is found and spans both rows
+    let mut search = SearchState::new();
+    search.needle = "xxx; // marker".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 1);
+    let found = search.current_match().unwrap();
+    let last_row = code.rect.y + lines.len() as u16 - 1;
+    assert_eq!(found.spans.last().unwrap().y, last_row);
+
+    // Clip mode keeps one row per source line
+    theme.blocks.code_block.wrap = CodeWrap::Clip;
+    let tree = layout_document(&doc, &theme, viewport, false);
+    let LayoutElement::CodeBlock { lines, .. } = &tree.root.children[0].element else {
+        panic!("expected a code block");
+    };
+    assert_eq!(lines.len(), 3);
+}
+
+#[test]
+fn test_mermaid_chart_fits_code_block() {
+    use lumen::layout::LayoutElement;
+
+    let markdown = "```mermaid\npie\n    \"Yes\" : 3\n    \"No\" : 1\n```\n";
+    let doc = parse_markdown(markdown);
+    let theme = Theme::builtin("docs").unwrap();
+
+    let mut widest = Vec::new();
+    for columns in [40, 100] {
+        let tree = layout_document(&doc, &theme, Viewport::new(columns, 24), false);
+        let code = &tree.root.children[0];
+        let LayoutElement::Diagram { lines, .. } = &code.element else {
+            panic!("expected a diagram");
+        };
+        assert!(lines[0].text().starts_with("Yes  ███"));
+        let width = lines
+            .iter()
+            .map(|row| row.text().chars().count())
+            .max()
+            .unwrap();
+        assert!(width <= code.rect.width as usize - 2);
+        widest.push(width);
+    }
+    // The bars stretch to the wider viewport
+    assert!(widest[1] > widest[0] + 40);
+}
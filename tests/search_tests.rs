@@ -161,3 +161,126 @@ fn test_search_special_characters() {
 
     assert_eq!(search.match_count(), 1);
 }
+
+#[test]
+fn test_search_smart_case() {
+    let markdown = "# TEST\n\nTest test TeSt";
+
+    let doc = parse_markdown(markdown);
+    let theme = Theme::builtin("docs").unwrap();
+    let viewport = Viewport::new(80, 24);
+    let tree = layout_document(&doc, &theme, viewport, false);
+
+    let mut search = SearchState::new();
+
+    // A capital letter makes the query case-sensitive
+    search.needle = "Test".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 1);
+
+    // ...unless \c forces case-insensitive matching
+    search.needle = "Test\\c".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 4);
+}
+
+#[test]
+fn test_search_regex_and_whole_word() {
+    let markdown = "TODO(alice) fix this\n\nTODO: later, and a TODOS list";
+
+    let doc = parse_markdown(markdown);
+    let theme = Theme::builtin("docs").unwrap();
+    let viewport = Viewport::new(80, 24);
+    let tree = layout_document(&doc, &theme, viewport, false);
+
+    let mut search = SearchState::new();
+    search.regex = true;
+    search.needle = r"TODO\(\w+\)".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 1);
+    assert_eq!(search.current_match().unwrap().text, "TODO(alice)");
+
+    search.regex = false;
+    search.whole_word = true;
+    search.needle = "TODO".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 2);
+}
+
+#[test]
+fn test_search_across_styles_and_wrapped_lines() {
+    let markdown = "Press **Ctrl** twice to quit. This sentence is long enough that it wraps \
+                    well before the important phrase at the end.";
+
+    let doc = parse_markdown(markdown);
+    let theme = Theme::builtin("docs").unwrap();
+    let viewport = Viewport::new(30, 24);
+    let tree = layout_document(&doc, &theme, viewport, false);
+
+    let mut search = SearchState::new();
+    search.needle = "ctrl twice".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 1);
+
+    // Every word pair of the paragraph is findable, wherever the wrap fell
+    for phrase in ["long enough that it wraps well before", "important phrase"] {
+        search.needle = phrase.to_string();
+        search.execute_search(&tree.root);
+        assert_eq!(search.match_count(), 1, "phrase {:?}", phrase);
+    }
+    let long = "long enough that it wraps well before";
+    search.needle = long.to_string();
+    search.execute_search(&tree.root);
+    let found = search.current_match().unwrap();
+    assert!(found.spans.len() > 1, "expected the match to wrap");
+    let highlighted: usize = found.spans.iter().map(|span| span.length).sum();
+    assert_eq!(highlighted + found.spans.len() - 1, long.len());
+}
+
+#[test]
+fn test_search_in_table_cells_and_callouts() {
+    let markdown = r#"
+| Name | Notes |
+|------|-------|
+| alpha | needle in a cell |
+
+> [!NOTE]
+> A needle in a **callout**.
+"#;
+
+    let doc = parse_markdown(markdown);
+    let theme = Theme::builtin("docs").unwrap();
+    let viewport = Viewport::new(80, 24);
+    let tree = layout_document(&doc, &theme, viewport, false);
+
+    let mut search = SearchState::new();
+    search.needle = "needle".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 2);
+
+    search.needle = "in a callout".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 1);
+}
+
+#[test]
+fn test_search_diagram_labels_only() {
+    let markdown = "```mermaid\ngraph LR\n    A[Client] --> B[Server]\n```\n";
+
+    let doc = parse_markdown(markdown);
+    let theme = Theme::builtin("docs").unwrap();
+    let tree = layout_document(&doc, &theme, Viewport::new(80, 24), false);
+
+    let mut search = SearchState::new();
+    search.needle = "server".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 1);
+
+    // The source isn't searched, and neither are the lines drawn
+    search.needle = "-->".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 0);
+    search.needle = "──".to_string();
+    search.execute_search(&tree.root);
+    assert_eq!(search.match_count(), 0);
+}